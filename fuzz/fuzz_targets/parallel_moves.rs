@@ -10,7 +10,7 @@ use libfuzzer_sys::fuzz_target;
 use regalloc3::debug_utils::{self, GenericRegInfo};
 use regalloc3::entity::{PrimaryMap, SecondaryMap};
 use regalloc3::function::{
-    Block, Function, Inst, InstRange, Operand, RematCost, TerminatorKind, Value, ValueGroup,
+    Block, Function, Inst, InstRange, Operand, TerminatorKind, Value, ValueGroup,
 };
 use regalloc3::output::{Allocation, AllocationKind, SpillSlot};
 use regalloc3::parallel_moves::ParallelMoves;
@@ -101,7 +101,7 @@ bank1 {
 struct ValueData {
     bank: RegBank,
     alloc: Option<Allocation>,
-    remat: Option<(RematCost, RegClass)>,
+    remat: Option<(f32, RegClass)>,
 }
 
 struct TestCase {
@@ -155,11 +155,7 @@ impl Arbitrary<'_> for TestCase {
 
             let (alloc, bank) = gen_alloc()?;
             let remat = if alloc.is_none() || u.arbitrary()? {
-                let cost = if u.arbitrary()? {
-                    RematCost::CheaperThanMove
-                } else {
-                    RematCost::CheaperThanLoad
-                };
+                let cost = f32::from(u.int_in_range(0..=5u8)?) * 0.25;
                 let remat_classes: Vec<_> = reginfo
                     .classes()
                     .filter(|&class| {
@@ -308,7 +304,7 @@ impl Function for TestCase {
         unreachable!()
     }
 
-    fn can_rematerialize(&self, value: Value) -> Option<(RematCost, RegClass)> {
+    fn can_rematerialize(&self, value: Value) -> Option<(f32, RegClass)> {
         self.values[value].remat
     }
 
@@ -335,10 +331,6 @@ impl fmt::Debug for TestCase {
                 write!(f, "{value} = {bank}").unwrap();
             }
             if let Some((cost, class)) = remat {
-                let cost = match cost {
-                    RematCost::CheaperThanMove => "cheaper_than_move",
-                    RematCost::CheaperThanLoad => "cheaper_than_load",
-                };
                 write!(f, " remat({cost}, {class})").unwrap();
             }
             writeln!(f)?;
@@ -368,7 +360,7 @@ fuzz_target!(|t: TestCase| {
     for &(src, dest, value) in &t.moves {
         if let Some(src) = src {
             if src != dest {
-                parallel_moves.add_move(src, dest, value, &t, &t.reginfo);
+                parallel_moves.add_move(src, dest, value, None, &t, &t.reginfo);
             }
         } else {
             parallel_moves.add_remat(dest, value, &t, &t.reginfo);
@@ -3,7 +3,8 @@ use std::sync::OnceLock;
 
 use arbitrary::{Arbitrary, Result, Unstructured};
 use regalloc3::Options;
-use regalloc3::debug_utils::{self, GenericFunction, GenericRegInfo};
+use regalloc3::debug_utils::{self, ArbitraryFunctionConfig, GenericFunction, GenericRegInfo};
+use regalloc3::reginfo::{ExcludeRegs, PhysReg, PhysRegSet, RegInfo};
 
 /// Example register descriptions that are parsed and validated once.
 static EXAMPLE_REGINFOS: OnceLock<Vec<(&'static str, GenericRegInfo)>> = OnceLock::new();
@@ -13,6 +14,11 @@ enum TestCaseRegInfo {
         path: &'static str,
         reginfo: &'static GenericRegInfo,
     },
+    Excluded {
+        path: &'static str,
+        excluded: PhysRegSet,
+        reginfo: GenericRegInfo,
+    },
     Arbitrary {
         reginfo: GenericRegInfo,
     },
@@ -22,6 +28,11 @@ impl TestCaseRegInfo {
     pub fn get(&self) -> &GenericRegInfo {
         match *self {
             TestCaseRegInfo::Example { path: _, reginfo } => reginfo,
+            TestCaseRegInfo::Excluded {
+                path: _,
+                excluded: _,
+                ref reginfo,
+            } => reginfo,
             TestCaseRegInfo::Arbitrary { ref reginfo } => reginfo,
         }
     }
@@ -63,13 +74,37 @@ impl Arbitrary<'_> for TestCase {
         let reginfo = if u.arbitrary()? {
             let (path, reginfo) = u.choose(example_reginfos)?;
             log::trace!("Using example reginfo: {path}");
-            TestCaseRegInfo::Example { path, reginfo }
+
+            // Occasionally exclude some registers from the example, as long as
+            // the result is still valid.
+            let mut excluded = PhysRegSet::new();
+            if u.ratio(1, 4)? {
+                for _ in 0..u.int_in_range(1..=4)? {
+                    excluded.insert(PhysReg::new(u.choose_index(reginfo.num_regs())?));
+                }
+            }
+            let excluded_reginfo =
+                GenericRegInfo::from_reginfo(&ExcludeRegs::new(reginfo, excluded));
+            if !excluded.is_empty() && debug_utils::validate_reginfo(&excluded_reginfo).is_ok() {
+                log::trace!("Excluding registers: {excluded:?}");
+                TestCaseRegInfo::Excluded {
+                    path,
+                    excluded,
+                    reginfo: excluded_reginfo,
+                }
+            } else {
+                TestCaseRegInfo::Example { path, reginfo }
+            }
         } else {
             let reginfo = u.arbitrary()?;
             log::trace!("Using arbitrary reginfo:\n{reginfo}");
             TestCaseRegInfo::Arbitrary { reginfo }
         };
-        let func = GenericFunction::arbitrary_with_config(reginfo.get(), u, Default::default())?;
+        let config = ArbitraryFunctionConfig {
+            critical_edges: u.arbitrary()?,
+            ..Default::default()
+        };
+        let func = GenericFunction::arbitrary_with_config(reginfo.get(), u, config)?;
         Ok(TestCase {
             reginfo,
             func,
@@ -85,6 +120,14 @@ impl fmt::Debug for TestCase {
             TestCaseRegInfo::Example { path, reginfo: _ } => {
                 writeln!(f, "Using example reginfo: {path}")?;
             }
+            TestCaseRegInfo::Excluded {
+                path,
+                excluded,
+                reginfo: _,
+            } => {
+                writeln!(f, "Using example reginfo: {path}")?;
+                writeln!(f, "Excluding registers: {excluded:?}")?;
+            }
             TestCaseRegInfo::Arbitrary { reginfo } => {
                 writeln!(f, "{reginfo}")?;
             }
@@ -8,6 +8,7 @@ use example_reginfo::Arch;
 use rand::RngCore;
 use regalloc3::debug_utils::{
     self, ArbitraryFunctionConfig, ArbitraryRegInfoConfig, GenericFunction, GenericRegInfo,
+    ValidationError,
 };
 use regalloc3::{Options, RegisterAllocator};
 
@@ -67,6 +68,10 @@ enum Args {
         /// Number of clobbers per instruction.
         #[clap(long, default_value_t = 10)]
         clobbers_per_inst: usize,
+
+        /// Generate a function with critical edges.
+        #[clap(long)]
+        critical_edges: bool,
     },
 
     /// Generate a random register description.
@@ -123,7 +128,9 @@ fn load_reginfo(path: &Path) -> Result<GenericRegInfo> {
     let reginfo = fs::read(path).context("could not read reginfo input file")?;
     let reginfo = String::from_utf8(reginfo).context("reginfo input is not UTF-8")?;
     let reginfo = GenericRegInfo::parse(&reginfo).context("could not parse reginfo input file")?;
-    debug_utils::validate_reginfo(&reginfo).context("reginfo validation failed")?;
+    debug_utils::validate_reginfo_all(&reginfo)
+        .map_err(validation_errors)
+        .context("reginfo validation failed")?;
     Ok(reginfo)
 }
 
@@ -132,10 +139,18 @@ fn load_function(path: &Path, reginfo: &GenericRegInfo) -> Result<GenericFunctio
     let function = String::from_utf8(function).context("function input is not UTF-8")?;
     let function =
         GenericFunction::parse(&function).context("could not parse function input file")?;
-    debug_utils::validate_function(&function, reginfo).context("function validation failed")?;
+    debug_utils::validate_function_all(&function, reginfo)
+        .map_err(validation_errors)
+        .context("function validation failed")?;
     Ok(function)
 }
 
+/// Combines all of the errors reported by validation into a single error.
+fn validation_errors(errors: Vec<ValidationError>) -> anyhow::Error {
+    let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+    anyhow::anyhow!(errors.join("\n"))
+}
+
 fn main() -> Result<()> {
     pretty_env_logger::init();
     let args = Args::parse();
@@ -160,7 +175,7 @@ fn main() -> Result<()> {
             let mut regalloc = RegisterAllocator::new();
             let output = regalloc
                 .allocate_registers(&function, &reginfo, options)
-                .unwrap();
+                .context("register allocation failed")?;
 
             println!("================ Output ================\n{output}");
 
@@ -182,6 +197,7 @@ fn main() -> Result<()> {
             defs_per_inst,
             uses_per_inst,
             clobbers_per_inst,
+            critical_edges,
         } => {
             let reginfo = load_reginfo(reginfo)?;
             let config = ArbitraryFunctionConfig {
@@ -191,6 +207,7 @@ fn main() -> Result<()> {
                 defs_per_inst: 0..=defs_per_inst,
                 uses_per_inst: 0..=uses_per_inst,
                 clobbers_per_inst: 0..=clobbers_per_inst,
+                critical_edges,
             };
             let mut bytes = [0; 4096];
             rand::rng().fill_bytes(&mut bytes);
@@ -3,7 +3,7 @@
 
 use alloc::vec;
 use alloc::vec::Vec;
-use core::fmt;
+use core::{fmt, slice};
 
 use anyhow::{Result, bail, ensure};
 use smallvec::SmallVec;
@@ -12,6 +12,7 @@ use crate::allocation_unit::AllocationUnit;
 use crate::debug_utils::DisplayOutputInst;
 use crate::entity::{EntitySet, SecondaryMap, SparseMap};
 use crate::function::{Block, Function, Inst, Operand, OperandConstraint, OperandKind, Value};
+use crate::internal::edge_split::edge_blockparams;
 use crate::output::{Allocation, AllocationKind, Output, OutputInst, SpillSlot};
 use crate::reginfo::{MAX_REG_UNITS, PhysReg, RegBank, RegClass, RegGroup, RegInfo, RegUnitSet};
 
@@ -125,6 +126,14 @@ impl CheckerState {
             .map(|&(unit, _)| unit)
     }
 
+    /// Removes all values for which `keep` returns false.
+    fn retain_values(&mut self, mut keep: impl FnMut(AllocationUnit, Value) -> bool) {
+        self.unit_values.retain(|unit, values| {
+            values.retain(|&mut value| keep(unit, value));
+            !values.is_empty()
+        });
+    }
+
     /// Updates the state of an `AllocationUnit` to indicate it no longer holds
     /// a value.
     fn clobber_unit(&mut self, unit: AllocationUnit) {
@@ -178,12 +187,17 @@ struct Context<'a, F, R> {
     state: CheckerState,
     evicted: SparseMap<SpillSlot, EvictedReg>,
     blockparams_to_insert: Vec<(AllocationUnit, Value)>,
+    stack_map_units: Vec<(AllocationUnit, Value)>,
     def_units: EntitySet<AllocationUnit>,
     fixed_def_units: RegUnitSet,
     early_reused_operands: Vec<usize>,
     next_inst: Inst,
     terminated: bool,
     can_have_move: bool,
+    has_edit: bool,
+    skipped_inst: bool,
+    output_insts: Vec<Inst>,
+    next_output_inst: usize,
 }
 
 impl<F: Function, R: RegInfo> Context<'_, F, R> {
@@ -222,10 +236,14 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                 "{slot} offset {offset} is not aligned to size {size}"
             );
             let end = offset + size.bytes();
-            ensure!(
-                end <= stack_layout.spillslot_area_size(),
-                "{slot} ends at {end} which exceeds spill area size {}",
+            let area_size = if size.is_scalable() {
+                stack_layout.scalable_spillslot_area_size()
+            } else {
                 stack_layout.spillslot_area_size()
+            };
+            ensure!(
+                end <= area_size,
+                "{slot} ends at {end} which exceeds spill area size {area_size}"
             );
         }
         Ok(())
@@ -241,74 +259,182 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
         // a single parallel move anyways.
         self.evicted.clear();
         self.state = self.block_entry_state[block].clone().unwrap();
+
+        // Edge blocks only contain moves, after which control flow continues
+        // to the successor of the edge.
+        if let Some(idx) = block.index().checked_sub(func.num_blocks()) {
+            let edge = self.output.edge_blocks()[idx];
+            ensure!(
+                edge.block == block,
+                "Edge block {} has wrong block number {}",
+                block,
+                edge.block
+            );
+            let is_critical =
+                func.block_succs(edge.pred).len() > 1 && func.block_preds(edge.succ).len() > 1;
+            ensure!(
+                func.block_succs(edge.pred).get(edge.succ_idx) == Some(&edge.succ)
+                    && (is_critical
+                        || !edge_blockparams(func, edge.pred, edge.succ_idx).is_empty()),
+                "{block} is not on a critical edge or an edge with block parameters: {} -> {}",
+                edge.pred,
+                edge.succ
+            );
+            trace!("Checking {block} on edge {} -> {}...", edge.pred, edge.succ);
+            trace!("Values: {}", self.state);
+            self.terminated = false;
+            self.can_have_move = true;
+            for inst in self.output.output_insts(block) {
+                ensure!(
+                    !matches!(inst, OutputInst::Inst { .. }),
+                    "Instruction in edge block {block}"
+                );
+                self.check_inst(inst, block)?;
+                trace!("Values: {}", self.state);
+            }
+            if !func.block_params(edge.succ).is_empty() {
+                self.insert_blockparams(edge.pred, edge.succ, edge.succ_idx);
+            }
+            self.propagate_to_succ(edge.succ);
+            return Ok(());
+        }
+
         self.next_inst = func.block_insts(block).from;
         self.terminated = false;
         self.can_have_move = func.block_preds(block).len() == 1;
+        self.has_edit = false;
         trace!("Checking {block}...");
         trace!("Values: {}", self.state);
 
-        // Update the state based on the instructions in the block.
-        for inst in self.output.output_insts(block) {
+        // Collect the instructions present in the output so that eliminated
+        // instructions can be processed before the edits that follow them.
+        self.output_insts.clear();
+        self.output_insts.extend(
+            self.output
+                .output_insts_with_copies(block)
+                .filter_map(|inst| match inst {
+                    OutputInst::Inst { inst, .. } => Some(inst),
+                    OutputInst::Rematerialize { .. } | OutputInst::Move { .. } => None,
+                }),
+        );
+        self.next_output_inst = 0;
+        self.check_skipped_insts(block)?;
+
+        // Moves are allowed at the start of the block if the first instruction
+        // was eliminated since they then appear before the second instruction.
+        if self.skipped_inst {
+            self.can_have_move = true;
+        }
+
+        // Update the state based on the instructions in the block. Eliminated
+        // copies are included since they still define their destination.
+        for inst in self.output.output_insts_with_copies(block) {
+            let is_inst = matches!(inst, OutputInst::Inst { .. });
             self.check_inst(inst, block)?;
+            if is_inst {
+                self.check_skipped_insts(block)?;
+            }
             trace!("Values: {}", self.state);
         }
-        self.check_skipped_inst(func.block_insts(block).to)?;
+        ensure!(
+            self.next_inst == func.block_insts(block).to,
+            "Output skipped instructions at the end of {block}"
+        );
         debug_assert!(self.terminated);
 
-        // If there are any outgoing block parameters, add the corresponding
-        // incoming block parameter value to any unit that holds the outgoing
-        // value at the end of the block.
-        if let &[succ] = func.block_succs(block) {
-            // Find units which hold a value that is an outoing block parameter.
-            self.blockparams_to_insert.clear();
-            for (&blockparam_out, &blockparam_in) in self
-                .output
-                .function()
-                .jump_blockparams(block)
-                .iter()
-                .zip(func.block_params(succ))
-            {
-                self.blockparams_to_insert.extend(
-                    self.state
-                        .units_containing_value(blockparam_out)
-                        .map(|unit| (unit, blockparam_in)),
-                );
+        // Propagate the end state to successor blocks, going through the edge
+        // block if one was inserted. Outgoing block parameters are handled at
+        // the end of the edge block in that case.
+        //
+        // If edge edits were separated from the blocks then they are applied
+        // on each edge before propagating.
+        let separated = self.output.edge_edits_separated();
+        let end_state = (func.block_succs(block).len() > 1).then(|| self.state.clone());
+        for (succ_idx, &succ) in func.block_succs(block).iter().enumerate() {
+            if let Some(edge_block) = self.output.edge_block_between(block, succ_idx) {
+                self.propagate_to_succ(edge_block);
+                continue;
             }
-
-            // Remove any stale block parameter values from previous loop
-            // iterations.
-            for &blockparam_in in func.block_params(succ) {
-                self.state.remove_value(blockparam_in);
+            let mut modified = false;
+            if separated {
+                trace!("Checking edge {block} -> {succ}...");
+                self.evicted.clear();
+                self.terminated = false;
+                self.can_have_move = true;
+                for inst in self.output.edge_edits(block, succ_idx) {
+                    ensure!(
+                        !matches!(inst, OutputInst::Inst { .. }),
+                        "Instruction on edge {block} -> {succ}"
+                    );
+                    self.check_inst(inst, block)?;
+                    trace!("Values: {}", self.state);
+                    modified = true;
+                }
             }
-
-            // Then add the incoming block parameters to units which held the
-            // corresponding outgoing value.
-            for &(unit, blockparam_in) in &self.blockparams_to_insert {
-                self.state.add_value(unit, blockparam_in);
+            if !func.block_params(succ).is_empty() {
+                self.insert_blockparams(block, succ, succ_idx);
+                modified = true;
+            }
+            self.propagate_to_succ(succ);
+            if modified && let Some(end_state) = &end_state {
+                self.state.clone_from(end_state);
             }
+        }
+
+        Ok(())
+    }
+
+    /// Adds the incoming block parameter values of `succ` to any unit that
+    /// holds the corresponding outgoing value on the edge from `pred`.
+    ///
+    /// `succ_idx` is the index of `succ` in the successors of `pred`.
+    fn insert_blockparams(&mut self, pred: Block, succ: Block, succ_idx: usize) {
+        let func = self.output.function();
 
-            trace!("Values after blockparams: {}", self.state);
+        // Find units which hold a value that is an outoing block parameter.
+        self.blockparams_to_insert.clear();
+        for (&blockparam_out, &blockparam_in) in edge_blockparams(func, pred, succ_idx)
+            .iter()
+            .zip(func.block_params(succ))
+        {
+            self.blockparams_to_insert.extend(
+                self.state
+                    .units_containing_value(blockparam_out)
+                    .map(|unit| (unit, blockparam_in)),
+            );
         }
 
-        // Propagate the end state to successor blocks. If a successor has not
-        // been visited yet or the merge changes their state, queue up that
-        // block for another pass.
-        for &succ in func.block_succs(block) {
-            let mut changed = false;
-            match self.block_entry_state[succ] {
-                Some(ref mut succ_state) => succ_state.meet(&self.state, &mut changed),
-                None => {
-                    changed = true;
-                    self.block_entry_state[succ] = Some(self.state.clone());
-                }
-            }
-            if changed {
-                trace!("Propagation changed state in {succ}");
-                self.blocks_to_check.insert(succ, ());
-            }
+        // Remove any stale block parameter values from previous loop
+        // iterations.
+        for &blockparam_in in func.block_params(succ) {
+            self.state.remove_value(blockparam_in);
         }
 
-        Ok(())
+        // Then add the incoming block parameters to units which held the
+        // corresponding outgoing value.
+        for &(unit, blockparam_in) in &self.blockparams_to_insert {
+            self.state.add_value(unit, blockparam_in);
+        }
+
+        trace!("Values after blockparams: {}", self.state);
+    }
+
+    /// Propagates the end state of the current block to a successor block. If
+    /// the successor has not been visited yet or the merge changes its state,
+    /// queue up that block for another pass.
+    fn propagate_to_succ(&mut self, succ: Block) {
+        let mut changed = false;
+        match self.block_entry_state[succ] {
+            Some(ref mut succ_state) => succ_state.meet(&self.state, &mut changed),
+            None => {
+                changed = true;
+                self.block_entry_state[succ] = Some(self.state.clone());
+            }
+        }
+        if changed {
+            trace!("Propagation changed state in {succ}");
+            self.blocks_to_check.insert(succ, ());
+        }
     }
 
     /// Checks an `OutputInst` and updates the checker state to reflect that
@@ -330,33 +456,41 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
 
         // Moves before the first instruction in a block are not allowed in
         // blocks with multiple (or no) predecessors, except if the first
-        // instruction of that block has "use" operands or is a terminator.
+        // instruction of that block has "use" operands, is a safepoint or is a
+        // terminator.
         if !self.can_have_move {
-            let has_use =
-                func.inst_operands(func.block_insts(block).from)
-                    .iter()
-                    .any(|op| match op.kind() {
-                        OperandKind::Use(_) | OperandKind::UseGroup(_) => true,
-                        OperandKind::Def(_)
-                        | OperandKind::EarlyDef(_)
-                        | OperandKind::DefGroup(_)
-                        | OperandKind::EarlyDefGroup(_)
-                        | OperandKind::NonAllocatable => false,
-                    });
-            if func.block_insts(block).len() == 1 || has_use {
+            let first_inst = func.block_insts(block).from;
+            let has_use = func
+                .inst_operands(first_inst)
+                .iter()
+                .any(|op| match op.kind() {
+                    OperandKind::Use(_) | OperandKind::UseGroup(_) => true,
+                    OperandKind::Def(_)
+                    | OperandKind::EarlyDef(_)
+                    | OperandKind::DefGroup(_)
+                    | OperandKind::EarlyDefGroup(_)
+                    | OperandKind::NonAllocatable => false,
+                });
+            if func.block_insts(block).len() == 1 || has_use || func.is_safepoint(first_inst) {
                 self.can_have_move = true;
             }
         }
         match inst {
             OutputInst::Inst { .. } => self.can_have_move = true,
-            OutputInst::Rematerialize { .. } => ensure!(
-                self.can_have_move,
-                "Cannot have remat before first instruction"
-            ),
-            OutputInst::Move { .. } => ensure!(
-                self.can_have_move,
-                "Cannot have move before first instruction"
-            ),
+            OutputInst::Rematerialize { .. } => {
+                ensure!(
+                    self.can_have_move,
+                    "Cannot have remat before first instruction"
+                );
+                self.has_edit = true;
+            }
+            OutputInst::Move { .. } => {
+                ensure!(
+                    self.can_have_move,
+                    "Cannot have move before first instruction"
+                );
+                self.has_edit = true;
+            }
         }
 
         match inst {
@@ -364,7 +498,23 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                 inst,
                 operand_allocs,
             } => {
-                self.check_skipped_inst(inst)?;
+                // Edits are not allowed before an instruction which forbids
+                // them. Edits before an eliminated copy end up before the
+                // following instruction.
+                ensure!(
+                    !self.has_edit || !func.no_edits_before(inst),
+                    "{inst}: Edit before instruction which forbids edits before it"
+                );
+                if !self.output.is_eliminated_copy(inst) {
+                    self.has_edit = false;
+                }
+
+                ensure!(
+                    self.next_inst == inst,
+                    "Output instructions in wrong order: expected {}, got {inst}",
+                    self.next_inst
+                );
+                self.next_inst = inst.next();
 
                 if func.terminator_kind(inst).is_some() {
                     self.terminated = true;
@@ -378,6 +528,14 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                     operand_allocs.len()
                 );
 
+                // A copy can only be eliminated if it is a no-op.
+                if self.output.is_eliminated_copy(inst) {
+                    ensure!(
+                        func.is_copy(inst) && operand_allocs[0] == operand_allocs[1],
+                        "{inst}: Eliminated copy is not a no-op"
+                    );
+                }
+
                 // Process instruction operands in order. We also track which
                 // units are written to detect conflicting outputs.
                 self.def_units
@@ -385,6 +543,11 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                 self.fixed_def_units.clear();
                 self.early_reused_operands.clear();
                 for pass in [Pass::EarlyDef, Pass::Use, Pass::Def] {
+                    // The garbage collector runs after the inputs of a
+                    // safepoint are read and before its outputs are written.
+                    if pass == Pass::Def {
+                        self.check_safepoint(inst)?;
+                    }
                     for (idx, (&op, &alloc)) in operands.iter().zip(operand_allocs).enumerate() {
                         self.check_operand(pass, inst, idx, op, alloc, operand_allocs)?;
                     }
@@ -403,12 +566,36 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                     }
                 }
             }
-            OutputInst::Rematerialize { value, to } => {
+            OutputInst::Rematerialize { value, to, input } => {
                 let Some((_cost, class)) = func.can_rematerialize(value) else {
                     bail!("{value} is not rematerializable");
                 };
                 self.check_class(to, class)?;
 
+                // The input value must be available in a register.
+                match (func.remat_input(value), input) {
+                    (Some(input_value), Some(input)) => {
+                        ensure!(
+                            !input.is_memory(reginfo),
+                            "before {}: Remat input of {value} in memory {input}",
+                            self.next_inst
+                        );
+                        for unit in input.units(reginfo) {
+                            ensure!(
+                                self.state.unit_contains(unit, input_value),
+                                "before {}: {unit} in {input} does not contain {input_value} \
+                                 for remat of {value}",
+                                self.next_inst
+                            );
+                        }
+                    }
+                    (None, None) => {}
+                    (Some(input_value), None) => {
+                        bail!("Remat of {value} is missing input {input_value}")
+                    }
+                    (None, Some(input)) => bail!("Remat of {value} has unexpected input {input}"),
+                }
+
                 for unit in to.units(reginfo) {
                     self.state.set_value(unit, value);
                 }
@@ -515,28 +702,103 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
         Ok(())
     }
 
-    /// Checks that no instructions were skipped and that instructions are
-    /// processed in order.
+    /// Processes the instructions which were eliminated between the previous
+    /// instruction in the output and the next one (or the end of the block).
+    ///
+    /// Only pure instructions are allowed to be eliminated. Their outputs are
+    /// never defined, so any stale copies of them from previous loop
+    /// iterations are removed from the state. Any later reads of these values
+    /// will then be flagged as errors unless they are rematerialized.
     ///
-    /// An exception is made for pure instructions which are allowed to be
-    /// eliminated if their outputs are unused.
-    fn check_skipped_inst(&mut self, current_inst: Inst) -> Result<()> {
+    /// This is done before processing the edits preceding the next
+    /// instruction since these come after the eliminated instructions.
+    fn check_skipped_insts(&mut self, block: Block) -> Result<()> {
+        let func = self.output.function();
+        let next = match self.output_insts.get(self.next_output_inst) {
+            Some(&inst) => {
+                self.next_output_inst += 1;
+                inst
+            }
+            None => func.block_insts(block).to,
+        };
         ensure!(
-            self.next_inst <= current_inst,
-            "Output instructions in wrong order: expected {}, got {current_inst}",
+            self.next_inst <= next,
+            "Output instructions in wrong order: expected {}, got {next}",
             self.next_inst
         );
-        while self.next_inst != current_inst {
+        self.skipped_inst = self.next_inst != next;
+        while self.next_inst != next {
             ensure!(
-                self.output
-                    .function()
-                    .can_eliminate_dead_inst(self.next_inst),
+                func.can_eliminate_dead_inst(self.next_inst),
                 "Output skipped non-pure {}",
                 self.next_inst
             );
+            for op in func.inst_operands(self.next_inst) {
+                let kind = op.kind();
+                let values = match kind {
+                    OperandKind::Def(ref value) | OperandKind::EarlyDef(ref value) => {
+                        slice::from_ref(value)
+                    }
+                    OperandKind::DefGroup(group) | OperandKind::EarlyDefGroup(group) => {
+                        func.value_group_members(group)
+                    }
+                    OperandKind::Use(_)
+                    | OperandKind::UseGroup(_)
+                    | OperandKind::NonAllocatable => &[],
+                };
+                for &value in values {
+                    self.state.remove_value(value);
+                }
+            }
             self.next_inst = self.next_inst.next();
         }
-        self.next_inst = current_inst.next();
+        Ok(())
+    }
+
+    /// Checks the stack map of an instruction and then simulates a moving
+    /// garbage collector by invalidating all copies of reference values which
+    /// are not in the stack map.
+    ///
+    /// A reference value that is live across the safepoint but missing from
+    /// the stack map will then be flagged as an error at its next use.
+    fn check_safepoint(&mut self, inst: Inst) -> Result<()> {
+        let func = self.output.function();
+        let reginfo = self.output.reginfo();
+
+        if !func.is_safepoint(inst) {
+            ensure!(
+                self.output.stack_map(inst).next().is_none(),
+                "{inst}: Stack map on an instruction which is not a safepoint"
+            );
+            return Ok(());
+        }
+
+        self.stack_map_units.clear();
+        for (value, alloc) in self.output.stack_map(inst) {
+            ensure!(
+                func.is_reference(value),
+                "{inst}: {value} in stack map is not a reference"
+            );
+            self.check_bank(alloc, func.value_bank(value))?;
+            for unit in alloc.units(reginfo) {
+                ensure!(
+                    self.state.unit_contains(unit, value),
+                    "{inst}: {unit} in stack map entry {alloc} does not contain {value}"
+                );
+                self.stack_map_units.push((unit, value));
+            }
+        }
+
+        // Units written by early defs already hold the outputs of the
+        // instruction, which are not affected by the garbage collector.
+        let def_units = &self.def_units;
+        let stack_map_units = &self.stack_map_units;
+        self.state.retain_values(|unit, value| {
+            def_units.contains(unit)
+                || !func.is_reference(value)
+                || stack_map_units.contains(&(unit, value))
+        });
+
         Ok(())
     }
 
@@ -620,10 +882,21 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
         let func = self.output.function();
         let reginfo = self.output.reginfo();
 
+        // Early defs tied to a fixed-register use only overwrite the register
+        // after the instruction, so they are treated as normal defs.
+        let fixed_reuse = match op.constraint() {
+            OperandConstraint::Reuse(target) => matches!(
+                func.inst_operands(inst)[target].constraint(),
+                OperandConstraint::Fixed(_)
+            ),
+            _ => false,
+        };
+
         // We need to process operands in a specific order: EarlyDef -> Use -> Def
         let expected_pass = match op.kind() {
             OperandKind::Def(_) | OperandKind::DefGroup(_) => Pass::Def,
             OperandKind::Use(_) | OperandKind::UseGroup(_) => Pass::Use,
+            OperandKind::EarlyDef(_) if fixed_reuse => Pass::Def,
             OperandKind::EarlyDef(_) | OperandKind::EarlyDefGroup(_) => Pass::EarlyDef,
             // It doesn't matter which pass we process these in, just pick one.
             OperandKind::NonAllocatable => Pass::EarlyDef,
@@ -638,6 +911,7 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
             OperandKind::EarlyDef(_) | OperandKind::EarlyDefGroup(_),
             OperandConstraint::Reuse(target),
         ) = (op.kind(), op.constraint())
+            && !fixed_reuse
         {
             self.check_operand(
                 Pass::Use,
@@ -651,7 +925,7 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
         }
 
         // Check that the selected allocation is suitable for the constraint.
-        self.check_constraint(alloc, op, operand_allocs)?;
+        self.check_constraint(inst, alloc, op, operand_allocs)?;
 
         // Skip inputs tied to EarlyDef Reuse operands that have already been
         // checked. The values in the registers have by this point already been
@@ -687,7 +961,9 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
             OperandKind::DefGroup(value_group) | OperandKind::EarlyDefGroup(value_group) => {
                 let class = match op.constraint() {
                     OperandConstraint::Class(class) => class,
-                    OperandConstraint::Fixed(_) => unreachable!(),
+                    OperandConstraint::Fixed(_) | OperandConstraint::Alternatives(_) => {
+                        unreachable!()
+                    }
                     OperandConstraint::Reuse(idx) => {
                         let OperandConstraint::Class(class) =
                             func.inst_operands(inst)[idx].constraint()
@@ -744,6 +1020,7 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
     /// constraints.
     fn check_constraint(
         &mut self,
+        inst: Inst,
         alloc: Allocation,
         op: Operand,
         operand_allocs: &[Allocation],
@@ -776,12 +1053,50 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                     self.fixed_def_units.insert(unit);
                 }
             }
-            OperandConstraint::Reuse(idx) => {
+            OperandConstraint::Reuse(target) => {
                 ensure!(
-                    alloc == operand_allocs[idx],
+                    alloc == operand_allocs[target],
                     "Expected reused allocation {}, got {alloc}",
-                    operand_allocs[idx]
+                    operand_allocs[target]
                 );
+
+                // A def tied to a fixed-register use is a fixed definition.
+                if let OperandConstraint::Fixed(reg) =
+                    self.output.function().inst_operands(inst)[target].constraint()
+                {
+                    for unit in self.output.reginfo().reg_units(reg) {
+                        self.fixed_def_units.insert(unit);
+                    }
+                }
+            }
+            OperandConstraint::Alternatives(set) => {
+                // Any of the alternatives is acceptable, independently of
+                // which one the allocator selected. The allocation is only
+                // treated as a fixed definition if no class contains it.
+                let alternatives = self.output.function().alternative_set(set);
+                let mut fixed = None;
+                let mut in_class = false;
+                for &(constraint, _) in alternatives {
+                    match constraint {
+                        OperandConstraint::Class(class) => {
+                            in_class |= self.check_class(alloc, class).is_ok();
+                        }
+                        OperandConstraint::Fixed(reg) => {
+                            if alloc.kind() == AllocationKind::PhysReg(reg) {
+                                fixed = Some(reg);
+                            }
+                        }
+                        OperandConstraint::Reuse(_) | OperandConstraint::Alternatives(_) => {}
+                    }
+                }
+                if !in_class {
+                    let Some(reg) = fixed else {
+                        bail!("{alloc} doesn't satisfy any of the alternatives in {set}");
+                    };
+                    for unit in self.output.reginfo().reg_units(reg) {
+                        self.fixed_def_units.insert(unit);
+                    }
+                }
             }
         }
         Ok(())
@@ -793,19 +1108,25 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
 /// If this fails then it indicates a bug in the register allocator, assuming
 /// the `Function` and `RegInfo` have passed validation.
 pub fn check_output(output: &Output<'_, impl Function, impl RegInfo>) -> Result<()> {
+    let num_blocks = output.func.num_blocks() + output.edge_blocks().len();
     let mut context = Context {
         output,
-        blocks_to_check: SparseMap::with_max_index(output.func.num_blocks()),
-        block_entry_state: SecondaryMap::with_max_index(output.func.num_blocks()),
+        blocks_to_check: SparseMap::with_max_index(num_blocks),
+        block_entry_state: SecondaryMap::with_max_index(num_blocks),
         state: CheckerState::new(output),
         evicted: SparseMap::with_max_index(output.stack_layout().num_spillslots()),
         blockparams_to_insert: vec![],
+        stack_map_units: vec![],
         next_inst: Inst::new(0),
         early_reused_operands: vec![],
         def_units: EntitySet::new(),
         fixed_def_units: RegUnitSet::new(),
         terminated: false,
         can_have_move: false,
+        has_edit: false,
+        skipped_inst: false,
+        output_insts: vec![],
+        next_output_inst: 0,
     };
     context.check_function()
 }
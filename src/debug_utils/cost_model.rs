@@ -1,5 +1,8 @@
-use crate::function::{Function, OperandConstraint, OperandKind, RematCost};
-use crate::output::{Output, OutputInst};
+use crate::function::{
+    AlternativeSet, Function, Inst, MOVE_COST, OperandConstraint, OperandKind, SPILL_RELOAD_COST,
+    Value,
+};
+use crate::output::{Allocation, AllocationKind, Output, OutputInst};
 use crate::reginfo::RegInfo;
 
 /// Cost model to evaluate and compare the quality of different register
@@ -17,11 +20,6 @@ pub struct CostModel {
 
     /// Cost of a move from one register to another.
     pub move_cost: f32,
-
-    /// Cost of rematerializing a value with [`RematCost::CheaperThanLoad`].
-    ///
-    /// The cost for [`RematCost::CheaperThanMove`] is assumed to be zero.
-    pub remat_cost: f32,
 }
 
 impl Default for CostModel {
@@ -31,7 +29,6 @@ impl Default for CostModel {
             load_cost: 4.0,
             store_cost: 1.0,
             move_cost: 0.2,
-            remat_cost: 1.0,
         }
     }
 }
@@ -47,30 +44,84 @@ impl CostModel {
         let mut score = 0.0;
         let reginfo = output.reginfo();
         let func = output.function();
-        for block in func.blocks() {
-            let freq = func.block_frequency(block);
-            for inst in output.output_insts(block) {
+        // Moves on edge blocks are executed at most as often as either side
+        // of the edge. The same applies to edge edits if they were separated
+        // from the blocks.
+        let edge_freq = |pred, succ| func.block_frequency(pred).min(func.block_frequency(succ));
+        let edge_blocks = output.edge_blocks().iter().map(|edge| {
+            (
+                output.output_insts(edge.block),
+                edge_freq(edge.pred, edge.succ),
+            )
+        });
+        let edge_edits = func
+            .blocks()
+            .filter(|_| output.edge_edits_separated())
+            .flat_map(|pred| {
+                func.block_succs(pred)
+                    .iter()
+                    .enumerate()
+                    .map(move |(succ_idx, &succ)| (pred, succ_idx, succ))
+            })
+            .map(|(pred, succ_idx, succ)| {
+                (output.edge_edits(pred, succ_idx), edge_freq(pred, succ))
+            });
+        for (insts, freq) in func
+            .blocks()
+            .map(|block| (output.output_insts(block), func.block_frequency(block)))
+            .chain(edge_blocks)
+            .chain(edge_edits)
+        {
+            for inst in insts {
                 match inst {
                     OutputInst::Inst {
                         inst,
                         operand_allocs,
                     } => {
-                        // Penalize instruction operands that are assigned to
-                        // memory instead of registers.
+                        // Pure instructions which define rematerializable
+                        // values are charged the same cost as a
+                        // rematerialization. Such instructions are eliminated
+                        // if all uses of their outputs are rematerialized, in
+                        // which case they don't appear in the output at all.
+                        if func.can_eliminate_dead_inst(inst) {
+                            score += self.pure_inst_cost(inst, func) * freq;
+                        }
+
                         for (&op, &alloc) in func.inst_operands(inst).iter().zip(operand_allocs) {
+                            // Charge the cost of the cheapest alternative
+                            // satisfied by the allocation. A worse selection
+                            // by the allocator therefore shows up in the score.
+                            let constraint = match op.constraint() {
+                                OperandConstraint::Alternatives(set) => {
+                                    let Some((constraint, cost)) =
+                                        satisfied_alternative(set, alloc, func, reginfo)
+                                    else {
+                                        continue;
+                                    };
+                                    score += cost * freq;
+                                    constraint
+                                }
+                                constraint => constraint,
+                            };
+
+                            // Penalize instruction operands that are assigned to
+                            // memory instead of registers.
                             if !alloc.is_memory(reginfo) {
                                 continue;
                             }
-                            let class = match op.constraint() {
+                            let class = match constraint {
                                 OperandConstraint::Class(class) => class,
-                                OperandConstraint::Fixed(_) => continue,
-                                OperandConstraint::Reuse(idx) => {
-                                    let OperandConstraint::Class(class) =
-                                        func.inst_operands(inst)[idx].constraint()
-                                    else {
-                                        unreachable!()
-                                    };
-                                    class
+                                OperandConstraint::Fixed(_)
+                                | OperandConstraint::Alternatives(_) => {
+                                    continue;
+                                }
+                                OperandConstraint::Reuse(target) => {
+                                    match func.inst_operands(inst)[target].constraint() {
+                                        OperandConstraint::Class(class) => class,
+                                        OperandConstraint::Fixed(_) => continue,
+                                        OperandConstraint::Reuse(_)
+                                        | OperandConstraint::Alternatives(_) => unreachable!(),
+                                    }
                                 }
                             };
                             let cost = match op.kind() {
@@ -90,14 +141,15 @@ impl CostModel {
                             score += cost * freq * reginfo.class_spill_cost(class);
                         }
                     }
-                    OutputInst::Rematerialize { value, to } => {
+                    OutputInst::Rematerialize {
+                        value,
+                        to,
+                        input: _,
+                    } => {
                         // Treat a rematerialization as having 2 parts: actually
                         // computing the value and then moving it to its
                         // destination.
-                        let remat_cost = match func.can_rematerialize(value).unwrap().0 {
-                            RematCost::CheaperThanMove => 0.0,
-                            RematCost::CheaperThanLoad => self.remat_cost,
-                        };
+                        let remat_cost = self.value_remat_cost(value, func);
                         let move_cost = match to.is_memory(reginfo) {
                             false => self.move_cost,
                             true => self.store_cost,
@@ -120,4 +172,67 @@ impl CostModel {
         }
         score
     }
+
+    /// Returns the cost of computing a rematerializable value.
+    ///
+    /// The cost returned by [`Function::can_rematerialize`] is mapped linearly
+    /// onto `move_cost` and `load_cost` between [`MOVE_COST`] and
+    /// [`SPILL_RELOAD_COST`], and scaled proportionally to `move_cost` below a
+    /// move.
+    fn value_remat_cost(&self, value: Value, func: &impl Function) -> f32 {
+        let Some((cost, _class)) = func.can_rematerialize(value) else {
+            return 0.0;
+        };
+        if cost < MOVE_COST {
+            cost / MOVE_COST * self.move_cost
+        } else {
+            self.move_cost
+                + (cost - MOVE_COST) / (SPILL_RELOAD_COST - MOVE_COST)
+                    * (self.load_cost - self.move_cost)
+        }
+    }
+
+    /// Returns the cost of a pure instruction, which is the cost of
+    /// rematerializing the most expensive value that it defines.
+    fn pure_inst_cost(&self, inst: Inst, func: &impl Function) -> f32 {
+        let mut cost = 0.0f32;
+        for op in func.inst_operands(inst) {
+            match op.kind() {
+                OperandKind::Def(value) | OperandKind::EarlyDef(value) => {
+                    cost = cost.max(self.value_remat_cost(value, func));
+                }
+                OperandKind::DefGroup(group) | OperandKind::EarlyDefGroup(group) => {
+                    for &value in func.value_group_members(group) {
+                        cost = cost.max(self.value_remat_cost(value, func));
+                    }
+                }
+                OperandKind::Use(_) | OperandKind::UseGroup(_) | OperandKind::NonAllocatable => {}
+            }
+        }
+        cost
+    }
+}
+
+/// Returns the cheapest alternative in `set` which is satisfied by `alloc`,
+/// along with its cost.
+fn satisfied_alternative(
+    set: AlternativeSet,
+    alloc: Allocation,
+    func: &impl Function,
+    reginfo: &impl RegInfo,
+) -> Option<(OperandConstraint, f32)> {
+    func.alternative_set(set)
+        .iter()
+        .copied()
+        .filter(|&(constraint, _)| match (constraint, alloc.kind()) {
+            (OperandConstraint::Class(class), AllocationKind::PhysReg(reg)) => {
+                reginfo.class_members(class).contains(reg)
+            }
+            (OperandConstraint::Class(class), AllocationKind::SpillSlot(_)) => {
+                reginfo.class_includes_spillslots(class)
+            }
+            (OperandConstraint::Fixed(reg), kind) => kind == AllocationKind::PhysReg(reg),
+            (OperandConstraint::Reuse(_) | OperandConstraint::Alternatives(_), _) => false,
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
 }
@@ -4,7 +4,7 @@
 use core::cell::Cell;
 use core::fmt;
 
-use crate::function::{Block, Function, OperandConstraint, OperandKind, RematCost, TerminatorKind};
+use crate::function::{Block, Function, OperandConstraint, OperandKind, TerminatorKind};
 use crate::output::{Allocation, AllocationKind, Output, OutputInst};
 use crate::reginfo::{RegClass, RegClassSet, RegInfo};
 
@@ -39,6 +39,52 @@ pub(crate) fn display_iter<I: IntoIterator<Item = impl fmt::Display>>(
     }
 }
 
+/// Helper type to format the successors of a branch terminator along with any
+/// outgoing block parameters.
+struct DisplayBranchTargets<'a, F> {
+    func: &'a F,
+    block: Block,
+}
+
+impl<F: Function> fmt::Display for DisplayBranchTargets<'_, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (succ_idx, &succ) in self.func.block_succs(self.block).iter().enumerate() {
+            if succ_idx != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{succ}")?;
+            let args = self.func.branch_blockparams(self.block, succ_idx);
+            if !args.is_empty() {
+                write!(f, "({})", display_iter(args, ","))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Helper type to format an operand constraint, including the contents of its
+/// alternative set if it has one.
+struct DisplayConstraint<'a, F> {
+    func: &'a F,
+    constraint: OperandConstraint,
+}
+
+impl<F: Function> fmt::Display for DisplayConstraint<'_, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let OperandConstraint::Alternatives(set) = self.constraint else {
+            return write!(f, "{}", self.constraint);
+        };
+        write!(f, "alt(")?;
+        for (idx, &(constraint, cost)) in self.func.alternative_set(set).iter().enumerate() {
+            if idx != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{constraint}={cost}")?;
+        }
+        write!(f, ")")
+    }
+}
+
 /// Wrapper around a type implementing [`Function`] that provides a [`Display`]
 /// implementation which dumps the function in a format that is both
 /// human-readable and machine-parseable.
@@ -58,17 +104,32 @@ impl<F: Function> fmt::Debug for DisplayFunction<'_, F> {
 
 impl<F: Function> fmt::Display for DisplayFunction<'_, F> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.allows_critical_edges() {
+            writeln!(f, "allow_critical_edges")?;
+        }
+
         // Value declarations
         for value in self.0.values() {
             let bank = self.0.value_bank(value);
             write!(f, "{value} = {bank}")?;
             if let Some((cost, class)) = self.0.can_rematerialize(value) {
-                let cost = match cost {
-                    RematCost::CheaperThanMove => "cheaper_than_move",
-                    RematCost::CheaperThanLoad => "cheaper_than_load",
-                };
                 write!(f, " remat({cost}, {class})")?;
             }
+            if let Some(input) = self.0.remat_input(value) {
+                write!(f, " remat_input({input})")?;
+            }
+            if self.0.is_reference(value) {
+                write!(f, " ref")?;
+            }
+            if let Some(inst) = self.0.value_scope_end(value) {
+                write!(f, " scope_end({inst})")?;
+            }
+            if let Some((reg, strength)) = self.0.value_hint(value) {
+                write!(f, " hint({reg}, {strength})")?;
+            }
+            if let Some(reg) = self.0.value_pinned_reg(value) {
+                write!(f, " pinned({reg})")?;
+            }
             writeln!(f)?;
         }
 
@@ -109,7 +170,10 @@ impl<F: Function> fmt::Display for DisplayFunction<'_, F> {
                     Some(TerminatorKind::Branch) => write!(
                         f,
                         "branch({})",
-                        display_iter(self.0.block_succs(block), ",")
+                        DisplayBranchTargets {
+                            func: self.0,
+                            block
+                        }
                     )?,
                 };
 
@@ -117,11 +181,23 @@ impl<F: Function> fmt::Display for DisplayFunction<'_, F> {
                 if self.0.can_eliminate_dead_inst(inst) {
                     write!(f, " pure")?;
                 }
+                if self.0.is_copy(inst) {
+                    write!(f, " copy")?;
+                }
+                if self.0.is_safepoint(inst) {
+                    write!(f, " safepoint")?;
+                }
+                if self.0.no_edits_before(inst) {
+                    write!(f, " no_edits_before")?;
+                }
 
                 // Operands and clobbers
                 for operand in self.0.inst_operands(inst) {
                     f.write_str(" ")?;
-                    let constraint = operand.constraint();
+                    let constraint = DisplayConstraint {
+                        func: self.0,
+                        constraint: operand.constraint(),
+                    };
                     match operand.kind() {
                         OperandKind::Def(value) => write!(f, "Def({value}):{constraint}")?,
                         OperandKind::Use(value) => write!(f, "Use({value}):{constraint}")?,
@@ -218,7 +294,12 @@ impl<R: RegInfo> fmt::Display for DisplayRegInfo<'_, R> {
             if self.0.bank_for_reg(reg).is_none() {
                 writeln!(f, " nonallocatable")?;
             } else {
-                writeln!(f, " {}", display_iter(self.0.reg_units(reg), ""))?;
+                write!(f, " {}", display_iter(self.0.reg_units(reg), ""))?;
+                let callee_saved_cost = self.0.callee_saved_cost(reg);
+                if callee_saved_cost != 0.0 {
+                    write!(f, " callee_saved({callee_saved_cost})")?;
+                }
+                writeln!(f)?;
             }
         }
 
@@ -346,7 +427,10 @@ impl<F: Function, R: RegInfo> fmt::Display for DisplayOutputInst<'_, F, R> {
                     Some(TerminatorKind::Branch) => write!(
                         f,
                         "branch({})",
-                        display_iter(func.block_succs(self.block), ",")
+                        DisplayBranchTargets {
+                            func,
+                            block: self.block
+                        }
                     )?,
                 };
 
@@ -354,6 +438,15 @@ impl<F: Function, R: RegInfo> fmt::Display for DisplayOutputInst<'_, F, R> {
                 if func.can_eliminate_dead_inst(inst) {
                     write!(f, " pure")?;
                 }
+                if func.is_copy(inst) {
+                    write!(f, " copy")?;
+                }
+                if func.is_safepoint(inst) {
+                    write!(f, " safepoint")?;
+                }
+                if func.no_edits_before(inst) {
+                    write!(f, " no_edits_before")?;
+                }
 
                 // Operands and clobbers
                 for (&operand, &alloc) in func.inst_operands(inst).iter().zip(operand_allocs) {
@@ -368,7 +461,9 @@ impl<F: Function, R: RegInfo> fmt::Display for DisplayOutputInst<'_, F, R> {
                         };
                         let class = match constraint {
                             OperandConstraint::Class(class) => class,
-                            OperandConstraint::Fixed(_) => unreachable!(),
+                            OperandConstraint::Fixed(_) | OperandConstraint::Alternatives(_) => {
+                                unreachable!()
+                            }
                             OperandConstraint::Reuse(target) => {
                                 let OperandConstraint::Class(class) =
                                     func.inst_operands(inst)[target].constraint()
@@ -413,9 +508,15 @@ impl<F: Function, R: RegInfo> fmt::Display for DisplayOutputInst<'_, F, R> {
                 for unit in func.inst_clobbers(inst) {
                     write!(f, " Clobber:{unit}")?;
                 }
+                for (value, alloc) in self.output.stack_map(inst) {
+                    write!(f, " StackMap({value}):{alloc}")?;
+                }
             }
-            OutputInst::Rematerialize { value, to } => {
+            OutputInst::Rematerialize { value, to, input } => {
                 write!(f, "remat {to} <- {value}")?;
+                if let Some(input) = input {
+                    write!(f, " from {input}")?;
+                }
             }
             OutputInst::Move { from, to, value } => {
                 if let Some(value) = value {
@@ -440,6 +541,13 @@ impl<F: Function, R: RegInfo> fmt::Display for Output<'_, F, R> {
         // Spill slots
         let spillslot_area_size = self.stack_layout().spillslot_area_size();
         writeln!(f, "spillslot_area_size = {spillslot_area_size}")?;
+        let scalable_spillslot_area_size = self.stack_layout().scalable_spillslot_area_size();
+        if scalable_spillslot_area_size != 0 {
+            writeln!(
+                f,
+                "scalable_spillslot_area_size = {scalable_spillslot_area_size}vl"
+            )?;
+        }
         for slot in self.stack_layout().spillslots() {
             let offset = self.stack_layout().spillslot_offset(slot);
             let size = self.stack_layout().spillslot_size(slot);
@@ -474,12 +582,60 @@ impl<F: Function, R: RegInfo> fmt::Display for Output<'_, F, R> {
             }
         }
 
+        // Edge blocks
+        for edge in self.edge_blocks() {
+            writeln!(f)?;
+            writeln!(f, "{}: ; edge {} -> {}", edge.block, edge.pred, edge.succ)?;
+            for inst in self.output_insts(edge.block) {
+                writeln!(
+                    f,
+                    "    {}",
+                    DisplayOutputInst {
+                        inst,
+                        block: edge.block,
+                        output: self
+                    }
+                )?;
+            }
+        }
+
+        // Edge edits, if they were separated from the blocks
+        if self.edge_edits_separated() {
+            for pred in func.blocks() {
+                for (succ_idx, &succ) in func.block_succs(pred).iter().enumerate() {
+                    let mut edits = self.edge_edits(pred, succ_idx).peekable();
+                    if edits.peek().is_none() {
+                        continue;
+                    }
+                    writeln!(f)?;
+                    writeln!(f, "; edge {pred} -> {succ}")?;
+                    for inst in edits {
+                        writeln!(
+                            f,
+                            "    {}",
+                            DisplayOutputInst {
+                                inst,
+                                block: pred,
+                                output: self
+                            }
+                        )?;
+                    }
+                }
+            }
+        }
+
         // Value locations
         writeln!(f)?;
         for (value, range, alloc) in self.value_locations() {
             writeln!(f, "{value} in {range} => {alloc}")?;
         }
 
+        // Precise value locations in the output instruction stream
+        writeln!(f)?;
+        for (value, start, end, alloc) in self.value_location_ranges() {
+            writeln!(f, "{value} at {start}..{} => {alloc}", end.index)?;
+        }
+
         Ok(())
     }
 }
@@ -1,6 +1,7 @@
 use alloc::vec;
 use alloc::vec::Vec;
 use core::ops::RangeInclusive;
+use core::slice;
 
 use arbitrary::{Result, Unstructured};
 
@@ -10,10 +11,12 @@ use crate::debug_utils::generic_function::ValueData;
 use crate::debug_utils::postorder::PostOrder;
 use crate::entity::{PrimaryMap, SecondaryMap};
 use crate::function::{
-    Block, Function, Inst, InstRange, Operand, OperandConstraint, OperandKind, RematCost,
+    AlternativeSet, Block, Function, Inst, InstRange, Operand, OperandConstraint, OperandKind,
     TerminatorKind, Value,
 };
-use crate::reginfo::{MAX_REG_UNITS, PhysReg, RegBank, RegClass, RegInfo, RegUnit, RegUnitSet};
+use crate::reginfo::{
+    MAX_REG_UNITS, PhysReg, PhysRegSet, RegBank, RegClass, RegInfo, RegUnit, RegUnitSet,
+};
 
 /// Configuration options for [`GenericFunction::arbitrary_with_config`].
 ///
@@ -46,6 +49,11 @@ pub struct ArbitraryFunctionConfig {
 
     /// Number of clobbers per instruction.
     pub clobbers_per_inst: RangeInclusive<usize>,
+
+    /// Whether to generate a function with critical edges, which sets
+    /// [`Function::allows_critical_edges`]. Block parameters on critical
+    /// edges are passed with [`Function::branch_blockparams`].
+    pub critical_edges: bool,
 }
 
 impl Default for ArbitraryFunctionConfig {
@@ -57,6 +65,7 @@ impl Default for ArbitraryFunctionConfig {
             defs_per_inst: 0..=20,
             uses_per_inst: 0..=20,
             clobbers_per_inst: 0..=20,
+            critical_edges: false,
         }
     }
 }
@@ -94,6 +103,10 @@ impl GenericFunction {
         }
 
         builder.finalize()?;
+        builder.check_no_edits_sequences();
+        builder.add_remat_inputs();
+        builder.add_scope_ends()?;
+        builder.add_pinned_regs()?;
 
         Ok(builder.func)
     }
@@ -150,6 +163,9 @@ struct FunctionBuilder<'a, 'b, R> {
 
     /// Def operands which are suitable for `OperandConstraint::Reuse`.
     reuse_operands: Vec<usize>,
+
+    /// Rematerializable values which should depend on an input value.
+    remat_input_values: Vec<Value>,
 }
 
 impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
@@ -160,6 +176,8 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
             insts: PrimaryMap::new(),
             values: PrimaryMap::new(),
             value_groups: PrimaryMap::new(),
+            alternative_sets: PrimaryMap::new(),
+            allow_critical_edges: config.critical_edges,
         };
 
         let mut class_per_bank: SecondaryMap<RegBank, Vec<RegClass>> =
@@ -206,6 +224,7 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
             early_fixed: RegUnitSet::new(),
             late_fixed: RegUnitSet::new(),
             reuse_operands: vec![],
+            remat_input_values: vec![],
         }
     }
 
@@ -226,6 +245,7 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
             succs: vec![],
             block_params_in: vec![],
             block_params_out: vec![],
+            branch_params_out: vec![],
             immediate_dominator: None.into(),
             frequency: entry_frequency,
             is_critical_edge: false,
@@ -235,6 +255,10 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
         // successors only jump to blocks with a single predecessors, and that
         // blocks with multiple predecessors are only jumped to from blocks with
         // a single successor.
+        //
+        // If critical edges are allowed then any block can be linked to any
+        // existing block other than the entry block.
+        let critical_edges = self.config.critical_edges;
         let mut can_add_succ = vec![Block::ENTRY_BLOCK];
         let mut can_add_pred = vec![];
 
@@ -251,12 +275,21 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
             // If the chosen block has no successors, try linking it to an
             // existing block that accepts predecessors.
             if !can_add_pred.is_empty()
-                && self.func.blocks[from].succs.is_empty()
+                && (critical_edges || self.func.blocks[from].succs.is_empty())
                 && self.u.arbitrary()?
             {
                 to = Some(*self.u.choose(&can_add_pred)?);
             }
 
+            // Multiple edges between 2 blocks are only allowed if critical
+            // edges are.
+            if let Some(existing) = to
+                && !critical_edges
+                && self.func.blocks[from].succs.contains(&existing)
+            {
+                to = None;
+            }
+
             if let Some(to) = to {
                 // Create an edge to an existing block.
                 self.func.blocks[from].succs.push(to);
@@ -264,7 +297,7 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
 
                 // If the `to` block now has multiple predecessors, prevent
                 // adding new successors to them.
-                if self.func.blocks[to].preds.len() > 1 {
+                if !critical_edges && self.func.blocks[to].preds.len() > 1 {
                     can_add_succ.retain(|b| !self.func.blocks[to].preds.contains(b));
                 }
             } else {
@@ -276,6 +309,7 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
                     succs: vec![],
                     block_params_in: vec![],
                     block_params_out: vec![],
+                    branch_params_out: vec![],
                     immediate_dominator: None.into(),
                     frequency,
                     is_critical_edge: self.u.arbitrary()?,
@@ -284,7 +318,7 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
 
                 // We can add more predecessors to the new block if it is the
                 // only successor of `from`.
-                if self.func.blocks[from].succs.len() == 1 {
+                if critical_edges || self.func.blocks[from].succs.len() == 1 {
                     can_add_pred.push(to);
                 }
                 can_add_succ.push(to);
@@ -292,7 +326,7 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
 
             // If the `from` block now has multiple successors, prevent
             // adding new predecessors to them.
-            if self.func.blocks[from].succs.len() > 1 {
+            if !critical_edges && self.func.blocks[from].succs.len() > 1 {
                 can_add_pred.retain(|b| !self.func.blocks[from].succs.contains(b));
             }
         }
@@ -303,17 +337,33 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
     /// Defines a new value with randomized properties.
     fn new_value(&mut self, bank: RegBank) -> Result<Value> {
         let remat = if !self.remat_class_per_bank[bank].is_empty() && self.u.arbitrary()? {
-            let cost = if self.u.arbitrary()? {
-                RematCost::CheaperThanMove
-            } else {
-                RematCost::CheaperThanLoad
-            };
+            let cost = f32::from(self.u.int_in_range(0..=5u8)?) * 0.25;
             let class = *self.u.choose(&self.remat_class_per_bank[bank])?;
             Some((cost, class))
         } else {
             None
         };
-        let value = self.func.values.push(ValueData { bank, remat });
+        let is_reference = self.u.arbitrary()?;
+        let hint = if !self.reg_per_bank[bank].is_empty() && self.u.arbitrary()? {
+            let reg = *self.u.choose(&self.reg_per_bank[bank])?;
+            let strength = f32::from(self.u.int_in_range(0..=8u8)?) * 0.25;
+            Some((reg, strength))
+        } else {
+            None
+        };
+        let wants_remat_input = remat.is_some() && self.u.ratio(1, 4)?;
+        let value = self.func.values.push(ValueData {
+            bank,
+            remat,
+            remat_input: None,
+            is_reference,
+            scope_end: None,
+            hint,
+            pinned_reg: None,
+        });
+        if wants_remat_input {
+            self.remat_input_values.push(value);
+        }
         Ok(value)
     }
 
@@ -322,7 +372,15 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
         // The entry block cannot have blockparams.
         for block in self.func.blocks.keys().skip(1) {
             // Blockparams are only allowed if we have multiple predecessors.
-            if self.func.blocks[block].preds.len() <= 1 {
+            // Predecessors on a critical edge pass them as branch arguments,
+            // which is only used when critical edges are allowed.
+            if self.func.blocks[block].preds.len() <= 1
+                || (!self.config.critical_edges
+                    && self.func.blocks[block]
+                        .preds
+                        .iter()
+                        .any(|&pred| self.func.blocks[pred].succs.len() > 1))
+            {
                 continue;
             }
 
@@ -375,10 +433,27 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
                     block,
                     terminator_kind: Some(TerminatorKind::Jump),
                     is_pure: false,
+                    is_copy: false,
+                    is_safepoint: false,
+                    no_edits_before: false,
                 });
             }
         }
 
+        // Set up outgoing blockparams for each successor of a branch. These
+        // can also be defined by the branch itself.
+        if self.func.blocks[block].succs.len() > 1 {
+            for succ_idx in 0..self.func.blocks[block].succs.len() {
+                let succ = self.func.blocks[block].succs[succ_idx];
+                let mut args = vec![];
+                for idx in 0..self.func.blocks[succ].block_params_in.len() {
+                    let bank = self.func.values[self.func.blocks[succ].block_params_in[idx]].bank;
+                    args.push(self.get_value_for_use(bank, block)?);
+                }
+                self.func.blocks[block].branch_params_out.push(args);
+            }
+        }
+
         // We need a terminator instruction if an empty one wasn't created
         // above. This one is allowed to have operands like a normal
         // instruction.
@@ -386,6 +461,8 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
             let is_ret = self.func.blocks[block].succs.is_empty();
             let mut terminator = self.gen_inst(block, num_insts == 0, is_ret)?;
             terminator.is_pure = false;
+            terminator.is_copy = false;
+            terminator.is_safepoint = false;
             terminator.terminator_kind = Some(if is_ret {
                 TerminatorKind::Ret
             } else {
@@ -485,6 +562,37 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
         Ok(value)
     }
 
+    /// Generates a set of alternative constraints for a value in the given
+    /// bank.
+    ///
+    /// The first alternative is always a class so that there is at least one
+    /// alternative which doesn't conflict with other fixed-register operands.
+    /// Returns `None` if the bank has no single-register class.
+    fn gen_alternatives(&mut self, bank: RegBank) -> Result<Option<AlternativeSet>> {
+        let classes: Vec<RegClass> = self.class_per_bank[bank]
+            .iter()
+            .copied()
+            .filter(|&class| self.reginfo.class_group_size(class) == 1)
+            .collect();
+        if classes.is_empty() {
+            return Ok(None);
+        }
+        let costs = [0.0, 0.5, 1.0, 4.0];
+        let mut alternatives = vec![(
+            OperandConstraint::Class(*self.u.choose(&classes)?),
+            *self.u.choose(&costs)?,
+        )];
+        for _ in 0..self.u.int_in_range(0..=2)? {
+            let constraint = if self.u.arbitrary()? {
+                OperandConstraint::Class(*self.u.choose(&classes)?)
+            } else {
+                OperandConstraint::Fixed(*self.u.choose(&self.reg_per_bank[bank])?)
+            };
+            alternatives.push((constraint, *self.u.choose(&costs)?));
+        }
+        Ok(Some(self.func.alternative_sets.push(alternatives)))
+    }
+
     /// Generates an operand which uses a value.
     fn gen_use(&mut self, first_block_for_def: Block, inst: &mut InstData) -> Result<Operand> {
         // Generate a NonAllocatable operand if we have non-allocatable registers.
@@ -498,7 +606,28 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
         if self.u.arbitrary()? {
             let bank = RegBank::new(self.u.choose_index(self.reginfo.num_banks())?);
             let reg = *self.u.choose(&self.reg_per_bank[bank])?;
-            if self.check_fixed_conflict(reg, true, false) {
+
+            // Sometimes also tie a Def from the same bank to this register,
+            // which then also needs the register after the instruction.
+            let tied_def = self.reuse_operands.iter().position(|&idx| {
+                matches!(
+                    inst.operands[idx].kind(),
+                    OperandKind::Def(value) | OperandKind::EarlyDef(value)
+                        if self.func.values[value].bank == bank
+                )
+            });
+            let tied_def = match tied_def {
+                Some(idx) if self.u.arbitrary()? => Some(idx),
+                _ => None,
+            };
+            if self.check_fixed_conflict(reg, true, tied_def.is_some()) {
+                if let Some(idx) = tied_def {
+                    let def_idx = self.reuse_operands.swap_remove(idx);
+                    inst.operands[def_idx] = Operand::new(
+                        inst.operands[def_idx].kind(),
+                        OperandConstraint::Reuse(inst.operands.len()),
+                    );
+                }
                 let value = self.get_value_for_use(bank, first_block_for_def)?;
                 return Ok(Operand::new(
                     OperandKind::Use(value),
@@ -507,6 +636,18 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
             }
         }
 
+        // Occasionally allow a choice of constraints.
+        if self.u.ratio(1, 8)? {
+            let bank = RegBank::new(self.u.choose_index(self.reginfo.num_banks())?);
+            if let Some(set) = self.gen_alternatives(bank)? {
+                let value = self.get_value_for_use(bank, first_block_for_def)?;
+                return Ok(Operand::new(
+                    OperandKind::Use(value),
+                    OperandConstraint::Alternatives(set),
+                ));
+            }
+        }
+
         let class = if !self.reuse_operands.is_empty() && self.u.arbitrary()? {
             // Reuse the class of a Def and turn that Def into a Reuse.
             let idx = self.u.choose_index(self.reuse_operands.len())?;
@@ -563,6 +704,18 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
             }
         }
 
+        // Occasionally allow a choice of constraints.
+        if self.u.ratio(1, 8)? {
+            if let Some(set) = self.gen_alternatives(bank)? {
+                let kind = if !is_ret && self.u.arbitrary()? {
+                    OperandKind::Def(value)
+                } else {
+                    OperandKind::EarlyDef(value)
+                };
+                return Ok(Operand::new(kind, OperandConstraint::Alternatives(set)));
+            }
+        }
+
         // Pick a register class to use.
         let class = *self.u.choose(&self.class_per_bank[bank])?;
         let group_size = self.reginfo.class_group_size(class);
@@ -607,6 +760,80 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
         Ok(Operand::new(kind, OperandConstraint::Class(class)))
     }
 
+    /// Turns `inst` into a copy between two values of the same bank.
+    ///
+    /// Returns false if the chosen bank has no single-register class.
+    fn gen_copy(&mut self, block: Block, inst: &mut InstData) -> Result<bool> {
+        let bank = RegBank::new(self.u.choose_index(self.reginfo.num_banks())?);
+        let classes: Vec<RegClass> = self.class_per_bank[bank]
+            .iter()
+            .copied()
+            .filter(|&class| self.reginfo.class_group_size(class) == 1)
+            .collect();
+        if classes.is_empty() {
+            return Ok(false);
+        }
+
+        // Copy into a value that was previously used if possible, otherwise
+        // generate a new dead value.
+        let defs = &self.defs_by_blocks[block];
+        let dst = match defs.iter().position(|&v| self.func.values[v].bank == bank) {
+            Some(idx) if self.u.arbitrary()? => self.defs_by_blocks[block].remove(idx),
+            _ => self.new_value(bank)?,
+        };
+        let src = self.get_value_for_use(bank, block)?;
+        inst.operands.push(Operand::new(
+            OperandKind::Def(dst),
+            OperandConstraint::Class(*self.u.choose(&classes)?),
+        ));
+        inst.operands.push(Operand::new(
+            OperandKind::Use(src),
+            OperandConstraint::Class(*self.u.choose(&classes)?),
+        ));
+        inst.is_safepoint = false;
+        inst.is_copy = true;
+        Ok(true)
+    }
+
+    /// Turns `inst` into an instruction with a few `Class` operands of a single
+    /// bank, which always use the same class for that bank.
+    ///
+    /// Sequences of such instructions satisfy the restrictions of
+    /// `Function::no_edits_before`. Returns false if the chosen bank has no
+    /// single-register class.
+    fn gen_simple_inst(&mut self, block: Block, inst: &mut InstData) -> Result<bool> {
+        let bank = RegBank::new(self.u.choose_index(self.reginfo.num_banks())?);
+        let Some(&class) = self.class_per_bank[bank]
+            .iter()
+            .find(|&&class| self.reginfo.class_group_size(class) == 1)
+        else {
+            return Ok(false);
+        };
+
+        for _ in 0..self.u.int_in_range(0..=2)? {
+            let defs = &self.defs_by_blocks[block];
+            let value = match defs.iter().position(|&v| self.func.values[v].bank == bank) {
+                Some(idx) if self.u.arbitrary()? => self.defs_by_blocks[block].remove(idx),
+                _ => self.new_value(bank)?,
+            };
+            inst.operands.push(if self.u.arbitrary()? {
+                Operand::regclass_def(value, class)
+            } else {
+                Operand::regclass_early_def(value, class)
+            });
+        }
+        for _ in 0..self.u.int_in_range(0..=2)? {
+            let value = self.get_value_for_use(bank, block)?;
+            inst.operands.push(Operand::regclass_use(value, class));
+        }
+        inst.is_safepoint = false;
+
+        // Request that edits are forbidden before this instruction. This is
+        // only kept later if the restrictions are satisfied.
+        inst.no_edits_before = self.u.ratio(3, 4)?;
+        Ok(true)
+    }
+
     /// Generates a single instruction in the given block.
     fn gen_inst(&mut self, block: Block, is_first_inst: bool, is_ret: bool) -> Result<InstData> {
         // These are temporary for the scope of this instruction.
@@ -620,10 +847,33 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
             block,
             terminator_kind: None,
             is_pure: self.u.arbitrary()?,
+            is_copy: false,
+            is_safepoint: false,
+            no_edits_before: false,
         };
+        if !inst.is_pure {
+            inst.is_safepoint = self.u.arbitrary()?;
+        }
+
+        // Occasionally generate a copy instead of a normal instruction.
+        if !is_first_inst && !is_ret && self.u.ratio(1, 8)? && self.gen_copy(block, &mut inst)? {
+            return Ok(inst);
+        }
+
+        // Also generate simple instructions which may be part of a sequence
+        // that forbids edits.
+        let simple = !is_first_inst
+            && !is_ret
+            && self.u.ratio(1, 4)?
+            && self.gen_simple_inst(block, &mut inst)?;
 
         // Add operands which define values.
-        for _ in 0..self.u.int_in_range(self.config.defs_per_inst.clone())? {
+        let num_defs = if simple {
+            0
+        } else {
+            self.u.int_in_range(self.config.defs_per_inst.clone())?
+        };
+        for _ in 0..num_defs {
             // Generate a value that was previously used, otherwise generate a
             // new dead value.
             let value = if self.u.arbitrary()? && !self.defs_by_blocks[block].is_empty() {
@@ -657,7 +907,9 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
         };
 
         // Add operands which use values.
-        if let Some(first_block_for_def) = first_block_for_def {
+        if let Some(first_block_for_def) = first_block_for_def
+            && !simple
+        {
             for _ in 0..self.u.int_in_range(self.config.uses_per_inst.clone())? {
                 let op = self.gen_use(first_block_for_def, &mut inst)?;
                 inst.operands.push(op);
@@ -674,6 +926,35 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
                 self.late_fixed.insert(unit);
                 inst.clobbers.push(unit);
             }
+
+            // Occasionally emulate a call which clobbers every register that
+            // isn't callee-saved. Only do this if all definitions are fixed
+            // since there may not be any registers left otherwise.
+            let only_fixed_defs = inst.operands.iter().all(|op| match op.kind() {
+                OperandKind::Def(_) | OperandKind::EarlyDef(_) => {
+                    matches!(op.constraint(), OperandConstraint::Fixed(_))
+                }
+                OperandKind::DefGroup(_) | OperandKind::EarlyDefGroup(_) => false,
+                OperandKind::Use(_) | OperandKind::UseGroup(_) | OperandKind::NonAllocatable => {
+                    true
+                }
+            });
+            if only_fixed_defs && self.u.ratio(1, 10)? {
+                for reg in self.reginfo.regs() {
+                    if self.reginfo.bank_for_reg(reg).is_none()
+                        || self.reginfo.is_memory(reg)
+                        || self.reginfo.callee_saved_cost(reg) != 0.0
+                    {
+                        continue;
+                    }
+                    for unit in self.reginfo.reg_units(reg) {
+                        if !self.late_fixed.contains(unit) {
+                            self.late_fixed.insert(unit);
+                            inst.clobbers.push(unit);
+                        }
+                    }
+                }
+            }
         }
 
         Ok(inst)
@@ -694,4 +975,300 @@ impl<'a, 'b, R: RegInfo> FunctionBuilder<'a, 'b, R> {
         }
         Ok(())
     }
+
+    /// Only keeps `no_edits_before` on instructions which satisfy its
+    /// restrictions.
+    ///
+    /// This is done after all instructions have been generated since the
+    /// restrictions depend on the preceding instructions.
+    fn check_no_edits_sequences(&mut self) {
+        for block in self.func.blocks.keys() {
+            let insts = self.func.blocks[block].insts;
+            self.func.insts[insts.from].no_edits_before = false;
+            let mut seq_start = insts.from;
+            for inst in insts.iter().skip(1) {
+                if !self.func.insts[inst].no_edits_before
+                    || !self.can_extend_edit_sequence(seq_start, inst)
+                {
+                    self.func.insts[inst].no_edits_before = false;
+                    seq_start = inst;
+                }
+            }
+        }
+    }
+
+    /// Gives an input value to the rematerializable values selected in
+    /// `new_value`.
+    ///
+    /// This is done after all instructions have been generated since the input
+    /// must be defined before the value.
+    fn add_remat_inputs(&mut self) {
+        for i in 0..self.remat_input_values.len() {
+            let value = self.remat_input_values[i];
+            self.func.values[value].remat_input = self.find_remat_input(value);
+        }
+    }
+
+    /// Finds the closest non-rematerializable value whose definition dominates
+    /// that of `value`.
+    fn find_remat_input(&self, value: Value) -> Option<Value> {
+        // Find the block defining the value and the instructions preceding the
+        // definition in that block. Block parameters are defined together so
+        // none of them can be the input of another.
+        let (mut block, mut insts) = self.func.blocks.iter().find_map(|(block, data)| {
+            if data.block_params_in.contains(&value) {
+                return Some((block, None));
+            }
+            let inst = data
+                .insts
+                .iter()
+                .find(|&inst| self.inst_defs(inst).any(|def| def == value))?;
+            Some((block, Some(InstRange::new(data.insts.from, inst))))
+        })?;
+
+        // Walk up the dominator tree, searching for a suitable definition.
+        let is_candidate = |value: Value| self.func.values[value].remat.is_none();
+        loop {
+            if let Some(insts) = insts {
+                for inst in insts.iter().rev() {
+                    if let Some(input) = self.inst_defs(inst).find(|&def| is_candidate(def)) {
+                        return Some(input);
+                    }
+                }
+                let params = &self.func.blocks[block].block_params_in;
+                if let Some(&input) = params.iter().find(|&&param| is_candidate(param)) {
+                    return Some(input);
+                }
+            }
+            block = self.domtree.immediate_dominator(block)?;
+            insts = Some(self.func.blocks[block].insts);
+        }
+    }
+
+    /// Returns the values defined by an instruction.
+    fn inst_defs(&self, inst: Inst) -> impl Iterator<Item = Value> + '_ {
+        self.func.insts[inst].operands.iter().flat_map(|op| {
+            let (value, group) = match op.kind() {
+                OperandKind::Def(value) | OperandKind::EarlyDef(value) => (Some(value), &[][..]),
+                OperandKind::DefGroup(group) | OperandKind::EarlyDefGroup(group) => {
+                    (None, &self.func.value_groups[group][..])
+                }
+                OperandKind::Use(_) | OperandKind::UseGroup(_) | OperandKind::NonAllocatable => {
+                    (None, &[][..])
+                }
+            };
+            value.into_iter().chain(group.iter().copied())
+        })
+    }
+
+    /// Checks whether the sequence of instructions starting at `seq_start` can
+    /// be extended to include `inst`, which immediately follows it.
+    fn can_extend_edit_sequence(&self, seq_start: Inst, inst: Inst) -> bool {
+        let prev = inst.prev();
+        if self.func.terminator_kind(inst) == Some(TerminatorKind::Jump)
+            || self.func.is_safepoint(inst)
+            || self.func.is_safepoint(prev)
+        {
+            return false;
+        }
+
+        // The preceding instruction can't have fixed definitions.
+        let fixed_def = self.func.inst_operands(prev).iter().any(|op| {
+            !matches!(
+                op.kind(),
+                OperandKind::Use(_) | OperandKind::UseGroup(_) | OperandKind::NonAllocatable
+            ) && !matches!(op.constraint(), OperandConstraint::Class(_))
+        });
+        if fixed_def {
+            return false;
+        }
+
+        // All values in the sequence must have the same class in every
+        // operand, and the instructions after the first may only have such
+        // operands.
+        let mut classes: Vec<(Value, RegClass)> = vec![];
+        for inst in InstRange::new(seq_start.next(), inst.next()).iter() {
+            for op in self.func.inst_operands(inst) {
+                let (OperandKind::Use(value)
+                | OperandKind::Def(value)
+                | OperandKind::EarlyDef(value)) = op.kind()
+                else {
+                    if op.kind() == OperandKind::NonAllocatable {
+                        continue;
+                    }
+                    return false;
+                };
+                let OperandConstraint::Class(class) = op.constraint() else {
+                    return false;
+                };
+                match classes.iter().find(|&&(v, _)| v == value) {
+                    Some(&(_, existing)) if existing != class => return false,
+                    Some(_) => {}
+                    None => classes.push((value, class)),
+                }
+            }
+        }
+        self.func.inst_operands(seq_start).iter().all(|op| {
+            let kind = op.kind();
+            let values = match kind {
+                OperandKind::Use(ref value)
+                | OperandKind::Def(ref value)
+                | OperandKind::EarlyDef(ref value) => slice::from_ref(value),
+                OperandKind::UseGroup(group)
+                | OperandKind::DefGroup(group)
+                | OperandKind::EarlyDefGroup(group) => self.func.value_group_members(group),
+                OperandKind::NonAllocatable => return true,
+            };
+            let class = match op.constraint() {
+                OperandConstraint::Class(class) if values.len() == 1 => Some(class),
+                OperandConstraint::Fixed(_) if matches!(kind, OperandKind::Use(_)) => return true,
+                _ => None,
+            };
+            values
+                .iter()
+                .all(|&value| classes.iter().all(|&(v, c)| v != value || Some(c) == class))
+        })
+    }
+
+    /// Extends the scope of some used values beyond one of their uses.
+    ///
+    /// The scope end is placed either later in the block of the use or in a
+    /// block dominated by it so that it is always dominated by the value's
+    /// definition.
+    fn add_scope_ends(&mut self) -> Result<()> {
+        for inst in self.func.insts.keys() {
+            for i in 0..self.func.insts[inst].operands.len() {
+                let OperandKind::Use(value) = self.func.insts[inst].operands[i].kind() else {
+                    continue;
+                };
+                if !self.u.ratio(1, 4)? {
+                    continue;
+                }
+                let use_block = self.func.insts[inst].block;
+                let block = Block::new(self.u.choose_index(self.func.blocks.len())?);
+                let range = if block != use_block && self.domtree.dominates(use_block, block) {
+                    self.func.blocks[block].insts
+                } else {
+                    InstRange::new(inst, self.func.blocks[use_block].insts.to)
+                };
+                let scope_end = Inst::new(
+                    self.u
+                        .int_in_range(range.from.index()..=range.last().index())?,
+                );
+                self.func.values[value].scope_end = Some(scope_end);
+            }
+        }
+        Ok(())
+    }
+
+    /// Pins some values to a physical register.
+    ///
+    /// The register is chosen so that it satisfies the register class of every
+    /// operand of the value. Values that are part of a value group can't be
+    /// pinned.
+    ///
+    /// Pinned registers are never used by fixed operands or clobbers and are
+    /// only pinned for one value, so that the allocation usually succeeds.
+    fn add_pinned_regs(&mut self) -> Result<()> {
+        if !self.u.ratio(1, 8)? {
+            return Ok(());
+        }
+
+        // Collect all register units which have a fixed reservation.
+        let mut fixed_units = RegUnitSet::new();
+        for inst in self.func.insts.values() {
+            fixed_units.extend(inst.clobbers.iter().copied());
+            for op in &inst.operands {
+                match op.constraint() {
+                    OperandConstraint::Fixed(reg) => {
+                        fixed_units.extend(self.reginfo.reg_units(reg));
+                    }
+                    OperandConstraint::Alternatives(set) => {
+                        for &(constraint, _) in &self.func.alternative_sets[set] {
+                            if let OperandConstraint::Fixed(reg) = constraint {
+                                fixed_units.extend(self.reginfo.reg_units(reg));
+                            }
+                        }
+                    }
+                    OperandConstraint::Class(_) | OperandConstraint::Reuse(_) => {}
+                }
+            }
+        }
+
+        let mut candidates: SecondaryMap<Value, Option<PhysRegSet>> =
+            SecondaryMap::with_max_index(self.func.values.len());
+        for (value, value_data) in &self.func.values {
+            candidates[value] = Some(
+                self.reg_per_bank[value_data.bank]
+                    .iter()
+                    .copied()
+                    .filter(|&reg| {
+                        !self
+                            .reginfo
+                            .reg_units(reg)
+                            .any(|unit| fixed_units.contains(unit))
+                    })
+                    .collect(),
+            );
+        }
+        for inst in self.func.insts.values() {
+            for op in &inst.operands {
+                match op.kind() {
+                    OperandKind::Def(value)
+                    | OperandKind::EarlyDef(value)
+                    | OperandKind::Use(value) => {
+                        let constraint = match op.constraint() {
+                            OperandConstraint::Reuse(target) => inst.operands[target].constraint(),
+                            constraint => constraint,
+                        };
+                        match constraint {
+                            OperandConstraint::Class(class) => {
+                                if let Some(regs) = &mut candidates[value] {
+                                    *regs &= self.reginfo.class_members(class);
+                                }
+                            }
+                            // Don't pin values with alternatives so that the
+                            // selected alternative is never affected.
+                            OperandConstraint::Alternatives(_) => candidates[value] = None,
+                            OperandConstraint::Fixed(_) | OperandConstraint::Reuse(_) => {}
+                        }
+                    }
+                    OperandKind::DefGroup(group)
+                    | OperandKind::EarlyDefGroup(group)
+                    | OperandKind::UseGroup(group) => {
+                        for &value in &self.func.value_groups[group] {
+                            candidates[value] = None;
+                        }
+                    }
+                    OperandKind::NonAllocatable => {}
+                }
+            }
+        }
+
+        let mut pinned_units = RegUnitSet::new();
+        for value in self.func.values.keys() {
+            let Some(regs) = candidates[value] else {
+                continue;
+            };
+            if regs.is_empty() || !self.u.ratio(1, 32)? {
+                continue;
+            }
+            let regs: Vec<PhysReg> = regs
+                .into_iter()
+                .filter(|&reg| {
+                    !self
+                        .reginfo
+                        .reg_units(reg)
+                        .any(|unit| pinned_units.contains(unit))
+                })
+                .collect();
+            if regs.is_empty() {
+                continue;
+            }
+            let reg = *self.u.choose(&regs)?;
+            pinned_units.extend(self.reginfo.reg_units(reg));
+            self.func.values[value].pinned_reg = Some(reg);
+        }
+        Ok(())
+    }
 }
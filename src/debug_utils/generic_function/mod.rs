@@ -7,9 +7,10 @@ use core::fmt;
 use crate::entity::PrimaryMap;
 use crate::entity::packed_option::PackedOption;
 use crate::function::{
-    Block, Function, Inst, InstRange, Operand, RematCost, TerminatorKind, Value, ValueGroup,
+    AlternativeSet, Block, Function, Inst, InstRange, Operand, OperandConstraint, TerminatorKind,
+    Value, ValueGroup,
 };
-use crate::reginfo::{RegBank, RegClass, RegUnit};
+use crate::reginfo::{PhysReg, RegBank, RegClass, RegUnit};
 
 #[cfg(feature = "arbitrary")]
 mod arbitrary;
@@ -28,6 +29,7 @@ struct BlockData {
     succs: Vec<Block>,
     block_params_in: Vec<Value>,
     block_params_out: Vec<Value>,
+    branch_params_out: Vec<Vec<Value>>,
     immediate_dominator: PackedOption<Block>,
     frequency: f32,
     is_critical_edge: bool,
@@ -41,13 +43,21 @@ struct InstData {
     block: Block,
     terminator_kind: Option<TerminatorKind>,
     is_pure: bool,
+    is_copy: bool,
+    is_safepoint: bool,
+    no_edits_before: bool,
 }
 
 #[derive(Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct ValueData {
     bank: RegBank,
-    remat: Option<(RematCost, RegClass)>,
+    remat: Option<(f32, RegClass)>,
+    remat_input: Option<Value>,
+    is_reference: bool,
+    scope_end: Option<Inst>,
+    hint: Option<(PhysReg, f32)>,
+    pinned_reg: Option<PhysReg>,
 }
 
 /// A generic implementation of [`Function`] which can be constructed from an
@@ -63,6 +73,8 @@ pub struct GenericFunction {
     insts: PrimaryMap<Inst, InstData>,
     values: PrimaryMap<Value, ValueData>,
     value_groups: PrimaryMap<ValueGroup, Vec<Value>>,
+    alternative_sets: PrimaryMap<AlternativeSet, Vec<(OperandConstraint, f32)>>,
+    allow_critical_edges: bool,
 }
 
 impl fmt::Debug for GenericFunction {
@@ -85,6 +97,7 @@ impl GenericFunction {
         let mut insts = PrimaryMap::new();
         let mut values = PrimaryMap::new();
         let mut value_groups = PrimaryMap::new();
+        let mut alternative_sets = PrimaryMap::new();
         for block in func.blocks() {
             blocks.push(BlockData {
                 insts: func.block_insts(block),
@@ -92,6 +105,9 @@ impl GenericFunction {
                 succs: func.block_succs(block).into(),
                 block_params_in: func.block_params(block).into(),
                 block_params_out: func.jump_blockparams(block).into(),
+                branch_params_out: (0..func.block_succs(block).len())
+                    .map(|succ_idx| func.branch_blockparams(block, succ_idx).into())
+                    .collect(),
                 frequency: func.block_frequency(block),
                 immediate_dominator: func.block_immediate_dominator(block).into(),
                 is_critical_edge: func.block_is_critical_edge(block),
@@ -104,22 +120,35 @@ impl GenericFunction {
                 block: func.inst_block(inst),
                 terminator_kind: func.terminator_kind(inst),
                 is_pure: func.can_eliminate_dead_inst(inst),
+                is_copy: func.is_copy(inst),
+                is_safepoint: func.is_safepoint(inst),
+                no_edits_before: func.no_edits_before(inst),
             });
         }
         for value in func.values() {
             values.push(ValueData {
                 bank: func.value_bank(value),
                 remat: func.can_rematerialize(value),
+                remat_input: func.remat_input(value),
+                is_reference: func.is_reference(value),
+                scope_end: func.value_scope_end(value),
+                hint: func.value_hint(value),
+                pinned_reg: func.value_pinned_reg(value),
             });
         }
         for group in func.value_groups() {
             value_groups.push(func.value_group_members(group).into());
         }
+        for set in func.alternative_sets() {
+            alternative_sets.push(func.alternative_set(set).into());
+        }
         Self {
             blocks,
             insts,
             values,
             value_groups,
+            alternative_sets,
+            allow_critical_edges: func.allows_critical_edges(),
         }
     }
 }
@@ -170,6 +199,14 @@ impl Function for GenericFunction {
         &self.blocks[block].block_params_out
     }
 
+    #[inline]
+    fn branch_blockparams(&self, block: Block, succ_idx: usize) -> &[Value] {
+        self.blocks[block]
+            .branch_params_out
+            .get(succ_idx)
+            .map_or(&[], |params| params)
+    }
+
     #[inline]
     fn inst_block(&self, inst: Inst) -> Block {
         self.insts[inst].block
@@ -185,6 +222,11 @@ impl Function for GenericFunction {
         self.blocks[block].is_critical_edge
     }
 
+    #[inline]
+    fn allows_critical_edges(&self) -> bool {
+        self.allow_critical_edges
+    }
+
     #[inline]
     fn inst_operands(&self, inst: Inst) -> &[Operand] {
         &self.insts[inst].operands
@@ -216,12 +258,62 @@ impl Function for GenericFunction {
     }
 
     #[inline]
-    fn can_rematerialize(&self, value: Value) -> Option<(RematCost, RegClass)> {
+    fn num_alternative_sets(&self) -> usize {
+        self.alternative_sets.len()
+    }
+
+    #[inline]
+    fn alternative_set(&self, set: AlternativeSet) -> &[(OperandConstraint, f32)] {
+        &self.alternative_sets[set]
+    }
+
+    #[inline]
+    fn value_hint(&self, value: Value) -> Option<(PhysReg, f32)> {
+        self.values[value].hint
+    }
+
+    #[inline]
+    fn value_pinned_reg(&self, value: Value) -> Option<PhysReg> {
+        self.values[value].pinned_reg
+    }
+
+    #[inline]
+    fn can_rematerialize(&self, value: Value) -> Option<(f32, RegClass)> {
         self.values[value].remat
     }
 
+    #[inline]
+    fn remat_input(&self, value: Value) -> Option<Value> {
+        self.values[value].remat_input
+    }
+
     #[inline]
     fn can_eliminate_dead_inst(&self, inst: Inst) -> bool {
         self.insts[inst].is_pure
     }
+
+    #[inline]
+    fn is_copy(&self, inst: Inst) -> bool {
+        self.insts[inst].is_copy
+    }
+
+    #[inline]
+    fn value_scope_end(&self, value: Value) -> Option<Inst> {
+        self.values[value].scope_end
+    }
+
+    #[inline]
+    fn no_edits_before(&self, inst: Inst) -> bool {
+        self.insts[inst].no_edits_before
+    }
+
+    #[inline]
+    fn is_reference(&self, value: Value) -> bool {
+        self.values[value].is_reference
+    }
+
+    #[inline]
+    fn is_safepoint(&self, inst: Inst) -> bool {
+        self.insts[inst].is_safepoint
+    }
 }
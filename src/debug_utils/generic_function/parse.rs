@@ -13,7 +13,7 @@ use crate::debug_utils::dominator_tree::DominatorTree;
 use crate::debug_utils::postorder::PostOrder;
 use crate::entity::{EntityRef, PrimaryMap, SecondaryMap};
 use crate::function::{
-    Block, Function, Inst, InstRange, Operand, OperandConstraint, OperandKind, RematCost,
+    AlternativeSet, Block, Function, Inst, InstRange, Operand, OperandConstraint, OperandKind,
     TerminatorKind, Value, ValueGroup,
 };
 
@@ -89,6 +89,11 @@ fn parse_value_declaration(
     let span = pair.as_span();
     let mut bank = None;
     let mut remat = None;
+    let mut remat_input = None;
+    let mut is_reference = false;
+    let mut scope_end = None;
+    let mut hint = None;
+    let mut pinned_reg = None;
     for pair in pair.into_inner() {
         match pair.as_rule() {
             Rule::value => {
@@ -104,14 +109,42 @@ fn parse_value_declaration(
                 if remat.is_some() {
                     Err(custom_error(pair.as_span(), "duplicate attribute"))?;
                 }
-                let [cost, class] = extract(pair, [Rule::remat_cost, Rule::regclass]);
-                let cost = match cost.as_str() {
-                    "cheaper_than_move" => RematCost::CheaperThanMove,
-                    "cheaper_than_load" => RematCost::CheaperThanLoad,
-                    _ => unreachable!(),
-                };
-                let class = parse_entity(class)?;
-                remat = Some((cost, class));
+                let [cost, class] = extract(pair, [Rule::float, Rule::regclass]);
+                remat = Some((parse_number(cost)?, parse_entity(class)?));
+            }
+            Rule::remat_input => {
+                if remat_input.is_some() {
+                    Err(custom_error(pair.as_span(), "duplicate attribute"))?;
+                }
+                let [value] = extract(pair, [Rule::value]);
+                remat_input = Some(parse_entity(value)?);
+            }
+            Rule::reference => {
+                if is_reference {
+                    Err(custom_error(pair.as_span(), "duplicate attribute"))?;
+                }
+                is_reference = true;
+            }
+            Rule::scope_end => {
+                if scope_end.is_some() {
+                    Err(custom_error(pair.as_span(), "duplicate attribute"))?;
+                }
+                let [inst] = extract(pair, [Rule::inst]);
+                scope_end = Some(parse_entity(inst)?);
+            }
+            Rule::hint => {
+                if hint.is_some() {
+                    Err(custom_error(pair.as_span(), "duplicate attribute"))?;
+                }
+                let [reg, strength] = extract(pair, [Rule::physreg, Rule::float]);
+                hint = Some((parse_entity(reg)?, parse_number(strength)?));
+            }
+            Rule::pinned => {
+                if pinned_reg.is_some() {
+                    Err(custom_error(pair.as_span(), "duplicate attribute"))?;
+                }
+                let [reg] = extract(pair, [Rule::physreg]);
+                pinned_reg = Some(parse_entity(reg)?);
             }
             _ => unreachable!(),
         }
@@ -119,7 +152,15 @@ fn parse_value_declaration(
     let Some(bank) = bank else {
         Err(custom_error(span, "missing bank attribute"))?
     };
-    values.push(ValueData { bank, remat });
+    values.push(ValueData {
+        bank,
+        remat,
+        remat_input,
+        is_reference,
+        scope_end,
+        hint,
+        pinned_reg,
+    });
     Ok(())
 }
 
@@ -152,6 +193,7 @@ fn parse_block_label(
         succs: vec![],
         block_params_in,
         block_params_out: vec![],
+        branch_params_out: vec![],
         immediate_dominator: None.into(),
         frequency,
         is_critical_edge,
@@ -178,30 +220,55 @@ fn parse_opcode(
         }
         Rule::branch => {
             data.terminator_kind = Some(TerminatorKind::Branch);
-            let [block_list] = extract(pair, [Rule::block_list]);
-            block_data.succs = parse_entity_list(block_list)?;
+            for target in pair.into_inner() {
+                let mut inner = target.into_inner();
+                block_data.succs.push(parse_entity(inner.next().unwrap())?);
+                block_data.branch_params_out.push(match inner.next() {
+                    Some(value_list) => parse_entity_list(value_list)?,
+                    None => vec![],
+                });
+            }
         }
         _ => unreachable!(),
     }
     Ok(())
 }
 
-fn parse_attribute(pair: Pair<'_, Rule>, is_pure: &mut bool) -> Result<()> {
-    match pair.as_str() {
-        "pure" => {
-            if *is_pure {
-                Err(custom_error(pair.as_span(), "duplicate attribute"))?;
-            }
-            *is_pure = true;
-        }
+fn parse_attribute(pair: Pair<'_, Rule>, data: &mut InstData) -> Result<()> {
+    let flag = match pair.as_str() {
+        "pure" => &mut data.is_pure,
+        "copy" => &mut data.is_copy,
+        "safepoint" => &mut data.is_safepoint,
+        "no_edits_before" => &mut data.no_edits_before,
         _ => unreachable!(),
+    };
+    if *flag {
+        Err(custom_error(pair.as_span(), "duplicate attribute"))?;
     }
+    *flag = true;
     Ok(())
 }
 
+fn parse_alternatives(pair: Pair<'_, Rule>) -> Result<Vec<(OperandConstraint, f32)>> {
+    let mut alternatives = vec![];
+    for alternative in pair.into_inner() {
+        let mut inner = alternative.into_inner();
+        let constraint_pair = inner.next().unwrap();
+        let constraint = match constraint_pair.as_rule() {
+            Rule::physreg => OperandConstraint::Fixed(parse_entity(constraint_pair)?),
+            Rule::regclass => OperandConstraint::Class(parse_entity(constraint_pair)?),
+            _ => unreachable!(),
+        };
+        let cost = parse_number(inner.next().unwrap())?;
+        alternatives.push((constraint, cost));
+    }
+    Ok(alternatives)
+}
+
 fn parse_operand(
     pair: Pair<'_, Rule>,
     groups: &mut PrimaryMap<ValueGroup, Vec<Value>>,
+    alternative_sets: &mut PrimaryMap<AlternativeSet, Vec<(OperandConstraint, f32)>>,
 ) -> Result<Operand> {
     let [operand_kind, value_list, constraint] = extract(
         pair,
@@ -232,6 +299,9 @@ fn parse_operand(
             let [number] = extract(constraint_pair, [Rule::number]);
             OperandConstraint::Reuse(parse_number(number)?)
         }
+        Rule::alternatives => OperandConstraint::Alternatives(
+            alternative_sets.push(parse_alternatives(constraint_pair)?),
+        ),
         _ => unreachable!(),
     };
     Ok(Operand::new(kind, constraint))
@@ -242,6 +312,7 @@ fn parse_instruction(
     blocks: &mut PrimaryMap<Block, BlockData>,
     insts: &mut PrimaryMap<Inst, InstData>,
     groups: &mut PrimaryMap<ValueGroup, Vec<Value>>,
+    alternative_sets: &mut PrimaryMap<AlternativeSet, Vec<(OperandConstraint, f32)>>,
 ) -> Result<()> {
     let Some((block, block_data)) = blocks.last_mut() else {
         Err(custom_error(
@@ -255,6 +326,9 @@ fn parse_instruction(
         block,
         terminator_kind: None,
         is_pure: false,
+        is_copy: false,
+        is_safepoint: false,
+        no_edits_before: false,
     };
     for pair in pair.into_inner() {
         match pair.as_rule() {
@@ -262,8 +336,11 @@ fn parse_instruction(
                 // We specifically ignore instruction labels to make it easier to edit code.
             }
             Rule::opcode => parse_opcode(pair, &mut data, block_data)?,
-            Rule::attribute => parse_attribute(pair, &mut data.is_pure)?,
-            Rule::normal_operand => data.operands.push(parse_operand(pair, groups)?),
+            Rule::attribute => parse_attribute(pair, &mut data)?,
+            Rule::normal_operand => {
+                data.operands
+                    .push(parse_operand(pair, groups, alternative_sets)?);
+            }
             Rule::nonallocatable_operand => {
                 let [physreg] = extract(pair, [Rule::physreg]);
                 data.operands
@@ -314,15 +391,24 @@ impl GenericFunction {
         let mut insts = PrimaryMap::new();
         let mut values = PrimaryMap::new();
         let mut value_groups = PrimaryMap::new();
+        let mut alternative_sets = PrimaryMap::new();
+        let mut allow_critical_edges = false;
 
         for pair in parse_result {
             match pair.as_rule() {
+                Rule::allow_critical_edges => allow_critical_edges = true,
                 Rule::value_declaration => {
                     parse_value_declaration(pair, &mut values)?;
                 }
                 Rule::block_label => parse_block_label(pair, &mut blocks, &mut insts)?,
                 Rule::instruction => {
-                    parse_instruction(pair, &mut blocks, &mut insts, &mut value_groups)?;
+                    parse_instruction(
+                        pair,
+                        &mut blocks,
+                        &mut insts,
+                        &mut value_groups,
+                        &mut alternative_sets,
+                    )?;
                 }
                 Rule::EOI => {}
                 _ => unreachable!(),
@@ -334,6 +420,8 @@ impl GenericFunction {
             insts,
             values,
             value_groups,
+            alternative_sets,
+            allow_critical_edges,
         };
 
         // Compute block predecessors and immediate dominators since they are
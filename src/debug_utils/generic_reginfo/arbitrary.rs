@@ -99,7 +99,13 @@ impl<'a, 'b> RegInfoBuilder<'a, 'b> {
 
     /// Generates a reasonable spillslot size for a register bank.
     fn spillslot_size(&mut self) -> Result<SpillSlotSize> {
-        Ok(SpillSlotSize::from_log2_bytes(self.u.int_in_range(0..=15)?))
+        if self.u.ratio(1, 4)? {
+            Ok(SpillSlotSize::scalable_from_log2(
+                self.u.int_in_range(0..=3)?,
+            ))
+        } else {
+            Ok(SpillSlotSize::from_log2_bytes(self.u.int_in_range(0..=15)?))
+        }
     }
 
     /// Creates a new register bank.
@@ -298,9 +304,16 @@ impl<'a, 'b> RegInfoBuilder<'a, 'b> {
             let idx = self.u.choose_index(units_available.len())?;
             units.push(units_available.swap_remove(idx));
         }
+        let is_fixed_stack = self.u.arbitrary()?;
+        let callee_saved_cost = if is_fixed_stack {
+            0.0
+        } else {
+            *self.u.choose(&[0.0, 0.0, 1.0, 2.0, 10.0])?
+        };
         Ok(self.reginfo.regs.push(PhysRegData {
             bank: Some(bank),
-            is_fixed_stack: self.u.arbitrary()?,
+            is_fixed_stack,
+            callee_saved_cost,
             units,
         }))
     }
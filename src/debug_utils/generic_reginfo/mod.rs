@@ -46,6 +46,7 @@ struct RegClassData {
 struct PhysRegData {
     bank: Option<RegBank>,
     is_fixed_stack: bool,
+    callee_saved_cost: f32,
     units: Vec<RegUnit>,
 }
 
@@ -116,6 +117,7 @@ impl GenericRegInfo {
             regs.push(PhysRegData {
                 bank,
                 is_fixed_stack,
+                callee_saved_cost: reginfo.callee_saved_cost(reg),
                 units: reginfo.reg_units(reg).collect(),
             });
         }
@@ -224,6 +226,11 @@ impl RegInfo for GenericRegInfo {
         self.regs[reg].is_fixed_stack
     }
 
+    #[inline]
+    fn callee_saved_cost(&self, reg: PhysReg) -> f32 {
+        self.regs[reg].callee_saved_cost
+    }
+
     #[inline]
     fn num_reg_groups(&self) -> usize {
         self.groups.len()
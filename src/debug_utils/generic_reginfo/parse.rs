@@ -115,7 +115,10 @@ fn parse_reg_group_list(pair: Pair<'_, Rule>, is_group: &mut Option<bool>) -> Re
 }
 
 fn parse_reg_def(pair: Pair<'_, Rule>, regs: &mut PrimaryMap<PhysReg, PhysRegData>) -> Result<()> {
-    let [reg, location, kind] = extract(pair, [Rule::reg, Rule::reg_location, Rule::reg_kind]);
+    let mut inner = pair.into_inner();
+    let reg = inner.next().unwrap();
+    let location = inner.next().unwrap();
+    let kind = inner.next().unwrap();
     parse_expected_entity(reg, regs.next_key())?;
     let is_fixed_stack = location.as_str() == "stack";
     let kind = kind.into_inner().next().unwrap();
@@ -124,9 +127,17 @@ fn parse_reg_def(pair: Pair<'_, Rule>, regs: &mut PrimaryMap<PhysReg, PhysRegDat
         Rule::unit_list => parse_entity_list(kind)?,
         _ => unreachable!(),
     };
+    let callee_saved_cost = match inner.next() {
+        Some(callee_saved) => {
+            let [float] = extract(callee_saved, [Rule::float]);
+            parse_number(float)?
+        }
+        None => 0.0,
+    };
     regs.push(PhysRegData {
         bank: None,
         is_fixed_stack,
+        callee_saved_cost,
         units,
     });
     Ok(())
@@ -294,13 +305,17 @@ fn parse_bank_def(
                 if spillslot_size.is_some() {
                     Err(custom_error(pair.as_span(), "duplicate attribute"))?;
                 }
-                let [number] = extract(pair, [Rule::number]);
+                let mut inner = pair.into_inner();
+                let number = inner.next().unwrap();
                 let span = number.as_span();
                 let size: u32 = parse_number(number)?;
                 if !size.is_power_of_two() {
                     Err(custom_error(span, "spillslot size must be a power of two"))?;
                 }
-                spillslot_size = Some(SpillSlotSize::new(size));
+                spillslot_size = Some(match inner.next() {
+                    Some(_) => SpillSlotSize::scalable(size),
+                    None => SpillSlotSize::new(size),
+                });
             }
             Rule::class_def => {
                 parse_class_def(pair, banks.next_key(), classes, regs, superclasses)?;
@@ -10,9 +10,10 @@ mod display;
 mod dominator_tree;
 mod generic_function;
 mod generic_reginfo;
-mod postorder;
+pub(crate) mod postorder;
 mod validate_func;
 mod validate_reginfo;
+mod validation_error;
 
 pub use checker::*;
 pub use cost_model::*;
@@ -21,3 +22,4 @@ pub use generic_function::*;
 pub use generic_reginfo::*;
 pub use validate_func::*;
 pub use validate_reginfo::*;
+pub use validation_error::{Entity, Limit, ValidationError};
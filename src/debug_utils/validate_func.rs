@@ -2,18 +2,20 @@
 
 use alloc::vec;
 use alloc::vec::Vec;
-use core::{fmt, slice};
-
-use anyhow::{Result, bail, ensure};
+use core::slice;
 
+use super::validation_error::{Abort, Entity, Errors, Limit, ValidationError};
 use crate::debug_utils::dominator_tree::DominatorTree;
 use crate::debug_utils::postorder::PostOrder;
 use crate::entity::{EntitySet, SecondaryMap};
 use crate::function::{
-    Block, Function, Inst, InstRange, MAX_BLOCK_PARAMS, MAX_BLOCKS, MAX_INST_OPERANDS, MAX_INSTS,
-    MAX_VALUES, Operand, OperandConstraint, OperandKind, TerminatorKind, Value, ValueGroup,
+    AlternativeSet, Block, Function, Inst, InstRange, MAX_BLOCK_PARAMS, MAX_INST_OPERANDS,
+    OperandConstraint, OperandKind, TerminatorKind, Value, ValueGroup,
 };
-use crate::reginfo::{PhysReg, RegInfo, RegUnitSet};
+use crate::internal::alternatives::AlternativeResolver;
+use crate::reginfo::{PhysReg, RegBank, RegClass, RegInfo, RegUnitSet};
+
+type Result<T = ()> = core::result::Result<T, Abort>;
 
 /// Checks `func` to ensure it satisfies all of the pre-conditions required by
 /// the register allocator.
@@ -26,41 +28,45 @@ use crate::reginfo::{PhysReg, RegInfo, RegUnitSet};
 /// This assumes that `reginfo` has already been validated by
 /// [`validate_reginfo`].
 ///
+/// Validation stops at the first error found, use [`validate_function_all`] to
+/// collect all errors instead.
+///
 /// [`validate_reginfo`]: super::validate_reginfo()
-pub fn validate_function(func: &impl Function, reginfo: &impl RegInfo) -> Result<()> {
+pub fn validate_function(
+    func: &impl Function,
+    reginfo: &impl RegInfo,
+) -> core::result::Result<(), ValidationError> {
+    validate(func, reginfo, false).into_first()
+}
+
+/// Same as [`validate_function`], but keeps going after an error is found and
+/// returns all of the errors in `func`.
+///
+/// Some errors, such as invalid entity references or unreachable blocks, still
+/// prevent the checks that depend on them from running.
+pub fn validate_function_all(
+    func: &impl Function,
+    reginfo: &impl RegInfo,
+) -> core::result::Result<(), Vec<ValidationError>> {
+    validate(func, reginfo, true).into_all()
+}
+
+fn validate(func: &impl Function, reginfo: &impl RegInfo, collect_all: bool) -> Errors {
     let mut ctx = Context {
         func,
         reginfo,
+        errors: Errors::new(collect_all),
         value_defs: SecondaryMap::with_max_index(func.num_values()),
         early_fixed: RegUnitSet::new(),
         late_fixed: RegUnitSet::new(),
         used_value_groups: EntitySet::with_max_index(func.num_value_groups()),
         reuse_targets: vec![],
+        sequence_values: vec![],
         domtree: DominatorTree::new(),
+        alternatives: AlternativeResolver::new(),
     };
-    ctx.check_function()?;
-    Ok(())
-}
-
-/// An entity reference.
-///
-/// This is used by [`ValidationError::InvalidEntity`] to report entity
-/// references with an invalid index.
-#[derive(Debug, Clone, Copy)]
-enum Entity {
-    Value(Value),
-    ValueGroup(ValueGroup),
-    Inst(Inst),
-}
-
-impl fmt::Display for Entity {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match *self {
-            Entity::Value(x) => x.fmt(f),
-            Entity::ValueGroup(x) => x.fmt(f),
-            Entity::Inst(x) => x.fmt(f),
-        }
-    }
+    let _ = ctx.check_function();
+    ctx.errors
 }
 
 /// Point at which a `Value` is defined.
@@ -91,65 +97,82 @@ impl ValueOrGroup {
 struct Context<'a, F, R> {
     func: &'a F,
     reginfo: &'a R,
+    errors: Errors,
     value_defs: SecondaryMap<Value, Option<ValueDef>>,
     early_fixed: RegUnitSet,
     late_fixed: RegUnitSet,
     used_value_groups: EntitySet<ValueGroup>,
     reuse_targets: Vec<usize>,
+    sequence_values: Vec<(Value, RegClass)>,
     domtree: DominatorTree,
+    alternatives: AlternativeResolver,
 }
 
 impl<F: Function, R: RegInfo> Context<'_, F, R> {
     /// Check that an entity refers to a valid object.
-    fn check_entity(&mut self, entity: Entity) -> Result<()> {
+    ///
+    /// Validation can't continue past an invalid reference since it would
+    /// cause out-of-bounds accesses.
+    fn check_entity(&mut self, entity: Entity) -> Result {
         let (index, len) = match entity {
             Entity::Value(x) => (x.index(), self.func.num_values()),
             Entity::ValueGroup(x) => {
-                ensure!(
-                    !self.used_value_groups.contains(x),
-                    "{x} cannot be used multiple times in a function"
-                );
-                self.used_value_groups.insert(x);
+                if x.index() < self.func.num_value_groups() {
+                    if self.used_value_groups.contains(x) {
+                        self.errors.report(ValidationError::ValueGroupReused(x))?;
+                    }
+                    self.used_value_groups.insert(x);
+                }
                 (x.index(), self.func.num_value_groups())
             }
             Entity::Inst(x) => (x.index(), self.func.num_insts()),
+            Entity::AlternativeSet(x) => (x.index(), self.func.num_alternative_sets()),
+            Entity::RegUnit(_)
+            | Entity::PhysReg(_)
+            | Entity::RegGroup(_)
+            | Entity::RegClass(_)
+            | Entity::RegBank(_) => unreachable!(),
         };
-        ensure!(index < len, "{entity}: Invalid entity reference");
+        if index >= len {
+            return Err(self.errors.fatal(ValidationError::InvalidEntity(entity)));
+        }
         Ok(())
     }
 
     /// Checks a range of instructions.
-    fn check_inst_range(&mut self, range: InstRange) -> Result<()> {
+    fn check_inst_range(&mut self, block: Block, range: InstRange) -> Result {
         self.check_entity(Entity::Inst(range.from))?;
         self.check_entity(Entity::Inst(range.last()))?;
-        ensure!(range.to >= range.from, "{range}: Invalid instruction range");
+        if range.to < range.from {
+            return Err(self
+                .errors
+                .fatal(ValidationError::InvalidInstRange { block, range }));
+        }
         Ok(())
     }
 
     /// Check the limits on the number of entities.
-    fn check_limits(&self) -> Result<()> {
-        ensure!(
-            self.func.num_values() <= MAX_VALUES,
-            "Too many values: {} (max: {MAX_VALUES})",
-            self.func.num_values(),
-        );
-        ensure!(
-            self.func.num_blocks() <= MAX_BLOCKS,
-            "Too many blocks: {} (max: {MAX_BLOCKS})",
-            self.func.num_blocks(),
-        );
-        ensure!(
-            self.func.num_insts() <= MAX_INSTS,
-            "Too many instructions: {} (max: {MAX_INSTS})",
-            self.func.num_insts(),
-        );
+    fn check_limits(&mut self) -> Result {
+        for (limit, count) in [
+            (Limit::Values, self.func.num_values()),
+            (Limit::Blocks, self.func.num_blocks()),
+            (Limit::Insts, self.func.num_insts()),
+            (Limit::AlternativeSets, self.func.num_alternative_sets()),
+        ] {
+            if count > limit.max() {
+                self.errors
+                    .report(ValidationError::LimitExceeded { limit, count })?;
+            }
+        }
         Ok(())
     }
 
     /// Record the definition of a value and check for duplicate definitions.
-    fn check_value_def(&mut self, value: Value, def: ValueDef) -> Result<()> {
+    fn check_value_def(&mut self, value: Value, def: ValueDef) -> Result {
         match self.value_defs[value] {
-            Some(_) => bail!("{value} defined multiple times"),
+            Some(_) => self
+                .errors
+                .report(ValidationError::ValueDefinedMultipleTimes(value))?,
             ref mut opt @ None => *opt = Some(def),
         }
         Ok(())
@@ -157,16 +180,42 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
 
     /// Check for multiple conflicting uses of a fixed register in a single
     /// instruction.
-    fn check_fixed(&mut self, inst: Inst, reg: PhysReg, early: bool) -> Result<()> {
+    fn check_fixed(&mut self, inst: Inst, operand: usize, reg: PhysReg, early: bool) -> Result {
         let set = if early {
             &mut self.early_fixed
         } else {
             &mut self.late_fixed
         };
+        let mut conflict = false;
         for unit in self.reginfo.reg_units(reg) {
-            ensure!(!set.contains(unit), "{inst}: Conflicting uses of {reg}");
+            conflict |= set.contains(unit);
             set.insert(unit);
         }
+        if conflict {
+            self.errors
+                .report(ValidationError::ConflictingFixedReg { inst, operand, reg })?;
+        }
+        Ok(())
+    }
+
+    /// Check that `value` is in the same bank as the constraint of an operand.
+    fn check_operand_bank(
+        &mut self,
+        inst: Inst,
+        operand: usize,
+        value: Value,
+        bank: RegBank,
+    ) -> Result {
+        let value_bank = self.func.value_bank(value);
+        if bank != value_bank {
+            self.errors.report(ValidationError::OperandBankMismatch {
+                inst,
+                operand,
+                value,
+                bank,
+                value_bank,
+            })?;
+        }
         Ok(())
     }
 
@@ -174,41 +223,38 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
     fn check_constraint(
         &mut self,
         inst: Inst,
-        operands: &[Operand],
-        operand: Operand,
+        operand: usize,
         value_or_group: ValueOrGroup,
-    ) -> Result<()> {
-        match operand.constraint() {
+    ) -> Result {
+        let operands = self.func.inst_operands(inst);
+        let op = operands[operand];
+        match op.constraint() {
             OperandConstraint::Class(class) => {
                 let bank = self.reginfo.bank_for_class(class);
                 let group_size = self.reginfo.class_group_size(class);
                 match value_or_group {
                     ValueOrGroup::Value(value) => {
-                        ensure!(
-                            group_size == 1,
-                            "{inst} {operand}: Value used with group register class"
-                        );
-                        let value_bank = self.func.value_bank(value);
-                        ensure!(
-                            bank == value_bank,
-                            "{inst} {operand}: {value} used with different register banks: {bank} \
-                             vs {value_bank}"
-                        );
+                        if group_size != 1 {
+                            self.errors.report(ValidationError::ValueWithGroupClass {
+                                inst,
+                                operand,
+                                class,
+                            })?;
+                        }
+                        self.check_operand_bank(inst, operand, value, bank)?;
                     }
                     ValueOrGroup::Group(group) => {
                         let members = self.func.value_group_members(group);
-                        ensure!(
-                            group_size == members.len(),
-                            "{inst} {operand}: group size mismatch {group_size} vs {}",
-                            members.len()
-                        );
+                        if group_size != members.len() {
+                            self.errors.report(ValidationError::GroupSizeMismatch {
+                                inst,
+                                operand,
+                                class_size: group_size,
+                                group_size: members.len(),
+                            })?;
+                        }
                         for &value in members {
-                            let value_bank = self.func.value_bank(value);
-                            ensure!(
-                                bank == value_bank,
-                                "{inst} {operand}: {value} used with different register banks: \
-                                 {bank} vs {value_bank}"
-                            );
+                            self.check_operand_bank(inst, operand, value, bank)?;
                         }
                     }
                 }
@@ -217,34 +263,32 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                 match value_or_group {
                     ValueOrGroup::Value(value) => {
                         let Some(bank) = self.reginfo.bank_for_reg(reg) else {
-                            bail!("{inst} {operand}: Use of non-allocatable register");
+                            return self.errors.report(ValidationError::NonAllocatableFixedReg {
+                                inst,
+                                operand,
+                                reg,
+                            });
                         };
-                        let value_bank = self.func.value_bank(value);
-                        ensure!(
-                            bank == value_bank,
-                            "{inst} {operand}: {value} used with different register banks: {bank} \
-                             vs {value_bank}"
-                        );
+                        self.check_operand_bank(inst, operand, value, bank)?;
                     }
                     ValueOrGroup::Group(_) => {
-                        bail!(
-                            "{inst} {operand}: Fixed constraint cannot be used with register \
-                             groups"
-                        );
+                        return self
+                            .errors
+                            .report(ValidationError::FixedGroup { inst, operand });
                     }
                 }
 
                 // Check for conflicting fixed-register constraints.
-                match operand.kind() {
+                match op.kind() {
                     OperandKind::Def(_) => {
-                        self.check_fixed(inst, reg, false)?;
+                        self.check_fixed(inst, operand, reg, false)?;
                     }
                     OperandKind::Use(_) => {
-                        self.check_fixed(inst, reg, true)?;
+                        self.check_fixed(inst, operand, reg, true)?;
                     }
                     OperandKind::EarlyDef(_) => {
-                        self.check_fixed(inst, reg, true)?;
-                        self.check_fixed(inst, reg, false)?;
+                        self.check_fixed(inst, operand, reg, true)?;
+                        self.check_fixed(inst, operand, reg, false)?;
                     }
                     OperandKind::DefGroup(_)
                     | OperandKind::UseGroup(_)
@@ -252,8 +296,8 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                     | OperandKind::NonAllocatable => unreachable!(),
                 }
             }
-            OperandConstraint::Reuse(index) => {
-                match operand.kind() {
+            OperandConstraint::Reuse(target) => {
+                match op.kind() {
                     OperandKind::Def(_)
                     | OperandKind::EarlyDef(_)
                     | OperandKind::DefGroup(_)
@@ -261,17 +305,25 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                     OperandKind::Use(_)
                     | OperandKind::UseGroup(_)
                     | OperandKind::NonAllocatable => {
-                        bail!("{inst} {operand}: Reuse operand must be a Def or EarlyDef")
+                        self.errors
+                            .report(ValidationError::ReuseNotDef { inst, operand })?;
                     }
                 }
-                let Some(&target_operand) = operands.get(index) else {
-                    bail!("{inst} {operand}: Invalid index for reuse operand");
+                let Some(&target_operand) = operands.get(target) else {
+                    return self.errors.report(ValidationError::InvalidReuseTarget {
+                        inst,
+                        operand,
+                        target,
+                    });
                 };
-                ensure!(
-                    !self.reuse_targets.contains(&index),
-                    "{inst} {operand}: multiple reuse of same target operand {index}"
-                );
-                self.reuse_targets.push(index);
+                if self.reuse_targets.contains(&target) {
+                    self.errors.report(ValidationError::MultipleReuse {
+                        inst,
+                        operand,
+                        target,
+                    })?;
+                }
+                self.reuse_targets.push(target);
                 let target_value_or_group = match target_operand.kind() {
                     OperandKind::Use(target_value) => ValueOrGroup::Value(target_value),
                     OperandKind::UseGroup(target_group) => ValueOrGroup::Group(target_group),
@@ -280,17 +332,28 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                     | OperandKind::DefGroup(_)
                     | OperandKind::EarlyDefGroup(_)
                     | OperandKind::NonAllocatable => {
-                        bail!(
-                            "{inst} {operand} -> {target_operand}: Reuse operand target must be a \
-                             Use"
-                        );
+                        return self.errors.report(ValidationError::ReuseTargetNotUse {
+                            inst,
+                            operand,
+                            target,
+                        });
                     }
                 };
-                ensure!(
-                    matches!(target_operand.constraint(), OperandConstraint::Class(_)),
-                    "{inst} {operand} -> {target_operand}: Reuse operand target must have a Class \
-                     constraint"
-                );
+                match target_operand.constraint() {
+                    OperandConstraint::Class(_) => {}
+                    OperandConstraint::Fixed(reg) => {
+                        // The def overwrites the fixed register of the input
+                        // after the instruction, even for an `EarlyDef`.
+                        self.check_fixed(inst, operand, reg, false)?;
+                    }
+                    OperandConstraint::Reuse(_) | OperandConstraint::Alternatives(_) => {
+                        self.errors.report(ValidationError::ReuseTargetNotClass {
+                            inst,
+                            operand,
+                            target,
+                        })?;
+                    }
+                }
 
                 // Ensure both source and target have the same group width.
                 match (value_or_group, target_value_or_group) {
@@ -299,10 +362,13 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                         if self.func.value_group_members(group).len()
                             == self.func.value_group_members(target_group).len() => {}
                     _ => {
-                        bail!(
-                            "{inst} {operand} -> {target_operand}: Tied operands must either both \
-                             be values or both be groups of the same size"
-                        );
+                        return self
+                            .errors
+                            .report(ValidationError::TiedOperandShapeMismatch {
+                                inst,
+                                operand,
+                                target,
+                            });
                     }
                 }
 
@@ -314,40 +380,109 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                 {
                     let target_bank = self.func.value_bank(target_value);
                     let source_bank = self.func.value_bank(source_value);
-                    ensure!(
-                        source_bank == target_bank,
-                        "{inst}: Tied operand with different register banks: {operand} \
-                         ({source_bank}) vs {target_operand} ({target_bank})"
-                    );
+                    if source_bank != target_bank {
+                        self.errors
+                            .report(ValidationError::TiedOperandBankMismatch {
+                                inst,
+                                operand,
+                                target,
+                                bank: source_bank,
+                                target_bank,
+                            })?;
+                    }
+                }
+            }
+            OperandConstraint::Alternatives(set) => {
+                self.check_entity(Entity::AlternativeSet(set))?;
+                let ValueOrGroup::Value(value) = value_or_group else {
+                    return self
+                        .errors
+                        .report(ValidationError::AlternativesGroup { inst, operand });
+                };
+
+                // Conflicts between fixed registers are avoided when selecting
+                // an alternative, so only check the register banks here.
+                for &(constraint, _cost) in self.func.alternative_set(set) {
+                    match constraint {
+                        OperandConstraint::Class(class) => {
+                            if self.reginfo.class_group_size(class) != 1 {
+                                self.errors.report(ValidationError::ValueWithGroupClass {
+                                    inst,
+                                    operand,
+                                    class,
+                                })?;
+                            }
+                            let bank = self.reginfo.bank_for_class(class);
+                            self.check_operand_bank(inst, operand, value, bank)?;
+                        }
+                        OperandConstraint::Fixed(reg) => {
+                            let Some(bank) = self.reginfo.bank_for_reg(reg) else {
+                                self.errors
+                                    .report(ValidationError::NonAllocatableFixedReg {
+                                        inst,
+                                        operand,
+                                        reg,
+                                    })?;
+                                continue;
+                            };
+                            self.check_operand_bank(inst, operand, value, bank)?;
+                        }
+                        // Reported by `check_alternative_set`.
+                        OperandConstraint::Reuse(_) | OperandConstraint::Alternatives(_) => {}
+                    }
                 }
             }
         }
         Ok(())
     }
 
+    /// Check the contents of an alternative set.
+    fn check_alternative_set(&mut self, set: AlternativeSet) -> Result {
+        let alternatives = self.func.alternative_set(set);
+        if alternatives.is_empty() {
+            self.errors
+                .report(ValidationError::EmptyAlternativeSet(set))?;
+        }
+        for (alternative, &(constraint, cost)) in alternatives.iter().enumerate() {
+            match constraint {
+                OperandConstraint::Class(_) | OperandConstraint::Fixed(_) => {}
+                OperandConstraint::Reuse(_) | OperandConstraint::Alternatives(_) => {
+                    self.errors
+                        .report(ValidationError::InvalidAlternative { set, alternative })?;
+                }
+            }
+            if !(cost.is_finite() && cost >= 0.0) {
+                self.errors
+                    .report(ValidationError::InvalidAlternativeCost { set, alternative })?;
+            }
+        }
+        Ok(())
+    }
+
     /// Check an instruction.
-    fn check_inst(&mut self, block: Block, inst: Inst) -> Result<()> {
+    fn check_inst(&mut self, block: Block, inst: Inst) -> Result {
         // These are temporary for the scope of this instruction.
         self.early_fixed.clear();
         self.late_fixed.clear();
         self.reuse_targets.clear();
 
         let operands = self.func.inst_operands(inst);
-        ensure!(
-            operands.len() <= MAX_INST_OPERANDS,
-            "{inst}: Too many operands: {} (max: {MAX_INST_OPERANDS})",
-            operands.len(),
-        );
-        for &op in operands {
+        if operands.len() > MAX_INST_OPERANDS {
+            self.errors.report(ValidationError::TooManyOperands {
+                inst,
+                count: operands.len(),
+            })?;
+        }
+        for (operand, &op) in operands.iter().enumerate() {
             match op.kind() {
                 OperandKind::Def(value) | OperandKind::EarlyDef(value) => {
                     self.check_entity(Entity::Value(value))?;
                     self.check_value_def(value, ValueDef::Inst(block, inst))?;
-                    self.check_constraint(inst, operands, op, ValueOrGroup::Value(value))?;
+                    self.check_constraint(inst, operand, ValueOrGroup::Value(value))?;
                 }
                 OperandKind::Use(value) => {
                     self.check_entity(Entity::Value(value))?;
-                    self.check_constraint(inst, operands, op, ValueOrGroup::Value(value))?;
+                    self.check_constraint(inst, operand, ValueOrGroup::Value(value))?;
                 }
                 OperandKind::DefGroup(group) | OperandKind::EarlyDefGroup(group) => {
                     self.check_entity(Entity::ValueGroup(group))?;
@@ -355,121 +490,274 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                         self.check_entity(Entity::Value(value))?;
                         self.check_value_def(value, ValueDef::Inst(block, inst))?;
                     }
-                    self.check_constraint(inst, operands, op, ValueOrGroup::Group(group))?;
+                    self.check_constraint(inst, operand, ValueOrGroup::Group(group))?;
                 }
                 OperandKind::UseGroup(group) => {
                     self.check_entity(Entity::ValueGroup(group))?;
                     for &value in self.func.value_group_members(group) {
                         self.check_entity(Entity::Value(value))?;
                     }
-                    self.check_constraint(inst, operands, op, ValueOrGroup::Group(group))?;
+                    self.check_constraint(inst, operand, ValueOrGroup::Group(group))?;
                 }
                 OperandKind::NonAllocatable => match op.constraint() {
                     OperandConstraint::Fixed(reg) => {
-                        ensure!(
-                            self.reginfo.bank_for_reg(reg).is_none(),
-                            "{inst} {op}: NonAllocatable register must be outside a bank"
-                        );
+                        if self.reginfo.bank_for_reg(reg).is_some() {
+                            self.errors
+                                .report(ValidationError::AllocatableNonAllocatableReg {
+                                    inst,
+                                    operand,
+                                    reg,
+                                })?;
+                        }
                     }
-                    OperandConstraint::Class(_) | OperandConstraint::Reuse(_) => {
-                        bail!("{inst} {op}: NonAllocatable operand must have a Fixed constraint")
+                    OperandConstraint::Class(_)
+                    | OperandConstraint::Reuse(_)
+                    | OperandConstraint::Alternatives(_) => {
+                        self.errors
+                            .report(ValidationError::NonAllocatableNotFixed { inst, operand })?;
                     }
                 },
             }
+            self.check_pinned_operand(inst, operand)?;
+        }
+
+        // Check that an alternative can be selected for every operand with
+        // alternatives. Other errors with alternatives on groups and
+        // non-allocatable operands have already been reported above.
+        if let Err(operand) = self.alternatives.resolve(inst, self.func, self.reginfo) {
+            if matches!(
+                operands[operand].kind(),
+                OperandKind::Use(_) | OperandKind::Def(_) | OperandKind::EarlyDef(_)
+            ) {
+                self.errors
+                    .report(ValidationError::NoUsableAlternative { inst, operand })?;
+            }
         }
 
         // Check that clobbers don't overlap with fixed defs or other clobbers.
         let mut clobbers = RegUnitSet::new();
         for unit in self.func.inst_clobbers(inst) {
-            ensure!(
-                !clobbers.contains(unit),
-                "{inst}: Clobber {unit} specified multiple times in same instruction"
-            );
-            ensure!(
-                !self.late_fixed.contains(unit),
-                "{inst}: Clobber {unit} conflicts with fixed def"
-            );
+            if clobbers.contains(unit) {
+                self.errors
+                    .report(ValidationError::DuplicateClobber { inst, unit })?;
+            }
+            if self.late_fixed.contains(unit) {
+                self.errors
+                    .report(ValidationError::ClobberConflictsWithFixedDef { inst, unit })?;
+            }
             clobbers.insert(unit);
         }
 
+        // Safepoints must be preserved in the output.
+        if self.func.is_safepoint(inst) && self.func.can_eliminate_dead_inst(inst) {
+            self.errors.report(ValidationError::PureSafepoint(inst))?;
+        }
+
+        if self.func.is_copy(inst) {
+            self.check_copy(inst)?;
+        }
+
+        Ok(())
+    }
+
+    /// Check that a copy instruction has exactly one `Use` and one `Def` of the
+    /// same bank, both with a class constraint, and no other side effects.
+    fn check_copy(&mut self, inst: Inst) -> Result {
+        if self.func.is_safepoint(inst) {
+            self.errors.report(ValidationError::CopySafepoint(inst))?;
+        }
+        let mut def = None;
+        let mut use_ = None;
+        for op in self.func.inst_operands(inst) {
+            let OperandConstraint::Class(_) = op.constraint() else {
+                return self.errors.report(ValidationError::InvalidCopy(inst));
+            };
+            match op.kind() {
+                OperandKind::Def(value) if def.is_none() => def = Some(value),
+                OperandKind::Use(value) if use_.is_none() => use_ = Some(value),
+                _ => return self.errors.report(ValidationError::InvalidCopy(inst)),
+            }
+        }
+        let (Some(def), Some(use_)) = (def, use_) else {
+            return self.errors.report(ValidationError::InvalidCopy(inst));
+        };
+        if self.func.value_bank(def) != self.func.value_bank(use_)
+            || self.func.inst_clobbers(inst).next().is_some()
+        {
+            self.errors.report(ValidationError::InvalidCopy(inst))?;
+        }
+        Ok(())
+    }
+
+    /// Check the terminator of a basic block.
+    fn check_terminator(&mut self, block: Block, inst: Inst, kind: TerminatorKind) -> Result {
+        if self.func.can_eliminate_dead_inst(inst) {
+            self.errors.report(ValidationError::PureTerminator(inst))?;
+        }
+        if self.func.is_safepoint(inst) {
+            self.errors
+                .report(ValidationError::SafepointTerminator(inst))?;
+        }
+        if self.func.is_copy(inst) {
+            self.errors.report(ValidationError::CopyTerminator(inst))?;
+        }
+        if kind == TerminatorKind::Jump {
+            if let &[succ] = self.func.block_succs(block) {
+                if self.func.block_preds(succ).len() <= 1 {
+                    self.errors
+                        .report(ValidationError::JumpToSinglePred(inst))?;
+                }
+            } else {
+                self.errors
+                    .report(ValidationError::JumpWithoutSingleSucc(inst))?;
+            }
+            if !self.func.inst_operands(inst).is_empty() {
+                self.errors
+                    .report(ValidationError::JumpWithOperands(inst))?;
+            }
+            if self.func.inst_clobbers(inst).count() != 0 {
+                self.errors
+                    .report(ValidationError::JumpWithClobbers(inst))?;
+            }
+        } else if !self.func.jump_blockparams(block).is_empty() {
+            self.errors
+                .report(ValidationError::BlockParamsWithoutJump(inst))?;
+        }
+        if kind == TerminatorKind::Branch {
+            // Every successor with block parameters needs matching outgoing
+            // block parameters.
+            for (succ_idx, &succ) in self.func.block_succs(block).iter().enumerate() {
+                let args = self.func.branch_blockparams(block, succ_idx);
+                if !args.is_empty() || !self.func.block_params(succ).is_empty() {
+                    self.check_outgoing_blockparams(block, succ, args)?;
+                }
+            }
+        } else if (0..self.func.block_succs(block).len())
+            .any(|succ_idx| !self.func.branch_blockparams(block, succ_idx).is_empty())
+        {
+            self.errors
+                .report(ValidationError::BranchBlockParamsWithoutBranch(inst))?;
+        }
+        if kind == TerminatorKind::Ret {
+            if !self.func.block_succs(block).is_empty() {
+                self.errors.report(ValidationError::RetWithSuccs(inst))?;
+            }
+            for (operand, op) in self.func.inst_operands(inst).iter().enumerate() {
+                match op.kind() {
+                    OperandKind::Def(_) | OperandKind::DefGroup(_) => {
+                        self.errors
+                            .report(ValidationError::RetWithDef { inst, operand })?;
+                    }
+                    OperandKind::Use(_)
+                    | OperandKind::EarlyDef(_)
+                    | OperandKind::UseGroup(_)
+                    | OperandKind::EarlyDefGroup(_)
+                    | OperandKind::NonAllocatable => {}
+                }
+            }
+            if self.func.inst_clobbers(inst).count() != 0 {
+                self.errors.report(ValidationError::RetWithClobbers(inst))?;
+            }
+        } else if self.func.block_succs(block).is_empty() {
+            self.errors
+                .report(ValidationError::TerminatorWithoutSuccs(inst))?;
+        }
+        Ok(())
+    }
+
+    /// Check the outgoing block parameters on the edge from `block` to `succ`.
+    fn check_outgoing_blockparams(&mut self, block: Block, succ: Block, args: &[Value]) -> Result {
+        if self.func.block_params(succ).len() != args.len() {
+            self.errors
+                .report(ValidationError::BlockParamCountMismatch { block, succ })?;
+        }
+        for (&value_out, &value_in) in args.iter().zip(self.func.block_params(succ).iter()) {
+            self.check_entity(Entity::Value(value_out))?;
+            let bank_out = self.func.value_bank(value_out);
+            let bank_in = self.func.value_bank(value_in);
+            if bank_out != bank_in {
+                self.errors
+                    .report(ValidationError::BlockParamBankMismatch {
+                        value_out,
+                        bank_out,
+                        value_in,
+                        bank_in,
+                    })?;
+            }
+        }
         Ok(())
     }
 
     /// Check a basic block.
-    fn check_block(&mut self, block: Block) -> Result<()> {
+    fn check_block(&mut self, block: Block) -> Result {
         let insts = self.func.block_insts(block);
-        self.check_inst_range(insts)?;
+        self.check_inst_range(block, insts)?;
 
         // Block frequency must be positive. This also excludes zero and NaN.
-        ensure!(
-            self.func.block_frequency(block) > 0.0,
-            "{block}: Frequency must be positive and non-zero"
-        );
+        let positive_freq = self.func.block_frequency(block) > 0.0;
+        if !positive_freq {
+            self.errors
+                .report(ValidationError::InvalidBlockFrequency(block))?;
+        }
 
         // Instruction indicies must be ordered by block and with no gaps.
-        if block.index() != 0 {
+        let ordered_before = if block.index() != 0 {
             let prev_block = Block::new(block.index() - 1);
             let prev_insts = self.func.block_insts(prev_block);
-            ensure!(
-                insts.from == prev_insts.to,
-                "{block}: Instructions are not ordered by block"
-            );
+            insts.from == prev_insts.to
         } else {
-            ensure!(
-                insts.from.index() == 0,
-                "{block}: Instructions are not ordered by block"
-            );
-        }
-        if block.index() != self.func.num_blocks() - 1 {
+            insts.from.index() == 0
+        };
+        let ordered_after = if block.index() != self.func.num_blocks() - 1 {
             let next_block = Block::new(block.index() + 1);
             let next_insts = self.func.block_insts(next_block);
-            ensure!(
-                next_insts.from == insts.to,
-                "{block}: Instructions are not ordered by block"
-            );
+            next_insts.from == insts.to
         } else {
-            ensure!(
-                insts.to.index() == self.func.num_insts(),
-                "{block}: Instructions are not ordered by block"
-            );
+            insts.to.index() == self.func.num_insts()
+        };
+        if !ordered_before || !ordered_after {
+            self.errors
+                .report(ValidationError::InstsNotOrderedByBlock(block))?;
         }
 
         // Check consistency of successors & predecessors.
         for &pred in self.func.block_preds(block) {
-            ensure!(
-                self.func.block_succs(pred).contains(&block),
-                "Inconsistent predecessors and successors between {pred} and {block}"
-            );
+            if !self.func.block_succs(pred).contains(&block) {
+                self.errors
+                    .report(ValidationError::InconsistentPredsSuccs { pred, succ: block })?;
+            }
         }
         for &succ in self.func.block_succs(block) {
-            ensure!(
-                self.func.block_preds(succ).contains(&block),
-                "Inconsistent predecessors and successors between {block} and {succ}"
-            );
+            if !self.func.block_preds(succ).contains(&block) {
+                self.errors
+                    .report(ValidationError::InconsistentPredsSuccs { pred: block, succ })?;
+            }
         }
 
         // Check for crtical edges. If we have more than one predecessors, those
         // must only have one successor (this block).
-        if self.func.block_preds(block).len() > 1 {
+        //
+        // Functions can opt into critical edges, which may also be duplicated.
+        if self.func.block_preds(block).len() > 1 && !self.func.allows_critical_edges() {
             for &pred in self.func.block_preds(block) {
-                ensure!(
-                    self.func.block_succs(pred).len() == 1,
-                    "Critical edge beween {pred} and {block}"
-                );
+                if self.func.block_succs(pred).len() != 1 {
+                    self.errors
+                        .report(ValidationError::CriticalEdge { pred, succ: block })?;
+                }
             }
         }
 
         // Check incoming block parameters.
-        if !self.func.block_params(block).is_empty() {
-            ensure!(
-                self.func.block_preds(block).len() > 1,
-                "{block}: Block parameters are only allowed on blocks with multiple predecessors"
-            );
-        }
-        ensure!(
-            self.func.block_params(block).len() < MAX_BLOCK_PARAMS,
-            "{block} has too many block parameters (max {MAX_BLOCK_PARAMS})"
-        );
+        if !self.func.block_params(block).is_empty() && self.func.block_preds(block).len() <= 1 {
+            self.errors
+                .report(ValidationError::BlockParamsWithSinglePred(block))?;
+        }
+        if self.func.block_params(block).len() >= MAX_BLOCK_PARAMS {
+            self.errors.report(ValidationError::TooManyBlockParams {
+                block,
+                count: self.func.block_params(block).len(),
+            })?;
+        }
         for &param in self.func.block_params(block) {
             self.check_entity(Entity::Value(param))?;
             self.check_value_def(param, ValueDef::Blockparam(block))?;
@@ -477,174 +765,281 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
 
         // Check outgoing block parameters.
         if !self.func.jump_blockparams(block).is_empty() {
-            let &[succ] = self.func.block_succs(block) else {
-                bail!("{block}: Jump blockparams can only be used with a single successor");
-            };
-            ensure!(
-                self.func.block_params(succ).len() == self.func.jump_blockparams(block).len(),
-                "Blockparam count mismatch between {block} and {succ}"
-            );
-            for (&blockparam_out, &blockparam_in) in self
-                .func
-                .jump_blockparams(block)
-                .iter()
-                .zip(self.func.block_params(succ).iter())
-            {
-                self.check_entity(Entity::Value(blockparam_out))?;
-                let bank_out = self.func.value_bank(blockparam_out);
-                let bank_in = self.func.value_bank(blockparam_in);
-                ensure!(
-                    bank_out == bank_in,
-                    "Blockparams with different register banks: {blockparam_out} ({bank_out}) vs \
-                     {blockparam_in} ({bank_in})"
-                );
+            if let &[succ] = self.func.block_succs(block) {
+                self.check_outgoing_blockparams(block, succ, self.func.jump_blockparams(block))?;
+            } else {
+                self.errors
+                    .report(ValidationError::JumpBlockParamsWithoutSingleSucc(block))?;
             }
         }
 
         // Check instructions.
         let mut terminator = None;
+        let mut reported_terminator = false;
         for inst in insts.iter() {
-            ensure!(
-                self.func.inst_block(inst) == block,
-                "inst_block({inst}) should be {block}"
-            );
-            if let Some(inst) = terminator {
-                bail!("{inst}: Terminator in middle of block");
+            if self.func.inst_block(inst) != block {
+                self.errors
+                    .report(ValidationError::InconsistentInstBlock { inst, block })?;
+            }
+            if let Some(terminator) = terminator {
+                if !reported_terminator {
+                    self.errors
+                        .report(ValidationError::TerminatorInMiddleOfBlock { block, terminator })?;
+                    reported_terminator = true;
+                }
             }
             if let Some(kind) = self.func.terminator_kind(inst) {
-                ensure!(
-                    !self.func.can_eliminate_dead_inst(inst),
-                    "{inst}: Terminator cannot be marked as a pure instruction"
-                );
-                if kind == TerminatorKind::Jump {
-                    let &[succ] = self.func.block_succs(block) else {
-                        bail!("{inst}: Jump terminators can only be used with a single successor");
-                    };
-                    ensure!(
-                        self.func.block_preds(succ).len() > 1,
-                        "{inst}: Jump terminators can only target blocks with multiple \
-                         predecessors (use a Branch terminator instead)"
-                    );
-                    ensure!(
-                        self.func.inst_operands(inst).is_empty(),
-                        "{inst}: Terminator cannot have operands when the successor block has \
-                         multiple predecessors"
-                    );
-                    ensure!(
-                        self.func.inst_clobbers(inst).count() == 0,
-                        "{inst}: Terminator cannot have clobbers when the successor block has \
-                         multiple predecessors"
-                    );
-                } else {
-                    ensure!(
-                        self.func.jump_blockparams(block).is_empty(),
-                        "{inst}: Only jump terminators may have outgoing block parameters"
-                    );
+                self.check_terminator(block, inst, kind)?;
+                terminator = Some(inst);
+            }
+            self.check_inst(block, inst)?;
+        }
+        if terminator.is_none() {
+            self.errors
+                .report(ValidationError::MissingTerminator(block))?;
+        }
+
+        // Check sequences of instructions that forbid edits between them.
+        let mut seq_start = insts.from;
+        for inst in insts.iter() {
+            if !self.func.no_edits_before(inst) {
+                seq_start = inst;
+                continue;
+            }
+            self.check_no_edits_before(block, inst)?;
+            if inst.next() == insts.to || !self.func.no_edits_before(inst.next()) {
+                self.check_edit_sequence(InstRange::new(seq_start, inst.next()))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the restrictions on an instruction marked with `no_edits_before`.
+    fn check_no_edits_before(&mut self, block: Block, inst: Inst) -> Result {
+        // Edits for incoming block parameters and for outgoing block
+        // parameters of a jump can't be moved elsewhere.
+        if inst == self.func.block_insts(block).from {
+            return self
+                .errors
+                .report(ValidationError::NoEditsBeforeFirstInst(inst));
+        }
+        if self.func.terminator_kind(inst) == Some(TerminatorKind::Jump) {
+            self.errors
+                .report(ValidationError::NoEditsBeforeJump(inst))?;
+        }
+
+        // References may be spilled before and reloaded after a safepoint.
+        let prev = inst.prev();
+        if self.func.is_safepoint(inst) || self.func.is_safepoint(prev) {
+            self.errors
+                .report(ValidationError::NoEditsBeforeSafepoint(inst))?;
+        }
+
+        // Fixed and tied operands may require moves before the instruction.
+        for (operand, op) in self.func.inst_operands(inst).iter().enumerate() {
+            let valid = match op.kind() {
+                OperandKind::Use(_) | OperandKind::Def(_) | OperandKind::EarlyDef(_) => {
+                    matches!(op.constraint(), OperandConstraint::Class(_))
                 }
-                if kind == TerminatorKind::Ret {
-                    ensure!(
-                        self.func.block_succs(block).is_empty(),
-                        "{inst}: Ret terminators cannot have successors"
-                    );
-                    for op in self.func.inst_operands(inst) {
-                        match op.kind() {
-                            OperandKind::Def(_) | OperandKind::DefGroup(_) => bail!(
-                                "{inst}: Ret terminators cannot have Def operands, only \
-                                 Use/EarlyDef"
-                            ),
-                            OperandKind::Use(_)
-                            | OperandKind::EarlyDef(_)
-                            | OperandKind::UseGroup(_)
-                            | OperandKind::EarlyDefGroup(_)
-                            | OperandKind::NonAllocatable => {}
-                        }
+                OperandKind::NonAllocatable => true,
+                OperandKind::DefGroup(_)
+                | OperandKind::EarlyDefGroup(_)
+                | OperandKind::UseGroup(_) => false,
+            };
+            if !valid {
+                self.errors
+                    .report(ValidationError::NoEditsInvalidOperand { inst, operand })?;
+            }
+        }
+
+        // A fixed definition may require a move after the instruction.
+        let prev_operands = self.func.inst_operands(prev);
+        for (operand, op) in prev_operands.iter().enumerate() {
+            if matches!(op.kind(), OperandKind::Use(_) | OperandKind::UseGroup(_)) {
+                continue;
+            }
+            let fixed = match op.constraint() {
+                OperandConstraint::Fixed(_) => !matches!(op.kind(), OperandKind::NonAllocatable),
+                OperandConstraint::Reuse(target) => {
+                    prev_operands.get(target).is_some_and(|target| {
+                        matches!(target.constraint(), OperandConstraint::Fixed(_))
+                    })
+                }
+                OperandConstraint::Alternatives(set) => self
+                    .func
+                    .alternative_set(set)
+                    .iter()
+                    .any(|&(constraint, _)| matches!(constraint, OperandConstraint::Fixed(_))),
+                OperandConstraint::Class(_) => false,
+            };
+            if fixed {
+                self.errors.report(ValidationError::NoEditsAfterFixedDef {
+                    inst: prev,
+                    operand,
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that values used or defined in a sequence of instructions that
+    /// forbids edits between them have the same class in every operand of the
+    /// sequence.
+    ///
+    /// `insts` starts with the instruction before the first one marked with
+    /// `no_edits_before`.
+    fn check_edit_sequence(&mut self, insts: InstRange) -> Result {
+        // Collect the classes of values in the marked instructions, whose
+        // operands have already been checked to have a `Class` constraint.
+        self.sequence_values.clear();
+        for inst in insts.iter().skip(1) {
+            for (operand, op) in self.func.inst_operands(inst).iter().enumerate() {
+                let (OperandKind::Use(value)
+                | OperandKind::Def(value)
+                | OperandKind::EarlyDef(value)) = op.kind()
+                else {
+                    continue;
+                };
+                let OperandConstraint::Class(class) = op.constraint() else {
+                    continue;
+                };
+                match self.sequence_values.iter().find(|&&(v, _)| v == value) {
+                    Some(&(_, existing)) if existing != class => {
+                        self.errors
+                            .report(ValidationError::NoEditsConstraintMismatch { inst, operand })?;
                     }
-                    ensure!(
-                        self.func.inst_clobbers(inst).count() == 0,
-                        "{inst}: Ret terminators cannot have clobbers"
-                    );
-                } else {
-                    ensure!(
-                        !self.func.block_succs(block).is_empty(),
-                        "{inst}: Non-ret terminators must have successors"
-                    );
+                    Some(_) => {}
+                    None => self.sequence_values.push((value, class)),
+                }
+            }
+        }
+
+        // Operands in the first instruction may also be fixed uses.
+        let inst = insts.from;
+        let operands = self.func.inst_operands(inst);
+        for (operand, op) in operands.iter().enumerate() {
+            let kind = op.kind();
+            let values = match kind {
+                OperandKind::Use(ref value)
+                | OperandKind::Def(ref value)
+                | OperandKind::EarlyDef(ref value) => slice::from_ref(value),
+                OperandKind::UseGroup(group)
+                | OperandKind::DefGroup(group)
+                | OperandKind::EarlyDefGroup(group) => self.func.value_group_members(group),
+                OperandKind::NonAllocatable => continue,
+            };
+            let class = match op.constraint() {
+                OperandConstraint::Class(class) if values.len() == 1 => Some(class),
+                OperandConstraint::Fixed(_) if matches!(kind, OperandKind::Use(_)) => continue,
+                OperandConstraint::Reuse(target) if values.len() == 1 => {
+                    match operands.get(target).map(|target| target.constraint()) {
+                        Some(OperandConstraint::Class(class)) => Some(class),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            for &value in values {
+                if self
+                    .sequence_values
+                    .iter()
+                    .any(|&(v, c)| v == value && Some(c) != class)
+                {
+                    self.errors
+                        .report(ValidationError::NoEditsConstraintMismatch { inst, operand })?;
                 }
-                terminator = Some(inst);
             }
-            self.check_inst(block, inst)?;
         }
-        ensure!(terminator.is_some(), "{block}: Missing terminator");
 
         Ok(())
     }
 
+    /// Check that the definition of `value` dominates a use in `block`.
+    ///
+    /// `inst` is the using instruction, or `None` for outgoing block
+    /// parameters.
+    ///
+    /// Returns `None` if `value` has no definition.
+    fn def_dominates_use(&self, value: Value, block: Block, inst: Option<Inst>) -> Option<bool> {
+        let dominates = match self.value_defs[value]? {
+            ValueDef::Blockparam(def_block) => self.domtree.dominates(def_block, block),
+            ValueDef::Inst(def_block, def_inst) => match inst {
+                // Can't use a value defined in the same instruction.
+                Some(inst) if def_block == block => def_inst.index() < inst.index(),
+                _ => self.domtree.dominates(def_block, block),
+            },
+        };
+        Some(dominates)
+    }
+
     /// Check that defs dominate uses.
     ///
-    /// At this point the dominator tree should be valid and all values should
-    /// have a `ValueDef`.
-    fn check_ssa_dominance(&self, block: Block) -> Result<()> {
+    /// At this point the dominator tree should be valid.
+    fn check_ssa_dominance(&mut self, block: Block) -> Result {
         // Check that the block's immediate dominator is correct.
-        ensure!(
-            self.func.block_immediate_dominator(block) == self.domtree.immediate_dominator(block),
-            "{block} has incorrect immediate dominator: got {:?}, expected {:?}",
-            self.func.block_immediate_dominator(block),
-            self.domtree.immediate_dominator(block)
-        );
-
-        if let Some(idom) = self.domtree.immediate_dominator(block) {
+        let got = self.func.block_immediate_dominator(block);
+        let expected = self.domtree.immediate_dominator(block);
+        if got != expected {
+            self.errors
+                .report(ValidationError::IncorrectImmediateDominator {
+                    block,
+                    got,
+                    expected,
+                })?;
+        }
+
+        if let Some(idom) = expected {
             // This also ensures that all defs come before uses in the linear
             // instruction ordering.
-            ensure!(
-                idom.index() < block.index(),
-                "{idom} dominates {block} but has higher index"
-            );
+            if idom.index() >= block.index() {
+                self.errors
+                    .report(ValidationError::DominatorHasHigherIndex { block, idom })?;
+            }
         }
 
         for inst in self.func.block_insts(block).iter() {
             for op in self.func.inst_operands(inst) {
                 if let OperandKind::Use(value) = op.kind() {
-                    let Some(def) = self.value_defs[value] else {
-                        bail!("{value} used without being defined");
-                    };
-                    let dominates = match def {
-                        ValueDef::Blockparam(def_block) => self.domtree.dominates(def_block, block),
-                        ValueDef::Inst(def_block, def_inst) => {
-                            if def_block == block {
-                                // Can't use a value defined in the same instruction.
-                                def_inst.index() < inst.index()
-                            } else {
-                                self.domtree.dominates(def_block, block)
-                            }
-                        }
-                    };
-                    ensure!(
-                        dominates,
-                        "{value} definition does not dominate use at {inst}"
-                    );
+                    match self.def_dominates_use(value, block, Some(inst)) {
+                        None => self.errors.report(ValidationError::UndefinedValue(value))?,
+                        Some(false) => self
+                            .errors
+                            .report(ValidationError::DefDoesNotDominateUse { value, inst })?,
+                        Some(true) => {}
+                    }
                 }
             }
         }
-        for &value in self.func.jump_blockparams(block) {
-            let Some(def) = self.value_defs[value] else {
-                bail!("{value} used without being defined");
-            };
-            let dominates = match def {
-                ValueDef::Blockparam(def_block) | ValueDef::Inst(def_block, _) => {
-                    self.domtree.dominates(def_block, block)
-                }
-            };
-            ensure!(
-                dominates,
-                "{value} definition does not dominate use as outgoing blockparam in {block}"
-            );
+        let branch_blockparams = (0..self.func.block_succs(block).len())
+            .flat_map(|succ_idx| self.func.branch_blockparams(block, succ_idx));
+        for &value in self
+            .func
+            .jump_blockparams(block)
+            .iter()
+            .chain(branch_blockparams)
+        {
+            match self.def_dominates_use(value, block, None) {
+                None => self.errors.report(ValidationError::UndefinedValue(value))?,
+                Some(false) => self
+                    .errors
+                    .report(ValidationError::DefDoesNotDominateBlockParam { value, block })?,
+                Some(true) => {}
+            }
         }
         Ok(())
     }
 
     /// Main entry point for `Function` validation.
-    fn check_function(&mut self) -> Result<()> {
+    fn check_function(&mut self) -> Result {
         self.check_limits()?;
 
+        // Check alternative sets before they are used by instructions.
+        for set in self.func.alternative_sets() {
+            self.check_alternative_set(set)?;
+        }
+
         // Check blocks and instructions. This also records a `ValueDef` for
         // each defined value.
         for block in self.func.blocks() {
@@ -652,29 +1047,24 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
         }
 
         // Check the entry block.
-        ensure!(
-            self.func.block_preds(Block::ENTRY_BLOCK).is_empty(),
-            "{}: Entry block cannot have predecessors",
-            Block::ENTRY_BLOCK
-        );
-        ensure!(
-            self.func.block_params(Block::ENTRY_BLOCK).is_empty(),
-            "{}: Entry block cannot have block parameters",
-            Block::ENTRY_BLOCK
-        );
-
-        // Check that all blocks are reachable.
+        if !self.func.block_preds(Block::ENTRY_BLOCK).is_empty() {
+            self.errors.report(ValidationError::EntryBlockHasPreds)?;
+        }
+        if !self.func.block_params(Block::ENTRY_BLOCK).is_empty() {
+            self.errors.report(ValidationError::EntryBlockHasParams)?;
+        }
+
+        // Check that all blocks are reachable. The dominance checks below
+        // require a fully reachable CFG.
         let postorder = PostOrder::for_function(self.func);
         if postorder.cfg_postorder().len() != self.func.num_blocks() {
             for block in self.func.blocks() {
-                ensure!(
-                    postorder.is_reachable(block),
-                    "{block} is not reachable from the entry block"
-                );
+                if !postorder.is_reachable(block) {
+                    self.errors
+                        .report(ValidationError::UnreachableBlock(block))?;
+                }
             }
-
-            // There must be at least one unreachable block, so we can't get here.
-            unreachable!();
+            return Err(Abort);
         }
 
         // Check that defs dominate uses, as required by SSA.
@@ -685,35 +1075,188 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
 
         // Check values.
         for value in self.func.values() {
-            if let Some((_cost, class)) = self.func.can_rematerialize(value) {
-                ensure!(
-                    self.reginfo.class_group_size(class) == 1,
-                    "{value} cannot be rematerialized with group register class {class}"
-                );
-                let bank = self.reginfo.bank_for_class(class);
-                let value_bank = self.func.value_bank(value);
-                ensure!(
-                    bank == value_bank,
-                    "{value} cannot be rematerialized with different register banks: {bank} vs \
-                     {value_bank}"
-                );
-
-                ensure!(
-                    !self.reginfo.allocation_order(class).is_empty(),
-                    "{value} cannot be rematerialized into {class} which has an empty allocation \
-                     order"
-                );
-                for reg in self.reginfo.class_members(class) {
-                    ensure!(
-                        self.reginfo.class_includes_spillslots(class)
-                            || !self.reginfo.is_memory(reg),
-                        "{value} cannot be rematerialized into {class} which has in-memory \
-                         members but doesn't include spill slots"
-                    );
+            if let Some((cost, class)) = self.func.can_rematerialize(value) {
+                self.check_remat(value, cost, class)?;
+            }
+            if let Some(input) = self.func.remat_input(value) {
+                self.check_remat_input(value, input)?;
+            }
+            if let Some(inst) = self.func.value_scope_end(value) {
+                self.check_scope_end(value, inst)?;
+            }
+            if let Some((reg, strength)) = self.func.value_hint(value) {
+                self.check_value_hint(value, reg, strength)?;
+            }
+            if let Some(reg) = self.func.value_pinned_reg(value) {
+                self.check_pinned_reg(value, reg)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that the end of the scope of `value` is dominated by its
+    /// definition.
+    fn check_scope_end(&mut self, value: Value, inst: Inst) -> Result {
+        self.check_entity(Entity::Inst(inst))?;
+        let block = self.func.inst_block(inst);
+        match self.def_dominates_use(value, block, Some(inst)) {
+            None => self.errors.report(ValidationError::UndefinedValue(value))?,
+            Some(false) => self
+                .errors
+                .report(ValidationError::DefDoesNotDominateScopeEnd { value, inst })?,
+            Some(true) => {}
+        }
+        Ok(())
+    }
+
+    /// Check the input value that `value` depends on for rematerialization.
+    fn check_remat_input(&mut self, value: Value, input: Value) -> Result {
+        self.check_entity(Entity::Value(input))?;
+        if self.func.can_rematerialize(value).is_none() {
+            self.errors
+                .report(ValidationError::RematInputWithoutRemat { value, input })?;
+        }
+        if self.func.can_rematerialize(input).is_some() {
+            self.errors
+                .report(ValidationError::RematInputIsRemat { value, input })?;
+        }
+        Ok(())
+    }
+
+    /// Check the register hint for `value`.
+    fn check_value_hint(&mut self, value: Value, reg: PhysReg, strength: f32) -> Result {
+        if !(strength.is_finite() && strength >= 0.0) {
+            self.errors
+                .report(ValidationError::InvalidValueHintStrength(value))?;
+        }
+        let Some(bank) = self.reginfo.bank_for_reg(reg) else {
+            return self
+                .errors
+                .report(ValidationError::NonAllocatableValueHint { value, reg });
+        };
+        let value_bank = self.func.value_bank(value);
+        if bank != value_bank {
+            self.errors.report(ValidationError::ValueHintBankMismatch {
+                value,
+                reg,
+                bank,
+                value_bank,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Check the register that `value` is pinned to.
+    fn check_pinned_reg(&mut self, value: Value, reg: PhysReg) -> Result {
+        let Some(bank) = self.reginfo.bank_for_reg(reg) else {
+            return self
+                .errors
+                .report(ValidationError::NonAllocatablePinnedReg { value, reg });
+        };
+        let value_bank = self.func.value_bank(value);
+        if bank != value_bank {
+            self.errors.report(ValidationError::PinnedRegBankMismatch {
+                value,
+                reg,
+                bank,
+                value_bank,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Check that an operand is compatible with the registers that its values
+    /// are pinned to.
+    fn check_pinned_operand(&mut self, inst: Inst, operand: usize) -> Result {
+        let operands = self.func.inst_operands(inst);
+        let op = operands[operand];
+        match op.kind() {
+            OperandKind::Def(value) | OperandKind::EarlyDef(value) | OperandKind::Use(value) => {
+                let Some(reg) = self.func.value_pinned_reg(value) else {
+                    return Ok(());
+                };
+                let class = match op.constraint() {
+                    OperandConstraint::Class(class) => class,
+                    OperandConstraint::Reuse(target) => match operands.get(target) {
+                        Some(target) => match target.constraint() {
+                            OperandConstraint::Class(class) => class,
+                            OperandConstraint::Fixed(_)
+                            | OperandConstraint::Reuse(_)
+                            | OperandConstraint::Alternatives(_) => {
+                                return Ok(());
+                            }
+                        },
+                        None => return Ok(()),
+                    },
+                    // Alternatives whose class doesn't include the pinned
+                    // register are never selected.
+                    OperandConstraint::Fixed(_) | OperandConstraint::Alternatives(_) => {
+                        return Ok(());
+                    }
+                };
+                if !self.reginfo.class_members(class).contains(reg) {
+                    self.errors.report(ValidationError::PinnedRegNotInClass {
+                        inst,
+                        operand,
+                        value,
+                        reg,
+                        class,
+                    })?;
+                }
+            }
+            OperandKind::DefGroup(group)
+            | OperandKind::EarlyDefGroup(group)
+            | OperandKind::UseGroup(group) => {
+                for &value in self.func.value_group_members(group) {
+                    if self.func.value_pinned_reg(value).is_some() {
+                        self.errors.report(ValidationError::PinnedValueInGroup {
+                            inst,
+                            operand,
+                            value,
+                        })?;
+                    }
                 }
             }
+            OperandKind::NonAllocatable => {}
         }
+        Ok(())
+    }
 
+    /// Check the register class used to rematerialize `value`.
+    fn check_remat(&mut self, value: Value, cost: f32, class: RegClass) -> Result {
+        if !(cost.is_finite() && cost >= 0.0) {
+            self.errors
+                .report(ValidationError::InvalidRematCost(value))?;
+        }
+        if self.reginfo.class_group_size(class) != 1 {
+            self.errors
+                .report(ValidationError::RematGroupClass { value, class })?;
+        }
+        let bank = self.reginfo.bank_for_class(class);
+        let value_bank = self.func.value_bank(value);
+        if bank != value_bank {
+            self.errors.report(ValidationError::RematBankMismatch {
+                value,
+                class,
+                bank,
+                value_bank,
+            })?;
+        }
+        if self.reginfo.allocation_order(class).is_empty() {
+            self.errors
+                .report(ValidationError::RematEmptyAllocationOrder { value, class })?;
+        }
+        if !self.reginfo.class_includes_spillslots(class)
+            && self
+                .reginfo
+                .class_members(class)
+                .into_iter()
+                .any(|reg| self.reginfo.is_memory(reg))
+        {
+            self.errors
+                .report(ValidationError::RematMemoryClass { value, class })?;
+        }
         Ok(())
     }
 }
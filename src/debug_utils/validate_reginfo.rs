@@ -1,172 +1,197 @@
 //! Register information validation.
 
-use core::fmt;
-
-use anyhow::{Result, bail, ensure};
+use alloc::vec::Vec;
 
+use super::validation_error::{Abort, Entity, Errors, Limit, ValidationError};
 use crate::entity::SecondaryMap;
 use crate::reginfo::{
-    MAX_GROUP_SIZE, MAX_PHYSREGS, MAX_REG_BANKS, MAX_REG_CLASSES, MAX_REG_GROUPS, MAX_REG_UNITS,
-    MAX_UNITS_PER_REG, PhysReg, PhysRegSet, RegBank, RegClass, RegGroup, RegInfo, RegUnit,
-    RegUnitSet,
+    MAX_GROUP_SIZE, MAX_REG_UNITS, MAX_UNITS_PER_REG, PhysReg, PhysRegSet, RegBank, RegClass,
+    RegGroup, RegInfo, RegUnitSet,
 };
 
+type Result<T = ()> = core::result::Result<T, Abort>;
+
 /// Checks `reginfo` to ensure it satisfies all of the pre-conditions required
 /// by the register allocator.
-pub fn validate_reginfo(reginfo: &impl RegInfo) -> Result<()> {
-    let mut ctx = Context {
-        reginfo,
-        bank_units: SecondaryMap::with_max_index(reginfo.num_banks()),
-    };
-    ctx.check_reginfo()?;
-    Ok(())
+///
+/// Validation stops at the first error found, use [`validate_reginfo_all`] to
+/// collect all errors instead.
+pub fn validate_reginfo(reginfo: &impl RegInfo) -> core::result::Result<(), ValidationError> {
+    validate(reginfo, false).into_first()
 }
 
-/// An entity reference.
+/// Same as [`validate_reginfo`], but keeps going after an error is found and
+/// returns all of the errors in `reginfo`.
 ///
-/// This is used by [`ValidationError::InvalidEntity`] to report entity
-/// references with an invalid index.
-#[derive(Debug, Clone, Copy)]
-enum Entity {
-    RegUnit(RegUnit),
-    PhysReg(PhysReg),
-    RegGroup(RegGroup),
-    RegClass(RegClass),
-    RegBank(RegBank),
+/// Invalid entity references still prevent the checks that depend on them from
+/// running.
+pub fn validate_reginfo_all(
+    reginfo: &impl RegInfo,
+) -> core::result::Result<(), Vec<ValidationError>> {
+    validate(reginfo, true).into_all()
 }
 
-impl fmt::Display for Entity {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match *self {
-            Entity::RegUnit(x) => x.fmt(f),
-            Entity::PhysReg(x) => x.fmt(f),
-            Entity::RegGroup(x) => x.fmt(f),
-            Entity::RegClass(x) => x.fmt(f),
-            Entity::RegBank(x) => x.fmt(f),
-        }
-    }
+fn validate(reginfo: &impl RegInfo, collect_all: bool) -> Errors {
+    let mut ctx = Context {
+        reginfo,
+        errors: Errors::new(collect_all),
+        bank_units: SecondaryMap::with_max_index(reginfo.num_banks()),
+    };
+    let _ = ctx.check_reginfo();
+    ctx.errors
 }
 
 /// State used for validation.
 struct Context<'a, R> {
     reginfo: &'a R,
+    errors: Errors,
     bank_units: SecondaryMap<RegBank, RegUnitSet>,
 }
 
 impl<R: RegInfo> Context<'_, R> {
     /// Check that an entity refers to a valid object.
-    fn check_entity(&self, entity: Entity) -> Result<()> {
+    ///
+    /// Validation can't continue past an invalid reference since it would
+    /// cause out-of-bounds accesses.
+    fn check_entity(&mut self, entity: Entity) -> Result {
         let (index, len) = match entity {
             Entity::RegUnit(x) => (x.index(), MAX_REG_UNITS),
             Entity::PhysReg(x) => (x.index(), self.reginfo.num_regs()),
             Entity::RegGroup(x) => (x.index(), self.reginfo.num_reg_groups()),
             Entity::RegClass(x) => (x.index(), self.reginfo.num_classes()),
             Entity::RegBank(x) => (x.index(), self.reginfo.num_banks()),
+            Entity::Value(_)
+            | Entity::ValueGroup(_)
+            | Entity::AlternativeSet(_)
+            | Entity::Inst(_) => unreachable!(),
         };
-        ensure!(index < len, "{entity}: Invalid entity reference");
+        if index >= len {
+            return Err(self.errors.fatal(ValidationError::InvalidEntity(entity)));
+        }
         Ok(())
     }
 
     /// Check the limits on the number of entities.
-    fn check_limits(&self) -> Result<()> {
-        ensure!(
-            self.reginfo.num_regs() <= MAX_PHYSREGS,
-            "Too many registers: {} (max: {MAX_PHYSREGS})",
-            self.reginfo.num_regs(),
-        );
-        ensure!(
-            self.reginfo.num_reg_groups() <= MAX_REG_GROUPS,
-            "Too many register groups: {} (max: {MAX_REG_GROUPS})",
-            self.reginfo.num_reg_groups(),
-        );
-        ensure!(
-            self.reginfo.num_classes() <= MAX_REG_CLASSES,
-            "Too many register classes: {} (max: {MAX_REG_CLASSES})",
-            self.reginfo.num_classes(),
-        );
-        ensure!(
-            self.reginfo.num_banks() <= MAX_REG_BANKS,
-            "Too many register banks: {} (max: {MAX_REG_BANKS})",
-            self.reginfo.num_banks(),
-        );
+    fn check_limits(&mut self) -> Result {
+        for (limit, count) in [
+            (Limit::PhysRegs, self.reginfo.num_regs()),
+            (Limit::RegGroups, self.reginfo.num_reg_groups()),
+            (Limit::RegClasses, self.reginfo.num_classes()),
+            (Limit::RegBanks, self.reginfo.num_banks()),
+        ] {
+            if count > limit.max() {
+                self.errors
+                    .report(ValidationError::LimitExceeded { limit, count })?;
+            }
+        }
         Ok(())
     }
 
     /// Check a register bank.
-    fn check_bank(&mut self, bank: RegBank) -> Result<()> {
+    fn check_bank(&mut self, bank: RegBank) -> Result {
         // Check that the top-level class is part of this bank and allows
         // spillslots
         let top_level_class = self.reginfo.top_level_class(bank);
         self.check_entity(Entity::RegClass(top_level_class))?;
-        ensure!(
-            self.reginfo.bank_for_class(top_level_class) == bank,
-            "{bank}: Top-level class {top_level_class} is not in bank"
-        );
-        ensure!(
-            self.reginfo.class_includes_spillslots(top_level_class),
-            "{bank}: Top-level class {top_level_class} must include spillslots"
-        );
-        ensure!(
-            self.reginfo.class_group_size(top_level_class) == 1,
-            "{bank}: Top-level class {top_level_class} must have a group size of 1"
-        );
+        if self.reginfo.bank_for_class(top_level_class) != bank {
+            self.errors
+                .report(ValidationError::TopLevelClassNotInBank {
+                    bank,
+                    class: top_level_class,
+                })?;
+        }
+        if !self.reginfo.class_includes_spillslots(top_level_class) {
+            self.errors
+                .report(ValidationError::TopLevelClassWithoutSpillslots {
+                    bank,
+                    class: top_level_class,
+                })?;
+        }
+        if self.reginfo.class_group_size(top_level_class) != 1 {
+            self.errors.report(ValidationError::TopLevelClassIsGroup {
+                bank,
+                class: top_level_class,
+            })?;
+        }
 
         // Check stack_to_stack_class
         let stack_to_stack_class = self.reginfo.stack_to_stack_class(bank);
         self.check_entity(Entity::RegClass(stack_to_stack_class))?;
-        ensure!(
-            self.reginfo.bank_for_class(stack_to_stack_class) == bank,
-            "{bank}: Stack-to-stack class {stack_to_stack_class} is not in bank"
-        );
-        ensure!(
-            self.reginfo.class_group_size(stack_to_stack_class) == 1,
-            "{stack_to_stack_class}: Stack-to-stack class must have a group size of 1"
-        );
-        ensure!(
-            !self.reginfo.class_includes_spillslots(stack_to_stack_class),
-            "{stack_to_stack_class}: Stack-to-stack class cannot include spill slots"
-        );
+        if self.reginfo.bank_for_class(stack_to_stack_class) != bank {
+            self.errors
+                .report(ValidationError::StackToStackClassNotInBank {
+                    bank,
+                    class: stack_to_stack_class,
+                })?;
+        }
+        if self.reginfo.class_group_size(stack_to_stack_class) != 1 {
+            self.errors
+                .report(ValidationError::StackToStackClassIsGroup {
+                    bank,
+                    class: stack_to_stack_class,
+                })?;
+        }
+        if self.reginfo.class_includes_spillslots(stack_to_stack_class) {
+            self.errors
+                .report(ValidationError::StackToStackClassWithSpillslots {
+                    bank,
+                    class: stack_to_stack_class,
+                })?;
+        }
 
         // Check registers in the bank
         let mut empty = true;
         for reg in self.reginfo.regs() {
             if self.reginfo.bank_for_reg(reg) == Some(bank) {
                 empty = false;
-                ensure!(
-                    self.reginfo.class_members(top_level_class).contains(reg),
-                    "{bank}: {reg} not in top-level class {top_level_class}"
-                );
+                if !self.reginfo.class_members(top_level_class).contains(reg) {
+                    self.errors.report(ValidationError::RegNotInTopLevelClass {
+                        bank,
+                        class: top_level_class,
+                        reg,
+                    })?;
+                }
             }
         }
-        ensure!(!empty, "{bank} has no registers");
+        if empty {
+            self.errors.report(ValidationError::EmptyBank(bank))?;
+        }
 
         // Check stack_to_stack_class
         for reg in self.reginfo.class_members(stack_to_stack_class) {
-            ensure!(
-                !self.reginfo.is_memory(reg),
-                "{bank}: {reg} in stack-to-stack {stack_to_stack_class} cannot be in memory"
-            );
+            if self.reginfo.is_memory(reg) {
+                self.errors
+                    .report(ValidationError::StackToStackClassWithMemory {
+                        bank,
+                        class: stack_to_stack_class,
+                        reg,
+                    })?;
+            }
         }
 
         Ok(())
     }
 
     /// Check a register class.
-    fn check_class(&mut self, class: RegClass) -> Result<()> {
+    #[allow(clippy::too_many_lines)]
+    fn check_class(&mut self, class: RegClass) -> Result {
         let bank = self.reginfo.bank_for_class(class);
         self.check_entity(Entity::RegBank(bank))?;
 
         let group_size = self.reginfo.class_group_size(class);
-        ensure!(
-            group_size <= MAX_GROUP_SIZE,
-            "{class}: Group size {group_size} too large (max: {MAX_GROUP_SIZE})"
-        );
-        ensure!(group_size != 0, "{class}: Invalid group size of 0");
-        if group_size != 1 {
-            ensure!(
-                !self.reginfo.class_includes_spillslots(class),
-                "{class}: Group class cannot include spillslots"
-            );
+        if group_size > MAX_GROUP_SIZE {
+            // Further checks would index out of bounds.
+            return Err(self.errors.fatal(ValidationError::GroupSizeTooLarge {
+                class,
+                size: group_size,
+            }));
+        }
+        if group_size == 0 {
+            self.errors.report(ValidationError::ZeroGroupSize(class))?;
+        }
+        if group_size != 1 && self.reginfo.class_includes_spillslots(class) {
+            self.errors
+                .report(ValidationError::GroupClassWithSpillslots(class))?;
         }
 
         if group_size != 1 {
@@ -174,135 +199,163 @@ impl<R: RegInfo> Context<'_, R> {
             for group in self.reginfo.class_group_members(class) {
                 // Check that class members have the same group size as the class.
                 let members = self.reginfo.reg_group_members(group);
-                ensure!(
-                    members.len() == group_size,
-                    "{group} group size ({}) doesn't match {class} group size ({group_size})",
-                    members.len()
-                );
+                if members.len() != group_size {
+                    self.errors
+                        .report(ValidationError::ClassGroupSizeMismatch {
+                            class,
+                            group,
+                            size: members.len(),
+                            class_size: group_size,
+                        })?;
+                }
 
-                for (group_index, &reg) in members.iter().enumerate() {
+                for (group_index, &reg) in members.iter().enumerate().take(MAX_GROUP_SIZE) {
                     // Check that class members are in the same bank as the class.
-                    ensure!(
-                        self.reginfo.bank_for_reg(reg) == Some(bank),
-                        "{class} contains {reg} which is outside {bank}"
-                    );
+                    if self.reginfo.bank_for_reg(reg) != Some(bank) {
+                        self.errors.report(ValidationError::ClassRegNotInBank {
+                            class,
+                            reg,
+                            bank,
+                        })?;
+                    }
 
                     // Check that, at each index, each register is unique within the class.
-                    ensure!(
-                        !regs_per_index[group_index].contains(reg),
-                        "{class} has duplicate register {reg} at group index {group_index}"
-                    );
+                    if regs_per_index[group_index].contains(reg) {
+                        self.errors.report(ValidationError::DuplicateClassReg {
+                            class,
+                            reg,
+                            group_index,
+                        })?;
+                    }
                     regs_per_index[group_index].insert(reg);
 
                     // Check the reverse mapping in group_for_reg.
-                    ensure!(
-                        self.reginfo.group_for_reg(reg, group_index, class) == Some(group),
-                        "Inconsistent group_for_reg({reg}, {group_index}, {class}): got {:?} \
-                         expected {:?}",
-                        self.reginfo.group_for_reg(reg, group_index, class),
-                        Some(group)
-                    );
+                    let got = self.reginfo.group_for_reg(reg, group_index, class);
+                    if got != Some(group) {
+                        self.errors
+                            .report(ValidationError::InconsistentGroupForReg {
+                                class,
+                                reg,
+                                group_index,
+                                got,
+                                expected: group,
+                            })?;
+                    }
                 }
             }
         } else {
             for reg in self.reginfo.class_members(class) {
                 // Check that class members are in the same bank as the class.
-                ensure!(
-                    self.reginfo.bank_for_reg(reg) == Some(bank),
-                    "{class} contains {reg} which is outside {bank}"
-                );
+                if self.reginfo.bank_for_reg(reg) != Some(bank) {
+                    self.errors
+                        .report(ValidationError::ClassRegNotInBank { class, reg, bank })?;
+                }
             }
         }
 
         // Check that the allocation order isn't empty if spillslot are not
         // allowed or if this is a group register class.
         if group_size == 1 {
-            if !self.reginfo.class_includes_spillslots(class) {
-                ensure!(
-                    !self.reginfo.allocation_order(class).is_empty(),
-                    "{class} cannot have an empty allocation order unless it allows spillslots"
-                );
+            if !self.reginfo.class_includes_spillslots(class)
+                && self.reginfo.allocation_order(class).is_empty()
+            {
+                self.errors
+                    .report(ValidationError::EmptyAllocationOrder(class))?;
+            }
+            if !self.reginfo.group_allocation_order(class).is_empty() {
+                self.errors
+                    .report(ValidationError::UnexpectedGroupAllocationOrder(class))?;
             }
-            ensure!(
-                self.reginfo.group_allocation_order(class).is_empty(),
-                "{class}: Non-group class cannot have a group allocation order"
-            );
         } else {
-            ensure!(
-                !self.reginfo.group_allocation_order(class).is_empty(),
-                "{class}: Group class cannot have an empty allocation order"
-            );
-            ensure!(
-                self.reginfo.allocation_order(class).is_empty(),
-                "{class}: Non-group class cannot have a non-group allocation order"
-            );
+            if self.reginfo.group_allocation_order(class).is_empty() {
+                self.errors
+                    .report(ValidationError::EmptyGroupAllocationOrder(class))?;
+            }
+            if !self.reginfo.allocation_order(class).is_empty() {
+                self.errors
+                    .report(ValidationError::UnexpectedAllocationOrder(class))?;
+            }
         }
 
         // Check that the allocation order only contains class members.
         if group_size == 1 {
             for &reg in self.reginfo.allocation_order(class) {
                 self.check_entity(Entity::PhysReg(reg))?;
-                ensure!(
-                    self.reginfo.class_members(class).contains(reg),
-                    "{class}: Allocation order contains {reg} which is outside class"
-                );
+                if !self.reginfo.class_members(class).contains(reg) {
+                    self.errors
+                        .report(ValidationError::AllocationOrderRegNotInClass { class, reg })?;
+                }
             }
         } else {
             for &group in self.reginfo.group_allocation_order(class) {
                 self.check_entity(Entity::RegGroup(group))?;
-                ensure!(
-                    self.reginfo.class_group_members(class).contains(group),
-                    "{class}: Allocation order contains {group} which is outside class"
-                );
+                if !self.reginfo.class_group_members(class).contains(group) {
+                    self.errors
+                        .report(ValidationError::AllocationOrderGroupNotInClass { class, group })?;
+                }
             }
         }
 
         // Check subclasses
         let top_level_class = self.reginfo.top_level_class(bank);
-        ensure!(
-            self.reginfo.sub_classes(top_level_class).contains(class),
-            "{class} must be a subclass of the top-level class for {bank} ({top_level_class})"
-        );
-        ensure!(
-            self.reginfo.sub_classes(class).contains(class),
-            "{class} must be a subclass of itself"
-        );
+        if !self.reginfo.sub_classes(top_level_class).contains(class) {
+            self.errors
+                .report(ValidationError::NotSubclassOfTopLevelClass {
+                    class,
+                    bank,
+                    top_level_class,
+                })?;
+        }
+        if !self.reginfo.sub_classes(class).contains(class) {
+            self.errors
+                .report(ValidationError::NotSubclassOfItself(class))?;
+        }
         for subclass in self.reginfo.sub_classes(class) {
-            ensure!(
-                subclass.index() >= class.index(),
-                "{class} must not have any subclasses with a lower index than itself"
-            );
-            ensure!(
-                self.reginfo.bank_for_class(subclass) == bank,
-                "Subclass {subclass} of {class} is not in {bank}"
-            );
-            if !self.reginfo.class_includes_spillslots(class) {
-                ensure!(
-                    !self.reginfo.class_includes_spillslots(subclass),
-                    "{subclass} allows spillslots but is subclass of {class} which doesn't"
-                );
+            if subclass.index() < class.index() {
+                self.errors
+                    .report(ValidationError::SubclassHasLowerIndex { class, subclass })?;
+            }
+            if self.reginfo.bank_for_class(subclass) != bank {
+                self.errors.report(ValidationError::SubclassNotInBank {
+                    class,
+                    subclass,
+                    bank,
+                })?;
+            }
+            if !self.reginfo.class_includes_spillslots(class)
+                && self.reginfo.class_includes_spillslots(subclass)
+            {
+                self.errors
+                    .report(ValidationError::SubclassWithSpillslots { class, subclass })?;
             }
 
             if group_size == 1 && self.reginfo.class_group_size(subclass) > 1 {
                 for group in self.reginfo.class_group_members(subclass) {
-                    for &member in self.reginfo.reg_group_members(group) {
-                        ensure!(
-                            self.reginfo.class_members(class).contains(member),
-                            "Superclass {class} of {subclass} doesn't contain {member} (member of \
-                             {group})"
-                        );
+                    for &reg in self.reginfo.reg_group_members(group) {
+                        if !self.reginfo.class_members(class).contains(reg) {
+                            self.errors
+                                .report(ValidationError::SuperclassMissingGroupMember {
+                                    class,
+                                    subclass,
+                                    group,
+                                    reg,
+                                })?;
+                        }
                     }
                 }
             } else {
-                ensure!(
-                    self.reginfo.class_group_size(subclass) == group_size,
-                    "Subclass {subclass} must have same group size as {class}"
-                );
+                if self.reginfo.class_group_size(subclass) != group_size {
+                    self.errors
+                        .report(ValidationError::SubclassGroupSizeMismatch { class, subclass })?;
+                }
                 for reg in self.reginfo.class_members(subclass) {
-                    ensure!(
-                        self.reginfo.class_members(class).contains(reg),
-                        "Subclass {subclass} of {class} doesn't contain {reg}"
-                    );
+                    if !self.reginfo.class_members(class).contains(reg) {
+                        self.errors.report(ValidationError::SubclassRegNotInClass {
+                            class,
+                            subclass,
+                            reg,
+                        })?;
+                    }
                 }
             }
         }
@@ -311,67 +364,76 @@ impl<R: RegInfo> Context<'_, R> {
     }
 
     /// Check a register.
-    fn check_reg(&mut self, reg: PhysReg) -> Result<()> {
+    fn check_reg(&mut self, reg: PhysReg) -> Result {
         // Non-allocatable registers have no constraints.
         if let Some(bank) = self.reginfo.bank_for_reg(reg) {
             self.check_entity(Entity::RegBank(bank))?;
-            ensure!(
-                self.reginfo.reg_units(reg).count() != 0,
-                "{reg}: Allocatable register must have at least 1 register unit"
-            );
-
-            ensure!(
-                self.reginfo.reg_units(reg).count() <= MAX_UNITS_PER_REG,
-                "{reg} has too many register units (max is {MAX_UNITS_PER_REG})"
-            );
+            let count = self.reginfo.reg_units(reg).count();
+            if count == 0 {
+                self.errors.report(ValidationError::RegWithoutUnits(reg))?;
+            }
+            if count > MAX_UNITS_PER_REG {
+                self.errors
+                    .report(ValidationError::TooManyRegUnits { reg, count })?;
+            }
 
             for unit in self.reginfo.reg_units(reg) {
                 self.check_entity(Entity::RegUnit(unit))?;
-                ensure!(
-                    !self.bank_units[bank].contains(unit),
-                    "{unit} in {reg} overlaps with other registers in {bank}"
-                );
+                if self.bank_units[bank].contains(unit) {
+                    self.errors
+                        .report(ValidationError::OverlappingRegUnit { reg, unit, bank })?;
+                }
                 self.bank_units[bank].insert(unit);
             }
+
+            let cost = self.reginfo.callee_saved_cost(reg);
+            if !(cost.is_finite() && cost >= 0.0) {
+                self.errors
+                    .report(ValidationError::InvalidCalleeSavedCost(reg))?;
+            }
         }
 
         Ok(())
     }
 
     /// Check a register group.
-    fn check_reg_group(&mut self, group: RegGroup) -> Result<()> {
+    fn check_reg_group(&mut self, group: RegGroup) -> Result {
         let members = self.reginfo.reg_group_members(group);
-        ensure!(
-            members.len() >= 2 || members.len() > MAX_GROUP_SIZE,
-            "{group}: Invalid group size {}",
-            members.len()
-        );
+        if !(members.len() >= 2 || members.len() > MAX_GROUP_SIZE) {
+            return self.errors.report(ValidationError::InvalidRegGroupSize {
+                group,
+                size: members.len(),
+            });
+        }
+        self.check_entity(Entity::PhysReg(members[0]))?;
         let Some(bank) = self.reginfo.bank_for_reg(members[0]) else {
-            bail!(
-                "{group}: Register member {} must be in a register bank",
-                members[0]
-            );
+            return self
+                .errors
+                .report(ValidationError::NonAllocatableGroupMember {
+                    group,
+                    reg: members[0],
+                });
         };
 
         // Check for overlaps within a group.
         let mut set = PhysRegSet::new();
-        for &member in members {
-            self.check_entity(Entity::PhysReg(member))?;
-            ensure!(
-                self.reginfo.bank_for_reg(member) == Some(bank),
-                "{group}: Group member {member} expected to be from {bank}"
-            );
-            ensure!(
-                !set.contains(member),
-                "{group} contains duplicate member {member}"
-            );
-            set.insert(member);
+        for &reg in members {
+            self.check_entity(Entity::PhysReg(reg))?;
+            if self.reginfo.bank_for_reg(reg) != Some(bank) {
+                self.errors
+                    .report(ValidationError::RegGroupBankMismatch { group, reg, bank })?;
+            }
+            if set.contains(reg) {
+                self.errors
+                    .report(ValidationError::DuplicateRegGroupMember { group, reg })?;
+            }
+            set.insert(reg);
         }
         Ok(())
     }
 
     /// Main entry point for `RegInfo` validation.
-    fn check_reginfo(&mut self) -> Result<()> {
+    fn check_reginfo(&mut self) -> Result {
         self.check_limits()?;
 
         for bank in self.reginfo.banks() {
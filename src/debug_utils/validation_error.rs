@@ -0,0 +1,1796 @@
+//! Errors reported by input validation.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::function::{
+    AlternativeSet, Block, Inst, InstRange, MAX_ALTERNATIVE_SETS, MAX_BLOCK_PARAMS, MAX_BLOCKS,
+    MAX_INST_OPERANDS, MAX_INSTS, MAX_VALUES, Value, ValueGroup,
+};
+use crate::reginfo::{
+    MAX_GROUP_SIZE, MAX_PHYSREGS, MAX_REG_BANKS, MAX_REG_CLASSES, MAX_REG_GROUPS,
+    MAX_UNITS_PER_REG, PhysReg, RegBank, RegClass, RegGroup, RegUnit,
+};
+
+/// An entity reference.
+///
+/// This is used by [`ValidationError::InvalidEntity`] to report entity
+/// references with an invalid index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Entity {
+    /// A reference to a [`Value`].
+    Value(Value),
+    /// A reference to a [`ValueGroup`].
+    ValueGroup(ValueGroup),
+    /// A reference to an [`AlternativeSet`].
+    AlternativeSet(AlternativeSet),
+    /// A reference to an [`Inst`].
+    Inst(Inst),
+    /// A reference to a [`RegUnit`].
+    RegUnit(RegUnit),
+    /// A reference to a [`PhysReg`].
+    PhysReg(PhysReg),
+    /// A reference to a [`RegGroup`].
+    RegGroup(RegGroup),
+    /// A reference to a [`RegClass`].
+    RegClass(RegClass),
+    /// A reference to a [`RegBank`].
+    RegBank(RegBank),
+}
+
+impl fmt::Display for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Entity::Value(x) => x.fmt(f),
+            Entity::ValueGroup(x) => x.fmt(f),
+            Entity::AlternativeSet(x) => x.fmt(f),
+            Entity::Inst(x) => x.fmt(f),
+            Entity::RegUnit(x) => x.fmt(f),
+            Entity::PhysReg(x) => x.fmt(f),
+            Entity::RegGroup(x) => x.fmt(f),
+            Entity::RegClass(x) => x.fmt(f),
+            Entity::RegBank(x) => x.fmt(f),
+        }
+    }
+}
+
+/// Global limit on the number of entities of a given kind.
+///
+/// This is used by [`ValidationError::LimitExceeded`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Limit {
+    /// Number of values in a function, at most [`MAX_VALUES`].
+    Values,
+    /// Number of blocks in a function, at most [`MAX_BLOCKS`].
+    Blocks,
+    /// Number of instructions in a function, at most [`MAX_INSTS`].
+    Insts,
+    /// Number of alternative sets in a function, at most
+    /// [`MAX_ALTERNATIVE_SETS`].
+    AlternativeSets,
+    /// Number of registers, at most [`MAX_PHYSREGS`].
+    PhysRegs,
+    /// Number of register groups, at most [`MAX_REG_GROUPS`].
+    RegGroups,
+    /// Number of register classes, at most [`MAX_REG_CLASSES`].
+    RegClasses,
+    /// Number of register banks, at most [`MAX_REG_BANKS`].
+    RegBanks,
+}
+
+impl Limit {
+    /// Returns the maximum number of entities allowed by this limit.
+    #[inline]
+    #[must_use]
+    pub fn max(self) -> usize {
+        match self {
+            Limit::Values => MAX_VALUES,
+            Limit::Blocks => MAX_BLOCKS,
+            Limit::Insts => MAX_INSTS,
+            Limit::AlternativeSets => MAX_ALTERNATIVE_SETS,
+            Limit::PhysRegs => MAX_PHYSREGS,
+            Limit::RegGroups => MAX_REG_GROUPS,
+            Limit::RegClasses => MAX_REG_CLASSES,
+            Limit::RegBanks => MAX_REG_BANKS,
+        }
+    }
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Limit::Values => "values",
+            Limit::Blocks => "blocks",
+            Limit::Insts => "instructions",
+            Limit::AlternativeSets => "alternative sets",
+            Limit::PhysRegs => "registers",
+            Limit::RegGroups => "register groups",
+            Limit::RegClasses => "register classes",
+            Limit::RegBanks => "register banks",
+        };
+        f.write_str(s)
+    }
+}
+
+/// A violation of one of the pre-conditions required by the register
+/// allocator, as reported by [`validate_function`] and [`validate_reginfo`].
+///
+/// The fields of each variant name the entities involved in the violation.
+/// Operands are identified by their index in [`Function::inst_operands`].
+///
+/// [`validate_function`]: super::validate_function()
+/// [`validate_reginfo`]: super::validate_reginfo()
+/// [`Function::inst_operands`]: crate::function::Function::inst_operands
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ValidationError {
+    // Common errors.
+    /// An entity reference has an index that is out of range.
+    InvalidEntity(Entity),
+
+    /// The number of entities exceeds a global limit.
+    LimitExceeded {
+        /// The limit which was exceeded.
+        limit: Limit,
+
+        /// Number of entities of that kind.
+        count: usize,
+    },
+
+    // Function errors.
+    /// A `ValueGroup` is used by more than one operand.
+    ValueGroupReused(ValueGroup),
+
+    /// The instruction range of a block is invalid.
+    InvalidInstRange {
+        /// The block.
+        block: Block,
+
+        /// The instruction range of the block.
+        range: InstRange,
+    },
+
+    /// A value has more than one definition.
+    ValueDefinedMultipleTimes(Value),
+
+    /// Multiple operands of an instruction conflict on a fixed register.
+    ConflictingFixedReg {
+        /// The instruction containing the operand.
+        inst: Inst,
+
+        /// Index of the operand.
+        operand: usize,
+
+        /// The fixed register of the operand.
+        reg: PhysReg,
+    },
+
+    /// An operand constrains a value to a register from a different bank.
+    OperandBankMismatch {
+        /// The instruction containing the operand.
+        inst: Inst,
+
+        /// Index of the operand.
+        operand: usize,
+
+        /// The value of the operand.
+        value: Value,
+
+        /// Bank of the register or class in the operand constraint.
+        bank: RegBank,
+
+        /// Bank of the value.
+        value_bank: RegBank,
+    },
+
+    /// A single value is used with a group register class.
+    ValueWithGroupClass {
+        /// The instruction containing the operand.
+        inst: Inst,
+
+        /// Index of the operand.
+        operand: usize,
+
+        /// The group class in the operand constraint.
+        class: RegClass,
+    },
+
+    /// A value group is used with a class of a different group size.
+    GroupSizeMismatch {
+        /// The instruction containing the operand.
+        inst: Inst,
+
+        /// Index of the operand.
+        operand: usize,
+
+        /// Group size of the class in the operand constraint.
+        class_size: usize,
+
+        /// Number of values in the value group.
+        group_size: usize,
+    },
+
+    /// A `Fixed` constraint uses a register that is not in any bank.
+    NonAllocatableFixedReg {
+        /// The instruction containing the operand.
+        inst: Inst,
+
+        /// Index of the operand.
+        operand: usize,
+
+        /// The fixed register of the operand.
+        reg: PhysReg,
+    },
+
+    /// A `Fixed` constraint is applied to a value group.
+    FixedGroup {
+        /// The instruction containing the operand.
+        inst: Inst,
+
+        /// Index of the operand.
+        operand: usize,
+    },
+
+    /// A `Reuse` constraint is applied to an operand that isn't a def.
+    ReuseNotDef {
+        /// The instruction containing the operand.
+        inst: Inst,
+
+        /// Index of the operand with the `Reuse` constraint.
+        operand: usize,
+    },
+
+    /// A `Reuse` constraint refers to a non-existent operand.
+    InvalidReuseTarget {
+        /// The instruction containing the operand.
+        inst: Inst,
+
+        /// Index of the operand with the `Reuse` constraint.
+        operand: usize,
+
+        /// Index of the operand referenced by the `Reuse` constraint.
+        target: usize,
+    },
+
+    /// Multiple `Reuse` constraints refer to the same operand.
+    MultipleReuse {
+        /// The instruction containing the operand.
+        inst: Inst,
+
+        /// Index of the operand with the `Reuse` constraint.
+        operand: usize,
+
+        /// Index of the operand referenced by the `Reuse` constraint.
+        target: usize,
+    },
+
+    /// The target of a `Reuse` constraint is not a use.
+    ReuseTargetNotUse {
+        /// The instruction containing the operand.
+        inst: Inst,
+
+        /// Index of the operand with the `Reuse` constraint.
+        operand: usize,
+
+        /// Index of the operand referenced by the `Reuse` constraint.
+        target: usize,
+    },
+
+    /// The target of a `Reuse` constraint doesn't have a `Class` or `Fixed`
+    /// constraint.
+    ReuseTargetNotClass {
+        /// The instruction containing the operand.
+        inst: Inst,
+
+        /// Index of the operand with the `Reuse` constraint.
+        operand: usize,
+
+        /// Index of the operand referenced by the `Reuse` constraint.
+        target: usize,
+    },
+
+    /// Tied operands differ in whether they are values or groups, or in the
+    /// size of their groups.
+    TiedOperandShapeMismatch {
+        /// The instruction containing the operand.
+        inst: Inst,
+
+        /// Index of the operand with the `Reuse` constraint.
+        operand: usize,
+
+        /// Index of the operand referenced by the `Reuse` constraint.
+        target: usize,
+    },
+
+    /// Tied operands have values from different register banks.
+    TiedOperandBankMismatch {
+        /// The instruction containing the operand.
+        inst: Inst,
+
+        /// Index of the operand with the `Reuse` constraint.
+        operand: usize,
+
+        /// Index of the operand referenced by the `Reuse` constraint.
+        target: usize,
+
+        /// Bank of the values of `operand`.
+        bank: RegBank,
+
+        /// Bank of the values of `target`.
+        target_bank: RegBank,
+    },
+
+    /// An instruction has more than [`MAX_INST_OPERANDS`] operands.
+    TooManyOperands {
+        /// The instruction.
+        inst: Inst,
+
+        /// Number of operands of the instruction.
+        count: usize,
+    },
+
+    /// A `NonAllocatable` operand uses a register that is in a bank.
+    AllocatableNonAllocatableReg {
+        /// The instruction containing the operand.
+        inst: Inst,
+
+        /// Index of the operand.
+        operand: usize,
+
+        /// The fixed register of the operand.
+        reg: PhysReg,
+    },
+
+    /// A `NonAllocatable` operand doesn't have a `Fixed` constraint.
+    NonAllocatableNotFixed {
+        /// The instruction containing the operand.
+        inst: Inst,
+
+        /// Index of the operand.
+        operand: usize,
+    },
+
+    /// A register unit is clobbered multiple times by an instruction.
+    DuplicateClobber {
+        /// The instruction.
+        inst: Inst,
+
+        /// The register unit which is clobbered multiple times.
+        unit: RegUnit,
+    },
+
+    /// A clobbered register unit is also used by a fixed def.
+    ClobberConflictsWithFixedDef {
+        /// The instruction.
+        inst: Inst,
+
+        /// The clobbered register unit.
+        unit: RegUnit,
+    },
+
+    /// A block frequency is zero, negative or NaN.
+    InvalidBlockFrequency(Block),
+
+    /// Instructions are not contiguous and ordered by block.
+    InstsNotOrderedByBlock(Block),
+
+    /// `pred` and `succ` don't list each other as predecessor and successor.
+    InconsistentPredsSuccs {
+        /// The predecessor block.
+        pred: Block,
+
+        /// The successor block.
+        succ: Block,
+    },
+
+    /// The edge between `pred` and `succ` is a critical edge, but the function
+    /// doesn't allow critical edges.
+    CriticalEdge {
+        /// The predecessor block.
+        pred: Block,
+
+        /// The successor block.
+        succ: Block,
+    },
+
+    /// A block with a single predecessor has block parameters.
+    BlockParamsWithSinglePred(Block),
+
+    /// A block has more than [`MAX_BLOCK_PARAMS`] block parameters.
+    TooManyBlockParams {
+        /// The block.
+        block: Block,
+
+        /// Number of block parameters of the block.
+        count: usize,
+    },
+
+    /// A block with outgoing block parameters doesn't have exactly one
+    /// successor.
+    JumpBlockParamsWithoutSingleSucc(Block),
+
+    /// The outgoing block parameters of `block` don't match the number of
+    /// incoming block parameters of `succ`.
+    BlockParamCountMismatch {
+        /// The block with outgoing block parameters.
+        block: Block,
+
+        /// The successor of `block`.
+        succ: Block,
+    },
+
+    /// An outgoing block parameter and the corresponding incoming block
+    /// parameter are in different register banks.
+    BlockParamBankMismatch {
+        /// The outgoing block parameter.
+        value_out: Value,
+
+        /// Bank of `value_out`.
+        bank_out: RegBank,
+
+        /// The corresponding incoming block parameter.
+        value_in: Value,
+
+        /// Bank of `value_in`.
+        bank_in: RegBank,
+    },
+
+    /// `Function::inst_block` returns the wrong block for an instruction.
+    InconsistentInstBlock {
+        /// The instruction.
+        inst: Inst,
+
+        /// The block which contains the instruction.
+        block: Block,
+    },
+
+    /// A terminator is followed by other instructions in its block.
+    TerminatorInMiddleOfBlock {
+        /// The block.
+        block: Block,
+
+        /// The terminator instruction.
+        terminator: Inst,
+    },
+
+    /// A terminator is marked as a pure instruction.
+    PureTerminator(Inst),
+
+    /// A terminator is marked as a safepoint.
+    SafepointTerminator(Inst),
+
+    /// A safepoint is marked as a pure instruction.
+    PureSafepoint(Inst),
+
+    /// A terminator is marked as a copy.
+    CopyTerminator(Inst),
+
+    /// A safepoint is marked as a copy.
+    CopySafepoint(Inst),
+
+    /// A copy doesn't have exactly one `Use` and one `Def` of the same bank
+    /// with class constraints, or has clobbers.
+    InvalidCopy(Inst),
+
+    /// A `Jump` terminator is in a block without exactly one successor.
+    JumpWithoutSingleSucc(Inst),
+
+    /// A `Jump` terminator targets a block with a single predecessor.
+    JumpToSinglePred(Inst),
+
+    /// A `Jump` terminator has operands.
+    JumpWithOperands(Inst),
+
+    /// A `Jump` terminator has clobbers.
+    JumpWithClobbers(Inst),
+
+    /// A block with outgoing jump block parameters doesn't end with a `Jump`.
+    BlockParamsWithoutJump(Inst),
+
+    /// A block with outgoing branch block parameters doesn't end with a
+    /// `Branch`.
+    BranchBlockParamsWithoutBranch(Inst),
+
+    /// A `Ret` terminator is in a block with successors.
+    RetWithSuccs(Inst),
+
+    /// A `Ret` terminator has a `Def` operand.
+    RetWithDef {
+        /// The `Ret` terminator.
+        inst: Inst,
+
+        /// Index of the `Def` operand.
+        operand: usize,
+    },
+
+    /// A `Ret` terminator has clobbers.
+    RetWithClobbers(Inst),
+
+    /// A non-`Ret` terminator is in a block without successors.
+    TerminatorWithoutSuccs(Inst),
+
+    /// A block has no terminator.
+    MissingTerminator(Block),
+
+    /// `Function::block_immediate_dominator` is incorrect for a block.
+    IncorrectImmediateDominator {
+        /// The block.
+        block: Block,
+
+        /// Immediate dominator returned by `Function::block_immediate_dominator`.
+        got: Option<Block>,
+
+        /// Actual immediate dominator of the block.
+        expected: Option<Block>,
+    },
+
+    /// A block has a lower index than its immediate dominator.
+    DominatorHasHigherIndex {
+        /// The block.
+        block: Block,
+
+        /// The immediate dominator of the block.
+        idom: Block,
+    },
+
+    /// A value is used but never defined.
+    UndefinedValue(Value),
+
+    /// The definition of a value doesn't dominate its use in an instruction.
+    DefDoesNotDominateUse {
+        /// The value.
+        value: Value,
+
+        /// The instruction using the value.
+        inst: Inst,
+    },
+
+    /// The definition of a value doesn't dominate its use as an outgoing block
+    /// parameter.
+    DefDoesNotDominateBlockParam {
+        /// The value.
+        value: Value,
+
+        /// The block passing the value as an outgoing block parameter.
+        block: Block,
+    },
+
+    /// The definition of a value doesn't dominate the end of its scope.
+    DefDoesNotDominateScopeEnd {
+        /// The value.
+        value: Value,
+
+        /// The instruction at which the scope of the value ends.
+        inst: Inst,
+    },
+
+    /// The entry block has predecessors.
+    EntryBlockHasPreds,
+
+    /// The entry block has block parameters.
+    EntryBlockHasParams,
+
+    /// A block is not reachable from the entry block.
+    UnreachableBlock(Block),
+
+    /// The rematerialization cost of a value is negative, infinite or NaN.
+    InvalidRematCost(Value),
+
+    /// A value is rematerialized into a group register class.
+    RematGroupClass {
+        /// The rematerializable value.
+        value: Value,
+
+        /// The class the value is rematerialized into.
+        class: RegClass,
+    },
+
+    /// A value is rematerialized into a class from a different bank.
+    RematBankMismatch {
+        /// The rematerializable value.
+        value: Value,
+
+        /// The class the value is rematerialized into.
+        class: RegClass,
+
+        /// Bank of `class`.
+        bank: RegBank,
+
+        /// Bank of the value.
+        value_bank: RegBank,
+    },
+
+    /// A value is rematerialized into a class with an empty allocation order.
+    RematEmptyAllocationOrder {
+        /// The rematerializable value.
+        value: Value,
+
+        /// The class the value is rematerialized into.
+        class: RegClass,
+    },
+
+    /// A value is rematerialized into a class with in-memory members which
+    /// doesn't include spill slots.
+    RematMemoryClass {
+        /// The rematerializable value.
+        value: Value,
+
+        /// The class the value is rematerialized into.
+        class: RegClass,
+    },
+
+    /// A value has a rematerialization input but is not rematerializable.
+    RematInputWithoutRemat {
+        /// The value.
+        value: Value,
+
+        /// The rematerialization input of the value.
+        input: Value,
+    },
+
+    /// The rematerialization input of a value is itself rematerializable.
+    RematInputIsRemat {
+        /// The value.
+        value: Value,
+
+        /// The rematerialization input of the value.
+        input: Value,
+    },
+
+    /// The strength of a value's register hint is negative, infinite or NaN.
+    InvalidValueHintStrength(Value),
+
+    /// A value's register hint is a non-allocatable register.
+    NonAllocatableValueHint {
+        /// The value.
+        value: Value,
+
+        /// The hinted register.
+        reg: PhysReg,
+    },
+
+    /// A value's register hint is a register from a different bank.
+    ValueHintBankMismatch {
+        /// The value.
+        value: Value,
+
+        /// The hinted register.
+        reg: PhysReg,
+
+        /// Bank of `reg`.
+        bank: RegBank,
+
+        /// Bank of the value.
+        value_bank: RegBank,
+    },
+
+    /// A value is pinned to a non-allocatable register.
+    NonAllocatablePinnedReg {
+        /// The pinned value.
+        value: Value,
+
+        /// The register the value is pinned to.
+        reg: PhysReg,
+    },
+
+    /// A value is pinned to a register from a different bank.
+    PinnedRegBankMismatch {
+        /// The pinned value.
+        value: Value,
+
+        /// The register the value is pinned to.
+        reg: PhysReg,
+
+        /// Bank of `reg`.
+        bank: RegBank,
+
+        /// Bank of the value.
+        value_bank: RegBank,
+    },
+
+    /// An operand's register class doesn't include the register its value is
+    /// pinned to.
+    PinnedRegNotInClass {
+        /// The instruction containing the operand.
+        inst: Inst,
+
+        /// Index of the operand.
+        operand: usize,
+
+        /// The pinned value.
+        value: Value,
+
+        /// The register the value is pinned to.
+        reg: PhysReg,
+
+        /// The class in the operand constraint.
+        class: RegClass,
+    },
+
+    /// A pinned value is a member of a value group.
+    PinnedValueInGroup {
+        /// The instruction containing the operand.
+        inst: Inst,
+
+        /// Index of the operand.
+        operand: usize,
+
+        /// The pinned value.
+        value: Value,
+    },
+
+    /// An alternative set doesn't contain any alternatives.
+    EmptyAlternativeSet(AlternativeSet),
+
+    /// An alternative in a set is not a `Class` or `Fixed` constraint.
+    InvalidAlternative {
+        /// The alternative set.
+        set: AlternativeSet,
+
+        /// Index of the alternative in the set.
+        alternative: usize,
+    },
+
+    /// The cost of an alternative is negative, infinite or NaN.
+    InvalidAlternativeCost {
+        /// The alternative set.
+        set: AlternativeSet,
+
+        /// Index of the alternative in the set.
+        alternative: usize,
+    },
+
+    /// An `Alternatives` constraint is applied to a value group.
+    AlternativesGroup {
+        /// The instruction containing the operand.
+        inst: Inst,
+
+        /// Index of the operand.
+        operand: usize,
+    },
+
+    /// None of the alternatives of an operand can be selected because they
+    /// all conflict with other operands of the instruction or with the
+    /// register that the operand's value is pinned to.
+    NoUsableAlternative {
+        /// The instruction containing the operand.
+        inst: Inst,
+
+        /// Index of the operand.
+        operand: usize,
+    },
+
+    /// An instruction marked with `no_edits_before` is the first instruction
+    /// of its block.
+    NoEditsBeforeFirstInst(Inst),
+
+    /// An instruction marked with `no_edits_before` is a `Jump` terminator.
+    NoEditsBeforeJump(Inst),
+
+    /// An instruction marked with `no_edits_before` is a safepoint or follows
+    /// a safepoint.
+    NoEditsBeforeSafepoint(Inst),
+
+    /// An operand of an instruction marked with `no_edits_before` is not a
+    /// single-value `Use`, `Def` or `EarlyDef` with a `Class` constraint, or a
+    /// `NonAllocatable` operand.
+    NoEditsInvalidOperand {
+        /// The instruction containing the operand.
+        inst: Inst,
+
+        /// Index of the operand.
+        operand: usize,
+    },
+
+    /// An instruction followed by one marked with `no_edits_before` has a
+    /// fixed-register definition.
+    NoEditsAfterFixedDef {
+        /// The instruction with the fixed-register definition.
+        inst: Inst,
+
+        /// Index of the fixed-register definition operand.
+        operand: usize,
+    },
+
+    /// An operand refers to a value used or defined in a sequence of
+    /// instructions marked with `no_edits_before` with a different constraint
+    /// than other operands in that sequence.
+    NoEditsConstraintMismatch {
+        /// The instruction containing the operand.
+        inst: Inst,
+
+        /// Index of the operand.
+        operand: usize,
+    },
+
+    // RegInfo errors.
+    /// The top-level class of a bank is in a different bank.
+    TopLevelClassNotInBank {
+        /// The bank.
+        bank: RegBank,
+
+        /// The top-level class of the bank.
+        class: RegClass,
+    },
+
+    /// The top-level class of a bank doesn't include spill slots.
+    TopLevelClassWithoutSpillslots {
+        /// The bank.
+        bank: RegBank,
+
+        /// The top-level class of the bank.
+        class: RegClass,
+    },
+
+    /// The top-level class of a bank is a group class.
+    TopLevelClassIsGroup {
+        /// The bank.
+        bank: RegBank,
+
+        /// The top-level class of the bank.
+        class: RegClass,
+    },
+
+    /// The stack-to-stack class of a bank is in a different bank.
+    StackToStackClassNotInBank {
+        /// The bank.
+        bank: RegBank,
+
+        /// The stack-to-stack class of the bank.
+        class: RegClass,
+    },
+
+    /// The stack-to-stack class of a bank is a group class.
+    StackToStackClassIsGroup {
+        /// The bank.
+        bank: RegBank,
+
+        /// The stack-to-stack class of the bank.
+        class: RegClass,
+    },
+
+    /// The stack-to-stack class of a bank includes spill slots.
+    StackToStackClassWithSpillslots {
+        /// The bank.
+        bank: RegBank,
+
+        /// The stack-to-stack class of the bank.
+        class: RegClass,
+    },
+
+    /// The stack-to-stack class of a bank contains an in-memory register.
+    StackToStackClassWithMemory {
+        /// The bank.
+        bank: RegBank,
+
+        /// The stack-to-stack class of the bank.
+        class: RegClass,
+
+        /// The in-memory register in the class.
+        reg: PhysReg,
+    },
+
+    /// A register in a bank is not in the top-level class of that bank.
+    RegNotInTopLevelClass {
+        /// The bank.
+        bank: RegBank,
+
+        /// The top-level class of the bank.
+        class: RegClass,
+
+        /// The register missing from the class.
+        reg: PhysReg,
+    },
+
+    /// A bank has no registers.
+    EmptyBank(RegBank),
+
+    /// A class has a group size larger than [`MAX_GROUP_SIZE`].
+    GroupSizeTooLarge {
+        /// The class.
+        class: RegClass,
+
+        /// Group size of the class.
+        size: usize,
+    },
+
+    /// A class has a group size of 0.
+    ZeroGroupSize(RegClass),
+
+    /// A group class includes spill slots.
+    GroupClassWithSpillslots(RegClass),
+
+    /// A member of a group class has a different size from the class.
+    ClassGroupSizeMismatch {
+        /// The group class.
+        class: RegClass,
+
+        /// The member of the class.
+        group: RegGroup,
+
+        /// Number of registers in `group`.
+        size: usize,
+
+        /// Group size of `class`.
+        class_size: usize,
+    },
+
+    /// A class contains a register from a different bank.
+    ClassRegNotInBank {
+        /// The class.
+        class: RegClass,
+
+        /// The register in a different bank.
+        reg: PhysReg,
+
+        /// Bank of the class.
+        bank: RegBank,
+    },
+
+    /// A group class has a duplicate register at the same group index.
+    DuplicateClassReg {
+        /// The group class.
+        class: RegClass,
+
+        /// The duplicate register.
+        reg: PhysReg,
+
+        /// Index in the group at which the register is duplicated.
+        group_index: usize,
+    },
+
+    /// `RegInfo::group_for_reg` is inconsistent with the members of a class.
+    InconsistentGroupForReg {
+        /// The group class.
+        class: RegClass,
+
+        /// A register in a member group of the class.
+        reg: PhysReg,
+
+        /// Index of `reg` in its group.
+        group_index: usize,
+
+        /// Group returned by `RegInfo::group_for_reg`.
+        got: Option<RegGroup>,
+
+        /// The member of the class which contains `reg` at `group_index`.
+        expected: RegGroup,
+    },
+
+    /// A class without spill slots has an empty allocation order.
+    EmptyAllocationOrder(RegClass),
+
+    /// A non-group class has a group allocation order.
+    UnexpectedGroupAllocationOrder(RegClass),
+
+    /// A group class has an empty group allocation order.
+    EmptyGroupAllocationOrder(RegClass),
+
+    /// A group class has a non-group allocation order.
+    UnexpectedAllocationOrder(RegClass),
+
+    /// The allocation order of a class contains a register outside the class.
+    AllocationOrderRegNotInClass {
+        /// The class.
+        class: RegClass,
+
+        /// The register outside the class.
+        reg: PhysReg,
+    },
+
+    /// The group allocation order of a class contains a group outside the
+    /// class.
+    AllocationOrderGroupNotInClass {
+        /// The class.
+        class: RegClass,
+
+        /// The group outside the class.
+        group: RegGroup,
+    },
+
+    /// A class is not a subclass of the top-level class of its bank.
+    NotSubclassOfTopLevelClass {
+        /// The class.
+        class: RegClass,
+
+        /// Bank of the class.
+        bank: RegBank,
+
+        /// The top-level class of `bank`.
+        top_level_class: RegClass,
+    },
+
+    /// A class is not a subclass of itself.
+    NotSubclassOfItself(RegClass),
+
+    /// A class has a subclass with a lower index.
+    SubclassHasLowerIndex {
+        /// The class.
+        class: RegClass,
+
+        /// The subclass with a lower index.
+        subclass: RegClass,
+    },
+
+    /// A class has a subclass from a different bank.
+    SubclassNotInBank {
+        /// The class.
+        class: RegClass,
+
+        /// The subclass in a different bank.
+        subclass: RegClass,
+
+        /// Bank of `class`.
+        bank: RegBank,
+    },
+
+    /// A class without spill slots has a subclass which includes them.
+    SubclassWithSpillslots {
+        /// The class without spill slots.
+        class: RegClass,
+
+        /// The subclass which includes spill slots.
+        subclass: RegClass,
+    },
+
+    /// A non-group class doesn't contain a member of a group in one of its
+    /// group subclasses.
+    SuperclassMissingGroupMember {
+        /// The non-group superclass.
+        class: RegClass,
+
+        /// The group subclass.
+        subclass: RegClass,
+
+        /// The member of `subclass` containing `reg`.
+        group: RegGroup,
+
+        /// The register missing from `class`.
+        reg: PhysReg,
+    },
+
+    /// A class has a subclass with a different group size.
+    SubclassGroupSizeMismatch {
+        /// The class.
+        class: RegClass,
+
+        /// The subclass with a different group size.
+        subclass: RegClass,
+    },
+
+    /// A class has a subclass with a register that isn't in the class.
+    SubclassRegNotInClass {
+        /// The class.
+        class: RegClass,
+
+        /// The subclass.
+        subclass: RegClass,
+
+        /// The register in `subclass` that is not in `class`.
+        reg: PhysReg,
+    },
+
+    /// An allocatable register has no register units.
+    RegWithoutUnits(PhysReg),
+
+    /// A register has more than [`MAX_UNITS_PER_REG`] register units.
+    TooManyRegUnits {
+        /// The register.
+        reg: PhysReg,
+
+        /// Number of register units of the register.
+        count: usize,
+    },
+
+    /// A register has a callee-saved cost which is negative or not finite.
+    InvalidCalleeSavedCost(PhysReg),
+
+    /// A register unit is shared by multiple registers in a bank.
+    OverlappingRegUnit {
+        /// The register.
+        reg: PhysReg,
+
+        /// The shared register unit.
+        unit: RegUnit,
+
+        /// Bank of the register.
+        bank: RegBank,
+    },
+
+    /// A register group has an invalid number of members.
+    InvalidRegGroupSize {
+        /// The group.
+        group: RegGroup,
+
+        /// Number of members of the group.
+        size: usize,
+    },
+
+    /// A register group contains a register that is not in any bank.
+    NonAllocatableGroupMember {
+        /// The group.
+        group: RegGroup,
+
+        /// The member which is not in any bank.
+        reg: PhysReg,
+    },
+
+    /// A register group contains registers from different banks.
+    RegGroupBankMismatch {
+        /// The group.
+        group: RegGroup,
+
+        /// The member in a different bank.
+        reg: PhysReg,
+
+        /// Bank of the first member of the group.
+        bank: RegBank,
+    },
+
+    /// A register group contains the same register multiple times.
+    DuplicateRegGroupMember {
+        /// The group.
+        group: RegGroup,
+
+        /// The duplicated member.
+        reg: PhysReg,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    #[allow(clippy::too_many_lines)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ValidationError::InvalidEntity(entity) => {
+                write!(f, "{entity}: Invalid entity reference")
+            }
+            ValidationError::LimitExceeded { limit, count } => {
+                write!(f, "Too many {limit}: {count} (max: {})", limit.max())
+            }
+            ValidationError::ValueGroupReused(group) => {
+                write!(f, "{group} cannot be used multiple times in a function")
+            }
+            ValidationError::InvalidInstRange { block, range } => {
+                write!(f, "{block}: Invalid instruction range {range}")
+            }
+            ValidationError::ValueDefinedMultipleTimes(value) => {
+                write!(f, "{value} defined multiple times")
+            }
+            ValidationError::ConflictingFixedReg { inst, operand, reg } => {
+                write!(f, "{inst} operand {operand}: Conflicting uses of {reg}")
+            }
+            ValidationError::OperandBankMismatch {
+                inst,
+                operand,
+                value,
+                bank,
+                value_bank,
+            } => write!(
+                f,
+                "{inst} operand {operand}: {value} used with different register banks: {bank} vs \
+                 {value_bank}"
+            ),
+            ValidationError::ValueWithGroupClass {
+                inst,
+                operand,
+                class,
+            } => write!(
+                f,
+                "{inst} operand {operand}: Value used with group register class {class}"
+            ),
+            ValidationError::GroupSizeMismatch {
+                inst,
+                operand,
+                class_size,
+                group_size,
+            } => write!(
+                f,
+                "{inst} operand {operand}: group size mismatch {class_size} vs {group_size}"
+            ),
+            ValidationError::NonAllocatableFixedReg { inst, operand, reg } => write!(
+                f,
+                "{inst} operand {operand}: Use of non-allocatable register {reg}"
+            ),
+            ValidationError::FixedGroup { inst, operand } => write!(
+                f,
+                "{inst} operand {operand}: Fixed constraint cannot be used with register groups"
+            ),
+            ValidationError::ReuseNotDef { inst, operand } => write!(
+                f,
+                "{inst} operand {operand}: Reuse operand must be a Def or EarlyDef"
+            ),
+            ValidationError::InvalidReuseTarget {
+                inst,
+                operand,
+                target,
+            } => write!(
+                f,
+                "{inst} operand {operand}: Invalid index {target} for reuse operand"
+            ),
+            ValidationError::MultipleReuse {
+                inst,
+                operand,
+                target,
+            } => write!(
+                f,
+                "{inst} operand {operand}: multiple reuse of same target operand {target}"
+            ),
+            ValidationError::ReuseTargetNotUse {
+                inst,
+                operand,
+                target,
+            } => write!(
+                f,
+                "{inst} operand {operand} -> {target}: Reuse operand target must be a Use"
+            ),
+            ValidationError::ReuseTargetNotClass {
+                inst,
+                operand,
+                target,
+            } => write!(
+                f,
+                "{inst} operand {operand} -> {target}: Reuse operand target must have a Class or \
+                 Fixed constraint"
+            ),
+            ValidationError::TiedOperandShapeMismatch {
+                inst,
+                operand,
+                target,
+            } => write!(
+                f,
+                "{inst} operand {operand} -> {target}: Tied operands must either both be values \
+                 or both be groups of the same size"
+            ),
+            ValidationError::TiedOperandBankMismatch {
+                inst,
+                operand,
+                target,
+                bank,
+                target_bank,
+            } => write!(
+                f,
+                "{inst}: Tied operand with different register banks: operand {operand} ({bank}) \
+                 vs operand {target} ({target_bank})"
+            ),
+            ValidationError::TooManyOperands { inst, count } => write!(
+                f,
+                "{inst}: Too many operands: {count} (max: {MAX_INST_OPERANDS})"
+            ),
+            ValidationError::AllocatableNonAllocatableReg { inst, operand, reg } => write!(
+                f,
+                "{inst} operand {operand}: NonAllocatable register {reg} must be outside a bank"
+            ),
+            ValidationError::NonAllocatableNotFixed { inst, operand } => write!(
+                f,
+                "{inst} operand {operand}: NonAllocatable operand must have a Fixed constraint"
+            ),
+            ValidationError::DuplicateClobber { inst, unit } => write!(
+                f,
+                "{inst}: Clobber {unit} specified multiple times in same instruction"
+            ),
+            ValidationError::ClobberConflictsWithFixedDef { inst, unit } => {
+                write!(f, "{inst}: Clobber {unit} conflicts with fixed def")
+            }
+            ValidationError::InvalidBlockFrequency(block) => {
+                write!(f, "{block}: Frequency must be positive and non-zero")
+            }
+            ValidationError::InstsNotOrderedByBlock(block) => {
+                write!(f, "{block}: Instructions are not ordered by block")
+            }
+            ValidationError::InconsistentPredsSuccs { pred, succ } => write!(
+                f,
+                "Inconsistent predecessors and successors between {pred} and {succ}"
+            ),
+            ValidationError::CriticalEdge { pred, succ } => {
+                write!(f, "Critical edge beween {pred} and {succ}")
+            }
+            ValidationError::BlockParamsWithSinglePred(block) => write!(
+                f,
+                "{block}: Block parameters are only allowed on blocks with multiple predecessors"
+            ),
+            ValidationError::TooManyBlockParams { block, count } => write!(
+                f,
+                "{block} has too many block parameters: {count} (max {MAX_BLOCK_PARAMS})"
+            ),
+            ValidationError::JumpBlockParamsWithoutSingleSucc(block) => write!(
+                f,
+                "{block}: Jump blockparams can only be used with a single successor"
+            ),
+            ValidationError::BlockParamCountMismatch { block, succ } => {
+                write!(f, "Blockparam count mismatch between {block} and {succ}")
+            }
+            ValidationError::BlockParamBankMismatch {
+                value_out,
+                bank_out,
+                value_in,
+                bank_in,
+            } => write!(
+                f,
+                "Blockparams with different register banks: {value_out} ({bank_out}) vs \
+                 {value_in} ({bank_in})"
+            ),
+            ValidationError::InconsistentInstBlock { inst, block } => {
+                write!(f, "inst_block({inst}) should be {block}")
+            }
+            ValidationError::TerminatorInMiddleOfBlock { block, terminator } => {
+                write!(f, "{block}: Terminator {terminator} in middle of block")
+            }
+            ValidationError::PureTerminator(inst) => {
+                write!(
+                    f,
+                    "{inst}: Terminator cannot be marked as a pure instruction"
+                )
+            }
+            ValidationError::SafepointTerminator(inst) => {
+                write!(f, "{inst}: Terminator cannot be a safepoint")
+            }
+            ValidationError::PureSafepoint(inst) => {
+                write!(
+                    f,
+                    "{inst}: Safepoint cannot be marked as a pure instruction"
+                )
+            }
+            ValidationError::CopyTerminator(inst) => {
+                write!(f, "{inst}: Terminator cannot be marked as a copy")
+            }
+            ValidationError::CopySafepoint(inst) => {
+                write!(f, "{inst}: Safepoint cannot be marked as a copy")
+            }
+            ValidationError::InvalidCopy(inst) => write!(
+                f,
+                "{inst}: Copy must have exactly one Use and one Def operand of the same bank with \
+                 class constraints and no clobbers"
+            ),
+            ValidationError::JumpWithoutSingleSucc(inst) => write!(
+                f,
+                "{inst}: Jump terminators can only be used with a single successor"
+            ),
+            ValidationError::JumpToSinglePred(inst) => write!(
+                f,
+                "{inst}: Jump terminators can only target blocks with multiple predecessors (use \
+                 a Branch terminator instead)"
+            ),
+            ValidationError::JumpWithOperands(inst) => write!(
+                f,
+                "{inst}: Terminator cannot have operands when the successor block has multiple \
+                 predecessors"
+            ),
+            ValidationError::JumpWithClobbers(inst) => write!(
+                f,
+                "{inst}: Terminator cannot have clobbers when the successor block has multiple \
+                 predecessors"
+            ),
+            ValidationError::BlockParamsWithoutJump(inst) => write!(
+                f,
+                "{inst}: Only jump terminators may have jump block parameters"
+            ),
+            ValidationError::BranchBlockParamsWithoutBranch(inst) => write!(
+                f,
+                "{inst}: Only branch terminators may have branch block parameters"
+            ),
+            ValidationError::RetWithSuccs(inst) => {
+                write!(f, "{inst}: Ret terminators cannot have successors")
+            }
+            ValidationError::RetWithDef { inst, operand } => write!(
+                f,
+                "{inst} operand {operand}: Ret terminators cannot have Def operands, only \
+                 Use/EarlyDef"
+            ),
+            ValidationError::RetWithClobbers(inst) => {
+                write!(f, "{inst}: Ret terminators cannot have clobbers")
+            }
+            ValidationError::TerminatorWithoutSuccs(inst) => {
+                write!(f, "{inst}: Non-ret terminators must have successors")
+            }
+            ValidationError::MissingTerminator(block) => {
+                write!(f, "{block}: Missing terminator")
+            }
+            ValidationError::IncorrectImmediateDominator {
+                block,
+                got,
+                expected,
+            } => write!(
+                f,
+                "{block} has incorrect immediate dominator: got {got:?}, expected {expected:?}"
+            ),
+            ValidationError::DominatorHasHigherIndex { block, idom } => {
+                write!(f, "{idom} dominates {block} but has higher index")
+            }
+            ValidationError::UndefinedValue(value) => {
+                write!(f, "{value} used without being defined")
+            }
+            ValidationError::DefDoesNotDominateUse { value, inst } => {
+                write!(f, "{value} definition does not dominate use at {inst}")
+            }
+            ValidationError::DefDoesNotDominateBlockParam { value, block } => write!(
+                f,
+                "{value} definition does not dominate use as outgoing blockparam in {block}"
+            ),
+            ValidationError::DefDoesNotDominateScopeEnd { value, inst } => {
+                write!(
+                    f,
+                    "{value} definition does not dominate scope end at {inst}"
+                )
+            }
+            ValidationError::EntryBlockHasPreds => write!(
+                f,
+                "{}: Entry block cannot have predecessors",
+                Block::ENTRY_BLOCK
+            ),
+            ValidationError::EntryBlockHasParams => write!(
+                f,
+                "{}: Entry block cannot have block parameters",
+                Block::ENTRY_BLOCK
+            ),
+            ValidationError::UnreachableBlock(block) => {
+                write!(f, "{block} is not reachable from the entry block")
+            }
+            ValidationError::InvalidRematCost(value) => {
+                write!(f, "{value} has an invalid rematerialization cost")
+            }
+            ValidationError::RematGroupClass { value, class } => write!(
+                f,
+                "{value} cannot be rematerialized with group register class {class}"
+            ),
+            ValidationError::RematBankMismatch {
+                value,
+                class,
+                bank,
+                value_bank,
+            } => write!(
+                f,
+                "{value} cannot be rematerialized into {class} with different register banks: \
+                 {bank} vs {value_bank}"
+            ),
+            ValidationError::RematEmptyAllocationOrder { value, class } => write!(
+                f,
+                "{value} cannot be rematerialized into {class} which has an empty allocation order"
+            ),
+            ValidationError::RematMemoryClass { value, class } => write!(
+                f,
+                "{value} cannot be rematerialized into {class} which has in-memory members but \
+                 doesn't include spill slots"
+            ),
+            ValidationError::RematInputWithoutRemat { value, input } => write!(
+                f,
+                "{value} has rematerialization input {input} but is not rematerializable"
+            ),
+            ValidationError::RematInputIsRemat { value, input } => write!(
+                f,
+                "{value} has rematerialization input {input} which is itself rematerializable"
+            ),
+            ValidationError::InvalidValueHintStrength(value) => {
+                write!(f, "{value} has a register hint with an invalid strength")
+            }
+            ValidationError::NonAllocatableValueHint { value, reg } => {
+                write!(f, "{value} has a register hint for non-allocatable {reg}")
+            }
+            ValidationError::ValueHintBankMismatch {
+                value,
+                reg,
+                bank,
+                value_bank,
+            } => write!(
+                f,
+                "{value} has a register hint for {reg} with different register banks: {bank} vs \
+                 {value_bank}"
+            ),
+            ValidationError::NonAllocatablePinnedReg { value, reg } => {
+                write!(f, "{value} is pinned to non-allocatable {reg}")
+            }
+            ValidationError::PinnedRegBankMismatch {
+                value,
+                reg,
+                bank,
+                value_bank,
+            } => write!(
+                f,
+                "{value} is pinned to {reg} with different register banks: {bank} vs \
+                 {value_bank}"
+            ),
+            ValidationError::PinnedRegNotInClass {
+                inst,
+                operand,
+                value,
+                reg,
+                class,
+            } => write!(
+                f,
+                "{inst}: Operand {operand} has {class} which doesn't include {reg} that {value} is \
+                 pinned to"
+            ),
+            ValidationError::PinnedValueInGroup {
+                inst,
+                operand,
+                value,
+            } => write!(
+                f,
+                "{inst}: Operand {operand} has pinned {value} as a member of a value group"
+            ),
+            ValidationError::EmptyAlternativeSet(set) => {
+                write!(f, "{set}: Alternative set must not be empty")
+            }
+            ValidationError::InvalidAlternative { set, alternative } => write!(
+                f,
+                "{set}: Alternative {alternative} must be a Class or Fixed constraint"
+            ),
+            ValidationError::InvalidAlternativeCost { set, alternative } => write!(
+                f,
+                "{set}: Cost of alternative {alternative} must be finite and non-negative"
+            ),
+            ValidationError::AlternativesGroup { inst, operand } => write!(
+                f,
+                "{inst} operand {operand}: Alternatives constraint cannot be used with register \
+                 groups"
+            ),
+            ValidationError::NoUsableAlternative { inst, operand } => write!(
+                f,
+                "{inst} operand {operand}: No usable alternative, all of them conflict with other \
+                 operands or the pinned register"
+            ),
+            ValidationError::NoEditsBeforeFirstInst(inst) => write!(
+                f,
+                "{inst}: First instruction in a block cannot forbid edits before it"
+            ),
+            ValidationError::NoEditsBeforeJump(inst) => {
+                write!(f, "{inst}: Jump terminator cannot forbid edits before it")
+            }
+            ValidationError::NoEditsBeforeSafepoint(inst) => write!(
+                f,
+                "{inst}: Safepoint and instruction following a safepoint cannot forbid edits \
+                 before it"
+            ),
+            ValidationError::NoEditsInvalidOperand { inst, operand } => write!(
+                f,
+                "{inst} operand {operand}: Instruction forbidding edits before it can only have \
+                 Use, Def or EarlyDef operands with a Class constraint or NonAllocatable operands"
+            ),
+            ValidationError::NoEditsAfterFixedDef { inst, operand } => write!(
+                f,
+                "{inst} operand {operand}: Instruction followed by one forbidding edits cannot \
+                 have a fixed-register definition"
+            ),
+            ValidationError::NoEditsConstraintMismatch { inst, operand } => write!(
+                f,
+                "{inst} operand {operand}: Value used in a sequence forbidding edits must have \
+                 the same Class constraint in all operands of that sequence"
+            ),
+            ValidationError::TopLevelClassNotInBank { bank, class } => {
+                write!(f, "{bank}: Top-level class {class} is not in bank")
+            }
+            ValidationError::TopLevelClassWithoutSpillslots { bank, class } => {
+                write!(f, "{bank}: Top-level class {class} must include spillslots")
+            }
+            ValidationError::TopLevelClassIsGroup { bank, class } => write!(
+                f,
+                "{bank}: Top-level class {class} must have a group size of 1"
+            ),
+            ValidationError::StackToStackClassNotInBank { bank, class } => {
+                write!(f, "{bank}: Stack-to-stack class {class} is not in bank")
+            }
+            ValidationError::StackToStackClassIsGroup { bank, class } => write!(
+                f,
+                "{bank}: Stack-to-stack class {class} must have a group size of 1"
+            ),
+            ValidationError::StackToStackClassWithSpillslots { bank, class } => write!(
+                f,
+                "{bank}: Stack-to-stack class {class} cannot include spill slots"
+            ),
+            ValidationError::StackToStackClassWithMemory { bank, class, reg } => write!(
+                f,
+                "{bank}: {reg} in stack-to-stack {class} cannot be in memory"
+            ),
+            ValidationError::RegNotInTopLevelClass { bank, class, reg } => {
+                write!(f, "{bank}: {reg} not in top-level class {class}")
+            }
+            ValidationError::EmptyBank(bank) => write!(f, "{bank} has no registers"),
+            ValidationError::GroupSizeTooLarge { class, size } => write!(
+                f,
+                "{class}: Group size {size} too large (max: {MAX_GROUP_SIZE})"
+            ),
+            ValidationError::ZeroGroupSize(class) => {
+                write!(f, "{class}: Invalid group size of 0")
+            }
+            ValidationError::GroupClassWithSpillslots(class) => {
+                write!(f, "{class}: Group class cannot include spillslots")
+            }
+            ValidationError::ClassGroupSizeMismatch {
+                class,
+                group,
+                size,
+                class_size,
+            } => write!(
+                f,
+                "{group} group size ({size}) doesn't match {class} group size ({class_size})"
+            ),
+            ValidationError::ClassRegNotInBank { class, reg, bank } => {
+                write!(f, "{class} contains {reg} which is outside {bank}")
+            }
+            ValidationError::DuplicateClassReg {
+                class,
+                reg,
+                group_index,
+            } => write!(
+                f,
+                "{class} has duplicate register {reg} at group index {group_index}"
+            ),
+            ValidationError::InconsistentGroupForReg {
+                class,
+                reg,
+                group_index,
+                got,
+                expected,
+            } => write!(
+                f,
+                "Inconsistent group_for_reg({reg}, {group_index}, {class}): got {got:?} expected \
+                 {expected:?}"
+            ),
+            ValidationError::EmptyAllocationOrder(class) => write!(
+                f,
+                "{class} cannot have an empty allocation order unless it allows spillslots"
+            ),
+            ValidationError::UnexpectedGroupAllocationOrder(class) => write!(
+                f,
+                "{class}: Non-group class cannot have a group allocation order"
+            ),
+            ValidationError::EmptyGroupAllocationOrder(class) => {
+                write!(
+                    f,
+                    "{class}: Group class cannot have an empty allocation order"
+                )
+            }
+            ValidationError::UnexpectedAllocationOrder(class) => write!(
+                f,
+                "{class}: Group class cannot have a non-group allocation order"
+            ),
+            ValidationError::AllocationOrderRegNotInClass { class, reg } => write!(
+                f,
+                "{class}: Allocation order contains {reg} which is outside class"
+            ),
+            ValidationError::AllocationOrderGroupNotInClass { class, group } => write!(
+                f,
+                "{class}: Allocation order contains {group} which is outside class"
+            ),
+            ValidationError::NotSubclassOfTopLevelClass {
+                class,
+                bank,
+                top_level_class,
+            } => write!(
+                f,
+                "{class} must be a subclass of the top-level class for {bank} ({top_level_class})"
+            ),
+            ValidationError::NotSubclassOfItself(class) => {
+                write!(f, "{class} must be a subclass of itself")
+            }
+            ValidationError::SubclassHasLowerIndex { class, subclass } => write!(
+                f,
+                "{class} must not have any subclasses with a lower index than itself ({subclass})"
+            ),
+            ValidationError::SubclassNotInBank {
+                class,
+                subclass,
+                bank,
+            } => write!(f, "Subclass {subclass} of {class} is not in {bank}"),
+            ValidationError::SubclassWithSpillslots { class, subclass } => write!(
+                f,
+                "{subclass} allows spillslots but is subclass of {class} which doesn't"
+            ),
+            ValidationError::SuperclassMissingGroupMember {
+                class,
+                subclass,
+                group,
+                reg,
+            } => write!(
+                f,
+                "Superclass {class} of {subclass} doesn't contain {reg} (member of {group})"
+            ),
+            ValidationError::SubclassGroupSizeMismatch { class, subclass } => write!(
+                f,
+                "Subclass {subclass} must have same group size as {class}"
+            ),
+            ValidationError::SubclassRegNotInClass {
+                class,
+                subclass,
+                reg,
+            } => write!(f, "Subclass {subclass} of {class} doesn't contain {reg}"),
+            ValidationError::RegWithoutUnits(reg) => write!(
+                f,
+                "{reg}: Allocatable register must have at least 1 register unit"
+            ),
+            ValidationError::TooManyRegUnits { reg, count } => write!(
+                f,
+                "{reg} has too many register units: {count} (max is {MAX_UNITS_PER_REG})"
+            ),
+            ValidationError::InvalidCalleeSavedCost(reg) => write!(
+                f,
+                "{reg}: Callee-saved cost must be finite and non-negative"
+            ),
+            ValidationError::OverlappingRegUnit { reg, unit, bank } => {
+                write!(f, "{unit} in {reg} overlaps with other registers in {bank}")
+            }
+            ValidationError::InvalidRegGroupSize { group, size } => {
+                write!(f, "{group}: Invalid group size {size}")
+            }
+            ValidationError::NonAllocatableGroupMember { group, reg } => write!(
+                f,
+                "{group}: Register member {reg} must be in a register bank"
+            ),
+            ValidationError::RegGroupBankMismatch { group, reg, bank } => {
+                write!(f, "{group}: Group member {reg} expected to be from {bank}")
+            }
+            ValidationError::DuplicateRegGroupMember { group, reg } => {
+                write!(f, "{group} contains duplicate member {reg}")
+            }
+        }
+    }
+}
+
+impl core::error::Error for ValidationError {}
+
+/// Marker indicating that validation should stop.
+///
+/// Errors themselves are recorded in [`Errors`].
+pub(super) struct Abort;
+
+/// Collection of errors found during validation.
+pub(super) enum Errors {
+    /// Validation stops at the first error, which is recorded here.
+    First(Option<ValidationError>),
+
+    /// Validation keeps going and records all errors.
+    All(Vec<ValidationError>),
+}
+
+impl Errors {
+    pub(super) fn new(collect_all: bool) -> Self {
+        if collect_all {
+            Self::All(Vec::new())
+        } else {
+            Self::First(None)
+        }
+    }
+
+    /// Records an error after which validation can continue.
+    ///
+    /// This only aborts validation if we are not collecting all errors.
+    pub(super) fn report(&mut self, err: ValidationError) -> Result<(), Abort> {
+        match self {
+            Self::First(first) => {
+                *first = Some(err);
+                Err(Abort)
+            }
+            Self::All(list) => {
+                list.push(err);
+                Ok(())
+            }
+        }
+    }
+
+    /// Records an error after which validation cannot safely continue.
+    pub(super) fn fatal(&mut self, err: ValidationError) -> Abort {
+        match self {
+            Self::First(first) => *first = Some(err),
+            Self::All(list) => list.push(err),
+        }
+        Abort
+    }
+
+    /// Returns the first error found, for entry points which stop at the
+    /// first error.
+    pub(super) fn into_first(self) -> Result<(), ValidationError> {
+        match self {
+            Self::First(None) => Ok(()),
+            Self::First(Some(err)) => Err(err),
+            Self::All(_) => unreachable!("errors were collected with collect_all"),
+        }
+    }
+
+    /// Returns all errors found, for entry points which collect all errors.
+    pub(super) fn into_all(self) -> Result<(), Vec<ValidationError>> {
+        match self {
+            Self::All(list) if list.is_empty() => Ok(()),
+            Self::All(list) => Err(list),
+            Self::First(_) => unreachable!("errors were not collected with collect_all"),
+        }
+    }
+}
@@ -16,6 +16,14 @@
 //! block A to block B such that A has more than one successor *and* B has
 //! more than one predecessor.
 //!
+//! Alternatively, a function can opt into having critical edges with
+//! [`Function::allows_critical_edges`]. In that case the allocator splits
+//! critical edges internally and any moves needed on such an edge are placed
+//! in an edge block which is reported by [`Output::edge_blocks`]. The client
+//! is then only required to insert a new block on the edges which appear
+//! there. There cannot be more than one critical edge between the same pair of
+//! blocks.
+//!
 //! Instructions are opaque to the allocator: their behavior is entirely
 //! described using a vector of [`Operand`]s. Every block must end with
 //! a terminator instruction:
@@ -60,6 +68,22 @@
 //! instructions (e.g. a function call) require values to be in a fixed
 //! register. For those, [`OperandConstraint::Fixed`] can be used.
 //!
+//! # Alternative constraints
+//!
+//! Some instructions accept several kinds of operands, for example x86
+//! instructions which can take either a register or a memory operand, or
+//! inline assembly with GCC-style `rm` constraints. This can be expressed
+//! with [`OperandConstraint::Alternatives`], which refers to an
+//! [`AlternativeSet`]: a list of `Class` or `Fixed` constraints returned by
+//! [`Function::alternative_set`], each with a cost.
+//!
+//! The register allocator picks one alternative for each such operand before
+//! allocation and then treats the operand as if it had that constraint. The
+//! cheapest alternative is selected, unless it is a `Fixed` register which
+//! conflicts with another fixed register or clobber in the same instruction.
+//! Alternatives can only be used for `Use`, `Def` and `EarlyDef` operands and
+//! cannot be the target of a `Reuse` constraint.
+//!
 //! # SSA and block parameters
 //!
 //! The function described by the [`Function`] trait must be in [Static Single-Assignment]
@@ -76,11 +100,17 @@
 //!
 //! A basic block with multiple predecessor blocks may specify a set of block
 //! parameters with [`Function::block_params`]. This defines new [`Value`]s at
-//! the start of the block. All predecessor blocks, which must end with a `jump`
-//! terminator, must provide a matching number of outgoing [`Value`]s with
-//! [`Function::jump_blockparams`]. On entry to the block, the block parameter
-//! values will be initialized with the value from the block that control flow
-//! actually came from.
+//! the start of the block. All predecessor blocks must provide a matching
+//! number of outgoing [`Value`]s, either with [`Function::jump_blockparams`]
+//! for a `jump` terminator or with [`Function::branch_blockparams`] for a
+//! `branch` terminator. On entry to the block, the block parameter values will
+//! be initialized with the value from the block that control flow actually
+//! came from.
+//!
+//! Since a `branch` terminator has several successors, the moves for its
+//! outgoing block parameters can't be placed at the end of its block. These
+//! moves are instead placed in an edge block which is reported by
+//! [`Output::edge_blocks`], in the same way as for critical edges.
 //!
 //! # Reusing an input register for an output
 //!
@@ -88,17 +118,21 @@
 //! register to be specified as both an input and output, where the instruction
 //! clobber the original input value with the output value.
 //!
-//! In the case of fixed registers, this can be represented by using the same
-//! fixed register for both an input and an output operand. However for
-//! class-constraint operands an explicit relationship needs to be created
+//! For class-constraint operands an explicit relationship needs to be created
 //! between the input and output operand to ensure they are assigned to the same
 //! register.
 //!
 //! This is achieved by using [`OperandConstraint::Reuse`] for the output
 //! operand and specifying the index of the corresponding input operand, which
-//! must have [`OperandConstraint::Class`]. This will force the register
-//! allocator to assign to the output operand the same register as the
-//! designated input operand.
+//! must have [`OperandConstraint::Class`] or [`OperandConstraint::Fixed`]. This
+//! will force the register allocator to assign to the output operand the same
+//! register as the designated input operand.
+//!
+//! Tying an output to a fixed-register input (e.g. the dividend of an x86
+//! `div`) is equivalent to using the same fixed register for both operands. If
+//! the input value is still live after the instruction then the register
+//! allocator will preserve it by copying it into the fixed register before the
+//! instruction.
 //!
 //! # Non-allocatable registers
 //!
@@ -111,8 +145,57 @@
 //! Any registers used with `OperandKind::NonAllocatable` must not be part of
 //! any register bank or register class.
 //!
+//! # Instruction sequences without edits
+//!
+//! Some instruction sequences must not have any moves, spills, reloads or
+//! rematerializations inserted between them, such as load-linked and
+//! store-conditional loops, macro-fused compare and branch pairs or
+//! sequences that depend on a flags register. Such a sequence is described by
+//! marking every instruction after the first one with
+//! [`Function::no_edits_before`].
+//!
+//! The register allocator will not insert any edits at these points. Values
+//! which are live across the sequence keep the same [`Allocation`] throughout
+//! it and any edits needed for values used by the sequence are placed before
+//! its first instruction or after its last instruction. Instructions in the
+//! sequence are never eliminated from the output, even if they are pure or
+//! copies.
+//!
+//! To make this always possible, instructions marked with
+//! `no_edits_before` have a few restrictions:
+//! - They cannot be the first instruction in a block, a `jump` terminator or a
+//!   safepoint, and cannot follow a safepoint.
+//! - Their operands must all be `Use`, `Def` or `EarlyDef` operands with a
+//!   `Class` constraint, or `NonAllocatable` operands.
+//! - The instruction before them cannot have a fixed-register definition.
+//! - Every operand referring to a value used or defined inside the sequence,
+//!   including in the first instruction of the sequence, must have the same
+//!   `Class` constraint. Fixed-register uses are allowed in the first
+//!   instruction.
+//!
+//! Sequences which need fixed registers after their first instruction are not
+//! supported, such as an x86 `cmpxchg` loop which expects its comparison value
+//! in `rax`. The fixed-register operands must instead be moved into the first
+//! instruction, or the sequence must be emitted as a single instruction.
+//!
+//! # Safepoints and stack maps
+//!
+//! Clients with a garbage collector can mark values holding references to
+//! managed objects with [`Function::is_reference`] and instructions at which
+//! the garbage collector may run with [`Function::is_safepoint`].
+//!
+//! For each safepoint, the register allocator reports the reference values
+//! which are live across the instruction along with their [`Allocation`]. A
+//! moving garbage collector may update the references in these locations,
+//! after which any other copies of those values are considered stale.
+//! Reference values that are defined or last used by the safepoint are not
+//! included.
+//!
+//! Safepoints cannot be terminators and cannot be marked as pure instructions.
+//!
 //! [Static Single-Assignment]: https://en.wikipedia.org/wiki/Static_single-assignment_form
 //! [`Allocation`]: super::output::Allocation
+//! [`Output::edge_blocks`]: super::output::Output::edge_blocks
 
 use core::fmt;
 
@@ -136,6 +219,17 @@ pub const MAX_INST_OPERANDS: usize = 1 << 10;
 /// Maximum number of basic block parameters.
 pub const MAX_BLOCK_PARAMS: usize = 1 << 28;
 
+/// Maximum number of alternative sets.
+pub const MAX_ALTERNATIVE_SETS: usize = 1 << 14;
+
+/// Cost of reloading a value from a spill slot, in the units of the costs
+/// returned by [`Function::can_rematerialize`].
+pub const SPILL_RELOAD_COST: f32 = 1.0;
+
+/// Cost of a register-register move, in the units of the costs returned by
+/// [`Function::can_rematerialize`].
+pub const MOVE_COST: f32 = 0.5;
+
 entity_def! {
     /// An opaque reference to a basic block in the input function.
     ///
@@ -169,6 +263,13 @@ entity_def! {
     /// even if the same set of value is used multiple times.
     #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub entity ValueGroup(u32, "group");
+
+    /// A reference to a list of alternative [`OperandConstraint`]s.
+    ///
+    /// Unlike a [`ValueGroup`], the same `AlternativeSet` may be used by
+    /// multiple operands.
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub entity AlternativeSet(u32, "alts");
 }
 
 impl Block {
@@ -297,10 +398,23 @@ pub enum OperandConstraint {
     /// [`PhysReg`] as an input operand.
     ///
     /// The target operand must be an [`OperandKind::Use`] with an
-    /// [`OperandConstraint::Class`]. It is also valid for register groups with
-    /// the corresponding [`OperandKind::DefGroup`]/[`OperandKind::EarlyDefGroup`]
-    /// and [`OperandKind::UseGroup`].
+    /// [`OperandConstraint::Class`] or [`OperandConstraint::Fixed`]. It is also
+    /// valid for register groups with the corresponding
+    /// [`OperandKind::DefGroup`]/[`OperandKind::EarlyDefGroup`] and
+    /// [`OperandKind::UseGroup`] with a `Class` constraint.
+    ///
+    /// When the target has a `Fixed` constraint, the output is placed in that
+    /// register after the instruction, even for an `EarlyDef`.
     Reuse(usize),
+
+    /// Operand must satisfy one of the constraints in the given
+    /// [`AlternativeSet`].
+    ///
+    /// Each alternative must be a [`OperandConstraint::Class`] or an
+    /// [`OperandConstraint::Fixed`]. This can only be used with
+    /// [`OperandKind::Use`], [`OperandKind::Def`] and
+    /// [`OperandKind::EarlyDef`].
+    Alternatives(AlternativeSet),
 }
 
 impl fmt::Display for OperandConstraint {
@@ -309,6 +423,7 @@ impl fmt::Display for OperandConstraint {
             Self::Class(rc) => write!(f, "{rc}"),
             Self::Fixed(reg) => write!(f, "{reg}"),
             Self::Reuse(idx) => write!(f, "reuse({idx})"),
+            Self::Alternatives(set) => write!(f, "{set}"),
         }
     }
 }
@@ -332,6 +447,10 @@ pub struct Operand {
     /// `OperandConstraint` encoded in 16 bits:
     ///
     /// type:2 unused:2 index:12
+    ///
+    /// or for `Alternatives`:
+    ///
+    /// type:2 index:14
     constraint: u16,
 }
 
@@ -355,6 +474,7 @@ impl Operand {
             OperandConstraint::Class(class) => (0 << 14) | class.index() as u16,
             OperandConstraint::Fixed(reg) => (1 << 14) | reg.index() as u16,
             OperandConstraint::Reuse(index) => (2 << 14) | index as u16,
+            OperandConstraint::Alternatives(set) => (3 << 14) | set.index() as u16,
         };
         Self {
             kind: kind_field,
@@ -426,7 +546,7 @@ impl Operand {
     /// Create an `Operand` that designates a definition of a `Value` that must
     /// be in the same register as an input to the instruction. The input is
     /// identified by `input_idx` (is the `idx`th `Operand` for the instruction)
-    /// and must have a `regclass_use` constraint.
+    /// and must have a `regclass_use` or `fixed_use` constraint.
     #[inline]
     #[must_use]
     pub fn reuse_def(value: Value, input_idx: usize) -> Self {
@@ -436,7 +556,7 @@ impl Operand {
     /// Create an `Operand` that designates a definition of a `Value` that must
     /// be in the same register as an input to the instruction. The input is
     /// identified by `input_idx` (is the `idx`th `Operand` for the instruction)
-    /// and must have a `regclass_use` constraint.
+    /// and must have a `regclass_use` or `fixed_use` constraint.
     ///
     /// Additionally, this constrains the operand to not reuse the register of
     /// any *other* input operand.
@@ -491,6 +611,9 @@ impl Operand {
             0 => OperandConstraint::Class(RegClass::new(index)),
             1 => OperandConstraint::Fixed(PhysReg::new(index)),
             2 => OperandConstraint::Reuse(index),
+            3 => OperandConstraint::Alternatives(AlternativeSet::new(
+                self.constraint as usize & (MAX_ALTERNATIVE_SETS - 1),
+            )),
             _ => unreachable!(),
         }
     }
@@ -520,37 +643,26 @@ impl fmt::Display for Operand {
     }
 }
 
-/// Information about the cost of rematerializing a value into a register.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-pub enum RematCost {
-    /// Rematerialization is cheaper than a register-register move and should
-    /// always been preferred instead of a move.
-    CheaperThanMove,
-
-    /// Rematerialization is more expensive than a move but cheaper than a
-    /// memory load. It should only be performed if doing so avoids a memory
-    /// access instrution.
-    CheaperThanLoad,
-}
-
 /// Type of terminator instruction at the end of a basic block.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TerminatorKind {
     /// Blocks that end with a `Branch` terminator have one or more successor
-    /// blocks, but those blocks may only have a single predecessor.
+    /// blocks, but those blocks may only have a single predecessor unless
+    /// [`Function::allows_critical_edges`] is set.
     ///
     /// `Branch` terminators can have operands and clobbers, including `Def`
-    /// operands which define values used in successor blocks.
+    /// operands which define values used in successor blocks. They can also
+    /// pass block parameters to each successor with
+    /// [`Function::branch_blockparams`].
     Branch,
 
     /// Blocks that end with a `Jump` terminator can only have a single
     /// successor block and the successor block must have more than one
     /// predecessor block.
     ///
-    /// `Jump` terminators cannot have operands or clobbers. Only blocks ending
-    /// with a `Jump` terminator may have block parameters.
+    /// `Jump` terminators cannot have operands or clobbers. Outgoing block
+    /// parameters are provided by [`Function::jump_blockparams`].
     Jump,
 
     /// Blocks that have no successors must end with a `Ret` terminator.
@@ -649,6 +761,21 @@ pub trait Function {
     ///   instruction cannot have any operands.
     fn jump_blockparams(&self, block: Block) -> &[Value];
 
+    /// If `block` ends with a branch terminator, returns the outgoing block
+    /// arguments for the successor at index `succ_idx` in
+    /// [`Function::block_succs`].
+    ///
+    /// * The number of arguments must match the number incoming blockparams in
+    ///   the successor.
+    /// * Any moves needed for these arguments are placed in an edge block on
+    ///   the edge to that successor, see [`Output::edge_blocks`].
+    ///
+    /// [`Output::edge_blocks`]: super::output::Output::edge_blocks
+    #[inline]
+    fn branch_blockparams(&self, _block: Block, _succ_idx: usize) -> &[Value] {
+        &[]
+    }
+
     /// Returns the estimated execution frequency of this block.
     ///
     /// The allocator uses this to prefer placing moves in lower-frequency
@@ -669,6 +796,19 @@ pub trait Function {
     /// moves and rematerializations in this block if possible.
     fn block_is_critical_edge(&self, block: Block) -> bool;
 
+    /// Whether the CFG is allowed to contain critical edges.
+    ///
+    /// If this returns true then blocks ending with a [`TerminatorKind::Branch`]
+    /// may have successors with multiple predecessors, including the same
+    /// successor more than once. The allocator splits these edges internally
+    /// and reports the ones which need moves in [`Output::edge_blocks`].
+    ///
+    /// [`Output::edge_blocks`]: super::output::Output::edge_blocks
+    #[inline]
+    fn allows_critical_edges(&self) -> bool {
+        false
+    }
+
     // --------------------------
     // Instruction register slots
     // --------------------------
@@ -737,6 +877,71 @@ pub trait Function {
     /// Get the members of a value group.
     fn value_group_members(&self, group: ValueGroup) -> &[Value];
 
+    /// Get the number of alternative sets in use in this function.
+    #[inline]
+    fn num_alternative_sets(&self) -> usize {
+        0
+    }
+
+    /// Iterator over all the [`AlternativeSet`]s in this function.
+    #[inline]
+    fn alternative_sets(&self) -> Keys<AlternativeSet> {
+        Keys::with_len(self.num_alternative_sets())
+    }
+
+    /// Get the alternative constraints in an alternative set, along with the
+    /// cost of selecting each of them.
+    ///
+    /// Each alternative must be a `Class` or `Fixed` constraint and the set
+    /// must not be empty. Costs must be finite and non-negative.
+    #[inline]
+    fn alternative_set(&self, _set: AlternativeSet) -> &[(OperandConstraint, f32)] {
+        &[]
+    }
+
+    /// Returns a register which the allocator should prefer for a [`Value`],
+    /// along with the strength of that preference.
+    ///
+    /// This is useful when the client knows that a value will later need to be
+    /// in a particular register, for example because it is passed to a call
+    /// which isn't visible as a fixed-register constraint. Unlike a fixed
+    /// constraint, the allocator is free to ignore the hint.
+    ///
+    /// The strength is scaled by the block frequency at each definition and
+    /// use of the value and is weighed against the hints derived from
+    /// fixed-register operands, which use a strength of 1 per definition or
+    /// use. It must be finite and non-negative.
+    ///
+    /// The register must be allocatable and in the same bank as the value.
+    #[inline]
+    fn value_hint(&self, _value: Value) -> Option<(PhysReg, f32)> {
+        None
+    }
+
+    /// Returns a register which a [`Value`] is pinned to for its entire live
+    /// range.
+    ///
+    /// This is useful for values such as a runtime context pointer which must
+    /// stay in a dedicated register, but only in some functions. No other
+    /// value is allocated to the register while the pinned value is live.
+    ///
+    /// The register must be allocatable and in the same bank as the value.
+    /// Every `Class` constraint on an operand using or defining the value must
+    /// include the register, and the value cannot be a member of a
+    /// [`ValueGroup`].
+    ///
+    /// Values connected by block parameters, copies or `Reuse` constraints are
+    /// only assigned to the same register if they are pinned to the same
+    /// register. Allocation fails with [`RegAllocError::PinnedRegConflict`] if
+    /// the register is needed by a fixed-register operand or clobber while the
+    /// value is live.
+    ///
+    /// [`RegAllocError::PinnedRegConflict`]: crate::RegAllocError::PinnedRegConflict
+    #[inline]
+    fn value_pinned_reg(&self, _value: Value) -> Option<PhysReg> {
+        None
+    }
+
     // -----------------
     // Rematerialization
     // -----------------
@@ -744,9 +949,21 @@ pub trait Function {
     /// Whether a [`Value`] can be re-materialized "cheaply" (specifically,
     /// cheaper than a stack spill and reload).
     ///
+    /// The returned cost uses the same units as spill costs: a cost of
+    /// [`SPILL_RELOAD_COST`] is equivalent to a reload from a spill slot and a
+    /// cost of [`MOVE_COST`] is equivalent to a register-register move. The
+    /// register allocator will
+    /// rematerialize a value instead of moving it between registers if the
+    /// cost is below that of a move, and instead of reloading it from memory
+    /// if the cost is below that of a reload. A cost of 0.0 is appropriate for
+    /// values that can be materialized with a zeroing idiom.
+    ///
+    /// The cost must be finite and non-negative.
+    ///
     /// There are strict restrictions on rematerializable values: they cannot
-    /// depend on any other allocatable register and they are only allowed to
-    /// use a single destination register as scratch space.
+    /// depend on any other allocatable register, except for a single input
+    /// value returned by [`Function::remat_input`], and they are only allowed
+    /// to use a single destination register as scratch space.
     ///
     /// Additionally, the register class for the rematerialization target must:
     /// - have an non-empty allocation order.
@@ -754,7 +971,26 @@ pub trait Function {
     ///   [`RegInfo::is_memory`] is false.
     ///
     /// [`RegInfo::is_memory`]: super::reginfo::RegInfo::is_memory
-    fn can_rematerialize(&self, value: Value) -> Option<(RematCost, RegClass)>;
+    fn can_rematerialize(&self, value: Value) -> Option<(f32, RegClass)>;
+
+    /// Returns the input [`Value`] that a rematerializable value depends on,
+    /// if any.
+    ///
+    /// This is useful for values such as `base + offset` or sign extensions
+    /// of another value. Such a value can only be rematerialized at points
+    /// where its input is available in a register, which is reported in
+    /// [`OutputInst::Rematerialize`]. Elsewhere the value is spilled and
+    /// reloaded like any other value.
+    ///
+    /// The input value must not itself be rematerializable and its definition
+    /// must dominate that of `value`. This must return `None` for values for
+    /// which [`Function::can_rematerialize`] returns `None`.
+    ///
+    /// [`OutputInst::Rematerialize`]: super::output::OutputInst::Rematerialize
+    #[inline]
+    fn remat_input(&self, _value: Value) -> Option<Value> {
+        None
+    }
 
     /// If all the outputs of an instruction are dead (never used), can the
     /// instruction be removed (i.e. it has no side effects apart from its
@@ -762,7 +998,84 @@ pub trait Function {
     ///
     /// Instruction outputs can become dead due to rematerialization when all
     /// existing users are using the rematerialized value and the original
-    /// instruction is no longer needed.
-    // TODO: We don't do this yet.
+    /// instruction is no longer needed. Such instructions are omitted from
+    /// [`Output::output_insts`].
+    ///
+    /// [`Output::output_insts`]: super::output::Output::output_insts
     fn can_eliminate_dead_inst(&self, inst: Inst) -> bool;
+
+    /// Whether an instruction is a plain copy of one [`Value`] into another.
+    ///
+    /// A copy must have exactly one `Use` and one `Def` operand, both with a
+    /// `Class` constraint, and no other operands or clobbers. It cannot be a
+    /// terminator or a safepoint.
+    ///
+    /// The allocator tries to assign both values to the same location, in
+    /// which case the copy is omitted from [`Output::output_insts`].
+    ///
+    /// [`Output::output_insts`]: super::output::Output::output_insts
+    #[inline]
+    fn is_copy(&self, _inst: Inst) -> bool {
+        false
+    }
+
+    /// Returns the last instruction at which a [`Value`] must still be
+    /// available, even if it has no uses there.
+    ///
+    /// This is typically used at `-O0` to keep variables visible in a
+    /// debugger until the end of their lexical scope. The value is kept live
+    /// up to and including the given instruction, in any location, and is
+    /// reported in [`Output::value_location_ranges`] accordingly.
+    ///
+    /// The definition of the value must dominate this instruction.
+    ///
+    /// If the instruction is a jump terminator then the value is only kept
+    /// alive until the moves for the outgoing block parameters, which are
+    /// placed before the jump.
+    ///
+    /// [`Output::value_location_ranges`]: super::output::Output::value_location_ranges
+    #[inline]
+    fn value_scope_end(&self, _value: Value) -> Option<Inst> {
+        None
+    }
+
+    /// Whether the register allocator is forbidden from inserting edits
+    /// (moves, spills, reloads or rematerializations) before the given
+    /// instruction.
+    ///
+    /// This ties the instruction to the preceding one so that a sequence of
+    /// such instructions is emitted without any intervening edits. See the
+    /// [module-level documentation] for the restrictions that apply to these
+    /// instructions. In particular, they cannot have fixed-register operands.
+    ///
+    /// [module-level documentation]: self
+    #[inline]
+    fn no_edits_before(&self, _inst: Inst) -> bool {
+        false
+    }
+
+    // -------------------------
+    // Safepoints and references
+    // -------------------------
+
+    /// Whether a [`Value`] is a reference to an object managed by a garbage
+    /// collector.
+    ///
+    /// Reference values which are live across a safepoint are reported in
+    /// [`Output::stack_map`].
+    ///
+    /// [`Output::stack_map`]: super::output::Output::stack_map
+    #[inline]
+    fn is_reference(&self, _value: Value) -> bool {
+        false
+    }
+
+    /// Whether the garbage collector may run during the given instruction.
+    ///
+    /// Safepoints cannot be terminators and cannot be marked as pure with
+    /// [`Function::can_eliminate_dead_inst`].
+    #[inline]
+    fn is_safepoint(&self, _inst: Inst) -> bool {
+        false
+    }
 }
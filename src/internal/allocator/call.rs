@@ -0,0 +1,216 @@
+//! Call-aware live range splitting.
+//!
+//! This is used by `SplitStrategy::Call`. Call instructions typically clobber
+//! most of the registers in a bank, which means that a value that is live
+//! across a call can only be allocated to a callee-saved register or spilled.
+//! Without special handling, such a value would need a callee-saved register
+//! or a spill slot for its entire live range, even in the parts of the live
+//! range that don't cross any call.
+//!
+//! An instruction is considered to be a call for a register class if its
+//! clobbers cover more than half of the registers in the allocation order of
+//! that class.
+//!
+//! For each call that a virtual register is live across, the live range is
+//! split after the last use before the call and before the first use after the
+//! call. This produces pieces containing uses which don't cross any calls and
+//! can therefore use caller-saved registers, and pieces without any uses that
+//! cross one or more calls. The latter will either find a free callee-saved
+//! register or be spilled.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::{Assignment, Context};
+use crate::function::{Function, Inst};
+use crate::internal::live_range::{Slot, ValueSegment};
+use crate::internal::split_placement::{edit_sequence_end, edit_sequence_start};
+use crate::internal::virt_regs::VirtReg;
+use crate::reginfo::{RegClass, RegInfo, RegUnitSet};
+use crate::{Options, SplitStrategy};
+
+/// Temporary state used for call-aware splitting.
+pub struct CallSplitter {
+    /// Instructions with clobbers, in increasing order, along with the set of
+    /// units that they clobber.
+    clobbering_insts: Vec<(Inst, RegUnitSet)>,
+
+    /// Instructions at which the virtual register being split is used.
+    use_insts: Vec<Inst>,
+
+    /// Instructions before which to split the virtual register.
+    split_points: Vec<Inst>,
+
+    /// Scratch space for the segments of the virtual register being split.
+    segments: Vec<ValueSegment>,
+}
+
+impl CallSplitter {
+    pub fn new() -> Self {
+        Self {
+            clobbering_insts: vec![],
+            use_insts: vec![],
+            split_points: vec![],
+            segments: vec![],
+        }
+    }
+
+    /// Collects all instructions with clobbers in the given function.
+    pub fn prepare(&mut self, func: &impl Function, options: &Options) {
+        self.clobbering_insts.clear();
+        if options.split_strategy != SplitStrategy::Call {
+            return;
+        }
+        for inst in func.insts() {
+            let mut clobbers = func.inst_clobbers(inst).peekable();
+            if clobbers.peek().is_some() {
+                self.clobbering_insts.push((inst, clobbers.collect()));
+            }
+        }
+    }
+}
+
+/// Returns whether `clobbers` covers more than half of the registers in the
+/// allocation order of `class`.
+fn is_call_for_class(clobbers: &RegUnitSet, class: RegClass, reginfo: &impl RegInfo) -> bool {
+    let order = reginfo.allocation_order(class);
+    let clobbered = order
+        .iter()
+        .filter(|&&reg| reginfo.reg_units(reg).any(|unit| clobbers.contains(unit)))
+        .count();
+    clobbered * 2 > order.len()
+}
+
+impl<F: Function, R: RegInfo> Context<'_, F, R> {
+    /// Collects the points at which to split `vreg` around the calls that it
+    /// is live across.
+    fn collect_call_split_points(&mut self, vreg: VirtReg) {
+        let cs = &mut self.allocator.call_splitter;
+        cs.use_insts.clear();
+        cs.split_points.clear();
+        let class = self.virt_regs[vreg].class;
+        let segments = self.virt_regs.segments(vreg);
+        for segment in segments {
+            cs.use_insts
+                .extend(self.uses[segment.use_list].iter().map(|u| u.pos));
+        }
+        cs.use_insts.sort_unstable();
+        cs.use_insts.dedup();
+
+        for segment in segments {
+            // Find the calls whose clobbers overlap the segment: the virtual
+            // register can't stay in a clobbered register across these.
+            let start = cs.clobbering_insts.partition_point(|&(inst, _)| {
+                inst.next().slot(Slot::Boundary) <= segment.live_range.from
+            });
+            for (inst, clobbers) in &cs.clobbering_insts[start..] {
+                if inst.slot(Slot::Normal) >= segment.live_range.to {
+                    break;
+                }
+                if !is_call_for_class(clobbers, class, self.reginfo) {
+                    continue;
+                }
+                trace!("{vreg} is live across call at {inst}");
+
+                // Split after the last use before the call and before the
+                // first use after it, keeping any sequence which forbids edits
+                // with the use.
+                let idx = cs.use_insts.partition_point(|&u| u <= *inst);
+                if let Some(&prev) = idx.checked_sub(1).map(|idx| &cs.use_insts[idx]) {
+                    cs.split_points.push(edit_sequence_end(self.func, prev));
+                }
+                if let Some(&next) = cs.use_insts.get(idx) {
+                    cs.split_points.push(edit_sequence_start(self.func, next));
+                }
+            }
+        }
+        cs.split_points.sort_unstable();
+        cs.split_points.dedup();
+    }
+
+    /// Attempts to split the given virtual register around the calls that it
+    /// is live across.
+    ///
+    /// Returns `false` if the virtual register is not live across any call
+    /// or if it cannot be split any further around calls.
+    pub(super) fn try_call_split(&mut self, vreg: VirtReg) -> bool {
+        if self.allocator.call_splitter.clobbering_insts.is_empty() {
+            return false;
+        }
+        self.collect_call_split_points(vreg);
+
+        let CallSplitter {
+            split_points,
+            segments,
+            ..
+        } = &mut self.allocator.call_splitter;
+        segments.clear();
+        segments.extend_from_slice(self.virt_regs.segments(vreg));
+
+        // Only split at points strictly inside the live range so that every
+        // split product is smaller than the original virtual register.
+        let from = segments[0].live_range.from;
+        let to = segments.last().unwrap().live_range.to;
+        split_points.retain(|inst| {
+            let point = inst.slot(Slot::Boundary);
+            point > from && point < to
+        });
+        if split_points.is_empty() {
+            trace!("No calls to split {vreg} around");
+            stat!(self.stats, no_call_split);
+            return false;
+        }
+        trace!("Splitting {vreg} around calls at {split_points:?}");
+        stat!(self.stats, split_vregs);
+        stat!(self.stats, call_split_vregs);
+
+        let set = self.virt_regs[vreg].value_set;
+        self.allocator.splitter.new_vregs.clear();
+        let mut remaining = &mut segments[..];
+        for &split_point in &*split_points {
+            // Skip split points that fall in a hole between segments which was
+            // already split at.
+            if split_point.slot(Slot::Boundary) <= remaining[0].live_range.from {
+                continue;
+            }
+            let mut split =
+                ValueSegment::split_segments_at(remaining, self.uses, self.hints, split_point);
+            self.virt_regs.create_vreg_from_segments(
+                split.first_half(),
+                self.func,
+                self.reginfo,
+                self.uses,
+                self.hints,
+                self.virt_reg_builder,
+                self.coalescing,
+                self.stats,
+                self.options,
+                set,
+                &mut self.allocator.splitter.new_vregs,
+            );
+            remaining = split.into_second_half();
+        }
+        self.virt_regs.create_vreg_from_segments(
+            remaining,
+            self.func,
+            self.reginfo,
+            self.uses,
+            self.hints,
+            self.virt_reg_builder,
+            self.coalescing,
+            self.stats,
+            self.options,
+            set,
+            &mut self.allocator.splitter.new_vregs,
+        );
+        self.allocator
+            .assignments
+            .grow_to_with(self.virt_regs.num_virt_regs(), Assignment::default);
+
+        // The original vreg is no longer used after this point.
+        self.allocator.assignments[vreg] = Assignment::Dead;
+
+        self.queue_new_vregs();
+        true
+    }
+}
@@ -10,6 +10,7 @@ use crate::internal::allocator::Stage;
 use crate::internal::allocator::queue::VirtRegOrGroup;
 use crate::internal::live_range::ValueSegment;
 use crate::internal::reg_matrix::{Interference, InterferenceKind};
+use crate::internal::virt_regs::builder::normalize_spill_weight;
 use crate::reginfo::RegInfo;
 
 impl<F: Function, R: RegInfo> Context<'_, F, R> {
@@ -34,8 +35,13 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
 
             // Check if we have a stronger claim to the register than all the
             // interfering virtual registers currently allocated to this
-            // register.
-            let mut interference_weight = 0.0;
+            // register. Any additional callee-saved register that would need
+            // to be claimed counts against the new candidate.
+            let mut interference_weight = order.reg_claim_cost(new_candidate.reg, self.reginfo)
+                - order.reg_claim_cost(candidate.reg, self.reginfo);
+            if interference_weight >= new_candidate.preference_weight {
+                continue;
+            }
             self.allocator.interfering_vregs.clear();
             for (vreg, reg) in
                 vreg.zip_with_reg_group(new_candidate.reg, self.virt_regs, self.reginfo)
@@ -89,19 +95,29 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
             preference_weight: f32,
 
             //// Maximum spill weight of the virtual registers that we would
-            //// need to evict.
+            //// need to evict, plus the callee-saved cost of claiming the
+            //// candidate register normalized like a spill weight.
             spill_weight: f32,
         }
         let mut best_cost = EvictCost {
             preference_weight: f32::INFINITY,
             spill_weight: f32::INFINITY,
         };
+        // Maximum spill weight of the evictees for the best candidate, without
+        // the claim cost included in `best_cost.spill_weight`.
+        let mut best_evictee_weight = 0.0;
         let mut best_candidate = None;
 
         // By default (unless we have a register preference) we only want to
         // evict virtual registers with a lower spill weight than us.
         let max_spill_weight = self.virt_regs[vreg.first_vreg(self.virt_regs)].spill_weight;
 
+        // The callee-saved cost of claiming a register is spread over our live
+        // range in the same way as the spill cost of our uses. This allows it
+        // to be added to the spill weights of the evictees.
+        let live_insts =
+            ValueSegment::live_insts(self.virt_regs.segments(vreg.first_vreg(self.virt_regs)));
+
         // Only allow evicting virtual registers with a higher spill weight
         // once per virtual register. This can happen if we have a higher
         // preference for this register than the evictees.
@@ -119,10 +135,16 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
         'outer: for candidate in order.order(vreg, self.virt_regs, self.reginfo) {
             trace!("Candidate: {candidate}");
 
+            let claim_weight = normalize_spill_weight(
+                order.reg_claim_cost(candidate.reg, self.reginfo),
+                live_insts,
+                self.options,
+            );
             let mut cost = EvictCost {
                 preference_weight: 0.0,
-                spill_weight: 0.0,
+                spill_weight: claim_weight,
             };
+            let mut evictee_weight: f32 = 0.0;
             self.allocator.candidate_interfering_vregs.clear();
 
             for (vreg, reg) in vreg.zip_with_reg_group(candidate.reg, self.virt_regs, self.reginfo)
@@ -163,7 +185,8 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                     }
 
                     cost.preference_weight += preference_weight;
-                    cost.spill_weight = cost.spill_weight.max(spill_weight);
+                    evictee_weight = evictee_weight.max(spill_weight);
+                    cost.spill_weight = claim_weight + evictee_weight;
                     trace!("Total cost so far: {cost:?}");
 
                     // Don't bother continuing with this candidate if we exceeded
@@ -176,7 +199,7 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                     // We can't evict virtual registers with a higher spill weight
                     // than ours, *except* if our preference for the candidate is
                     // higher than the total of those of all the evictees.
-                    if cost.spill_weight >= max_spill_weight
+                    if evictee_weight >= max_spill_weight
                         && (candidate.preference_weight == 0.0 || strict_max_weight)
                     {
                         if strict_max_weight {
@@ -222,6 +245,7 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
             trace!("Best candidate for evicition is now {candidate} with cost {cost:?}");
             best_candidate = Some(candidate);
             best_cost = cost;
+            best_evictee_weight = evictee_weight;
             mem::swap(
                 &mut self.allocator.candidate_interfering_vregs,
                 &mut self.allocator.interfering_vregs,
@@ -231,10 +255,14 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
         if let Some(best_candidate) = best_candidate {
             trace!("Evicting interference from {best_candidate}");
             self.evict_interfering_vregs();
+
+            // The claim cost only ranks candidates against each other. Like
+            // the `max_spill_weight` check above, whether we evicted for
+            // preference only depends on the evictees.
             self.assign(
                 vreg,
                 best_candidate,
-                best_cost.spill_weight >= max_spill_weight,
+                best_evictee_weight >= max_spill_weight,
             );
             true
         } else {
@@ -250,6 +278,7 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
             // There may be duplicates in the collected interferring vregs.
             let Assignment::Assigned {
                 evicted_for_preference,
+                evicted_unspillable,
                 reg,
                 preference_weight: _,
             } = assignments[vreg]
@@ -265,6 +294,7 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                 for &vreg in self.virt_regs.group_members(group) {
                     let Assignment::Assigned {
                         evicted_for_preference,
+                        evicted_unspillable,
                         reg,
                         preference_weight: _,
                     } = assignments[vreg]
@@ -273,6 +303,7 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                     };
                     assignments[vreg] = Assignment::Unassigned {
                         evicted_for_preference,
+                        evicted_unspillable,
                     };
                     self.reg_matrix
                         .evict(vreg, reg, self.virt_regs, self.reginfo);
@@ -289,6 +320,7 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                     .evict(vreg, reg, self.virt_regs, self.reginfo);
                 assignments[vreg] = Assignment::Unassigned {
                     evicted_for_preference,
+                    evicted_unspillable,
                 };
                 self.allocator.queue.enqueue(
                     VirtRegOrGroup::Reg(vreg),
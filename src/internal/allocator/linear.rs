@@ -0,0 +1,373 @@
+//! Fast linear-scan allocation loop.
+//!
+//! This replaces the main allocation loop when
+//! `AllocationAlgorithm::LinearScan` is selected. It trades allocation quality
+//! for compile time and is intended for unoptimized builds.
+//!
+//! Virtual registers are processed in a single pass, in order of the start of
+//! their live range, and are assigned to the first free register in their
+//! allocation order. There is no eviction queue and no live range splitting:
+//! a virtual register which conflicts with existing assignments is spilled.
+//! This leaves only minimal virtual registers around the uses that need a
+//! register, which are then allocated in the same pass.
+//!
+//! Minimal virtual registers can't be spilled, so if one of these conflicts
+//! with existing assignments then the spillable virtual registers interfering
+//! with it are spilled instead. If the interference is itself unspillable then
+//! it is evicted once, and then moved out of the way with last-chance
+//! recoloring as in the greedy allocator.
+
+use alloc::collections::BinaryHeap;
+use core::cmp::Reverse;
+use core::ops::ControlFlow;
+use core::{mem, slice};
+
+use super::order::CandidateReg;
+use super::queue::VirtRegOrGroup;
+use super::{AbstractVirtRegGroup, Assignment, Context};
+use crate::RegAllocError;
+use crate::entity::SecondaryMap;
+use crate::function::{Function, Inst};
+use crate::internal::reg_matrix::InterferenceKind;
+use crate::internal::virt_regs::{VirtReg, VirtRegGroup, VirtRegs};
+use crate::reginfo::{MAX_GROUP_SIZE, RegInfo};
+
+/// Entry in the linear queue.
+///
+/// Entries are ordered by the instruction at which their live range starts.
+/// Within an instruction, larger groups and virtual registers with a
+/// fixed-register hint are harder to allocate and so are prioritized. The
+/// virtual register index is used as a tiebreaker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Entry {
+    start: Inst,
+    inverted_group_size: u8,
+    no_fixed_hint: bool,
+    index: u32,
+}
+
+/// Queue of virtual registers and virtual register groups ordered by the
+/// start of their live range.
+pub struct LinearQueue {
+    queue: BinaryHeap<Reverse<Entry>>,
+}
+
+impl LinearQueue {
+    pub fn new() -> Self {
+        Self {
+            queue: BinaryHeap::new(),
+        }
+    }
+
+    /// Initializes the queue from the set of existing virtual register and
+    /// virtual register groups.
+    ///
+    /// Virtual registers which are already assigned (because their value is
+    /// pinned to a register) are skipped.
+    pub fn init(&mut self, virt_regs: &VirtRegs, assignments: &SecondaryMap<VirtReg, Assignment>) {
+        let mut vec = mem::take(&mut self.queue).into_vec();
+        vec.clear();
+        vec.extend(
+            virt_regs
+                .virt_regs()
+                .filter(|&vreg| {
+                    virt_regs[vreg].group.is_none()
+                        && matches!(assignments[vreg], Assignment::Unassigned { .. })
+                })
+                .map(|vreg| Reverse(Entry::encode(VirtRegOrGroup::Reg(vreg), virt_regs))),
+        );
+        vec.extend(
+            virt_regs
+                .groups()
+                .map(|group| Reverse(Entry::encode(VirtRegOrGroup::Group(group), virt_regs))),
+        );
+        self.queue = vec.into();
+    }
+
+    /// Dequeues the entry with the earliest start point.
+    pub fn dequeue(&mut self) -> Option<VirtRegOrGroup> {
+        self.queue.pop().map(|Reverse(entry)| entry.decode())
+    }
+
+    /// Enqueues an entry into the queue.
+    pub fn enqueue(&mut self, vreg_or_group: VirtRegOrGroup, virt_regs: &VirtRegs) {
+        self.queue
+            .push(Reverse(Entry::encode(vreg_or_group, virt_regs)));
+    }
+}
+
+impl Entry {
+    fn encode(vreg_or_group: VirtRegOrGroup, virt_regs: &VirtRegs) -> Self {
+        let members = match vreg_or_group {
+            VirtRegOrGroup::Reg(ref vreg) => slice::from_ref(vreg),
+            VirtRegOrGroup::Group(group) => virt_regs.group_members(group),
+        };
+        let start = members
+            .iter()
+            .map(|&vreg| virt_regs.segments(vreg)[0].live_range.from.inst())
+            .min()
+            .unwrap();
+        let has_fixed_hint = members.iter().any(|&vreg| virt_regs[vreg].has_fixed_hint);
+        let index = match vreg_or_group {
+            VirtRegOrGroup::Reg(vreg) => vreg.index(),
+            VirtRegOrGroup::Group(group) => group.index(),
+        };
+        Self {
+            start,
+            inverted_group_size: (MAX_GROUP_SIZE - members.len()) as u8,
+            no_fixed_hint: !has_fixed_hint,
+            index: index as u32,
+        }
+    }
+
+    fn decode(self) -> VirtRegOrGroup {
+        let index = self.index as usize;
+        if MAX_GROUP_SIZE - self.inverted_group_size as usize == 1 {
+            VirtRegOrGroup::Reg(VirtReg::new(index))
+        } else {
+            VirtRegOrGroup::Group(VirtRegGroup::new(index))
+        }
+    }
+}
+
+impl<F: Function, R: RegInfo> Context<'_, F, R> {
+    /// Assigns a physical register (or spill slot) to every virtual register
+    /// in a single linear pass.
+    pub(super) fn run_linear(&mut self) -> Result<(), RegAllocError> {
+        self.allocator
+            .linear_queue
+            .init(self.virt_regs, &self.allocator.assignments);
+        while let Some(vreg) = self.allocator.linear_queue.dequeue() {
+            match vreg {
+                VirtRegOrGroup::Reg(vreg) => self.allocate_linear(vreg)?,
+                VirtRegOrGroup::Group(group) => self.allocate_linear(group)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Assigns the given virtual register to the first free register in its
+    /// allocation order, or spills it if there is none.
+    fn allocate_linear<V: AbstractVirtRegGroup>(&mut self, vreg: V) -> Result<(), RegAllocError> {
+        trace!("Allocating {vreg}");
+        vreg.dump(self.virt_regs, self.uses);
+        if V::is_group() {
+            stat!(self.stats, dequeued_group);
+        } else {
+            stat!(self.stats, dequeued_reg);
+        }
+
+        let max_callee_saved_cost = self.max_callee_saved_cost(vreg);
+
+        let order = V::select_order(
+            &mut self.allocator.allocation_order,
+            &mut self.allocator.group_allocation_order,
+        );
+        order.compute(
+            vreg,
+            self.virt_regs,
+            self.hints,
+            &self.allocator.last_allocated_reg,
+            self.allocator.unclaimed_callee_saved,
+            max_callee_saved_cost,
+            self.reginfo,
+        );
+        if order.must_spill(vreg, self.virt_regs, self.reginfo) {
+            trace!("Empty allocation order, spilling immediately");
+            stat!(self.stats, must_spill_vreg);
+            self.spill(vreg);
+            return Ok(());
+        }
+
+        if let Some(candidate) = self.find_available_reg(vreg) {
+            trace!("-> Got candidate {candidate}");
+            stat!(self.stats, found_free_reg);
+            self.assign(vreg, candidate, false);
+            return Ok(());
+        }
+
+        // Spill on conflict, unless this virtual register only covers a
+        // single instruction and must be in a register.
+        if !self.virt_regs[vreg.first_vreg(self.virt_regs)]
+            .spill_weight
+            .is_infinite()
+        {
+            trace!("No free register, spilling {vreg}");
+            self.spill(vreg);
+            return Ok(());
+        }
+
+        trace!("No free register for unspillable {vreg}, spilling interference");
+        if let Some(candidate) = self.find_spillable_interference(vreg, false) {
+            trace!("-> Spilling interference from {candidate}");
+            self.spill_interfering_vregs();
+            self.assign(vreg, candidate, false);
+            return Ok(());
+        }
+
+        // As a last resort, move other unspillable virtual registers out of
+        // the way. They are re-queued and will look for another register.
+        //
+        // To avoid infinite eviction loops, we only allow this to happen once
+        // per virtual register.
+        if !self.allocator.assignments[vreg.first_vreg(self.virt_regs)].evicted_unspillable() {
+            if let Some(candidate) = self.find_spillable_interference(vreg, true) {
+                trace!("-> Evicting unspillable interference from {candidate}");
+                self.spill_interfering_vregs();
+                self.assign(vreg, candidate, false);
+                for vreg in vreg.vregs(self.virt_regs) {
+                    self.allocator.assignments[vreg].set_evicted_unspillable();
+                }
+                return Ok(());
+            }
+        }
+
+        // If that isn't enough, fall back to the same recursive search as the
+        // greedy allocator.
+        if self.try_last_chance_recoloring(vreg) {
+            return Ok(());
+        }
+
+        trace!("Allocation failed: could not allocate unspillable {vreg}");
+        Err(self.too_many_live_regs_error(vreg))
+    }
+
+    /// Searches for a register in which all interference can be spilled.
+    ///
+    /// If `allow_unspillable` is set then unspillable virtual registers may
+    /// also be evicted, except those which have themselves evicted another
+    /// unspillable virtual register. The register requiring the fewest such evictions is
+    /// selected.
+    ///
+    /// On success, the interfering virtual registers are left in
+    /// `interfering_vregs`.
+    fn find_spillable_interference<V: AbstractVirtRegGroup>(
+        &mut self,
+        vreg: V,
+        allow_unspillable: bool,
+    ) -> Option<CandidateReg<V>> {
+        let mut best_candidate = None;
+        let mut best_cost = usize::MAX;
+        let order = V::select_order(
+            &mut self.allocator.allocation_order,
+            &mut self.allocator.group_allocation_order,
+        );
+        'outer: for candidate in order.order(vreg, self.virt_regs, self.reginfo) {
+            let mut cost = 0;
+            self.allocator.candidate_interfering_vregs.clear();
+            for (vreg, reg) in vreg.zip_with_reg_group(candidate.reg, self.virt_regs, self.reginfo)
+            {
+                let result = self.reg_matrix.check_interference(
+                    self.virt_regs.segments(vreg),
+                    reg,
+                    self.reginfo,
+                    self.stats,
+                    false,
+                    |interference| {
+                        // Fixed interference can't be moved out of the way.
+                        let InterferenceKind::VirtReg(interfering_vreg) = interference.kind else {
+                            return ControlFlow::Break(());
+                        };
+                        if self.virt_regs[interfering_vreg].spill_weight.is_infinite() {
+                            if !allow_unspillable
+                                || self.allocator.assignments[interfering_vreg]
+                                    .evicted_unspillable()
+                            {
+                                return ControlFlow::Break(());
+                            }
+                            cost += 1;
+                            if cost >= best_cost {
+                                return ControlFlow::Break(());
+                            }
+                        }
+                        self.allocator
+                            .candidate_interfering_vregs
+                            .push(interfering_vreg);
+                        ControlFlow::Continue(())
+                    },
+                );
+                if result.is_break() {
+                    continue 'outer;
+                }
+            }
+
+            best_candidate = Some(candidate);
+            best_cost = cost;
+            mem::swap(
+                &mut self.allocator.candidate_interfering_vregs,
+                &mut self.allocator.interfering_vregs,
+            );
+
+            // Without unspillable interference, the first candidate is good
+            // enough.
+            if cost == 0 {
+                break;
+            }
+        }
+
+        best_candidate
+    }
+
+    /// Unassigns all the virtual registers in `interfering_vregs`.
+    ///
+    /// Spillable virtual registers are spilled immediately while unspillable
+    /// ones are re-queued.
+    fn spill_interfering_vregs(&mut self) {
+        while let Some(vreg) = self.allocator.interfering_vregs.pop() {
+            // There may be duplicates in the collected interferring vregs.
+            if !matches!(
+                self.allocator.assignments[vreg],
+                Assignment::Assigned { .. }
+            ) {
+                continue;
+            }
+            let unspillable = self.virt_regs[vreg].spill_weight.is_infinite();
+
+            // All members of a register group need to be evicted together.
+            let vreg_or_group = match self.virt_regs[vreg].group.expand() {
+                Some(group) => {
+                    stat!(self.stats, evicted_groups);
+                    VirtRegOrGroup::Group(group)
+                }
+                None => {
+                    stat!(self.stats, evicted_vregs);
+                    VirtRegOrGroup::Reg(vreg)
+                }
+            };
+            let members = match vreg_or_group {
+                VirtRegOrGroup::Reg(_) => slice::from_ref(&vreg),
+                VirtRegOrGroup::Group(group) => self.virt_regs.group_members(group),
+            };
+            for &vreg in members {
+                let Assignment::Assigned {
+                    evicted_for_preference,
+                    evicted_unspillable,
+                    reg,
+                    preference_weight: _,
+                } = self.allocator.assignments[vreg]
+                else {
+                    unreachable!();
+                };
+                self.allocator.assignments[vreg] = Assignment::Unassigned {
+                    evicted_for_preference,
+                    evicted_unspillable,
+                };
+                self.reg_matrix
+                    .evict(vreg, reg, self.virt_regs, self.reginfo);
+            }
+
+            if unspillable {
+                trace!("Evicting unspillable {vreg_or_group}");
+                self.allocator
+                    .linear_queue
+                    .enqueue(vreg_or_group, self.virt_regs);
+            } else {
+                trace!("Spilling interfering {vreg_or_group}");
+                match vreg_or_group {
+                    VirtRegOrGroup::Reg(vreg) => self.spill(vreg),
+                    VirtRegOrGroup::Group(group) => self.spill(group),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,309 @@
+//! Diagnostics for unspillable virtual registers which could not be allocated.
+//!
+//! When allocation fails, `RegAllocError::TooManyLiveRegs` reports what is
+//! occupying the registers of the failing class at the offending instruction.
+//! Every occupant which can't be evicted is collected for each candidate
+//! register, and a minimal set of occupants covering all candidates is then
+//! selected with a greedy set cover. This is only run on the error path so it
+//! favors simplicity over speed.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::ControlFlow;
+
+use super::{AbstractVirtRegGroup, Context};
+use crate::RegAllocError;
+use crate::function::{Function, Inst, OperandConstraint, OperandKind, Value};
+use crate::internal::alternatives::AlternativeResolver;
+use crate::internal::live_range::{LiveRangePoint, LiveRangeSegment, Slot, ValueSegment};
+use crate::internal::reg_matrix::InterferenceKind;
+use crate::internal::virt_regs::VirtReg;
+use crate::reginfo::{RegInfo, RegUnit};
+
+impl<F: Function, R: RegInfo> Context<'_, F, R> {
+    /// Builds a `RegAllocError::TooManyLiveRegs` for an unspillable virtual
+    /// register which could not be allocated.
+    ///
+    /// An unspillable virtual register usually only covers a single
+    /// instruction, but it may also span a sequence of instructions with no
+    /// edits between them. The error is reported for the covered instruction
+    /// with the most occupied registers.
+    #[cold]
+    pub(super) fn too_many_live_regs_error<V: AbstractVirtRegGroup>(
+        &self,
+        vreg: V,
+    ) -> RegAllocError {
+        let mut alternatives = AlternativeResolver::new();
+        let mut best: Option<(usize, RegAllocError)> = None;
+        for member in vreg.vregs(self.virt_regs) {
+            for segment in self.virt_regs.segments(member) {
+                let from = segment.live_range.from.round_to_prev_inst().inst();
+                let to = segment.live_range.to.round_to_next_inst().inst();
+                for inst in (from.index()..to.index()).map(Inst::new) {
+                    let (occupied, err) = self.live_regs_at_inst(vreg, inst, &mut alternatives);
+                    if best.as_ref().is_none_or(|&(best, _)| occupied > best) {
+                        best = Some((occupied, err));
+                    }
+                }
+            }
+        }
+        best.unwrap().1
+    }
+
+    /// Finds a minimal set of occupants which together block every register
+    /// in the class of an unspillable virtual register while `inst` is
+    /// executing, and maps it back to the operands, live-through values and
+    /// clobbers of the instruction.
+    ///
+    /// Also returns the number of registers in the allocation order which are
+    /// blocked at `inst`.
+    fn live_regs_at_inst<V: AbstractVirtRegGroup>(
+        &self,
+        vreg: V,
+        inst: Inst,
+        alternatives: &mut AlternativeResolver,
+    ) -> (usize, RegAllocError) {
+        let first_vreg = vreg.first_vreg(self.virt_regs);
+        let class = self.virt_regs[first_vreg].class;
+
+        // Finds operands of `inst` which refer to the given value.
+        let operands_for_value = |value: Value| {
+            self.func
+                .inst_operands(inst)
+                .iter()
+                .enumerate()
+                .filter(move |(_, op)| match op.kind() {
+                    OperandKind::Def(v) | OperandKind::EarlyDef(v) | OperandKind::Use(v) => {
+                        v == value
+                    }
+                    OperandKind::DefGroup(group)
+                    | OperandKind::EarlyDefGroup(group)
+                    | OperandKind::UseGroup(group) => {
+                        self.func.value_group_members(group).contains(&value)
+                    }
+                    OperandKind::NonAllocatable => false,
+                })
+                .map(|(idx, _)| idx)
+        };
+
+        // Fixed-register alternatives are reserved in the same way as fixed
+        // constraints. If no alternative can be selected then fall back to the
+        // unresolved constraints, in which case registers reserved by a fixed
+        // alternative are reported as fixed units instead of as operands.
+        let unresolved: Vec<OperandConstraint>;
+        let constraints = match alternatives.resolve(inst, self.func, self.reginfo) {
+            Ok(constraints) => constraints,
+            Err(_) => {
+                unresolved = self
+                    .func
+                    .inst_operands(inst)
+                    .iter()
+                    .map(|op| op.constraint())
+                    .collect();
+                &unresolved
+            }
+        };
+
+        // Only consider what occupies registers while `inst` is executing,
+        // not elsewhere in the live range of the virtual register.
+        let inst_range = LiveRangeSegment::new(
+            LiveRangePoint::new(inst, Slot::Boundary),
+            LiveRangePoint::new(inst.next(), Slot::Boundary),
+        );
+        let clip_segments = |vreg: VirtReg| -> Vec<ValueSegment> {
+            self.virt_regs
+                .segments(vreg)
+                .iter()
+                .filter_map(|segment| {
+                    let live_range = segment.live_range.intersection(inst_range)?;
+                    Some(ValueSegment {
+                        live_range,
+                        ..*segment
+                    })
+                })
+                .collect()
+        };
+
+        // Values which are not operands of `inst` are live across it.
+        let record_value =
+            |value: Value, operands: &mut Vec<usize>, live_values: &mut Vec<Value>| {
+                let len = operands.len();
+                operands.extend(operands_for_value(value));
+                if operands.len() == len {
+                    live_values.push(value);
+                }
+            };
+
+        // The values of the failing virtual register are always reported.
+        let mut operands = vec![];
+        let mut live_values = vec![];
+        let mut clobbers = vec![];
+        let mut fixed_units = vec![];
+        for vreg in vreg.vregs(self.virt_regs) {
+            for segment in clip_segments(vreg) {
+                record_value(segment.value, &mut operands, &mut live_values);
+            }
+        }
+
+        // Collect the occupants of each candidate register. Only occupants
+        // which can't be evicted are relevant, unless a candidate has none.
+        let mut occupants = vec![];
+        let mut blocked_by: Vec<Vec<usize>> = vec![];
+        for &cand in V::allocation_order(class, self.reginfo) {
+            let mut fixed = vec![];
+            let mut evictable = vec![];
+            for (vreg, reg) in vreg.zip_with_reg_group(cand, self.virt_regs, self.reginfo) {
+                let segments = clip_segments(vreg);
+                if segments.is_empty() {
+                    continue;
+                }
+                let _ = self.reg_matrix.check_interference(
+                    &segments,
+                    reg,
+                    self.reginfo,
+                    &mut Default::default(),
+                    true,
+                    |interference| {
+                        let (occupant, can_evict) = match interference.kind {
+                            InterferenceKind::VirtReg(other) => {
+                                // Only the segment of the other virtual
+                                // register which overlaps `inst` is relevant.
+                                let segment = self
+                                    .virt_regs
+                                    .segments(other)
+                                    .iter()
+                                    .find(|segment| {
+                                        segment
+                                            .live_range
+                                            .intersection(interference.range)
+                                            .is_some()
+                                    })
+                                    .unwrap();
+                                let can_evict = !self.virt_regs[other].spill_weight.is_infinite();
+                                (Occupant::Value(segment.value), can_evict)
+                            }
+                            InterferenceKind::Fixed => {
+                                let unit = interference.unit;
+                                let fixed_operand =
+                                    constraints.iter().position(|&constraint| match constraint {
+                                        OperandConstraint::Fixed(reg) => {
+                                            self.reginfo.reg_units(reg).any(|u| u == unit)
+                                        }
+                                        OperandConstraint::Class(_)
+                                        | OperandConstraint::Reuse(_)
+                                        | OperandConstraint::Alternatives(_) => false,
+                                    });
+                                let occupant = if let Some(idx) = fixed_operand {
+                                    Occupant::Operand(idx)
+                                } else if self.func.inst_clobbers(inst).any(|u| u == unit) {
+                                    Occupant::Clobber(unit)
+                                } else {
+                                    Occupant::FixedUnit(unit)
+                                };
+                                (occupant, false)
+                            }
+                        };
+                        let list = if can_evict {
+                            &mut evictable
+                        } else {
+                            &mut fixed
+                        };
+                        if !list.contains(&occupant) {
+                            list.push(occupant);
+                        }
+                        ControlFlow::<()>::Continue(())
+                    },
+                );
+            }
+            let list = if fixed.is_empty() { evictable } else { fixed };
+            blocked_by.push(
+                list.into_iter()
+                    .map(|occupant| {
+                        occupants
+                            .iter()
+                            .position(|&o| o == occupant)
+                            .unwrap_or_else(|| {
+                                occupants.push(occupant);
+                                occupants.len() - 1
+                            })
+                    })
+                    .collect(),
+            );
+        }
+        blocked_by.retain(|list| !list.is_empty());
+        let occupied = blocked_by.len();
+
+        // Greedily pick the occupant which blocks the most remaining
+        // candidates, then drop any picked occupant that turns out to be
+        // redundant.
+        let mut picked: Vec<usize> = vec![];
+        let mut remaining: Vec<&Vec<usize>> = blocked_by.iter().collect();
+        while !remaining.is_empty() {
+            let best = (0..occupants.len())
+                .max_by_key(|&occupant| {
+                    // Prefer the first occupant on ties.
+                    let count = remaining
+                        .iter()
+                        .filter(|list| list.contains(&occupant))
+                        .count();
+                    (count, usize::MAX - occupant)
+                })
+                .unwrap();
+            picked.push(best);
+            remaining.retain(|list| !list.contains(&best));
+        }
+        for i in (0..picked.len()).rev() {
+            let redundant = blocked_by.iter().all(|list| {
+                list.iter()
+                    .any(|occupant| *occupant != picked[i] && picked.contains(occupant))
+            });
+            if redundant {
+                picked.remove(i);
+            }
+        }
+
+        for &occupant in &picked {
+            match occupants[occupant] {
+                Occupant::Value(value) => record_value(value, &mut operands, &mut live_values),
+                Occupant::Operand(idx) => operands.push(idx),
+                Occupant::Clobber(unit) => clobbers.push(unit),
+                Occupant::FixedUnit(unit) => fixed_units.push(unit),
+            }
+        }
+        operands.sort_unstable();
+        operands.dedup();
+        live_values.sort_unstable();
+        live_values.dedup();
+        clobbers.sort_unstable();
+        clobbers.dedup();
+        fixed_units.sort_unstable();
+        fixed_units.dedup();
+
+        let err = RegAllocError::TooManyLiveRegs {
+            inst,
+            class,
+            operands,
+            live_values,
+            clobbers,
+            fixed_units,
+        };
+        (occupied, err)
+    }
+}
+
+/// Something which occupies a register while an instruction is executing,
+/// used to build `RegAllocError::TooManyLiveRegs`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Occupant {
+    /// A value in a virtual register.
+    Value(Value),
+
+    /// A fixed-register operand of the instruction.
+    Operand(usize),
+
+    /// A register unit clobbered by the instruction.
+    Clobber(RegUnit),
+
+    /// A register unit reserved by a neighboring fixed-register operand.
+    FixedUnit(RegUnit),
+}
@@ -20,10 +20,18 @@
 //!
 //! 3. If the virtual register's constraint allows it to be spilled to the stack
 //!    then do so if splitting is unprofitable.
+//!
+//! A much simpler linear-scan allocation loop is also available in the
+//! `linear` module for when compile time matters more than code quality.
 
+mod call;
 mod evict;
+mod linear;
+mod live_regs;
 mod order;
 mod queue;
+mod recolor;
+mod region;
 mod split;
 
 use alloc::vec;
@@ -31,8 +39,12 @@ use alloc::vec::Vec;
 use core::ops::ControlFlow;
 use core::{fmt, iter};
 
+use self::call::CallSplitter;
+use self::linear::LinearQueue;
 use self::order::{AllocationOrder, CandidateReg};
 use self::queue::{AllocationQueue, VirtRegOrGroup};
+use self::recolor::Recoloring;
+use self::region::RegionSplitter;
 use self::split::Splitter;
 use super::coalescing::Coalescing;
 use super::hints::Hints;
@@ -41,14 +53,14 @@ use super::reg_matrix::RegMatrix;
 use super::spill_allocator::SpillAllocator;
 use super::split_placement::SplitPlacement;
 use super::uses::Uses;
-use super::virt_regs::builder::VirtRegBuilder;
+use super::virt_regs::builder::{VirtRegBuilder, denormalize_spill_weight};
 use super::virt_regs::{VirtReg, VirtRegGroup, VirtRegs};
 use crate::entity::{EntityRef, PackedOption, SecondaryMap};
 use crate::function::Function;
 use crate::internal::reg_matrix::InterferenceKind;
 use crate::internal::value_live_ranges::ValueSet;
-use crate::reginfo::{PhysReg, RegClass, RegGroup, RegInfo};
-use crate::{Options, RegAllocError, Stats};
+use crate::reginfo::{PhysReg, PhysRegSet, RegClass, RegGroup, RegInfo};
+use crate::{AllocationAlgorithm, Options, RegAllocError, Stats};
 
 /// Abstraction over a virtual register group.
 ///
@@ -100,6 +112,9 @@ trait AbstractVirtRegGroup: Copy + fmt::Debug + fmt::Display + Into<VirtRegOrGro
     /// Returns the allocation order for `class`.
     fn allocation_order(class: RegClass, reginfo: &impl RegInfo) -> &[Self::Phys];
 
+    /// Iterates over the physical registers in a register or register group.
+    fn regs(reg: Self::Phys, reginfo: &impl RegInfo) -> impl Iterator<Item = PhysReg>;
+
     /// Selects the appropriate `AllocationOrder`.
     fn select_order<'a>(
         single: &'a mut AllocationOrder<VirtReg>,
@@ -152,6 +167,10 @@ impl AbstractVirtRegGroup for VirtReg {
         reginfo.allocation_order(class)
     }
 
+    fn regs(reg: PhysReg, _reginfo: &impl RegInfo) -> impl Iterator<Item = PhysReg> {
+        iter::once(reg)
+    }
+
     fn select_order<'a>(
         single: &'a mut AllocationOrder<VirtReg>,
         _multi: &'a mut AllocationOrder<VirtRegGroup>,
@@ -209,6 +228,10 @@ impl AbstractVirtRegGroup for VirtRegGroup {
         reginfo.group_allocation_order(class)
     }
 
+    fn regs(reg: RegGroup, reginfo: &impl RegInfo) -> impl Iterator<Item = PhysReg> {
+        reginfo.reg_group_members(reg).iter().copied()
+    }
+
     fn select_order<'a>(
         _single: &'a mut AllocationOrder<VirtReg>,
         multi: &'a mut AllocationOrder<VirtRegGroup>,
@@ -224,6 +247,7 @@ impl From<VirtRegGroup> for VirtRegOrGroup {
 }
 
 /// Assignments for each virtual register produced by this pass.
+#[derive(Clone, Copy)]
 enum Assignment {
     /// The virtual register has been assigned to a physical register.
     Assigned {
@@ -235,6 +259,14 @@ enum Assignment {
         /// per virtual register.
         evicted_for_preference: bool,
 
+        /// Whether this virtual register has evicted another unspillable virtual
+        /// register in the linear scan allocator because no register was
+        /// available.
+        ///
+        /// Like `evicted_for_preference`, this is only allowed once per
+        /// virtual register to avoid infinite eviction loops.
+        evicted_unspillable: bool,
+
         /// The physical register that this virtual register has been assigned to.
         reg: PhysReg,
 
@@ -257,6 +289,9 @@ enum Assignment {
         /// To avoid infinite eviction loops, we only allow this to happen once
         /// per virtual register.
         evicted_for_preference: bool,
+
+        /// Same as in `Assigned`, preserved across evictions.
+        evicted_unspillable: bool,
     },
 
     /// The virtual register is dead as a result of live range splitting: its
@@ -268,6 +303,7 @@ impl Default for Assignment {
     fn default() -> Self {
         Assignment::Unassigned {
             evicted_for_preference: false,
+            evicted_unspillable: false,
         }
     }
 }
@@ -283,16 +319,54 @@ impl Assignment {
         match *self {
             Assignment::Assigned {
                 evicted_for_preference,
+                evicted_unspillable: _,
                 reg: _,
                 preference_weight: _,
             } => evicted_for_preference,
             Assignment::Unassigned {
                 evicted_for_preference,
+                evicted_unspillable: _,
             } => evicted_for_preference,
             Assignment::Dead => false,
         }
     }
 
+    /// Whether this virtual register has evicted another unspillable virtual
+    /// register in the linear scan allocator.
+    ///
+    /// To avoid infinite eviction loops, we only allow this to happen once
+    /// per virtual register.
+    fn evicted_unspillable(&self) -> bool {
+        match *self {
+            Assignment::Assigned {
+                evicted_for_preference: _,
+                evicted_unspillable,
+                reg: _,
+                preference_weight: _,
+            } => evicted_unspillable,
+            Assignment::Unassigned {
+                evicted_for_preference: _,
+                evicted_unspillable,
+            } => evicted_unspillable,
+            Assignment::Dead => false,
+        }
+    }
+
+    /// Records that this virtual register has evicted an unspillable virtual
+    /// register.
+    ///
+    /// The assignment must currently be assigned.
+    fn set_evicted_unspillable(&mut self) {
+        match self {
+            Assignment::Assigned {
+                evicted_unspillable,
+                ..
+            } => *evicted_unspillable = true,
+            Assignment::Unassigned { .. } => unreachable!("unassigned virtual register"),
+            Assignment::Dead => unreachable!("dead virtual register"),
+        }
+    }
+
     /// Returns the preference weight associated with a virtual register.
     ///
     /// The assignment must currently be assigned.
@@ -300,6 +374,7 @@ impl Assignment {
         match *self {
             Assignment::Assigned {
                 evicted_for_preference: _,
+                evicted_unspillable: _,
                 reg: _,
                 preference_weight,
             } => preference_weight,
@@ -330,6 +405,9 @@ pub struct Allocator {
     /// Priority queue of virtual registers to allocate.
     queue: AllocationQueue,
 
+    /// Queue of virtual registers to allocate in linear-scan mode.
+    linear_queue: LinearQueue,
+
     /// Order in which to probe registers in a register class, taking hints and
     /// preferences into account.
     allocation_order: AllocationOrder<VirtReg>,
@@ -344,6 +422,11 @@ pub struct Allocator {
     /// other virtual registers in the set.
     last_allocated_reg: SecondaryMap<ValueSet, PackedOption<PhysReg>>,
 
+    /// Callee-saved registers which have not been used by any virtual register
+    /// or fixed reservation yet. The first assignment to one of these incurs
+    /// its callee-saved cost.
+    unclaimed_callee_saved: PhysRegSet,
+
     /// List of interfering virtual registers for `evict_interfering_vregs` to
     /// evict.
     interfering_vregs: Vec<VirtReg>,
@@ -355,6 +438,15 @@ pub struct Allocator {
     /// Temporary state used by live range splitting.
     splitter: Splitter,
 
+    /// Temporary state used by region splitting.
+    region_splitter: RegionSplitter,
+
+    /// Temporary state used by call-aware splitting.
+    call_splitter: CallSplitter,
+
+    /// Temporary state used by last-chance recoloring.
+    recoloring: Recoloring,
+
     /// Segments with an empty live range that are not part of a virtual
     /// register.
     ///
@@ -374,13 +466,18 @@ impl Allocator {
     pub fn new() -> Self {
         Self {
             queue: AllocationQueue::new(),
+            linear_queue: LinearQueue::new(),
             allocation_order: AllocationOrder::new(),
             group_allocation_order: AllocationOrder::new(),
             assignments: SecondaryMap::new(),
             last_allocated_reg: SecondaryMap::new(),
+            unclaimed_callee_saved: PhysRegSet::new(),
             interfering_vregs: vec![],
             candidate_interfering_vregs: vec![],
             splitter: Splitter::new(),
+            region_splitter: RegionSplitter::new(),
+            call_splitter: CallSplitter::new(),
+            recoloring: Recoloring::new(),
             empty_segments: vec![],
             remat_segments: vec![],
         }
@@ -411,6 +508,8 @@ impl Allocator {
         self.remat_segments.clear();
         self.allocation_order.prepare(reginfo);
         self.group_allocation_order.prepare(reginfo);
+        self.region_splitter.prepare(func);
+        self.call_splitter.prepare(func, options);
         let mut context = Context {
             func,
             reginfo,
@@ -427,16 +526,32 @@ impl Allocator {
             options,
         };
 
-        // Populate the queue with the initial set of virtual registers.
-        context.allocator.queue.init(context.virt_regs);
+        // Virtual registers for pinned values are assigned up front and never
+        // enter the allocation queue.
+        context.assign_pinned_vregs()?;
 
-        // Allocate each virtual register in priority order.
-        // TODO(perf): Optimize the case where we dequeue the same vreg twice in a row
-        while let Some((vreg, stage)) = context.allocator.queue.dequeue() {
-            match vreg {
-                VirtRegOrGroup::Reg(vreg) => context.allocate(vreg, stage)?,
-                VirtRegOrGroup::Group(group) => context.allocate(group, stage)?,
-            };
+        // This must be done after pinned virtual registers are reserved since
+        // those count as claiming their register.
+        context.collect_unclaimed_callee_saved();
+
+        match options.algorithm {
+            AllocationAlgorithm::Greedy => {
+                // Populate the queue with the initial set of virtual registers.
+                context
+                    .allocator
+                    .queue
+                    .init(context.virt_regs, &context.allocator.assignments);
+
+                // Allocate each virtual register in priority order.
+                // TODO(perf): Optimize the case where we dequeue the same vreg twice in a row
+                while let Some((vreg, stage)) = context.allocator.queue.dequeue() {
+                    match vreg {
+                        VirtRegOrGroup::Reg(vreg) => context.allocate(vreg, stage)?,
+                        VirtRegOrGroup::Group(group) => context.allocate(group, stage)?,
+                    };
+                }
+            }
+            AllocationAlgorithm::LinearScan => context.run_linear()?,
         }
 
         if trace_enabled!() {
@@ -491,6 +606,7 @@ impl Allocator {
             .filter_map(|(vreg, assignment)| match *assignment {
                 Assignment::Assigned {
                     evicted_for_preference: _,
+                    evicted_unspillable: _,
                     reg,
                     preference_weight: _,
                 } => Some((vreg, reg)),
@@ -517,6 +633,123 @@ struct Context<'a, F, R> {
 }
 
 impl<F: Function, R: RegInfo> Context<'_, F, R> {
+    /// Assigns virtual registers for values pinned to a physical register.
+    ///
+    /// These are reserved in the register matrix as fixed reservations so that
+    /// they can never be evicted by other virtual registers. Any existing
+    /// reservation that overlaps a pinned virtual register is an error since
+    /// the pinned value has nowhere else to go.
+    fn assign_pinned_vregs(&mut self) -> Result<(), RegAllocError> {
+        for vreg in self.virt_regs.virt_regs() {
+            let value = self.virt_regs.segments(vreg)[0].value;
+            let Some(reg) = self.func.value_pinned_reg(value) else {
+                continue;
+            };
+            trace!("Assigning pinned {vreg} to {reg}");
+            stat!(self.stats, pinned_vregs);
+
+            // Fixed uses of the pinned value itself may overlap the virtual
+            // register since they will be superseded by the reservation.
+            let conflict = self
+                .reg_matrix
+                .check_interference(
+                    self.virt_regs.segments(vreg),
+                    reg,
+                    self.reginfo,
+                    self.stats,
+                    false,
+                    |interference| {
+                        trace!(
+                            "-> Interference at {} in {}",
+                            interference.range, interference.unit
+                        );
+                        ControlFlow::Break(())
+                    },
+                )
+                .is_break();
+            if conflict {
+                return Err(RegAllocError::PinnedRegConflict { value, reg });
+            }
+
+            self.reg_matrix
+                .reserve_pinned(vreg, reg, self.virt_regs, self.reginfo);
+            self.allocator.assignments[vreg] = Assignment::Assigned {
+                evicted_for_preference: false,
+                evicted_unspillable: false,
+                reg,
+                preference_weight: 0.0,
+            };
+            let set = self.virt_regs[vreg].value_set;
+            self.allocator.last_allocated_reg[set] = Some(reg).into();
+        }
+
+        Ok(())
+    }
+
+    /// Collects the set of callee-saved registers which are not already
+    /// written by a fixed def, clobber or pinned value.
+    ///
+    /// Claiming these registers is free since the client needs to save and
+    /// restore them anyways.
+    fn collect_unclaimed_callee_saved(&mut self) {
+        self.allocator.unclaimed_callee_saved = self
+            .reginfo
+            .regs()
+            .filter(|&reg| {
+                self.reginfo.bank_for_reg(reg).is_some()
+                    && self.reginfo.callee_saved_cost(reg) != 0.0
+                    && !self.reg_matrix.has_fixed_def(reg, self.reginfo)
+            })
+            .collect();
+        trace!(
+            "Unclaimed callee-saved registers: {:?}",
+            self.allocator.unclaimed_callee_saved
+        );
+    }
+
+    /// Returns the absolute cost of spilling a virtual register, which is the
+    /// sum of the spill costs of its uses weighted by block frequency.
+    fn spill_cost(&self, vreg: VirtReg) -> f32 {
+        let mut spill_cost = 0.0;
+        for segment in self.virt_regs.segments(vreg) {
+            for &u in &self.uses[segment.use_list] {
+                let block_freq = self.func.block_frequency(self.func.inst_block(u.pos));
+                spill_cost += u.spill_cost(segment.value, self.func, self.reginfo) * block_freq;
+            }
+        }
+        spill_cost
+    }
+
+    /// Returns the maximum callee-saved cost that `vreg` may pay to claim an
+    /// unclaimed callee-saved register.
+    ///
+    /// This is the cost of spilling the virtual register: it is cheaper to
+    /// spill than to save and restore a register that is more expensive than
+    /// that. Unspillable virtual registers may claim any register.
+    ///
+    /// The spill cost is derived from the spill weight instead of walking all
+    /// uses, since this is called every time a virtual register is dequeued.
+    /// Members of a group share the lowest spill weight in the group, so this
+    /// underestimates the spill cost of groups.
+    fn max_callee_saved_cost<V: AbstractVirtRegGroup>(&self, vreg: V) -> f32 {
+        if self.allocator.unclaimed_callee_saved.is_empty()
+            || self.virt_regs[vreg.first_vreg(self.virt_regs)]
+                .spill_weight
+                .is_infinite()
+        {
+            return f32::INFINITY;
+        }
+        vreg.vregs(self.virt_regs)
+            .map(|vreg| {
+                denormalize_spill_weight(
+                    self.virt_regs[vreg].spill_weight,
+                    ValueSegment::live_insts(self.virt_regs.segments(vreg)),
+                    self.options,
+                )
+            })
+            .sum()
+    }
+
     /// Attempts to assign the given virtual register to a physical register.
     fn allocate<V: AbstractVirtRegGroup>(
         &mut self,
@@ -533,6 +766,7 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
         }
 
         // Determine the order in which to probe for available registers.
+        let max_callee_saved_cost = self.max_callee_saved_cost(vreg);
         let order = V::select_order(
             &mut self.allocator.allocation_order,
             &mut self.allocator.group_allocation_order,
@@ -542,6 +776,8 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
             self.virt_regs,
             self.hints,
             &self.allocator.last_allocated_reg,
+            self.allocator.unclaimed_callee_saved,
+            max_callee_saved_cost,
             self.reginfo,
         );
         if trace_enabled!() {
@@ -619,6 +855,12 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                     .spill_weight
                     .is_infinite()
                 {
+                    // As a last resort, try to move other unspillable virtual
+                    // registers out of the way.
+                    if self.try_last_chance_recoloring(vreg) {
+                        return Ok(());
+                    }
+
                     trace!("Allocation failed: could not allocate unspillable {vreg}");
 
                     if trace_enabled!() {
@@ -630,7 +872,7 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                             trace!("  {vreg} -> {reg}");
                         }
                     }
-                    return Err(RegAllocError::TooManyLiveRegs);
+                    return Err(self.too_many_live_regs_error(vreg));
                 }
 
                 // If we failed to evict, re-queue for splitting after all
@@ -746,6 +988,7 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                 evicted_for_preference || self.allocator.assignments[vreg].evicted_for_preference();
             self.allocator.assignments[vreg] = Assignment::Assigned {
                 evicted_for_preference,
+                evicted_unspillable: self.allocator.assignments[vreg].evicted_unspillable(),
                 reg,
                 preference_weight: candidate.preference_weight,
             };
@@ -754,6 +997,26 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
 
             let set = self.virt_regs[vreg].value_set;
             self.allocator.last_allocated_reg[set] = Some(reg).into();
+
+            // The client will need to save and restore any callee-saved
+            // register overlapping this one, but further uses are then free.
+            //
+            // Claims are never revoked, even if the virtual register is later
+            // evicted. This slightly overestimates the set of callee-saved
+            // registers in use, but keeps allocation decisions monotonic.
+            if !self.allocator.unclaimed_callee_saved.is_empty() {
+                for csr in self.allocator.unclaimed_callee_saved {
+                    if self
+                        .reginfo
+                        .reg_units(csr)
+                        .any(|unit| self.reginfo.reg_units(reg).any(|u| u == unit))
+                    {
+                        trace!("Claiming callee-saved {csr}");
+                        stat!(self.stats, claimed_callee_saved);
+                        self.allocator.unclaimed_callee_saved.remove(csr);
+                    }
+                }
+            }
         }
     }
 }
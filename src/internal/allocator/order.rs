@@ -5,6 +5,8 @@
 //! - If the virtual register has fixed-register uses, we want to try those
 //!   first. Give more priority to more frequent uses.
 //! - Otherwise defer to the register class for its allocation order.
+//! - Callee-saved registers that haven't been claimed yet come last, and are
+//!   only included if claiming them is cheaper than spilling.
 
 use core::cmp::Ordering;
 use core::fmt;
@@ -14,7 +16,7 @@ use crate::entity::{PackedOption, SecondaryMap, SparseMap};
 use crate::internal::hints::Hints;
 use crate::internal::value_live_ranges::ValueSet;
 use crate::internal::virt_regs::{VirtReg, VirtRegs};
-use crate::reginfo::{PhysReg, RegClass, RegInfo};
+use crate::reginfo::{PhysReg, PhysRegSet, RegClass, RegInfo};
 
 /// A candidate physical register to which a virtual register can be assigned.
 #[derive(Debug, Clone, Copy)]
@@ -45,12 +47,21 @@ pub struct AllocationOrder<V: AbstractVirtRegGroup> {
     ///
     /// Entries are sorted by preference weight.
     hinted_regs: SparseMap<V::Phys, f32>,
+
+    /// Callee-saved registers which have not been claimed yet.
+    unclaimed_callee_saved: PhysRegSet,
+
+    /// Maximum callee-saved cost that the current virtual register is willing
+    /// to pay to claim a register.
+    max_callee_saved_cost: f32,
 }
 
 impl<V: AbstractVirtRegGroup> AllocationOrder<V> {
     pub fn new() -> Self {
         Self {
             hinted_regs: SparseMap::new(),
+            unclaimed_callee_saved: PhysRegSet::new(),
+            max_callee_saved_cost: f32::INFINITY,
         }
     }
 
@@ -69,8 +80,13 @@ impl<V: AbstractVirtRegGroup> AllocationOrder<V> {
         virt_regs: &VirtRegs,
         hints: &Hints,
         last_allocated_reg: &SecondaryMap<ValueSet, PackedOption<PhysReg>>,
+        unclaimed_callee_saved: PhysRegSet,
+        max_callee_saved_cost: f32,
         reginfo: &impl RegInfo,
     ) {
+        self.unclaimed_callee_saved = unclaimed_callee_saved;
+        self.max_callee_saved_cost = max_callee_saved_cost;
+
         // If this virtual register has fixed-register constraints, collect them
         // and assign them weights based on their use frequency.
         self.hinted_regs.clear();
@@ -114,6 +130,34 @@ impl<V: AbstractVirtRegGroup> AllocationOrder<V> {
                 }
             }
         }
+
+        // Drop any hinted registers that are too expensive to claim.
+        if !self.unclaimed_callee_saved.is_empty() {
+            self.hinted_regs.retain(|reg, _| {
+                Self::claim_cost(self.unclaimed_callee_saved, reg, reginfo)
+                    <= self.max_callee_saved_cost
+            });
+        }
+    }
+
+    /// Returns the callee-saved cost that must be paid to claim `reg` for the
+    /// current virtual register.
+    ///
+    /// Eviction and splitting add this to their own costs when comparing
+    /// candidates from the allocation order.
+    pub fn reg_claim_cost(&self, reg: V::Phys, reginfo: &impl RegInfo) -> f32 {
+        if self.unclaimed_callee_saved.is_empty() {
+            return 0.0;
+        }
+        Self::claim_cost(self.unclaimed_callee_saved, reg, reginfo)
+    }
+
+    /// Returns the callee-saved cost that must be paid to claim `reg`.
+    fn claim_cost(unclaimed_callee_saved: PhysRegSet, reg: V::Phys, reginfo: &impl RegInfo) -> f32 {
+        V::regs(reg, reginfo)
+            .filter(|&reg| unclaimed_callee_saved.contains(reg))
+            .map(|reg| reginfo.callee_saved_cost(reg))
+            .sum()
     }
 
     /// Returns an iterator over all the registers in the allocation order.
@@ -124,24 +168,40 @@ impl<V: AbstractVirtRegGroup> AllocationOrder<V> {
         reginfo: &'a impl RegInfo,
     ) -> impl Iterator<Item = CandidateReg<V>> + 'a {
         let class = virt_regs[vreg.first_vreg(virt_regs)].class;
+        let not_hinted = |reg: V::Phys| {
+            // Fast path if there are no hinted registers.
+            self.hinted_regs.is_empty() || !self.hinted_regs.contains_key(reg)
+        };
+
+        // Registers which are free to claim come first, followed by
+        // callee-saved registers which are cheap enough to claim.
+        let claim_cost =
+            move |reg: V::Phys| Self::claim_cost(self.unclaimed_callee_saved, reg, reginfo);
+        let has_unclaimed = !self.unclaimed_callee_saved.is_empty();
+        let free_regs = V::allocation_order(class, reginfo)
+            .iter()
+            .copied()
+            .filter(move |&reg| not_hinted(reg) && (!has_unclaimed || claim_cost(reg) == 0.0));
+        let callee_saved_regs =
+            V::allocation_order(class, reginfo)
+                .iter()
+                .copied()
+                .filter(move |&reg| {
+                    has_unclaimed && not_hinted(reg) && {
+                        let cost = claim_cost(reg);
+                        cost != 0.0 && cost <= self.max_callee_saved_cost
+                    }
+                });
         self.hinted_regs
             .iter()
             .map(|&(reg, preference_weight)| CandidateReg {
                 reg,
                 preference_weight,
             })
-            .chain(
-                V::allocation_order(class, reginfo)
-                    .iter()
-                    .filter(|&&reg| {
-                        // Fast path if there are no hinted registers.
-                        self.hinted_regs.is_empty() || !self.hinted_regs.contains_key(reg)
-                    })
-                    .map(|&reg| CandidateReg {
-                        reg,
-                        preference_weight: 0.0,
-                    }),
-            )
+            .chain(free_regs.chain(callee_saved_regs).map(|reg| CandidateReg {
+                reg,
+                preference_weight: 0.0,
+            }))
     }
 
     /// Returns the subset of the allocation order that comes from hints rather
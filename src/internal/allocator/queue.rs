@@ -6,7 +6,8 @@
 use alloc::collections::BinaryHeap;
 use core::{fmt, mem};
 
-use super::Stage;
+use super::{Assignment, Stage};
+use crate::entity::SecondaryMap;
 use crate::internal::live_range::ValueSegment;
 use crate::internal::virt_regs::{VirtReg, VirtRegGroup, VirtRegs};
 use crate::reginfo::MAX_GROUP_SIZE;
@@ -129,7 +130,10 @@ impl AllocationQueue {
 
     /// Initializes the allocation queue from the set of existing virtual
     /// register and virtual register groups.
-    pub fn init(&mut self, virt_regs: &VirtRegs) {
+    ///
+    /// Virtual registers which are already assigned (because their value is
+    /// pinned to a register) are skipped.
+    pub fn init(&mut self, virt_regs: &VirtRegs, assignments: &SecondaryMap<VirtReg, Assignment>) {
         let mut vec = mem::take(&mut self.queue).into_vec();
         vec.clear();
 
@@ -137,7 +141,10 @@ impl AllocationQueue {
         vec.extend(
             virt_regs
                 .virt_regs()
-                .filter(|&vreg| virt_regs[vreg].group.is_none())
+                .filter(|&vreg| {
+                    virt_regs[vreg].group.is_none()
+                        && matches!(assignments[vreg], Assignment::Unassigned { .. })
+                })
                 .map(|vreg| Entry::encode(vreg, Stage::Evict, virt_regs)),
         );
 
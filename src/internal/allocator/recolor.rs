@@ -0,0 +1,367 @@
+//! Last-chance recoloring.
+//!
+//! This is the last thing attempted before giving up on an unspillable virtual
+//! register that could neither find a free register nor evict the interference
+//! in any of its candidate registers. It is similar to the last-chance
+//! recoloring in LLVM's greedy register allocator.
+//!
+//! For each candidate register, the interfering virtual registers are evicted
+//! and the virtual register is tentatively assigned to the candidate. Spillable
+//! evictees are simply re-queued, but unspillable evictees must immediately be
+//! moved to another register, recursively recoloring their own interference if
+//! needed up to `Options::recoloring_depth`.
+//!
+//! Virtual registers that have been assigned as part of the current recoloring
+//! attempt are locked and can't be evicted again until the whole attempt
+//! finishes. If an attempt fails then all changes made by it are rolled back.
+//!
+//! Like in LLVM, the total number of virtual registers visited by a single
+//! attempt is bounded, since otherwise the search is exponential in the
+//! recursion depth.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::ControlFlow;
+use core::{mem, slice};
+
+use super::order::CandidateReg;
+use super::queue::VirtRegOrGroup;
+use super::{AbstractVirtRegGroup, Assignment, Context, Stage};
+use crate::AllocationAlgorithm;
+use crate::function::Function;
+use crate::internal::reg_matrix::InterferenceKind;
+use crate::internal::virt_regs::VirtReg;
+use crate::reginfo::RegInfo;
+
+/// Maximum number of unspillable virtual registers that may be evicted from a
+/// single candidate register.
+///
+/// This bounds the branching factor of the recursive search.
+const MAX_RECOLOR_INTERFERENCE: usize = 4;
+
+/// Maximum number of virtual registers that may be visited by a single
+/// last-chance recoloring attempt, across all recursion levels.
+const MAX_RECOLOR_VISITED: usize = 64;
+
+/// Temporary state used by last-chance recoloring.
+pub struct Recoloring {
+    /// Previous assignment of every virtual register modified by the current
+    /// recoloring attempt, in order of modification.
+    log: Vec<(VirtReg, Assignment)>,
+
+    /// Virtual registers which have been assigned as part of the current
+    /// recoloring attempt and must not be evicted.
+    locked: Vec<VirtReg>,
+
+    /// Number of virtual registers visited by the current recoloring attempt.
+    visited: usize,
+}
+
+impl Recoloring {
+    pub fn new() -> Self {
+        Self {
+            log: vec![],
+            locked: vec![],
+            visited: 0,
+        }
+    }
+}
+
+impl<F: Function, R: RegInfo> Context<'_, F, R> {
+    /// Attempts to allocate an unspillable virtual register by moving
+    /// interfering unspillable virtual registers to other registers.
+    ///
+    /// Returns `false` without making any changes if this is not possible.
+    pub(super) fn try_last_chance_recoloring<V: AbstractVirtRegGroup>(&mut self, vreg: V) -> bool {
+        if self.options.recoloring_depth == 0 {
+            return false;
+        }
+
+        trace!("Attempting last-chance recoloring for {vreg}");
+        stat!(self.stats, try_recoloring);
+        self.allocator.recoloring.log.clear();
+        self.allocator.recoloring.locked.clear();
+        self.allocator.recoloring.visited = 1;
+        let success = self.recolor(vreg, 0);
+        self.allocator.recoloring.locked.clear();
+        if !success {
+            trace!("-> Recoloring failed");
+            return false;
+        }
+
+        // Spillable virtual registers that were evicted along the way need to
+        // go back into the queue. A virtual register may appear in the log
+        // multiple times but must only be queued once.
+        let mut log = mem::take(&mut self.allocator.recoloring.log);
+        log.sort_unstable_by_key(|&(vreg, _)| vreg);
+        log.dedup_by_key(|&mut (vreg, _)| vreg);
+        for &(vreg, _) in &log {
+            if !matches!(
+                self.allocator.assignments[vreg],
+                Assignment::Unassigned { .. }
+            ) {
+                continue;
+            }
+            let vreg_or_group = match self.virt_regs[vreg].group.expand() {
+                Some(group) => {
+                    if self.virt_regs[vreg].group_index != 0 {
+                        continue;
+                    }
+                    VirtRegOrGroup::Group(group)
+                }
+                None => VirtRegOrGroup::Reg(vreg),
+            };
+            trace!("Re-queuing {vreg_or_group} evicted by recoloring");
+            match self.options.algorithm {
+                AllocationAlgorithm::Greedy => {
+                    self.allocator
+                        .queue
+                        .enqueue(vreg_or_group, Stage::Evict, self.virt_regs);
+                }
+                AllocationAlgorithm::LinearScan => {
+                    self.allocator
+                        .linear_queue
+                        .enqueue(vreg_or_group, self.virt_regs);
+                }
+            }
+        }
+        log.clear();
+        self.allocator.recoloring.log = log;
+        true
+    }
+
+    /// Recursive part of last-chance recoloring.
+    ///
+    /// On success, `vreg` is assigned to a register and all virtual registers
+    /// placed along the way remain locked. On failure, all changes are rolled
+    /// back.
+    fn recolor<V: AbstractVirtRegGroup>(&mut self, vreg: V, depth: u32) -> bool {
+        trace!("Recoloring {vreg} at depth {depth}");
+
+        // The allocation order is shared scratch space which is overwritten by
+        // recursive calls, so take a copy of the candidates.
+        let max_callee_saved_cost = self.max_callee_saved_cost(vreg);
+        let order = V::select_order(
+            &mut self.allocator.allocation_order,
+            &mut self.allocator.group_allocation_order,
+        );
+        order.compute(
+            vreg,
+            self.virt_regs,
+            self.hints,
+            &self.allocator.last_allocated_reg,
+            self.allocator.unclaimed_callee_saved,
+            max_callee_saved_cost,
+            self.reginfo,
+        );
+        let candidates: Vec<CandidateReg<V>> =
+            order.order(vreg, self.virt_regs, self.reginfo).collect();
+
+        let mut unspillable = Vec::new();
+        for candidate in candidates {
+            trace!("Trying recoloring candidate {candidate}");
+            if !self.collect_recolor_interference(vreg, candidate) {
+                continue;
+            }
+
+            let mark = self.allocator.recoloring.log.len();
+            let locked_mark = self.allocator.recoloring.locked.len();
+            self.evict_for_recoloring(&mut unspillable);
+            self.assign_for_recoloring(vreg, candidate);
+
+            // Every unspillable evictee must find a new register.
+            let success = unspillable.iter().all(|&evictee| match evictee {
+                VirtRegOrGroup::Reg(evictee) => self.reassign_for_recoloring(evictee, depth),
+                VirtRegOrGroup::Group(evictee) => self.reassign_for_recoloring(evictee, depth),
+            });
+            if success {
+                trace!("-> Recolored {vreg} into {candidate}");
+                stat!(self.stats, recolored_vregs);
+                return true;
+            }
+
+            trace!("-> Rolling back recoloring of {vreg} into {candidate}");
+            self.rollback_recoloring(mark);
+            self.allocator.recoloring.locked.truncate(locked_mark);
+            if self.allocator.recoloring.visited >= MAX_RECOLOR_VISITED {
+                break;
+            }
+        }
+
+        false
+    }
+
+    /// Collects the virtual registers interfering with `vreg` in `candidate`
+    /// into `interfering_vregs`.
+    ///
+    /// Returns `false` if the interference can't be evicted.
+    fn collect_recolor_interference<V: AbstractVirtRegGroup>(
+        &mut self,
+        vreg: V,
+        candidate: CandidateReg<V>,
+    ) -> bool {
+        let mut num_unspillable = 0;
+        self.allocator.interfering_vregs.clear();
+        for (vreg, reg) in vreg.zip_with_reg_group(candidate.reg, self.virt_regs, self.reginfo) {
+            let result = self.reg_matrix.check_interference(
+                self.virt_regs.segments(vreg),
+                reg,
+                self.reginfo,
+                self.stats,
+                false,
+                |interference| {
+                    // Can't evict fixed interference.
+                    let InterferenceKind::VirtReg(interfering_vreg) = interference.kind else {
+                        return ControlFlow::Break(());
+                    };
+                    if self.allocator.interfering_vregs.contains(&interfering_vreg) {
+                        return ControlFlow::Continue(());
+                    }
+
+                    // Can't evict virtual registers that were placed by this
+                    // recoloring attempt.
+                    if self.allocator.recoloring.locked.contains(&interfering_vreg) {
+                        return ControlFlow::Break(());
+                    }
+
+                    if self.virt_regs[interfering_vreg].spill_weight.is_infinite() {
+                        num_unspillable += 1;
+                        if num_unspillable > MAX_RECOLOR_INTERFERENCE {
+                            return ControlFlow::Break(());
+                        }
+                    }
+                    self.allocator.interfering_vregs.push(interfering_vreg);
+                    ControlFlow::Continue(())
+                },
+            );
+            if result.is_break() {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Evicts all the virtual registers in `interfering_vregs`, recording
+    /// their previous assignment in the log.
+    ///
+    /// Unspillable evictees are returned in `unspillable`.
+    fn evict_for_recoloring(&mut self, unspillable: &mut Vec<VirtRegOrGroup>) {
+        unspillable.clear();
+        while let Some(vreg) = self.allocator.interfering_vregs.pop() {
+            if !matches!(
+                self.allocator.assignments[vreg],
+                Assignment::Assigned { .. }
+            ) {
+                continue;
+            }
+
+            // All members of a register group need to be evicted together.
+            let vreg_or_group = match self.virt_regs[vreg].group.expand() {
+                Some(group) => VirtRegOrGroup::Group(group),
+                None => VirtRegOrGroup::Reg(vreg),
+            };
+            let members = match vreg_or_group {
+                VirtRegOrGroup::Reg(_) => slice::from_ref(&vreg),
+                VirtRegOrGroup::Group(group) => self.virt_regs.group_members(group),
+            };
+            for &vreg in members {
+                let assignment = self.allocator.assignments[vreg];
+                let Assignment::Assigned {
+                    evicted_for_preference,
+                    evicted_unspillable,
+                    reg,
+                    preference_weight: _,
+                } = assignment
+                else {
+                    unreachable!();
+                };
+                trace!("Evicting {vreg} for recoloring");
+                self.allocator.recoloring.log.push((vreg, assignment));
+                self.allocator.assignments[vreg] = Assignment::Unassigned {
+                    evicted_for_preference,
+                    evicted_unspillable,
+                };
+                self.reg_matrix
+                    .evict(vreg, reg, self.virt_regs, self.reginfo);
+            }
+
+            if self.virt_regs[vreg].spill_weight.is_infinite() {
+                unspillable.push(vreg_or_group);
+            }
+        }
+    }
+
+    /// Assigns `vreg` to the given candidate, recording its previous
+    /// assignment in the log and locking it.
+    fn assign_for_recoloring<V: AbstractVirtRegGroup>(
+        &mut self,
+        vreg: V,
+        candidate: CandidateReg<V>,
+    ) {
+        for member in vreg.vregs(self.virt_regs) {
+            self.allocator
+                .recoloring
+                .log
+                .push((member, self.allocator.assignments[member]));
+            self.allocator.recoloring.locked.push(member);
+        }
+        self.assign(vreg, candidate, false);
+    }
+
+    /// Counts a virtual register towards the budget of the current recoloring
+    /// attempt.
+    ///
+    /// Returns `false` if the budget is exhausted.
+    fn visit_for_recoloring(&mut self) -> bool {
+        if self.allocator.recoloring.visited >= MAX_RECOLOR_VISITED {
+            trace!("-> Recoloring budget exhausted");
+            stat!(self.stats, recoloring_budget_exhausted);
+            return false;
+        }
+        self.allocator.recoloring.visited += 1;
+        true
+    }
+
+    /// Finds a new register for an unspillable virtual register which was
+    /// evicted by recoloring, recursing if there is no free register.
+    fn reassign_for_recoloring<V: AbstractVirtRegGroup>(&mut self, vreg: V, depth: u32) -> bool {
+        if !self.visit_for_recoloring() {
+            return false;
+        }
+        let max_callee_saved_cost = self.max_callee_saved_cost(vreg);
+        let order = V::select_order(
+            &mut self.allocator.allocation_order,
+            &mut self.allocator.group_allocation_order,
+        );
+        order.compute(
+            vreg,
+            self.virt_regs,
+            self.hints,
+            &self.allocator.last_allocated_reg,
+            self.allocator.unclaimed_callee_saved,
+            max_callee_saved_cost,
+            self.reginfo,
+        );
+        if let Some(candidate) = self.find_available_reg(vreg) {
+            self.assign_for_recoloring(vreg, candidate);
+            return true;
+        }
+        depth + 1 < self.options.recoloring_depth && self.recolor(vreg, depth + 1)
+    }
+
+    /// Undoes all changes made since the log had `mark` entries.
+    fn rollback_recoloring(&mut self, mark: usize) {
+        while self.allocator.recoloring.log.len() > mark {
+            let (vreg, assignment) = self.allocator.recoloring.log.pop().unwrap();
+            if let Assignment::Assigned { reg, .. } = self.allocator.assignments[vreg] {
+                self.reg_matrix
+                    .evict(vreg, reg, self.virt_regs, self.reginfo);
+            }
+            if let Assignment::Assigned { reg, .. } = assignment {
+                self.reg_matrix
+                    .assign(vreg, reg, self.virt_regs, self.reginfo);
+            }
+            self.allocator.assignments[vreg] = assignment;
+        }
+    }
+}
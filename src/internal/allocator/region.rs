@@ -0,0 +1,412 @@
+//! Region-based live range splitting.
+//!
+//! This is used by `SplitStrategy::Region`. It is similar to the region
+//! splitting with spill placement used by LLVM's greedy register allocator.
+//!
+//! For each candidate physical register, the blocks in which a virtual register
+//! is live are divided into those in which the register is free and those in
+//! which it has interference. We then select a region of free blocks in which
+//! the virtual register will keep the candidate register:
+//! - Free blocks containing uses are initially included in the region.
+//! - Free blocks are then iteratively added to or removed from the region,
+//!   depending on whether the weight of their uses and the frequency of the CFG
+//!   edges connecting them to the region outweigh the frequency of the edges
+//!   connecting them to blocks outside the region.
+//!
+//! The virtual register is then split at every block boundary where it enters
+//! or leaves the region, which places the split points on the CFG edges with
+//! the lowest block frequency independently of the linear order of blocks.
+//!
+//! The region becomes a new virtual register which can be assigned to the
+//! candidate register without interference. The rest of the live range is
+//! re-queued and may be split further.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::ControlFlow;
+
+use super::{Assignment, Context};
+use crate::entity::SecondaryMap;
+use crate::function::{Block, Function, Value};
+use crate::internal::live_range::{LiveRangeSegment, Slot, ValueSegment};
+use crate::internal::reg_matrix::InterferenceSegment;
+use crate::internal::virt_regs::VirtReg;
+use crate::reginfo::{PhysReg, RegInfo};
+
+/// Maximum number of iterations when refining a region.
+///
+/// Each iteration updates all blocks at once so the region may not always
+/// converge, but any set of free blocks is a valid region.
+const MAX_REGION_ITERATIONS: usize = 8;
+
+/// A block in which the virtual register being split is live.
+struct LiveBlock {
+    block: Block,
+
+    /// Whether the virtual register is live at the start of the block.
+    live_in: bool,
+
+    /// Whether the virtual register is live at the end of the block.
+    live_out: bool,
+
+    /// Total spill cost of the uses in this block, weighed by block frequency.
+    use_weight: f32,
+
+    /// Whether the candidate register has no interference in this block.
+    free: bool,
+
+    /// Whether this block is currently part of the region.
+    in_region: bool,
+
+    /// Scratch space for computing the benefit of including this block in the
+    /// region.
+    bias: f32,
+}
+
+/// Part of the live range of the virtual register within a single block.
+#[derive(Clone, Copy, Debug)]
+struct BlockSegment {
+    live_range: LiveRangeSegment,
+    value: Value,
+
+    /// Index of the `LiveBlock` containing this segment.
+    block_idx: u32,
+}
+
+impl InterferenceSegment for BlockSegment {
+    fn live_range(&self) -> LiveRangeSegment {
+        self.live_range
+    }
+
+    fn value(&self) -> Value {
+        self.value
+    }
+}
+
+/// CFG edge along which the virtual register is live.
+struct Link {
+    from: u32,
+    to: u32,
+
+    /// Cost of splitting on this edge.
+    freq: f32,
+}
+
+/// Temporary state used for region splitting.
+pub struct RegionSplitter {
+    /// Blocks in which the virtual register is live, in linear order.
+    blocks: Vec<LiveBlock>,
+
+    /// Index of each block in `blocks`.
+    block_map: SecondaryMap<Block, Option<u32>>,
+
+    /// Live range of the virtual register split at block boundaries.
+    block_segments: Vec<BlockSegment>,
+
+    /// CFG edges between live blocks.
+    links: Vec<Link>,
+
+    /// Region of the best candidate found so far.
+    best_region: Vec<bool>,
+
+    /// Segments for the region and for the rest of the live range.
+    region_segments: Vec<ValueSegment>,
+    other_segments: Vec<ValueSegment>,
+}
+
+impl RegionSplitter {
+    pub fn new() -> Self {
+        Self {
+            blocks: vec![],
+            block_map: SecondaryMap::new(),
+            block_segments: vec![],
+            links: vec![],
+            best_region: vec![],
+            region_segments: vec![],
+            other_segments: vec![],
+        }
+    }
+
+    /// Prepares the block map for the given function.
+    pub fn prepare(&mut self, func: &impl Function) {
+        self.blocks.clear();
+        self.block_map.clear_and_resize(func.num_blocks());
+    }
+
+    /// Selects the region for the current candidate register and returns its
+    /// score, or `None` if the region is not usable.
+    fn select_region(&mut self) -> Option<f32> {
+        for block in &mut self.blocks {
+            block.in_region = block.free && block.use_weight > 0.0;
+        }
+        for _ in 0..MAX_REGION_ITERATIONS {
+            for block in &mut self.blocks {
+                block.bias = block.use_weight;
+            }
+            for link in &self.links {
+                let (from, to) = (link.from as usize, link.to as usize);
+                let to_in_region = self.blocks[to].in_region;
+                let from_in_region = self.blocks[from].in_region;
+                self.blocks[from].bias += if to_in_region { link.freq } else { -link.freq };
+                self.blocks[to].bias += if from_in_region {
+                    link.freq
+                } else {
+                    -link.freq
+                };
+            }
+            let mut changed = false;
+            for block in &mut self.blocks {
+                let in_region = block.free && block.bias > 0.0;
+                changed |= in_region != block.in_region;
+                block.in_region = in_region;
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        // The region must be a strict subset of the live range for the split
+        // to make progress.
+        if self.blocks.iter().all(|block| block.in_region)
+            || !self.blocks.iter().any(|block| block.in_region)
+        {
+            return None;
+        }
+
+        let mut score: f32 = self
+            .blocks
+            .iter()
+            .filter(|block| block.in_region)
+            .map(|block| block.use_weight)
+            .sum();
+        for link in &self.links {
+            if self.blocks[link.from as usize].in_region != self.blocks[link.to as usize].in_region
+            {
+                score -= link.freq;
+            }
+        }
+        Some(score)
+    }
+}
+
+impl<F: Function, R: RegInfo> Context<'_, F, R> {
+    /// Collects the blocks in which the virtual register is live and the CFG
+    /// edges between them.
+    fn collect_live_blocks(&mut self, vreg: VirtReg) {
+        let rs = &mut self.allocator.region_splitter;
+        for block in &rs.blocks {
+            rs.block_map[block.block] = None;
+        }
+        rs.blocks.clear();
+        rs.block_segments.clear();
+        rs.links.clear();
+
+        for segment in self.virt_regs.segments(vreg) {
+            let mut from = segment.live_range.from;
+            let mut block = self.func.inst_block(from.inst());
+            loop {
+                let block_insts = self.func.block_insts(block);
+                let to = segment
+                    .live_range
+                    .to
+                    .min(block_insts.to.slot(Slot::Boundary));
+                let block_idx = *rs.block_map[block].get_or_insert_with(|| {
+                    rs.blocks.push(LiveBlock {
+                        block,
+                        live_in: false,
+                        live_out: false,
+                        use_weight: 0.0,
+                        free: true,
+                        in_region: false,
+                        bias: 0.0,
+                    });
+                    rs.blocks.len() as u32 - 1
+                });
+                let live_block = &mut rs.blocks[block_idx as usize];
+                live_block.live_in |= from == block_insts.from.slot(Slot::Boundary);
+                live_block.live_out |= to == block_insts.to.slot(Slot::Boundary);
+                rs.block_segments.push(BlockSegment {
+                    live_range: LiveRangeSegment::new(from, to),
+                    value: segment.value,
+                    block_idx,
+                });
+                if to == segment.live_range.to {
+                    break;
+                }
+                from = to;
+                block = block.next();
+            }
+
+            for u in &self.uses[segment.use_list] {
+                let block = self.func.inst_block(u.pos);
+                if let Some(block_idx) = rs.block_map[block] {
+                    rs.blocks[block_idx as usize].use_weight +=
+                        u.spill_cost(segment.value, self.func, self.reginfo)
+                            * self.func.block_frequency(block);
+                }
+            }
+        }
+
+        for (from, block) in rs.blocks.iter().enumerate() {
+            if !block.live_out {
+                continue;
+            }
+            let succs = self.func.block_succs(block.block);
+            for &succ in succs {
+                let Some(to) = rs.block_map[succ] else {
+                    continue;
+                };
+                if !rs.blocks[to as usize].live_in {
+                    continue;
+                }
+
+                // Moves on an edge are placed at the end of the predecessor if
+                // it has a single successor and at the start of the successor
+                // otherwise.
+                let move_block = if succs.len() == 1 { block.block } else { succ };
+                rs.links.push(Link {
+                    from: from as u32,
+                    to,
+                    freq: self.func.block_frequency(move_block),
+                });
+            }
+        }
+
+        if trace_enabled!() {
+            trace!("Live blocks for region splitting:");
+            for block in &rs.blocks {
+                trace!(
+                    "  {} use_weight={} live_in={} live_out={}",
+                    block.block, block.use_weight, block.live_in, block.live_out
+                );
+            }
+        }
+    }
+
+    /// Attempts to split the given virtual register around a region of blocks
+    /// in which some register is free.
+    ///
+    /// Returns `false` if no profitable region was found.
+    pub(super) fn try_region_split(&mut self, vreg: VirtReg) -> bool {
+        self.collect_live_blocks(vreg);
+        if self.allocator.region_splitter.blocks.len() <= 1 {
+            trace!("{vreg} is only live in a single block, can't split around a region");
+            return false;
+        }
+
+        let mut best: Option<(PhysReg, f32)> = None;
+        for candidate in self
+            .allocator
+            .allocation_order
+            .order(vreg, self.virt_regs, self.reginfo)
+        {
+            let rs = &mut self.allocator.region_splitter;
+            for block in &mut rs.blocks {
+                block.free = true;
+            }
+            let _ = self.reg_matrix.check_interference(
+                &rs.block_segments,
+                candidate.reg,
+                self.reginfo,
+                self.stats,
+                true,
+                |interference| {
+                    rs.blocks[interference.segment.block_idx as usize].free = false;
+                    ControlFlow::<()>::Continue(())
+                },
+            );
+
+            let Some(score) = rs.select_region() else {
+                continue;
+            };
+            let score = score
+                - self
+                    .allocator
+                    .allocation_order
+                    .reg_claim_cost(candidate.reg, self.reginfo);
+            trace!("Region for {} has score {score}", candidate.reg);
+            if score > 0.0 && best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((candidate.reg, score));
+                rs.best_region.clear();
+                rs.best_region
+                    .extend(rs.blocks.iter().map(|block| block.in_region));
+            }
+        }
+        let Some((reg, score)) = best else {
+            trace!("No profitable region found for {vreg}");
+            stat!(self.stats, no_region_split);
+            return false;
+        };
+        trace!("Splitting {vreg} around region for {reg} with score {score}");
+        stat!(self.stats, split_vregs);
+        stat!(self.stats, region_split_vregs);
+
+        // Split segments at each block boundary where the region is entered or
+        // left.
+        let RegionSplitter {
+            block_map,
+            best_region,
+            region_segments,
+            other_segments,
+            ..
+        } = &mut self.allocator.region_splitter;
+        let in_region = |block: Block| best_region[block_map[block].unwrap() as usize];
+        region_segments.clear();
+        other_segments.clear();
+        for &segment in self.virt_regs.segments(vreg) {
+            let mut segment = segment;
+            let mut block = self.func.inst_block(segment.live_range.from.inst());
+            let mut current = in_region(block);
+            loop {
+                let end = self.func.block_insts(block).to;
+                if end.slot(Slot::Boundary) >= segment.live_range.to {
+                    break;
+                }
+                block = block.next();
+                let next = in_region(block);
+                if next != current {
+                    let (first, second) = segment.split_at(end, self.uses, self.hints);
+                    if current {
+                        region_segments.push(first);
+                    } else {
+                        other_segments.push(first);
+                    }
+                    segment = second;
+                    current = next;
+                }
+            }
+            if current {
+                region_segments.push(segment);
+            } else {
+                other_segments.push(segment);
+            }
+        }
+
+        let set = self.virt_regs[vreg].value_set;
+        self.allocator.splitter.new_vregs.clear();
+        for segments in [region_segments, other_segments] {
+            self.virt_regs.create_vreg_from_segments(
+                segments,
+                self.func,
+                self.reginfo,
+                self.uses,
+                self.hints,
+                self.virt_reg_builder,
+                self.coalescing,
+                self.stats,
+                self.options,
+                set,
+                &mut self.allocator.splitter.new_vregs,
+            );
+        }
+        self.allocator
+            .assignments
+            .grow_to_with(self.virt_regs.num_virt_regs(), Assignment::default);
+
+        // The original vreg is no longer used after this point.
+        self.allocator.assignments[vreg] = Assignment::Dead;
+
+        self.queue_new_vregs();
+        true
+    }
+}
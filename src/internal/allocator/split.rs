@@ -11,12 +11,13 @@ use crate::internal::live_range::{LiveRangeSegment, Slot, ValueSegment};
 use crate::internal::reg_matrix::{
     InterferenceCursor, InterferenceKind, InterferenceSegment, RegMatrix,
 };
-use crate::internal::uses::{UseKind, Uses};
+use crate::internal::split_placement::{can_split_before, edit_sequence_end, edit_sequence_start};
+use crate::internal::uses::{UseKind, Uses, spill_remat_cost};
 use crate::internal::value_live_ranges::ValueSet;
 use crate::internal::virt_regs::builder::normalize_spill_weight;
 use crate::internal::virt_regs::{VirtReg, VirtRegGroup, VirtRegs};
 use crate::reginfo::{PhysReg, RegInfo};
-use crate::{Options, SplitStrategy, Stats};
+use crate::{AllocationAlgorithm, Options, SplitStrategy, Stats};
 
 /// Information about a use that we may want to include or exclude from a split.
 ///
@@ -121,15 +122,23 @@ struct SplitProposal {
     /// This is used to determine whether spilling is more profitable when
     /// allowed by the register class.
     split_cost: f32,
+
+    /// Callee-saved cost that must be paid to claim the register.
+    claim_cost: f32,
 }
 
 impl SplitProposal {
     /// Score used to select the best split proposal.
     fn score(&self) -> (f32, u32, f32) {
-        // - maximize weight covered by the split.
+        // - maximize weight covered by the split, minus the cost of claiming
+        //   the register.
         // - if equal weight, maximize instructions,
         // - if also equal instructions, minimize interference weight
-        (self.use_weight, self.live_insts, -self.interference_weight)
+        (
+            self.use_weight - self.claim_cost,
+            self.live_insts,
+            -self.interference_weight,
+        )
     }
 }
 
@@ -143,7 +152,7 @@ pub struct Splitter {
     minimal_segments: Vec<(ValueSegment, ValueSet)>,
 
     /// Newly created virtual register from the minimal live ranges.
-    new_vregs: Vec<VirtReg>,
+    pub(super) new_vregs: Vec<VirtReg>,
 
     /// Instructions at which a virtual register is used, with weighed spill
     /// costs.
@@ -187,7 +196,7 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
         for segment in segments {
             for u in &self.uses[segment.use_list] {
                 // Ignore uses with no spill weight.
-                let spill_cost = u.spill_cost(self.reginfo);
+                let spill_cost = u.spill_cost(segment.value, self.func, self.reginfo);
                 if spill_cost == 0.0 {
                     continue;
                 }
@@ -432,6 +441,35 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
         initial_gap.unwrap()
     }
 
+    /// Merges adjacent gaps if the virtual register can't be split at the
+    /// boundary between them because it is inside a sequence of instructions
+    /// which forbids edits.
+    ///
+    /// Returns the updated index of the initial gap.
+    fn merge_unsplittable_gaps(&mut self, vreg: VirtReg, mut initial_gap: usize) -> usize {
+        let segments = self.virt_regs.segments(vreg);
+        let gaps = &mut self.allocator.splitter.gaps;
+        let mut idx = 1;
+        while idx < gaps.len() {
+            let boundary = gaps[idx].range.from;
+            if can_split_before(self.func, segments, boundary) {
+                idx += 1;
+                continue;
+            }
+
+            trace!("Merging gaps {} and {idx} at {boundary}", idx - 1);
+            let gap = gaps.remove(idx);
+            let prev = &mut gaps[idx - 1];
+            prev.range.to = gap.range.to;
+            prev.weight += gap.weight;
+            prev.min_freq = prev.min_freq.min(gap.min_freq);
+            if initial_gap >= idx {
+                initial_gap -= 1;
+            }
+        }
+        initial_gap
+    }
+
     /// Counts the number of instructions in each gap.
     ///
     /// This is needed to correctly estimate the spill weight of a vreg covering
@@ -578,8 +616,11 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
 
     /// Builds a proposed split region starting from the best use that is small
     /// enough to evict any interference in the given physical register.
+    ///
+    /// `claim_cost` is the callee-saved cost of claiming `reg`.
     fn find_split_region(
         reg: PhysReg,
+        claim_cost: f32,
         initial_gap: usize,
         splitter: &Splitter,
         reg_matrix: &RegMatrix,
@@ -679,11 +720,13 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
         let mut right_gap = initial_gap;
         let mut can_grow_left = left_gap != 0;
         let mut can_grow_right = right_gap != splitter.gaps.len() - 1;
-        let mut insts = 1;
+        // The initial gap normally covers a single instruction, but it may be
+        // larger if it was merged with a sequence that forbids edits.
+        let mut insts = splitter.gaps[initial_gap].live_insts.max(1);
         let mut weight = splitter.gaps[initial_gap].weight;
         debug_assert_ne!(weight, 0.0);
         let mut interference_weight = 0.0;
-        let initial_spill_weight = normalize_spill_weight(weight, 1, options);
+        let initial_spill_weight = normalize_spill_weight(weight, insts, options);
 
         let initial_segment = splitter
             .gap_segments
@@ -831,7 +874,8 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
             use_weight: weight,
             live_insts: insts,
             interference_weight,
-            split_cost: left_split_cost + right_split_cost,
+            split_cost: left_split_cost + right_split_cost + claim_cost,
+            claim_cost,
         })
     }
 
@@ -874,9 +918,7 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
             );
             self.allocator
                 .assignments
-                .grow_to_with(self.virt_regs.num_virt_regs(), || Assignment::Unassigned {
-                    evicted_for_preference: false,
-                });
+                .grow_to_with(self.virt_regs.num_virt_regs(), Assignment::default);
         };
 
         let mut segments = &mut splitter.segments[..];
@@ -938,6 +980,17 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
             return;
         }
 
+        // Try to split around a region of blocks first, falling back to
+        // splitting in linear order if that fails.
+        if self.options.split_strategy == SplitStrategy::Region && self.try_region_split(vreg) {
+            return;
+        }
+
+        // Similarly, try to split around calls first.
+        if self.options.split_strategy == SplitStrategy::Call && self.try_call_split(vreg) {
+            return;
+        }
+
         // Collect a linear list of all the places where the virtual register is
         // used, with associated total spill weights.
         self.collect_uses(vreg);
@@ -955,6 +1008,12 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
 
         // Collect information about gaps between uses.
         let initial_gap = self.collect_gaps(best_use);
+        let initial_gap = self.merge_unsplittable_gaps(vreg, initial_gap);
+        if self.allocator.splitter.gaps.len() == 1 {
+            trace!("No gap boundaries allow edits, forcing a spill");
+            self.spill(vreg);
+            return;
+        }
         stat!(
             self.stats,
             num_split_gaps,
@@ -973,8 +1032,13 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
             .allocation_order
             .order(vreg, self.virt_regs, self.reginfo)
         {
+            let claim_cost = self
+                .allocator
+                .allocation_order
+                .reg_claim_cost(candidate.reg, self.reginfo);
             if let Some(new_split) = Self::find_split_region(
                 candidate.reg,
+                claim_cost,
                 initial_gap,
                 &self.allocator.splitter,
                 self.reg_matrix,
@@ -1023,13 +1087,7 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
             .reginfo
             .class_includes_spillslots(self.virt_regs[vreg].class)
         {
-            let mut spill_cost = 0.0;
-            for segment in self.virt_regs.segments(vreg) {
-                for &u in &self.uses[segment.use_list] {
-                    let block_freq = self.func.block_frequency(self.func.inst_block(u.pos));
-                    spill_cost += u.spill_cost(self.reginfo) * block_freq;
-                }
-            }
+            let spill_cost = self.spill_cost(vreg);
             trace!("{vreg} is directly spillable with a spill cost of {spill_cost}");
             if spill_cost < best_split.split_cost {
                 stat!(self.stats, spill_cheaper_than_split);
@@ -1130,7 +1188,8 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                             | UseKind::TiedUse { .. }
                             | UseKind::ConstraintConflict { .. }
                             | UseKind::BlockparamIn { .. }
-                            | UseKind::BlockparamOut { .. } => continue,
+                            | UseKind::BlockparamOut { .. }
+                            | UseKind::KeepAlive { .. } => continue,
                         };
 
                         trace!("Splitting around group use {}: {}", u.pos, u.kind);
@@ -1138,13 +1197,15 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                         // If there is a live range segment with no uses before
                         // this use, collect that segment for the non-group
                         // portion.
-                        if u.pos != segment.live_range.from.round_to_prev_inst().inst() {
-                            let (before, after) = segment.split_at(u.pos, self.uses, self.hints);
+                        let start = edit_sequence_start(self.func, u.pos);
+                        if start > segment.live_range.from.round_to_prev_inst().inst() {
+                            let (before, after) = segment.split_at(start, self.uses, self.hints);
                             splitter.segments.push(before);
                             segment = after;
                         }
 
-                        if u.pos.next() == segment.live_range.to.round_to_next_inst().inst() {
+                        let end = edit_sequence_end(self.func, u.pos);
+                        if end >= segment.live_range.to.round_to_next_inst().inst() {
                             // If this use is on the last instruction of the segment
                             // then split off the rest of the segment into a
                             // minimal segment.
@@ -1156,11 +1217,11 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                             break 'outer;
                         } else {
                             // Otherwise split the segment at the next
-                            // instruction boundary after the use. The first
-                            // half is split as a minimal segment and continue
-                            // processing the remaining half.
-                            let (before, after) =
-                                segment.split_at(u.pos.next(), self.uses, self.hints);
+                            // instruction boundary after the use, or after the
+                            // sequence containing it if edits are forbidden.
+                            // The first half is split as a minimal segment and
+                            // continue processing the remaining half.
+                            let (before, after) = segment.split_at(end, self.uses, self.hints);
                             trace!(
                                 "Generating minimal segment for {} at {}",
                                 before.value, before.live_range
@@ -1251,11 +1312,16 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
             stat!(self.stats, spilled_vregs);
             let value_set = self.virt_regs[vreg].value_set;
             for &segment in self.virt_regs.segments(vreg) {
-                // If the value of that segment is rematerializable then we
-                // don't need to spill it. This will result in it having no
-                // allocation, which the move resolver will handle by
-                // rematerializing the value.
-                let can_remat = self.func.can_rematerialize(segment.value).is_some();
+                // If the value of that segment can be rematerialized more
+                // cheaply than a reload then we don't need to spill it. This
+                // will result in it having no allocation, which the move
+                // resolver will handle by rematerializing the value.
+                //
+                // Values which depend on an input can only be rematerialized
+                // where that input is in a register, so they are always
+                // spilled. The move resolver may still rematerialize them
+                // instead of reloading them from the spill slot.
+                let can_remat = spill_remat_cost(segment.value, self.func).is_some();
 
                 let mut segment = segment;
                 'outer: loop {
@@ -1295,7 +1361,8 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                             | UseKind::TiedUse { .. }
                             | UseKind::ConstraintConflict { .. }
                             | UseKind::BlockparamIn { .. }
-                            | UseKind::BlockparamOut { .. } => true,
+                            | UseKind::BlockparamOut { .. }
+                            | UseKind::KeepAlive { .. } => true,
                         };
                         if can_spill {
                             trace!(
@@ -1308,9 +1375,12 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                         trace!("Splitting around unspillable use {}: {}", u.pos, u.kind);
 
                         // If there is a live range segment with no uses before
-                        // this use, spill that segment.
-                        if u.pos != segment.live_range.from.round_to_prev_inst().inst() {
-                            let (before, after) = segment.split_at(u.pos, self.uses, self.hints);
+                        // this use, spill that segment. The minimal segment
+                        // must cover any preceding instructions in a sequence
+                        // that forbids edits.
+                        let start = edit_sequence_start(self.func, u.pos);
+                        if start > segment.live_range.from.round_to_prev_inst().inst() {
+                            let (before, after) = segment.split_at(start, self.uses, self.hints);
                             if must_spill || !can_remat {
                                 self.spill_allocator.spill_segment(value_set, before);
                             } else {
@@ -1323,7 +1393,8 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                             segment = after;
                         }
 
-                        if u.pos.next() == segment.live_range.to.round_to_next_inst().inst() {
+                        let end = edit_sequence_end(self.func, u.pos);
+                        if end >= segment.live_range.to.round_to_next_inst().inst() {
                             // If this use is on the last instruction of the segment
                             // then split off the rest of the segment into a
                             // minimal segment.
@@ -1335,11 +1406,11 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                             break 'outer;
                         } else {
                             // Otherwise split the segment at the next
-                            // instruction boundary after the use. The first
-                            // half is split as a minimal segment and continue
-                            // processing the remaining half.
-                            let (before, after) =
-                                segment.split_at(u.pos.next(), self.uses, self.hints);
+                            // instruction boundary after the use, or after the
+                            // sequence containing it if edits are forbidden.
+                            // The first half is split as a minimal segment and
+                            // continue processing the remaining half.
+                            let (before, after) = segment.split_at(end, self.uses, self.hints);
                             trace!(
                                 "Generating minimal segment for {} at {}",
                                 before.value, before.live_range
@@ -1401,7 +1472,7 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
         self.queue_new_vregs();
     }
 
-    fn queue_new_vregs(&mut self) {
+    pub(super) fn queue_new_vregs(&mut self) {
         // Then queue the newly created virtual registers. This needs to be done
         // after all split products are created so that virtual register groups
         // are complete.
@@ -1421,9 +1492,18 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
             };
 
             trace!("Queuing split product {vreg_or_group}");
-            self.allocator
-                .queue
-                .enqueue(vreg_or_group, Stage::Evict, self.virt_regs);
+            match self.options.algorithm {
+                AllocationAlgorithm::Greedy => {
+                    self.allocator
+                        .queue
+                        .enqueue(vreg_or_group, Stage::Evict, self.virt_regs);
+                }
+                AllocationAlgorithm::LinearScan => {
+                    self.allocator
+                        .linear_queue
+                        .enqueue(vreg_or_group, self.virt_regs);
+                }
+            }
         }
     }
 }
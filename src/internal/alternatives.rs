@@ -0,0 +1,168 @@
+//! Selection of alternative operand constraints.
+//!
+//! Operands with an `Alternatives` constraint are resolved to a single `Class`
+//! or `Fixed` constraint before allocation. The selection only depends on the
+//! instruction itself so that it can be recomputed by any pass that needs it
+//! and always produces the same result.
+//!
+//! The cheapest alternative is chosen, except that `Fixed` alternatives are
+//! skipped if they would conflict with another fixed register or clobber in
+//! the same instruction. Conflicts are determined in the same way as in input
+//! validation: uses and early defs conflict with each other, and defs, early
+//! defs, defs tied to a fixed-register use and clobbers conflict with each
+//! other. `Class` alternatives are
+//! skipped if they don't contain the register that the operand's value is
+//! pinned to.
+
+use alloc::vec::Vec;
+
+use crate::function::{Function, Inst, OperandConstraint, OperandKind};
+use crate::reginfo::{PhysReg, RegInfo, RegUnitSet};
+
+/// Resolves the `Alternatives` constraints of an instruction's operands.
+pub struct AlternativeResolver {
+    /// Resolved constraint for each operand of the instruction.
+    constraints: Vec<OperandConstraint>,
+
+    /// Units reserved by fixed-register operands before the instruction.
+    early_fixed: RegUnitSet,
+
+    /// Units reserved by fixed-register operands and clobbers after the
+    /// instruction.
+    late_fixed: RegUnitSet,
+}
+
+impl AlternativeResolver {
+    pub fn new() -> Self {
+        Self {
+            constraints: Vec::new(),
+            early_fixed: RegUnitSet::new(),
+            late_fixed: RegUnitSet::new(),
+        }
+    }
+
+    /// Returns whether `reg` is free for a fixed-register operand that
+    /// occupies it before and/or after the instruction.
+    fn is_free(&self, reg: PhysReg, early: bool, late: bool, reginfo: &impl RegInfo) -> bool {
+        reginfo.reg_units(reg).all(|unit| {
+            !(early && self.early_fixed.contains(unit) || late && self.late_fixed.contains(unit))
+        })
+    }
+
+    /// Marks `reg` as used by a fixed-register operand.
+    fn reserve(&mut self, reg: PhysReg, early: bool, late: bool, reginfo: &impl RegInfo) {
+        for unit in reginfo.reg_units(reg) {
+            if early {
+                self.early_fixed.insert(unit);
+            }
+            if late {
+                self.late_fixed.insert(unit);
+            }
+        }
+    }
+
+    /// Returns the resolved constraint of an operand from the last call to
+    /// `resolve`.
+    pub fn constraint(&self, operand: usize) -> OperandConstraint {
+        self.constraints[operand]
+    }
+
+    /// Returns the constraint to use for each operand of `inst`, with all
+    /// `Alternatives` constraints replaced by the selected alternative.
+    ///
+    /// Returns the index of the offending operand if no alternative can be
+    /// selected for it.
+    pub fn resolve(
+        &mut self,
+        inst: Inst,
+        func: &impl Function,
+        reginfo: &impl RegInfo,
+    ) -> Result<&[OperandConstraint], usize> {
+        let operands = func.inst_operands(inst);
+        self.constraints.clear();
+        self.constraints
+            .extend(operands.iter().map(|op| op.constraint()));
+        if !self
+            .constraints
+            .iter()
+            .any(|c| matches!(c, OperandConstraint::Alternatives(_)))
+        {
+            return Ok(&self.constraints);
+        }
+
+        // Collect the registers that are already used by fixed operands and
+        // clobbers.
+        self.early_fixed.clear();
+        self.late_fixed.clear();
+        for op in operands {
+            match op.constraint() {
+                OperandConstraint::Fixed(reg) => match op.kind() {
+                    OperandKind::Use(_) => self.reserve(reg, true, false, reginfo),
+                    OperandKind::Def(_) => self.reserve(reg, false, true, reginfo),
+                    OperandKind::EarlyDef(_) => self.reserve(reg, true, true, reginfo),
+                    OperandKind::DefGroup(_)
+                    | OperandKind::UseGroup(_)
+                    | OperandKind::EarlyDefGroup(_)
+                    | OperandKind::NonAllocatable => {}
+                },
+
+                // Defs tied to a fixed-register use occupy that register
+                // after the instruction.
+                OperandConstraint::Reuse(target) => {
+                    if let Some(OperandConstraint::Fixed(reg)) =
+                        operands.get(target).map(|op| op.constraint())
+                    {
+                        self.reserve(reg, false, true, reginfo);
+                    }
+                }
+                OperandConstraint::Class(_) | OperandConstraint::Alternatives(_) => {}
+            }
+        }
+        for unit in func.inst_clobbers(inst) {
+            self.late_fixed.insert(unit);
+        }
+
+        // Select an alternative for each operand in order, accounting for the
+        // fixed registers selected for previous operands.
+        for (idx, op) in operands.iter().enumerate() {
+            let OperandConstraint::Alternatives(set) = op.constraint() else {
+                continue;
+            };
+            let (value, early, late) = match op.kind() {
+                OperandKind::Use(value) => (value, true, false),
+                OperandKind::Def(value) => (value, false, true),
+                OperandKind::EarlyDef(value) => (value, true, true),
+                OperandKind::DefGroup(_)
+                | OperandKind::UseGroup(_)
+                | OperandKind::EarlyDefGroup(_)
+                | OperandKind::NonAllocatable => return Err(idx),
+            };
+            let mut best: Option<(OperandConstraint, f32)> = None;
+            for &(constraint, cost) in func.alternative_set(set) {
+                if best.is_some_and(|(_, best_cost)| cost >= best_cost) {
+                    continue;
+                }
+                let usable = match constraint {
+                    OperandConstraint::Class(class) => func
+                        .value_pinned_reg(value)
+                        .is_none_or(|reg| reginfo.class_members(class).contains(reg)),
+                    OperandConstraint::Fixed(reg) => self.is_free(reg, early, late, reginfo),
+                    OperandConstraint::Reuse(_) | OperandConstraint::Alternatives(_) => false,
+                };
+                if usable {
+                    best = Some((constraint, cost));
+                }
+            }
+            let Some((constraint, _)) = best else {
+                return Err(idx);
+            };
+            trace!("Selected {constraint} for {inst}[{idx}]");
+            if let OperandConstraint::Fixed(reg) = constraint {
+                self.reserve(reg, early, late, reginfo);
+            }
+            self.constraints[idx] = constraint;
+        }
+
+        Ok(&self.constraints)
+    }
+}
@@ -8,6 +8,7 @@
 //! are allocated to different registers. Specifically we try to merge:
 //! - Incoming and outgoing block parameters.
 //! - Definitions which reuse an input register.
+//! - The source and destination of copy instructions.
 //! - Values which should be placed in the same register group.
 
 use alloc::vec;
@@ -156,7 +157,9 @@ impl Coalescing {
             let operands = func.inst_operands(inst);
             for &op in operands {
                 // Merge the values of output operands that reuse the allocation
-                // of an input with that input value.
+                // of an input with that input value. For a fixed-register
+                // input this allows both values to be allocated to that
+                // register, which eliminates the moves around the instruction.
                 if let OperandConstraint::Reuse(idx) = op.constraint() {
                     let use_op = operands[idx];
                     match (op.kind(), use_op.kind()) {
@@ -165,8 +168,13 @@ impl Coalescing {
                             OperandKind::Use(use_value),
                         ) => {
                             trace!("Reused operand: {use_value} -> {def_value}");
-                            if self.coalesce_values(use_value, def_value, value_live_ranges, stats)
-                            {
+                            if self.coalesce_values(
+                                use_value,
+                                def_value,
+                                func,
+                                value_live_ranges,
+                                stats,
+                            ) {
                                 stat!(stats, coalesced_tied);
                             } else {
                                 stat!(stats, coalesced_failed_tied);
@@ -186,6 +194,7 @@ impl Coalescing {
                                 if self.coalesce_values(
                                     use_value,
                                     def_value,
+                                    func,
                                     value_live_ranges,
                                     stats,
                                 ) {
@@ -225,6 +234,7 @@ impl Coalescing {
                                     if self.coalesce_values(
                                         value,
                                         prev_value,
+                                        func,
                                         value_live_ranges,
                                         stats,
                                     ) {
@@ -239,6 +249,33 @@ impl Coalescing {
                     }
                 }
             }
+
+            // Merge the source and destination of copy instructions so that
+            // the copy can be eliminated.
+            //
+            // This is skipped if edits are forbidden before the copy: the two
+            // values may have incompatible classes, which would require a move
+            // before the copy to resolve.
+            if func.is_copy(inst) && !func.no_edits_before(inst) {
+                let mut src = None;
+                let mut dst = None;
+                for op in operands {
+                    match op.kind() {
+                        OperandKind::Use(value) => src = Some(value),
+                        OperandKind::Def(value) => dst = Some(value),
+                        _ => unreachable!(),
+                    }
+                }
+                let (Some(src), Some(dst)) = (src, dst) else {
+                    unreachable!();
+                };
+                trace!("Copy: {src} -> {dst}");
+                if self.coalesce_values(src, dst, func, value_live_ranges, stats) {
+                    stat!(stats, coalesced_copy);
+                } else {
+                    stat!(stats, coalesced_failed_copy);
+                }
+            }
         }
 
         // Merge matching block parameters.
@@ -249,7 +286,13 @@ impl Coalescing {
                 .zip(func.block_params(succ))
             {
                 trace!("Block parameter: {blockparam_out} -> {blockparam_in}");
-                if self.coalesce_values(blockparam_out, blockparam_in, value_live_ranges, stats) {
+                if self.coalesce_values(
+                    blockparam_out,
+                    blockparam_in,
+                    func,
+                    value_live_ranges,
+                    stats,
+                ) {
                     stat!(stats, coalesced_blockparam);
                 } else {
                     stat!(stats, coalesced_failed_blockparam);
@@ -265,11 +308,20 @@ impl Coalescing {
         &mut self,
         a: Value,
         b: Value,
+        func: &impl Function,
         value_live_ranges: &mut ValueLiveRanges,
         stats: &mut Stats,
     ) -> bool {
         trace!("Trying to merge {a} and {b} into the same value set...");
 
+        // Values pinned to a register can only share a set with values pinned
+        // to the same register. Since this holds for all existing sets, only
+        // the two values being merged need to be checked.
+        if func.value_pinned_reg(a) != func.value_pinned_reg(b) {
+            trace!("-> different pinned registers");
+            return false;
+        }
+
         let mut merged = false;
         self.set_for_value.try_union(a, b, |set_a, set_b| {
             let set_a = ValueSet::new(set_a.index());
@@ -0,0 +1,655 @@
+//! Internal splitting of critical edges.
+//!
+//! Most of the allocator relies on every CFG edge either leaving a block with a
+//! single successor or entering a block with a single predecessor: this
+//! determines on which side of the edge moves are placed. When
+//! `Function::allows_critical_edges` is set, this is no longer guaranteed.
+//! Similarly, the moves for block parameters passed by a branch terminator
+//! (`Function::branch_blockparams`) can only be placed on the edge itself.
+//!
+//! Instead of handling these edges throughout the allocator, we run it on a
+//! view of the function in which every such edge is split by an edge block
+//! containing a single jump instruction without operands. If the edge carries
+//! branch arguments then these become the block parameters of that jump,
+//! which allows the rest of the allocator to treat them like any other jump
+//! arguments. Edge blocks are
+//! placed immediately after their predecessor block, which preserves the
+//! dominance ordering of blocks. This renumbers both blocks and instructions,
+//! so `Output` translates the results back to the numbering of the original
+//! function.
+//!
+//! Only edge blocks which end up containing moves or rematerializations are
+//! reported in `Output::edge_blocks`. Other edge blocks are empty and can
+//! simply be ignored by the client.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::entity::PrimaryMap;
+use crate::entity::packed_option::PackedOption;
+use crate::function::{
+    AlternativeSet, Block, Function, Inst, InstRange, Operand, OperandConstraint, TerminatorKind,
+    Value, ValueGroup,
+};
+use crate::internal::move_resolver::MoveResolver;
+use crate::output::EdgeBlock;
+use crate::reginfo::{PhysReg, RegBank, RegClass, RegUnit};
+
+/// What a block in the split view corresponds to.
+#[derive(Clone, Copy)]
+enum BlockKind {
+    /// A block of the original function.
+    ///
+    /// `inst_offset` is the number of edge block instructions inserted before
+    /// this block.
+    Orig { block: Block, inst_offset: u32 },
+
+    /// An edge block on the edge from `pred` to `succ`, using the original
+    /// block numbers. `succ_idx` is the index of `succ` in the successors of
+    /// `pred`.
+    Edge {
+        pred: Block,
+        succ: Block,
+        succ_idx: u32,
+    },
+}
+
+/// Block in the split view of the function.
+#[derive(Clone, Copy)]
+struct BlockData {
+    kind: BlockKind,
+    insts: InstRange,
+    preds: (u32, u32),
+    succs: (u32, u32),
+    immediate_dominator: PackedOption<Block>,
+    frequency: f32,
+}
+
+/// Split view of a function with critical edges.
+#[derive(Default)]
+pub struct EdgeSplit {
+    /// Whether the function had critical edges that were split. If this is
+    /// false then the allocator ran directly on the original function.
+    active: bool,
+
+    /// Blocks of the split view.
+    blocks: PrimaryMap<Block, BlockData>,
+
+    /// Block in the split view for each original block.
+    block_map: PrimaryMap<Block, Block>,
+
+    /// Block containing each instruction of the split view.
+    inst_blocks: PrimaryMap<Inst, Block>,
+
+    /// Number of instructions in the original function.
+    num_orig_insts: usize,
+
+    /// Storage for the predecessors and successors of all blocks.
+    cfg_edges: Vec<Block>,
+
+    /// Edge blocks which contain moves, in the order in which they are
+    /// exposed in the output.
+    edge_blocks: Vec<EdgeBlock>,
+
+    /// Block in the split view for each entry of `edge_blocks`.
+    edge_block_map: Vec<Block>,
+}
+
+/// Returns whether the edge from `pred` to its successor at `succ_idx` needs
+/// an edge block, either because it is a critical edge or because it carries
+/// branch arguments.
+fn needs_edge_block(func: &impl Function, pred: Block, succ_idx: usize) -> bool {
+    let succs = func.block_succs(pred);
+    (succs.len() > 1 && func.block_preds(succs[succ_idx]).len() > 1)
+        || !func.branch_blockparams(pred, succ_idx).is_empty()
+}
+
+/// Returns the outgoing block parameters on the edge from `pred` to its
+/// successor at `succ_idx` in the original function.
+pub fn edge_blockparams<F: Function>(func: &F, pred: Block, succ_idx: usize) -> &[Value] {
+    if !func.jump_blockparams(pred).is_empty() {
+        return func.jump_blockparams(pred);
+    }
+    func.branch_blockparams(pred, succ_idx)
+}
+
+/// Returns the index of `block` in the successors of its predecessor at
+/// `pred_idx` in `preds`.
+///
+/// If the predecessor branches to `block` several times then each occurrence
+/// of it in `preds` corresponds to the successor entry with the same rank.
+pub fn pred_succ_idx(
+    func: &impl Function,
+    preds: &[Block],
+    pred_idx: usize,
+    block: Block,
+) -> usize {
+    let pred = preds[pred_idx];
+    let succ_idxs = || {
+        func.block_succs(pred)
+            .iter()
+            .enumerate()
+            .filter(|&(_, &succ)| succ == block)
+            .map(|(succ_idx, _)| succ_idx)
+    };
+
+    // Only scan the predecessors for duplicate edges, which are rare.
+    let rank = if succ_idxs().nth(1).is_some() {
+        preds[..pred_idx].iter().filter(|&&p| p == pred).count()
+    } else {
+        0
+    };
+    succ_idxs().nth(rank).unwrap()
+}
+
+impl EdgeSplit {
+    pub fn new() -> Self {
+        Self {
+            active: false,
+            blocks: PrimaryMap::new(),
+            block_map: PrimaryMap::new(),
+            inst_blocks: PrimaryMap::new(),
+            num_orig_insts: 0,
+            cfg_edges: vec![],
+            edge_blocks: vec![],
+            edge_block_map: vec![],
+        }
+    }
+
+    /// Builds the split view of the function if it has any critical edges.
+    ///
+    /// Returns whether the split view should be used.
+    pub fn compute(&mut self, func: &impl Function) -> bool {
+        self.active = false;
+        self.blocks.clear();
+        self.block_map.clear();
+        self.inst_blocks.clear();
+        self.cfg_edges.clear();
+        self.edge_blocks.clear();
+        self.edge_block_map.clear();
+
+        if !func.blocks().any(|block| {
+            (0..func.block_succs(block).len()).any(|idx| needs_edge_block(func, block, idx))
+        }) {
+            return false;
+        }
+        self.active = true;
+        self.num_orig_insts = func.num_insts();
+
+        // Create the blocks, placing edge blocks right after their predecessor.
+        let mut inst_offset = 0;
+        for block in func.blocks() {
+            let insts = func.block_insts(block);
+            let new_block = self.blocks.next_key();
+            self.block_map.push(new_block);
+            let from = Inst::new(insts.from.index() + inst_offset as usize);
+            self.blocks.push(BlockData {
+                kind: BlockKind::Orig { block, inst_offset },
+                insts: InstRange::new(from, Inst::new(from.index() + insts.len())),
+                preds: (0, 0),
+                succs: (0, 0),
+                immediate_dominator: None.into(),
+                frequency: func.block_frequency(block),
+            });
+            for _ in insts.iter() {
+                self.inst_blocks.push(new_block);
+            }
+
+            for (succ_idx, &succ) in func.block_succs(block).iter().enumerate() {
+                if !needs_edge_block(func, block, succ_idx) {
+                    continue;
+                }
+                let edge_block = self.blocks.next_key();
+                let jump = self.inst_blocks.push(edge_block);
+                inst_offset += 1;
+                self.blocks.push(BlockData {
+                    kind: BlockKind::Edge {
+                        pred: block,
+                        succ,
+                        succ_idx: succ_idx as u32,
+                    },
+                    insts: InstRange::new(jump, jump.next()),
+                    preds: (0, 0),
+                    succs: (0, 0),
+                    immediate_dominator: PackedOption::from(new_block),
+                    frequency: func.block_frequency(block).min(func.block_frequency(succ)),
+                });
+            }
+        }
+
+        // Fill in the CFG of the split view.
+        for block in self.blocks.keys() {
+            match self.blocks[block].kind {
+                BlockKind::Orig {
+                    block: orig,
+                    inst_offset: _,
+                } => {
+                    let start = self.cfg_edges.len() as u32;
+                    let preds = func.block_preds(orig);
+                    for (pred_idx, &pred) in preds.iter().enumerate() {
+                        let succ_idx = pred_succ_idx(func, preds, pred_idx, orig);
+                        let pred = self
+                            .edge_block_between(func, pred, succ_idx)
+                            .unwrap_or(self.block_map[pred]);
+                        self.cfg_edges.push(pred);
+                    }
+                    let mid = self.cfg_edges.len() as u32;
+                    for (succ_idx, &succ) in func.block_succs(orig).iter().enumerate() {
+                        let succ = self
+                            .edge_block_between(func, orig, succ_idx)
+                            .unwrap_or(self.block_map[succ]);
+                        self.cfg_edges.push(succ);
+                    }
+                    let end = self.cfg_edges.len() as u32;
+                    self.blocks[block].preds = (start, mid);
+                    self.blocks[block].succs = (mid, end);
+
+                    // An edge block is the immediate dominator of its
+                    // successor if it is on the only path into that block
+                    // which doesn't come from a block it dominates.
+                    let mut forward_preds = preds
+                        .iter()
+                        .enumerate()
+                        .filter(|&(_, &pred)| !func.block_dominates(orig, pred));
+                    let idom = match (forward_preds.next(), forward_preds.next()) {
+                        (Some((pred_idx, &pred)), None) => {
+                            let succ_idx = pred_succ_idx(func, preds, pred_idx, orig);
+                            self.edge_block_between(func, pred, succ_idx)
+                        }
+                        _ => None,
+                    };
+                    self.blocks[block].immediate_dominator = idom
+                        .or_else(|| {
+                            func.block_immediate_dominator(orig)
+                                .map(|idom| self.block_map[idom])
+                        })
+                        .into();
+                }
+                BlockKind::Edge { pred, succ, .. } => {
+                    let start = self.cfg_edges.len() as u32;
+                    self.cfg_edges.push(self.block_map[pred]);
+                    self.cfg_edges.push(self.block_map[succ]);
+                    self.blocks[block].preds = (start, start + 1);
+                    self.blocks[block].succs = (start + 1, start + 2);
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Returns the edge block on the edge from `pred` to its successor at
+    /// `succ_idx`, if there is one.
+    fn edge_block_between(
+        &self,
+        func: &impl Function,
+        pred: Block,
+        succ_idx: usize,
+    ) -> Option<Block> {
+        if !needs_edge_block(func, pred, succ_idx) {
+            return None;
+        }
+        let prev_edge_blocks = (0..succ_idx)
+            .filter(|&idx| needs_edge_block(func, pred, idx))
+            .count();
+        Some(Block::new(
+            self.block_map[pred].index() + 1 + prev_edge_blocks,
+        ))
+    }
+
+    /// Maps an edge of the original function, from `pred` to its successor at
+    /// `succ_idx`, to the split view.
+    ///
+    /// Returns the predecessor, the edge block if the edge was split, and the
+    /// successor in the split view. The index of the successor in the
+    /// predecessor's successors is the same in the split view.
+    pub fn split_edge(
+        &self,
+        func: &impl Function,
+        pred: Block,
+        succ_idx: usize,
+    ) -> (Block, Option<Block>, Block) {
+        let succ = func.block_succs(pred)[succ_idx];
+        if !self.active {
+            return (pred, None, succ);
+        }
+        (
+            self.block_map[pred],
+            self.edge_block_between(func, pred, succ_idx),
+            self.block_map[succ],
+        )
+    }
+
+    /// Determines which edge blocks need to be materialized, once all moves
+    /// have been generated.
+    pub fn collect_edge_blocks(&mut self, num_blocks: usize, move_resolver: &MoveResolver) {
+        if !self.active {
+            return;
+        }
+        for (block, data) in &self.blocks {
+            let BlockKind::Edge {
+                pred,
+                succ,
+                succ_idx,
+            } = data.kind
+            else {
+                continue;
+            };
+            let jump = data.insts.from;
+            let has_edits = move_resolver
+                .edits_from(jump)
+                .iter()
+                .take_while(|&&(pos, _)| pos == jump)
+                .any(|(_, edit)| edit.to.is_some());
+            if has_edits {
+                self.edge_blocks.push(EdgeBlock {
+                    block: Block::new(num_blocks + self.edge_blocks.len()),
+                    pred,
+                    succ,
+                    succ_idx: succ_idx as usize,
+                });
+                self.edge_block_map.push(block);
+            }
+        }
+    }
+
+    /// Returns the edge blocks which need to be inserted by the client.
+    pub fn edge_blocks(&self) -> &[EdgeBlock] {
+        &self.edge_blocks
+    }
+
+    /// Returns the instructions of a block of the output, which may be an
+    /// edge block, in the split view.
+    ///
+    /// Also returns whether this is an edge block.
+    pub fn block_insts(&self, func: &impl Function, block: Block) -> (InstRange, bool) {
+        if !self.active {
+            (func.block_insts(block), false)
+        } else if block.index() < func.num_blocks() {
+            (self.blocks[self.block_map[block]].insts, false)
+        } else {
+            let block = self.edge_block_map[block.index() - func.num_blocks()];
+            (self.blocks[block].insts, true)
+        }
+    }
+
+    /// Maps an instruction in the original function to the split view.
+    pub fn split_inst(&self, func: &impl Function, inst: Inst) -> Inst {
+        if !self.active {
+            return inst;
+        }
+        let BlockKind::Orig {
+            block: _,
+            inst_offset,
+        } = self.blocks[self.block_map[func.inst_block(inst)]].kind
+        else {
+            unreachable!();
+        };
+        Inst::new(inst.index() + inst_offset as usize)
+    }
+
+    /// Maps an instruction in the split view back to the original function.
+    ///
+    /// Returns `None` for the jump instruction of edge blocks.
+    pub fn orig_inst(&self, inst: Inst) -> Option<Inst> {
+        if !self.active {
+            return Some(inst);
+        }
+        match self.blocks[self.inst_blocks[inst]].kind {
+            BlockKind::Orig {
+                block: _,
+                inst_offset,
+            } => Some(Inst::new(inst.index() - inst_offset as usize)),
+            BlockKind::Edge { .. } => None,
+        }
+    }
+
+    /// Maps an instruction in the split view back to the original function,
+    /// attributing the jump of an edge block to the branch of its
+    /// predecessor.
+    pub fn orig_inst_or_branch(&self, inst: Inst) -> Inst {
+        self.orig_inst(inst)
+            .unwrap_or_else(|| self.orig_pos(inst).prev())
+    }
+
+    /// Returns the number of original instructions which come before the
+    /// given instruction of the split view.
+    fn orig_pos(&self, inst: Inst) -> Inst {
+        let Some(&block) = self.inst_blocks.get(inst) else {
+            return Inst::new(self.num_orig_insts);
+        };
+        let block = match self.blocks[block].kind {
+            BlockKind::Orig { .. } => block,
+            BlockKind::Edge { pred, .. } => self.block_map[pred],
+        };
+        let BlockKind::Orig {
+            block: _,
+            inst_offset,
+        } = self.blocks[block].kind
+        else {
+            unreachable!();
+        };
+        let inst = inst.min(self.blocks[block].insts.to);
+        Inst::new(inst.index() - inst_offset as usize)
+    }
+
+    /// Maps a range of instructions in the split view back to the original
+    /// function, dropping the jump instructions of edge blocks.
+    pub fn orig_inst_range(&self, range: InstRange) -> InstRange {
+        if !self.active {
+            return range;
+        }
+        InstRange::new(self.orig_pos(range.from), self.orig_pos(range.to))
+    }
+}
+
+/// View of a function in which all critical edges are split.
+pub struct SplitFunction<'a, F> {
+    pub func: &'a F,
+    pub split: &'a EdgeSplit,
+}
+
+impl<F: Function> SplitFunction<'_, F> {
+    /// Returns the original instruction for an instruction in an original
+    /// block, or `None` for the jump of an edge block.
+    #[inline]
+    fn orig_inst(&self, inst: Inst) -> Option<Inst> {
+        self.split.orig_inst(inst)
+    }
+}
+
+impl<F: Function> Function for SplitFunction<'_, F> {
+    #[inline]
+    fn num_insts(&self) -> usize {
+        self.split.inst_blocks.len()
+    }
+
+    #[inline]
+    fn num_blocks(&self) -> usize {
+        self.split.blocks.len()
+    }
+
+    #[inline]
+    fn block_insts(&self, block: Block) -> InstRange {
+        self.split.blocks[block].insts
+    }
+
+    #[inline]
+    fn inst_block(&self, inst: Inst) -> Block {
+        self.split.inst_blocks[inst]
+    }
+
+    #[inline]
+    fn block_succs(&self, block: Block) -> &[Block] {
+        let (start, end) = self.split.blocks[block].succs;
+        &self.split.cfg_edges[start as usize..end as usize]
+    }
+
+    #[inline]
+    fn block_preds(&self, block: Block) -> &[Block] {
+        let (start, end) = self.split.blocks[block].preds;
+        &self.split.cfg_edges[start as usize..end as usize]
+    }
+
+    #[inline]
+    fn block_immediate_dominator(&self, block: Block) -> Option<Block> {
+        self.split.blocks[block].immediate_dominator.expand()
+    }
+
+    #[inline]
+    fn block_params(&self, block: Block) -> &[Value] {
+        match self.split.blocks[block].kind {
+            BlockKind::Orig {
+                block,
+                inst_offset: _,
+            } => self.func.block_params(block),
+            BlockKind::Edge { .. } => &[],
+        }
+    }
+
+    #[inline]
+    fn terminator_kind(&self, inst: Inst) -> Option<TerminatorKind> {
+        match self.orig_inst(inst) {
+            Some(inst) => self.func.terminator_kind(inst),
+            None => Some(TerminatorKind::Jump),
+        }
+    }
+
+    #[inline]
+    fn jump_blockparams(&self, block: Block) -> &[Value] {
+        match self.split.blocks[block].kind {
+            BlockKind::Orig {
+                block,
+                inst_offset: _,
+            } => self.func.jump_blockparams(block),
+            BlockKind::Edge {
+                pred,
+                succ: _,
+                succ_idx,
+            } => self.func.branch_blockparams(pred, succ_idx as usize),
+        }
+    }
+
+    #[inline]
+    fn block_frequency(&self, block: Block) -> f32 {
+        self.split.blocks[block].frequency
+    }
+
+    #[inline]
+    fn block_is_critical_edge(&self, block: Block) -> bool {
+        match self.split.blocks[block].kind {
+            BlockKind::Orig {
+                block,
+                inst_offset: _,
+            } => self.func.block_is_critical_edge(block),
+            BlockKind::Edge { .. } => true,
+        }
+    }
+
+    #[inline]
+    fn allows_critical_edges(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn inst_operands(&self, inst: Inst) -> &[Operand] {
+        match self.orig_inst(inst) {
+            Some(inst) => self.func.inst_operands(inst),
+            None => &[],
+        }
+    }
+
+    #[inline]
+    fn inst_clobbers(&self, inst: Inst) -> impl Iterator<Item = RegUnit> {
+        self.orig_inst(inst)
+            .map(|inst| self.func.inst_clobbers(inst))
+            .into_iter()
+            .flatten()
+    }
+
+    #[inline]
+    fn num_values(&self) -> usize {
+        self.func.num_values()
+    }
+
+    #[inline]
+    fn value_bank(&self, value: Value) -> RegBank {
+        self.func.value_bank(value)
+    }
+
+    #[inline]
+    fn num_value_groups(&self) -> usize {
+        self.func.num_value_groups()
+    }
+
+    #[inline]
+    fn value_group_members(&self, group: ValueGroup) -> &[Value] {
+        self.func.value_group_members(group)
+    }
+
+    #[inline]
+    fn num_alternative_sets(&self) -> usize {
+        self.func.num_alternative_sets()
+    }
+
+    #[inline]
+    fn alternative_set(&self, set: AlternativeSet) -> &[(OperandConstraint, f32)] {
+        self.func.alternative_set(set)
+    }
+
+    #[inline]
+    fn value_hint(&self, value: Value) -> Option<(PhysReg, f32)> {
+        self.func.value_hint(value)
+    }
+
+    #[inline]
+    fn value_pinned_reg(&self, value: Value) -> Option<PhysReg> {
+        self.func.value_pinned_reg(value)
+    }
+
+    #[inline]
+    fn can_rematerialize(&self, value: Value) -> Option<(f32, RegClass)> {
+        self.func.can_rematerialize(value)
+    }
+
+    #[inline]
+    fn remat_input(&self, value: Value) -> Option<Value> {
+        self.func.remat_input(value)
+    }
+
+    #[inline]
+    fn can_eliminate_dead_inst(&self, inst: Inst) -> bool {
+        self.orig_inst(inst)
+            .is_some_and(|inst| self.func.can_eliminate_dead_inst(inst))
+    }
+
+    #[inline]
+    fn is_copy(&self, inst: Inst) -> bool {
+        self.orig_inst(inst)
+            .is_some_and(|inst| self.func.is_copy(inst))
+    }
+
+    #[inline]
+    fn value_scope_end(&self, value: Value) -> Option<Inst> {
+        self.func
+            .value_scope_end(value)
+            .map(|inst| self.split.split_inst(self.func, inst))
+    }
+
+    #[inline]
+    fn no_edits_before(&self, inst: Inst) -> bool {
+        self.orig_inst(inst)
+            .is_some_and(|inst| self.func.no_edits_before(inst))
+    }
+
+    #[inline]
+    fn is_reference(&self, value: Value) -> bool {
+        self.func.is_reference(value)
+    }
+
+    #[inline]
+    fn is_safepoint(&self, inst: Inst) -> bool {
+        self.orig_inst(inst)
+            .is_some_and(|inst| self.func.is_safepoint(inst))
+    }
+}
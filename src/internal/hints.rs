@@ -4,6 +4,11 @@
 //! reservation which is connected to the rest of a value's live range with an
 //! implicit copy. To minimize copies, we prefer allocating live ranges linked
 //! to a fixed def/use to that fixed register.
+//!
+//! Clients can also provide a preferred register for a value with
+//! `Function::value_hint`. This is recorded as a hint at every definition and
+//! use of the value so that it is weighed in the same way as the hints from
+//! fixed defs/uses, including after a live range is split.
 
 use alloc::vec;
 use alloc::vec::Vec;
@@ -159,6 +164,27 @@ impl Hints {
         }
     }
 
+    /// Records a client-provided hint for a definition or use of a value at
+    /// the given instruction.
+    ///
+    /// This is an incoming hint at the instruction itself, which places it in
+    /// the segment containing the def/use.
+    pub fn add_value_hint(
+        &mut self,
+        value: Value,
+        inst: Inst,
+        reg: PhysReg,
+        strength: f32,
+        func: &impl Function,
+    ) {
+        self.hints.push(Hint {
+            key: HintKey::incoming(value, inst),
+            reg,
+            weight: strength * func.block_frequency(func.inst_block(inst)),
+        });
+        self.has_hint.insert(value);
+    }
+
     /// Sorts hints so that they can be efficiently queried by
     /// `hints_for_segment`.
     pub fn sort_hints(&mut self) {
@@ -0,0 +1,437 @@
+//! Precise value locations in terms of the output instruction stream.
+//!
+//! Unlike `Output::value_locations`, which is derived directly from the live
+//! range segments, this walks the output instructions of each block and
+//! tracks the contents of every allocation as moves, rematerializations and
+//! instructions are executed. This makes the result accurate in the gaps
+//! between inserted moves.
+//!
+//! The contents of allocations at the start of a block are the ones that are
+//! common to the end of all of its predecessors. This is a forward "must"
+//! dataflow problem which is solved iteratively: loop back-edges are initially
+//! ignored and blocks are re-processed until the state at the end of every
+//! block stops changing. The location ranges are only recorded in a final
+//! pass once the states are stable.
+//!
+//! Edge blocks are treated as separate blocks between their predecessor and
+//! successor. Edges without an edge block connect their predecessor and
+//! successor directly. If edge edits are separated from the blocks then they
+//! are applied to the state at the end of the predecessor, but since they have
+//! no output position they don't produce any location ranges.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::slice;
+
+use crate::debug_utils::postorder::PostOrder;
+use crate::entity::SecondaryMap;
+use crate::function::{Block, Function, OperandConstraint, OperandKind, Value};
+use crate::internal::edge_split::{edge_blockparams, pred_succ_idx};
+use crate::output::{Allocation, AllocationKind, Output, OutputInst, OutputPos};
+use crate::reginfo::{RegInfo, RegUnit};
+
+/// A value held in an allocation since the given output position.
+#[derive(Clone, Copy)]
+struct Entry {
+    alloc: Allocation,
+    value: Value,
+    start: u32,
+}
+
+struct Context<'a, 'b, F, R> {
+    output: &'b Output<'a, F, R>,
+    func: &'a F,
+    reginfo: &'a R,
+
+    /// Allocations holding a value at the current position.
+    state: Vec<Entry>,
+
+    /// Contents of the registers overlapping a register which was evicted to
+    /// a spill slot by the move resolver, as `(slot, reg, alloc, value)`.
+    ///
+    /// This is needed to restore values held in a register which only
+    /// partially overlaps the evicted one.
+    evicted: Vec<(Allocation, Allocation, Allocation, Value)>,
+
+    /// Contents of allocations at the end of each block, sorted.
+    ///
+    /// This is `None` for blocks which haven't been processed yet.
+    block_out: SecondaryMap<Block, Option<Vec<(Allocation, Value)>>>,
+
+    /// Finished location ranges, only recorded in the final pass.
+    ranges: Option<Vec<(Value, OutputPos, OutputPos, Allocation)>>,
+}
+
+/// Computes the location ranges of all values in the output instruction
+/// stream.
+pub fn compute<F: Function, R: RegInfo>(
+    output: &Output<'_, F, R>,
+) -> Vec<(Value, OutputPos, OutputPos, Allocation)> {
+    let mut ctx = Context {
+        output,
+        func: output.func,
+        reginfo: output.reginfo,
+        state: vec![],
+        evicted: vec![],
+        block_out: SecondaryMap::with_max_index(
+            output.func.num_blocks() + output.edge_blocks().len(),
+        ),
+        ranges: None,
+    };
+
+    // Process blocks in reverse post-order so that at least one predecessor
+    // of each block has already been processed. Edge blocks are processed
+    // right after their predecessor.
+    let postorder = PostOrder::for_function(output.func);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in postorder.cfg_postorder().rev() {
+            changed |= ctx.process_block(block);
+            for edge in output
+                .edge_blocks()
+                .iter()
+                .filter(|edge| edge.pred == block)
+            {
+                changed |= ctx.process_block(edge.block);
+            }
+        }
+    }
+
+    ctx.ranges = Some(vec![]);
+    for block in 0..output.func.num_blocks() + output.edge_blocks().len() {
+        ctx.process_block(Block::new(block));
+    }
+    ctx.ranges.unwrap()
+}
+
+impl<F: Function, R: RegInfo> Context<'_, '_, F, R> {
+    /// Simulates the output instructions of a block.
+    ///
+    /// Returns whether the state at the end of the block changed.
+    fn process_block(&mut self, block: Block) -> bool {
+        // Only keep the allocation contents which are the same at the end of
+        // all predecessors. Predecessors that haven't been processed yet are
+        // ignored.
+        //
+        // Edges to a block with parameters move the outgoing arguments into
+        // the location of the incoming parameters, so each argument is also
+        // considered to be a copy of the corresponding parameter.
+        let edge_block = block
+            .index()
+            .checked_sub(self.func.num_blocks())
+            .map(|idx| &self.output.edge_blocks()[idx]);
+        let (params, preds) = match edge_block {
+            Some(edge) => (&[][..], slice::from_ref(&edge.pred)),
+            None => (self.func.block_params(block), self.func.block_preds(block)),
+        };
+        let mut outs: Vec<Vec<(Allocation, Value)>> = vec![];
+        for (pred_idx, &orig_pred) in preds.iter().enumerate() {
+            // Multiple edges from the same predecessor are matched up with its
+            // successors in order.
+            let succ_idx = match edge_block {
+                Some(edge) => edge.succ_idx,
+                None => pred_succ_idx(self.func, preds, pred_idx, block),
+            };
+            let pred = if edge_block.is_none() {
+                self.output
+                    .edge_block_between(orig_pred, succ_idx)
+                    .unwrap_or(orig_pred)
+            } else {
+                orig_pred
+            };
+            let Some(out) = &self.block_out[pred] else {
+                continue;
+            };
+            let mut out = out.clone();
+            if self.output.edge_edits_separated() && edge_block.is_none() {
+                self.apply_edge_edits(&mut out, orig_pred, succ_idx);
+            }
+            if !params.is_empty() {
+                let args = edge_blockparams(self.func, orig_pred, succ_idx);
+                for i in 0..out.len() {
+                    let (alloc, value) = out[i];
+                    for (&arg, &param) in args.iter().zip(params) {
+                        if arg == value {
+                            out.push((alloc, param));
+                        }
+                    }
+                }
+                out.sort_unstable();
+            }
+            outs.push(out);
+        }
+        self.state.clear();
+        self.evicted.clear();
+        if let Some((first, rest)) = outs.split_first() {
+            self.state.extend(
+                first
+                    .iter()
+                    .filter(|entry| rest.iter().all(|out| out.binary_search(entry).is_ok()))
+                    .map(|&(alloc, value)| Entry {
+                        alloc,
+                        value,
+                        start: 0,
+                    }),
+            );
+        }
+
+        // Eliminated copies still define their destination value, but they
+        // don't occupy a position in the output.
+        let mut index = 0;
+        for inst in self.output.output_insts_with_copies(block) {
+            let next = match inst {
+                OutputInst::Inst { inst, .. } if self.output.is_eliminated_copy(inst) => index,
+                _ => index + 1,
+            };
+            match inst {
+                OutputInst::Move { .. } | OutputInst::Rematerialize { .. } => {
+                    self.process_edit(block, inst, next);
+                }
+                OutputInst::Inst {
+                    inst,
+                    operand_allocs,
+                } => {
+                    // A moving garbage collector only updates the references
+                    // in the stack map, any other copies become stale.
+                    if self.func.is_safepoint(inst) {
+                        let output = self.output;
+                        let (func, ranges) = (self.func, &mut self.ranges);
+                        self.state.retain(|entry| {
+                            let keep = !func.is_reference(entry.value)
+                                || output
+                                    .stack_map(inst)
+                                    .any(|slot| slot == (entry.value, entry.alloc));
+                            if !keep {
+                                push_range(ranges, block, *entry, next);
+                            }
+                            keep
+                        });
+                    }
+
+                    for unit in self.func.inst_clobbers(inst) {
+                        self.kill_unit(block, unit, next);
+                    }
+
+                    let operands = self.func.inst_operands(inst);
+                    for (&op, &alloc) in operands.iter().zip(operand_allocs) {
+                        match op.kind() {
+                            OperandKind::Def(value) | OperandKind::EarlyDef(value) => {
+                                self.kill(block, alloc, next);
+                                self.add(alloc, value, next);
+                            }
+                            OperandKind::DefGroup(group) | OperandKind::EarlyDefGroup(group) => {
+                                // Only the first register of the group is
+                                // recorded in the allocation, recover the full
+                                // group from the operand's class.
+                                let class = match op.constraint() {
+                                    OperandConstraint::Class(class) => class,
+                                    OperandConstraint::Reuse(target) => {
+                                        match operands[target].constraint() {
+                                            OperandConstraint::Class(class) => class,
+                                            OperandConstraint::Fixed(_)
+                                            | OperandConstraint::Reuse(_)
+                                            | OperandConstraint::Alternatives(_) => unreachable!(),
+                                        }
+                                    }
+                                    OperandConstraint::Fixed(_)
+                                    | OperandConstraint::Alternatives(_) => unreachable!(),
+                                };
+                                let AllocationKind::PhysReg(reg) = alloc.kind() else {
+                                    unreachable!();
+                                };
+                                let reg_group = self
+                                    .reginfo
+                                    .group_for_reg(reg, 0, class)
+                                    .expect("group allocation not in class");
+                                let members = self.reginfo.reg_group_members(reg_group);
+                                let values = self.func.value_group_members(group);
+                                for (&member, &value) in members.iter().zip(values) {
+                                    let alloc = Allocation::reg(member);
+                                    self.kill(block, alloc, next);
+                                    self.add(alloc, value, next);
+                                }
+                            }
+                            OperandKind::Use(_)
+                            | OperandKind::UseGroup(_)
+                            | OperandKind::NonAllocatable => {}
+                        }
+                    }
+                }
+            }
+            index = next;
+        }
+
+        // Close all the remaining ranges at the end of the block.
+        for &entry in &self.state {
+            push_range(&mut self.ranges, block, entry, index);
+        }
+
+        let mut out: Vec<_> = self
+            .state
+            .iter()
+            .map(|entry| (entry.alloc, entry.value))
+            .collect();
+        out.sort_unstable();
+        let changed = self.block_out[block].as_ref() != Some(&out);
+        self.block_out[block] = Some(out);
+        changed
+    }
+
+    /// Simulates a move or rematerialization.
+    fn process_edit(&mut self, block: Block, inst: OutputInst<'_>, next: u32) {
+        match inst {
+            OutputInst::Move { from, to, value } => {
+                // Evictions and restores don't record a value so use
+                // whatever is currently in the source allocation.
+                let value = value.or_else(|| {
+                    self.state
+                        .iter()
+                        .find(|entry| entry.alloc == from)
+                        .map(|entry| entry.value)
+                });
+                if let (AllocationKind::PhysReg(_), AllocationKind::SpillSlot(_)) =
+                    (from.kind(), to.kind())
+                {
+                    self.kill(block, to, next);
+                    for entry in &self.state {
+                        if overlaps(self.reginfo, entry.alloc, from) {
+                            self.evicted.push((to, from, entry.alloc, entry.value));
+                        }
+                    }
+                } else {
+                    self.kill(block, to, next);
+                }
+                if let Some(value) = value {
+                    self.add(to, value, next);
+                }
+
+                // Restoring an evicted register also restores any values
+                // in registers that overlap it.
+                if let (AllocationKind::SpillSlot(_), AllocationKind::PhysReg(_)) =
+                    (from.kind(), to.kind())
+                {
+                    for i in 0..self.evicted.len() {
+                        let (slot, reg, alloc, value) = self.evicted[i];
+                        if slot == from && reg == to && alloc != to {
+                            self.add(alloc, value, next);
+                        }
+                    }
+                }
+            }
+            OutputInst::Rematerialize {
+                value,
+                to,
+                input: _,
+            } => {
+                self.kill(block, to, next);
+                self.add(to, value, next);
+            }
+            OutputInst::Inst { .. } => unreachable!(),
+        }
+    }
+
+    /// Applies the edge edits from `pred` to its successor at `succ_idx` to the
+    /// contents of allocations at the end of `pred`.
+    fn apply_edge_edits(
+        &mut self,
+        out: &mut Vec<(Allocation, Value)>,
+        pred: Block,
+        succ_idx: usize,
+    ) {
+        let mut edits = self.output.edge_edits(pred, succ_idx).peekable();
+        if edits.peek().is_none() {
+            return;
+        }
+
+        // Edge edits have no output position so no ranges are recorded.
+        let ranges = self.ranges.take();
+        self.state.clear();
+        self.evicted.clear();
+        self.state.extend(out.iter().map(|&(alloc, value)| Entry {
+            alloc,
+            value,
+            start: 0,
+        }));
+        for inst in edits {
+            self.process_edit(pred, inst, 0);
+        }
+        out.clear();
+        out.extend(self.state.iter().map(|entry| (entry.alloc, entry.value)));
+        out.sort_unstable();
+        self.ranges = ranges;
+    }
+
+    /// Records that `value` is held in `alloc` from output position `start`.
+    fn add(&mut self, alloc: Allocation, value: Value, start: u32) {
+        self.state.push(Entry {
+            alloc,
+            value,
+            start,
+        });
+    }
+
+    /// Ends the ranges of all values held in an allocation overlapping
+    /// `alloc`.
+    fn kill(&mut self, block: Block, alloc: Allocation, end: u32) {
+        let reginfo = self.reginfo;
+        let ranges = &mut self.ranges;
+        self.state.retain(|entry| {
+            let overlaps = overlaps(reginfo, entry.alloc, alloc);
+            if overlaps {
+                push_range(ranges, block, *entry, end);
+            }
+            !overlaps
+        });
+        self.evicted.retain(|&(slot, _, _, _)| slot != alloc);
+    }
+
+    /// Ends the ranges of all values held in a register containing `unit`.
+    fn kill_unit(&mut self, block: Block, unit: RegUnit, end: u32) {
+        let reginfo = self.reginfo;
+        let ranges = &mut self.ranges;
+        self.state.retain(|entry| {
+            let overlaps = match entry.alloc.kind() {
+                AllocationKind::PhysReg(reg) => reginfo.reg_units(reg).any(|u| u == unit),
+                AllocationKind::SpillSlot(_) => false,
+            };
+            if overlaps {
+                push_range(ranges, block, *entry, end);
+            }
+            !overlaps
+        });
+    }
+}
+
+/// Returns whether 2 allocations share any storage.
+fn overlaps(reginfo: &impl RegInfo, a: Allocation, b: Allocation) -> bool {
+    match (a.kind(), b.kind()) {
+        (AllocationKind::PhysReg(a), AllocationKind::PhysReg(b)) => reginfo
+            .reg_units(a)
+            .any(|unit| reginfo.reg_units(b).any(|u| u == unit)),
+        (AllocationKind::SpillSlot(a), AllocationKind::SpillSlot(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Adds a finished range, unless it is empty or ranges are not being recorded.
+fn push_range(
+    ranges: &mut Option<Vec<(Value, OutputPos, OutputPos, Allocation)>>,
+    block: Block,
+    entry: Entry,
+    end: u32,
+) {
+    if let Some(ranges) = ranges
+        && entry.start < end
+    {
+        ranges.push((
+            entry.value,
+            OutputPos {
+                block,
+                index: entry.start,
+            },
+            OutputPos { block, index: end },
+            entry.alloc,
+        ));
+    }
+}
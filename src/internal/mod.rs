@@ -3,17 +3,22 @@
 
 pub(crate) mod allocations;
 pub(crate) mod allocator;
+pub(crate) mod alternatives;
 pub(crate) mod coalescing;
+pub(crate) mod edge_split;
 pub(crate) mod hints;
 pub(crate) mod live_range;
+pub(crate) mod location_ranges;
 pub(crate) mod move_optimizer;
 pub(crate) mod move_resolver;
 pub(crate) mod reg_matrix;
+pub(crate) mod safepoints;
 pub(crate) mod spill_allocator;
 pub(crate) mod split_placement;
 pub(crate) mod uses;
 pub(crate) mod value_live_ranges;
 pub(crate) mod virt_regs;
+pub(crate) mod written_regs;
 
 // Publicly exposed only for fuzzing.
 #[allow(missing_docs)]
@@ -40,6 +40,7 @@ use core::fmt;
 use smallvec::{SmallVec, smallvec};
 
 use super::allocations::Allocations;
+use super::alternatives::AlternativeResolver;
 use super::coalescing::Coalescing;
 use super::move_resolver::{Edit, MoveResolver};
 use super::spill_allocator::SpillAllocator;
@@ -47,7 +48,7 @@ use crate::entity::packed_option::{PackedOption, ReservedValue};
 use crate::entity::sparse::Entry;
 use crate::entity::{EntitySet, PrimaryMap, SecondaryMap, SparseMap};
 use crate::function::{
-    Block, Function, Inst, Operand, OperandConstraint, OperandKind, Value, ValueGroup,
+    Block, Function, Inst, MOVE_COST, Operand, OperandConstraint, OperandKind, Value, ValueGroup,
 };
 use crate::output::{Allocation, AllocationKind, SpillSlot};
 use crate::reginfo::{MAX_REG_UNITS, PhysReg, PhysRegSet, RegInfo, RegUnit, RegUnitSet};
@@ -374,6 +375,9 @@ struct StateTracker {
     /// If that block dominates the current block then all spills in the current
     /// block are redundant and can be eliminated.
     last_spilled_in: SecondaryMap<Value, PackedOption<Block>>,
+
+    /// Selection of alternative constraints for the current instruction.
+    alternatives: AlternativeResolver,
 }
 
 impl fmt::Display for StateTracker {
@@ -412,6 +416,7 @@ impl StateTracker {
             def_units: RegUnitSet::new(),
             emergency_spill: vec![],
             last_spilled_in: SecondaryMap::new(),
+            alternatives: AlternativeResolver::new(),
         }
     }
 
@@ -455,6 +460,19 @@ impl StateTracker {
         }
     }
 
+    /// Forgets the locations of all reference values at a safepoint.
+    ///
+    /// A moving garbage collector only updates the location reported in the
+    /// stack map, which leaves any other copies of a reference stale. We
+    /// conservatively forget all copies, including the one in the stack map.
+    fn forget_references(&mut self, func: &impl Function) {
+        self.value_regs.retain(|value, _| !func.is_reference(value));
+        self.last_unit_write
+            .retain(|_, &mut (_, value)| !func.is_reference(value));
+        self.spillslot_values
+            .retain(|_, &mut value| !func.is_reference(value));
+    }
+
     /// Handles the definition of a value.
     ///
     /// This consists of 3 parts:
@@ -526,7 +544,7 @@ impl StateTracker {
         };
         let class = match op.constraint() {
             OperandConstraint::Class(class) => class,
-            OperandConstraint::Fixed(_) => unreachable!(),
+            OperandConstraint::Fixed(_) | OperandConstraint::Alternatives(_) => unreachable!(),
             OperandConstraint::Reuse(idx) => {
                 let OperandConstraint::Class(class) = func.inst_operands(inst)[idx].constraint()
                 else {
@@ -649,9 +667,18 @@ impl StateTracker {
                 edits = rest;
             }
 
+            // Eliminated instructions don't define any values.
+            if move_resolver.is_dead_inst(inst) {
+                continue;
+            }
+
             trace!("Values: {self}");
             trace!("Pre-processing {inst}");
 
+            if func.is_safepoint(inst) {
+                self.forget_references(func);
+            }
+
             // Process def operands.
             self.def_units.clear();
             for (&op, &alloc) in func
@@ -738,7 +765,12 @@ impl StateTracker {
                     // - each value is only ever spilled to a single spill slot.
                     // - we require that blocks be topologically ordered with
                     //   regards to dominance.
-                    if self.slot_for_value[value].expand() == Some(slot) {
+                    //
+                    // This doesn't apply to reference values since the copy in
+                    // the spill slot may have become stale at a safepoint.
+                    if self.slot_for_value[value].expand() == Some(slot)
+                        && !func.is_reference(value)
+                    {
                         if let Some(prev_block) = self.last_spilled_in[value].expand() {
                             if func.block_dominates(prev_block, block) {
                                 stat!(stats, optimized_redundant_spill);
@@ -756,19 +788,30 @@ impl StateTracker {
             }
 
             // We couldn't eliminate the move entirely, but we may be able to
-            // turn a load from memory into a register move if the desired value
-            // is already present in another register.
-            if let Some(from) = edit.from.expand() {
-                if from.is_memory(reginfo) {
-                    if let Some(&regs_with_value) = self.value_regs.get(value) {
-                        if let Some(reg) = regs_with_value
-                            .into_iter()
-                            .find(|&reg| !reginfo.is_memory(reg))
-                        {
+            // turn a load from memory or an expensive rematerialization into a
+            // register move if the desired value is already present in another
+            // register.
+            let replace_with_move = match edit.from.expand() {
+                Some(from) => from.is_memory(reginfo),
+                None => func
+                    .can_rematerialize(value)
+                    .is_some_and(|(cost, _class)| cost > MOVE_COST),
+            };
+            if replace_with_move {
+                if let Some(&regs_with_value) = self.value_regs.get(value) {
+                    if let Some(reg) = regs_with_value
+                        .into_iter()
+                        .find(|&reg| !reginfo.is_memory(reg))
+                    {
+                        if edit.from.is_some() {
                             trace!("Optimizing reload to use {reg}");
                             stat!(stats, optimized_reload_to_move);
-                            edit.from = Some(Allocation::reg(reg)).into();
+                        } else {
+                            trace!("Optimizing remat to use {reg}");
+                            stat!(stats, optimized_remat_to_move);
+                            edit.input = None.into();
                         }
+                        edit.from = Some(Allocation::reg(reg)).into();
                     }
                 }
             }
@@ -794,7 +837,7 @@ impl StateTracker {
             trace!("Blockparam {value} in {alloc}");
             self.def_value(value, alloc, block, reginfo);
         }
-        let mut edits = move_resolver.edits_from_mut(func.block_insts(block).from);
+        let (mut edits, dead_insts) = move_resolver.edits_from_mut(func.block_insts(block).from);
 
         for inst in func.block_insts(block).iter() {
             // Process and optimize any edits before the current instruction.
@@ -809,9 +852,18 @@ impl StateTracker {
                 edits = &mut edits[1..];
             }
 
+            // Eliminated instructions don't define any values.
+            if dead_insts.contains(inst) {
+                continue;
+            }
+
             trace!("Values: {self}");
             trace!("Optimizing {inst}");
 
+            if func.is_safepoint(inst) {
+                self.forget_references(func);
+            }
+
             // Process early def operands.
             self.def_units.clear();
             self.reused_operands.clear();
@@ -836,6 +888,9 @@ impl StateTracker {
 
             // Search for Use operands that have been assigned to memory and try
             // to replace them with a register that already contains the value.
+            let Ok(constraints) = self.alternatives.resolve(inst, func, reginfo) else {
+                unreachable!();
+            };
             for (idx, (&op, alloc)) in func
                 .inst_operands(inst)
                 .iter()
@@ -849,7 +904,7 @@ impl StateTracker {
                     if self.reused_operands.contains(&idx) {
                         continue;
                     }
-                    if let OperandConstraint::Class(class) = op.constraint() {
+                    if let OperandConstraint::Class(class) = constraints[idx] {
                         if let Some(&regs_with_value) = self.value_regs.get(value) {
                             let class_members = reginfo.class_members(class);
                             if let Some(reg) = (class_members & regs_with_value)
@@ -16,10 +16,10 @@
 
 use alloc::vec;
 use alloc::vec::Vec;
-use core::fmt;
 use core::hash::{Hash, Hasher};
+use core::{fmt, mem};
 
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 use rustc_hash::FxBuildHasher;
 
 use super::allocations::Allocations;
@@ -28,10 +28,12 @@ use super::live_range::{Slot, ValueSegment};
 use super::parallel_moves::ParallelMoves;
 use super::reg_matrix::RegMatrix;
 use super::spill_allocator::SpillAllocator;
+use super::split_placement::in_edit_sequence;
 use super::uses::{Use, UseKind, Uses};
 use super::virt_regs::VirtRegs;
+use crate::entity::EntitySet;
 use crate::entity::packed_option::PackedOption;
-use crate::function::{Block, Function, Inst, TerminatorKind, Value};
+use crate::function::{Block, Function, Inst, OperandKind, TerminatorKind, Value};
 use crate::internal::live_range::{LiveRangeSegment, ValueSegmentComponent};
 use crate::output::{Allocation, AllocationKind};
 use crate::reginfo::{RegClass, RegInfo};
@@ -112,6 +114,9 @@ impl MovePosition {
 /// - Emergency reload: value:None from:Some(spillslot) to:None(reg)
 /// - Rematerialization: value:Some from:None to:Some
 ///
+/// `input` is only set for rematerializations of values which depend on an
+/// input value, and holds the register containing that input.
+///
 /// If `to` is `None` then it means the entire edit has be optimized away to a
 /// nop. This is only done in the move optimization pass.
 #[derive(Debug, Clone, Copy)]
@@ -119,6 +124,7 @@ pub struct Edit {
     pub value: PackedOption<Value>,
     pub from: PackedOption<Allocation>,
     pub to: PackedOption<Allocation>,
+    pub input: PackedOption<Allocation>,
 }
 
 impl fmt::Display for Edit {
@@ -126,6 +132,8 @@ impl fmt::Display for Edit {
         if let Some(to) = self.to.expand() {
             if let Some(from) = self.from.expand() {
                 write!(f, "move {:?} from {} to {to}", self.value, from,)
+            } else if let Some(input) = self.input.expand() {
+                write!(f, "remat {} in {to} from {input}", self.value.unwrap())
             } else {
                 write!(f, "remat {} in {to}", self.value.unwrap())
             }
@@ -175,24 +183,81 @@ impl Hash for HalfMoveKey {
 
 pub struct MoveResolver {
     source_half_moves: HashMap<HalfMoveKey, Allocation, FxBuildHasher>,
+
+    /// Half-moves at the start of a block with a single predecessor which
+    /// transfer a value from the end of the predecessor block.
+    ///
+    /// These moves are on the CFG edge, while any other moves at the same
+    /// position belong to the first instruction of the block.
+    edge_half_moves: HashSet<HalfMoveKey, FxBuildHasher>,
     dest_half_moves: Vec<(MovePosition, Value, Allocation)>,
     tied_moves: Vec<TiedMove>,
     tied_operands: Vec<TiedOperands>,
     edits: Vec<(Inst, Edit)>,
+    scratch_edits: Vec<(Inst, Edit)>,
+
+    /// Number of edits produced for each parallel move on a CFG edge: late
+    /// moves on a jump and early moves from the predecessor at the start of a
+    /// block with a single predecessor.
+    edge_edit_counts: Vec<(MovePosition, u32)>,
+
+    /// Copy of the edits on each CFG edge, see `collect_edge_edits`.
+    edge_edits: Vec<(Inst, Edit)>,
+
+    /// Range of `edge_edits` for each CFG edge with edits, as
+    /// `(pred, succ_idx, start, end)`, sorted by edge.
+    edge_edit_ranges: Vec<(Block, u32, u32, u32)>,
+
+    /// Whether the edge edits were removed from `edits`.
+    edge_edits_separated: bool,
+
     blockparam_allocs: Vec<(Block, Value, Allocation)>,
     parallel_move_resolver: ParallelMoves,
+
+    /// Values whose definition is read by a use or a move, as opposed to only
+    /// being rematerialized.
+    needed_defs: EntitySet<Value>,
+
+    /// Values which are the input of a rematerializable value.
+    remat_inputs: EntitySet<Value>,
+
+    /// Final allocations of the values in `remat_inputs`, sorted by value and
+    /// then by start point.
+    ///
+    /// Each range only covers the points at which the value is held in the
+    /// allocation once the parallel move at that point has been performed.
+    remat_input_segments: Vec<(Value, LiveRangeSegment, Allocation)>,
+
+    /// Pure instructions whose outputs are all unused and which are therefore
+    /// eliminated from the output.
+    dead_insts: EntitySet<Inst>,
+
+    /// Copy instructions whose source and destination have the same
+    /// allocation and which are therefore eliminated from the output.
+    eliminated_copies: EntitySet<Inst>,
 }
 
 impl MoveResolver {
     pub fn new() -> Self {
         Self {
             source_half_moves: HashMap::default(),
+            edge_half_moves: HashSet::default(),
             dest_half_moves: vec![],
             tied_moves: vec![],
             tied_operands: vec![],
             edits: vec![],
+            scratch_edits: vec![],
+            edge_edit_counts: vec![],
+            edge_edits: vec![],
+            edge_edit_ranges: vec![],
+            edge_edits_separated: false,
             blockparam_allocs: vec![],
             parallel_move_resolver: ParallelMoves::new(),
+            needed_defs: EntitySet::new(),
+            remat_inputs: EntitySet::new(),
+            remat_input_segments: vec![],
+            dead_insts: EntitySet::new(),
+            eliminated_copies: EntitySet::new(),
         }
     }
 
@@ -213,10 +278,13 @@ impl MoveResolver {
         move_optimization: MoveOptimizationLevel,
     ) {
         self.source_half_moves.clear();
+        self.edge_half_moves.clear();
         self.dest_half_moves.clear();
         self.tied_moves.clear();
         self.tied_operands.clear();
         self.blockparam_allocs.clear();
+        self.needed_defs.clear_and_resize(func.num_values());
+        self.collect_remat_inputs(func);
 
         let mut ctx = Context {
             func,
@@ -244,6 +312,8 @@ impl MoveResolver {
         for segment in &allocator.empty_segments {
             ctx.process_segment(segment, None);
         }
+        self.remat_input_segments
+            .sort_unstable_by_key(|&(value, live_range, _)| (value, live_range.from));
 
         trace!("Adding half-moves from rematerialized segments");
         for segment in &allocator.remat_segments {
@@ -305,6 +375,8 @@ impl MoveResolver {
             reginfo,
         );
 
+        self.find_dead_insts(stats, func);
+
         // The move optimizer needs per-block information on incoming
         // blockparams for each block, so ensure this is properly sorted.
         if move_optimization != MoveOptimizationLevel::Off {
@@ -331,15 +403,24 @@ impl MoveResolver {
         reginfo: &impl RegInfo,
     ) {
         self.edits.clear();
+        self.edge_edit_counts.clear();
 
         // Ensure that all allocations are filled in at this point.
         allocations.assert_all_assigned();
 
         self.parallel_move_resolver
             .prepare(func, spill_allocator.stack_layout.num_spillslots());
+        // Moves on a CFG edge at the start of a block are resolved separately
+        // from, and before, the moves for the first instruction of the block.
+        let is_edge_move = |&(pos, value, _): &(MovePosition, Value, Allocation)| {
+            self.edge_half_moves.contains(&HalfMoveKey { pos, value })
+        };
         self.dest_half_moves
-            .sort_unstable_by_key(|&(pos, _, _)| pos);
-        for half_moves in self.dest_half_moves.chunk_by(|a, b| a.0 == b.0) {
+            .sort_unstable_by_key(|half_move| (half_move.0, !is_edge_move(half_move)));
+        for half_moves in self
+            .dest_half_moves
+            .chunk_by(|a, b| a.0 == b.0 && is_edge_move(a) == is_edge_move(b))
+        {
             let pos = half_moves[0].0;
             trace!("Processing parallel moves at {pos}:");
 
@@ -352,8 +433,13 @@ impl MoveResolver {
                 if let Some(source) = source {
                     if source != dest {
                         trace!("- Move {value} from {source} to {dest}");
+                        let input = self.remat_input_alloc(pos, value, func, reginfo);
                         self.parallel_move_resolver
-                            .add_move(source, dest, value, func, reginfo);
+                            .add_move(source, dest, value, input, func, reginfo);
+                    } else {
+                        // The value flows into the destination without a
+                        // move, which still requires it to be defined.
+                        self.needed_defs.insert(value);
                     }
                 } else {
                     trace!("- Remat {value} in {dest}");
@@ -388,6 +474,13 @@ impl MoveResolver {
                 |size| spill_allocator.alloc_emergency_spillslot(size),
             );
 
+            // Remember how many edits were produced for moves which are on a
+            // CFG edge rather than inside a block.
+            if pos.is_late() || is_edge_move(&half_moves[0]) {
+                let count = self.parallel_move_resolver.edits().count() as u32;
+                self.edge_edit_counts.push((pos, count));
+            }
+
             trace!("Resolved sequential moves at {pos}:");
             self.edits
                 .extend(self.parallel_move_resolver.edits().map(|edit| {
@@ -416,6 +509,152 @@ impl MoveResolver {
                     (pos.inst(), edit)
                 }));
         }
+
+        // Any moves that remain after parallel move resolution read the value
+        // from the location it was defined in, or a copy of it.
+        for &(_, edit) in &self.edits {
+            if let (Some(value), Some(_)) = (edit.value.expand(), edit.from.expand()) {
+                self.needed_defs.insert(value);
+            }
+        }
+    }
+
+    /// Finds all values which are used as the input of a rematerialization.
+    ///
+    /// Their locations are recorded in `remat_input_segments` as segments are
+    /// processed.
+    fn collect_remat_inputs(&mut self, func: &impl Function) {
+        self.remat_inputs.clear_and_resize(func.num_values());
+        self.remat_input_segments.clear();
+        for value in func.values() {
+            if func.can_rematerialize(value).is_some() {
+                if let Some(input) = func.remat_input(value) {
+                    self.remat_inputs.insert(input);
+                }
+            }
+        }
+    }
+
+    /// Returns the register holding the input of `value` once the parallel
+    /// move at `pos` has been performed, if `value` depends on an input for
+    /// rematerialization.
+    ///
+    /// The input must be live in that register across the move boundary:
+    /// this guarantees that no other move at that boundary overwrites it.
+    fn remat_input_alloc(
+        &self,
+        pos: MovePosition,
+        value: Value,
+        func: &impl Function,
+        reginfo: &impl RegInfo,
+    ) -> Option<Allocation> {
+        func.can_rematerialize(value)?;
+        let input = func.remat_input(value)?;
+
+        // Late moves set up the live-ins of the successor block. The input
+        // can't be one of its block parameters since those are only defined
+        // by these moves.
+        let inst = if pos.is_late() {
+            let block = func.inst_block(pos.inst());
+            let succ = func.block_succs(block)[0];
+            if func.block_params(succ).contains(&input) {
+                return None;
+            }
+            func.block_insts(succ).from
+        } else {
+            pos.inst()
+        };
+        let point = inst.slot(Slot::Boundary);
+
+        let idx = self
+            .remat_input_segments
+            .partition_point(|&(value, live_range, _)| (value, live_range.from) <= (input, point));
+        let &(value, live_range, alloc) = self.remat_input_segments.get(idx.checked_sub(1)?)?;
+        (value == input && live_range.to > point && !alloc.is_memory(reginfo)).then_some(alloc)
+    }
+
+    /// Finds pure instructions whose outputs are never read and which can
+    /// therefore be removed from the output.
+    ///
+    /// This happens when all uses of the instruction's outputs have been
+    /// rematerialized, or if the outputs were never used in the first place.
+    fn find_dead_insts(&mut self, stats: &mut Stats, func: &impl Function) {
+        self.dead_insts.clear_and_resize(func.num_insts());
+        for inst in func.insts() {
+            if !func.can_eliminate_dead_inst(inst) || in_edit_sequence(func, inst) {
+                continue;
+            }
+            let is_dead = func.inst_operands(inst).iter().all(|op| match op.kind() {
+                OperandKind::Def(value) | OperandKind::EarlyDef(value) => {
+                    !self.needed_defs.contains(value)
+                }
+                OperandKind::DefGroup(group) | OperandKind::EarlyDefGroup(group) => func
+                    .value_group_members(group)
+                    .iter()
+                    .all(|&value| !self.needed_defs.contains(value)),
+                OperandKind::Use(_) | OperandKind::UseGroup(_) | OperandKind::NonAllocatable => {
+                    true
+                }
+            });
+            if is_dead {
+                trace!("Eliminating dead instruction {inst}");
+                stat!(stats, dead_insts);
+                self.dead_insts.insert(inst);
+            }
+        }
+    }
+
+    /// Returns whether the given instruction has been eliminated because all
+    /// of its outputs are unused.
+    pub fn is_dead_inst(&self, inst: Inst) -> bool {
+        self.dead_insts.contains(inst)
+    }
+
+    /// Finds copy instructions whose source and destination ended up in the
+    /// same allocation, which makes the copy a no-op.
+    ///
+    /// This must run after move optimization since it can change the
+    /// allocation of `Use` operands.
+    pub fn find_eliminated_copies(
+        &mut self,
+        allocations: &Allocations,
+        stats: &mut Stats,
+        func: &impl Function,
+    ) {
+        self.eliminated_copies.clear_and_resize(func.num_insts());
+        for inst in func.insts() {
+            if !func.is_copy(inst) || self.is_dead_inst(inst) || in_edit_sequence(func, inst) {
+                continue;
+            }
+            let &[a, b] = allocations.inst_allocations(inst) else {
+                unreachable!();
+            };
+            if a == b {
+                trace!("Eliminating copy {inst} in {a}");
+                stat!(stats, eliminated_copies);
+                self.eliminated_copies.insert(inst);
+            }
+        }
+    }
+
+    /// Returns whether the given instruction is a copy which has been
+    /// eliminated because its source and destination have the same allocation.
+    pub fn is_eliminated_copy(&self, inst: Inst) -> bool {
+        self.eliminated_copies.contains(inst)
+    }
+
+    /// Returns whether a segment starts at the definition of a value by an
+    /// instruction which has been eliminated.
+    ///
+    /// This looks at the uses of the segment rather than its live range since
+    /// a fixed-register definition only becomes live at the boundary after its
+    /// instruction.
+    pub fn is_dead_def_segment(&self, segment: &ValueSegment, uses: &Uses) -> bool {
+        uses[segment.use_list].first().is_some_and(|u| {
+            u.kind.is_def()
+                && !matches!(u.kind, UseKind::BlockparamIn { .. })
+                && self.is_dead_inst(u.pos)
+        })
     }
 
     fn emit_source_half_move(&mut self, pos: MovePosition, value: Value, alloc: Allocation) {
@@ -426,6 +665,20 @@ impl MoveResolver {
         debug_assert!(prev.is_none_or(|prev| prev == alloc));
     }
 
+    /// Emits a source half-move for a value flowing from the end of a
+    /// predecessor block to the start of its single successor.
+    fn emit_edge_source_half_move(&mut self, pos: MovePosition, value: Value, alloc: Allocation) {
+        self.emit_source_half_move(pos, value, alloc);
+        self.edge_half_moves.insert(HalfMoveKey { pos, value });
+    }
+
+    /// Emits a destination half-move for a value flowing into the start of a
+    /// block from its single predecessor.
+    fn emit_edge_dest_half_move(&mut self, pos: MovePosition, value: Value, alloc: Allocation) {
+        self.emit_dest_half_move(pos, value, alloc);
+        self.edge_half_moves.insert(HalfMoveKey { pos, value });
+    }
+
     fn emit_dest_half_move(&mut self, pos: MovePosition, value: Value, alloc: Allocation) {
         trace!("Dest half-move at {pos}: {value} to {alloc}");
         self.dest_half_moves.push((pos, value, alloc));
@@ -497,7 +750,8 @@ impl MoveResolver {
                 UseKind::FixedDef { reg: _ }
                 | UseKind::ConstraintConflict {}
                 | UseKind::BlockparamIn { blockparam_idx: _ }
-                | UseKind::BlockparamOut {} => {}
+                | UseKind::BlockparamOut {}
+                | UseKind::KeepAlive {} => {}
             }
         }
     }
@@ -508,10 +762,138 @@ impl MoveResolver {
         &self.edits[idx..]
     }
 
-    /// Returns the list of edits starting from the given instruction.
-    pub fn edits_from_mut(&mut self, inst: Inst) -> &mut [(Inst, Edit)] {
+    /// Returns the list of edits starting from the given instruction, along
+    /// with the set of eliminated instructions.
+    pub fn edits_from_mut(&mut self, inst: Inst) -> (&mut [(Inst, Edit)], &EntitySet<Inst>) {
         let idx = self.edits.partition_point(|&(pos, _)| pos < inst);
-        &mut self.edits[idx..]
+        (&mut self.edits[idx..], &self.dead_insts)
+    }
+
+    /// Inserts additional edits into the edit list.
+    ///
+    /// `append` edits are placed after any existing edits before the same
+    /// instruction and `prepend` edits are placed before them. Both lists must
+    /// be sorted by instruction.
+    pub fn insert_edits(&mut self, append: &[(Inst, Edit)], prepend: &[(Inst, Edit)]) {
+        if append.is_empty() && prepend.is_empty() {
+            return;
+        }
+        mem::swap(&mut self.edits, &mut self.scratch_edits);
+        self.edits.clear();
+        let mut existing = self.scratch_edits.iter().peekable();
+        let mut append = append.iter().peekable();
+        let mut prepend = prepend.iter().peekable();
+        while let Some(inst) = [existing.peek(), append.peek(), prepend.peek()]
+            .into_iter()
+            .flatten()
+            .map(|&&(inst, _)| inst)
+            .min()
+        {
+            while let Some(&edit) = prepend.next_if(|&&(pos, _)| pos == inst) {
+                self.edits.push(edit);
+            }
+            while let Some(&edit) = existing.next_if(|&&(pos, _)| pos == inst) {
+                self.edits.push(edit);
+            }
+            while let Some(&edit) = append.next_if(|&&(pos, _)| pos == inst) {
+                self.edits.push(edit);
+            }
+        }
+    }
+
+    /// Collects the edits on each CFG edge so that they can be queried with
+    /// `edge_edits`. If `separate` is set then these edits are also removed
+    /// from the edits before instructions.
+    ///
+    /// This must be called after all edits have been inserted. Edits inserted
+    /// by `insert_edits` are never placed on a CFG edge: appended edits are
+    /// only placed before safepoints, which can't be terminators, and
+    /// prepended edits are only placed after safepoints, which can't be the
+    /// first instruction of a block. This means that the edge edits for each
+    /// position are still at the start (early) or end (late) of the edits
+    /// before its instruction.
+    pub fn collect_edge_edits(&mut self, func: &impl Function, separate: bool) {
+        self.edge_edits.clear();
+        self.edge_edit_ranges.clear();
+        self.edge_edits_separated = separate;
+        mem::swap(&mut self.edits, &mut self.scratch_edits);
+        self.edits.clear();
+        let mut counts = self.edge_edit_counts.iter().peekable();
+        for chunk in self.scratch_edits.chunk_by(|a, b| a.0 == b.0) {
+            let inst = chunk[0].0;
+            let mut early = 0;
+            let mut late = 0;
+            while counts.next_if(|(pos, _)| pos.inst() < inst).is_some() {}
+            while let Some(&(pos, count)) = counts.next_if(|(pos, _)| pos.inst() == inst) {
+                if pos.is_late() {
+                    late = count as usize;
+                } else {
+                    early = count as usize;
+                }
+            }
+            debug_assert!(early + late <= chunk.len());
+            let (entry, rest) = chunk.split_at(early);
+            let (middle, exit) = rest.split_at(rest.len() - late);
+
+            // Entry edits are only placed in blocks with a single predecessor
+            // and exit edits only in blocks with a single successor, so each
+            // side maps to a single edge.
+            let block = func.inst_block(inst);
+            for (edge, edits) in [
+                (
+                    (!entry.is_empty()).then(|| {
+                        let pred = func.block_preds(block)[0];
+                        let succ_idx = func.block_succs(pred).iter().position(|&s| s == block);
+                        (pred, succ_idx.unwrap())
+                    }),
+                    entry,
+                ),
+                ((!exit.is_empty()).then_some((block, 0)), exit),
+            ] {
+                if let Some((pred, succ_idx)) = edge {
+                    let start = self.edge_edits.len() as u32;
+                    self.edge_edits.extend_from_slice(edits);
+                    self.edge_edit_ranges.push((
+                        pred,
+                        succ_idx as u32,
+                        start,
+                        self.edge_edits.len() as u32,
+                    ));
+                }
+            }
+
+            if separate {
+                self.edits.extend_from_slice(middle);
+            } else {
+                self.edits.extend_from_slice(chunk);
+            }
+        }
+        self.edge_edit_ranges
+            .sort_unstable_by_key(|&(pred, succ_idx, _, _)| (pred, succ_idx));
+    }
+
+    /// Returns the range of edge edits on the CFG edge from `pred` to its
+    /// successor at `succ_idx`, if there are any.
+    pub fn edge_edit_range(&self, pred: Block, succ_idx: usize) -> Option<(u32, u32)> {
+        let idx = self
+            .edge_edit_ranges
+            .binary_search_by_key(&(pred, succ_idx as u32), |&(pred, succ_idx, _, _)| {
+                (pred, succ_idx)
+            })
+            .ok()?;
+        let (_, _, start, end) = self.edge_edit_ranges[idx];
+        Some((start, end))
+    }
+
+    /// Returns all edits on CFG edges, indexed by `edge_edit_range`.
+    pub fn edge_edits(&self) -> &[(Inst, Edit)] {
+        &self.edge_edits
+    }
+
+    /// Returns whether the edits on CFG edges were removed from the edits
+    /// before instructions.
+    pub fn edge_edits_separated(&self) -> bool {
+        self.edge_edits_separated
     }
 
     /// Returns the locations for block parameter values at the start of a
@@ -590,7 +972,7 @@ impl<F: Function> Context<'_, F> {
                     from_same_segment = true;
                 } else {
                     debug_assert!(!segment.live_range.is_empty());
-                    self.move_resolver.emit_dest_half_move(
+                    self.move_resolver.emit_edge_dest_half_move(
                         MovePosition::early(inst),
                         segment.value,
                         alloc.expect("split live range must have an allocation"),
@@ -670,10 +1052,12 @@ impl<F: Function> Context<'_, F> {
             Some(TerminatorKind::Branch) => {
                 // If this terminator produced a fixed definition, use that
                 // as the move source.
-                let alloc_out = match self.fixed_def {
-                    Some((pos, alloc)) if pos == terminator.next() => alloc,
-                    _ => alloc.expect("missing allocation for terminator"),
+                let fixed_def_out = match self.fixed_def {
+                    Some((pos, alloc)) if pos == terminator.next() => Some(alloc),
+                    _ => None,
                 };
+                let alloc_out = fixed_def_out
+                    .unwrap_or_else(|| alloc.expect("missing allocation for terminator"));
 
                 // For branch terminators that target blocks with a single
                 // predecessor, the move must be at the start of each
@@ -688,11 +1072,10 @@ impl<F: Function> Context<'_, F> {
                     // block, which won't trigger the check in handle_block_live_in.
                     // Also it would have the wrong allocation anyways.
                     let block_start = self.func.block_insts(succ).from.slot(Slot::Boundary);
-                    if !(block_start >= segment.live_range.from
-                        && block_start < segment.live_range.to
-                        && self.fixed_def.is_none())
-                    {
-                        self.move_resolver.emit_source_half_move(
+                    let same_segment = block_start >= segment.live_range.from
+                        && block_start < segment.live_range.to;
+                    if !same_segment || fixed_def_out.is_some() {
+                        self.move_resolver.emit_edge_source_half_move(
                             MovePosition::early(self.func.block_insts(succ).from),
                             segment.value,
                             alloc_out,
@@ -961,6 +1344,9 @@ impl<F: Function> Context<'_, F> {
                     );
                 }
             }
+            UseKind::KeepAlive {} => {
+                // Keep-alive uses only extend the live range.
+            }
             UseKind::BlockparamOut {} => {
                 // Treat this like a block live-out for a jump terminator.
                 self.move_resolver.emit_source_half_move(
@@ -1024,6 +1410,33 @@ impl<F: Function> Context<'_, F> {
             segment.live_range, segment.value
         );
 
+        // If the segment containing the definition of a value has any other
+        // uses then the definition is needed. Any uses in other segments will
+        // read the value through a move, which is handled in `resolve_moves`.
+        let uses = &self.uses[segment.use_list];
+        if uses.iter().any(|u| u.kind.is_def()) && uses.iter().any(|u| !u.kind.is_def()) {
+            self.move_resolver.needed_defs.insert(segment.value);
+        }
+
+        // Record where the inputs of rematerializable values are held. A
+        // segment which starts with a definition only holds the value after
+        // the defining instruction.
+        if let Some(alloc) = alloc {
+            if self.move_resolver.remat_inputs.contains(segment.value) {
+                let mut live_range = segment.live_range;
+                if !segment.use_list.has_livein() {
+                    live_range.from = live_range.from.inst().next().slot(Slot::Boundary);
+                }
+                if live_range.from < live_range.to {
+                    self.move_resolver.remat_input_segments.push((
+                        segment.value,
+                        live_range,
+                        alloc,
+                    ));
+                }
+            }
+        }
+
         self.live_in = None;
         self.fixed_def = None;
         for component in segment.components(self.uses, self.func) {
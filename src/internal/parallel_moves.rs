@@ -10,7 +10,7 @@ use super::move_resolver::Edit;
 use crate::allocation_unit::AllocationUnit;
 use crate::entity::packed_option::ReservedValue;
 use crate::entity::{PrimaryMap, SecondaryMap, SparseMap};
-use crate::function::{Function, RematCost, Value};
+use crate::function::{Function, MOVE_COST, SPILL_RELOAD_COST, Value};
 use crate::output::{Allocation, AllocationKind, SpillSlot};
 use crate::reginfo::{
     MAX_REG_UNITS, PhysReg, RegBank, RegClass, RegInfo, RegUnit, RegUnitSet, SpillSlotSize,
@@ -25,17 +25,33 @@ use crate::reginfo::{
 /// multiple parallel moves.
 struct EmergencySpillSlotCache {
     slots_by_size: [Vec<SpillSlot>; 32],
+
+    /// Scalable spill slots live in a separate area of the stack frame, so
+    /// they can't be shared with fixed-size slots of the same log2 size.
+    scalable_slots_by_size: [Vec<SpillSlot>; 32],
 }
 
 impl EmergencySpillSlotCache {
     fn new() -> Self {
         EmergencySpillSlotCache {
             slots_by_size: [const { vec![] }; 32],
+            scalable_slots_by_size: [const { vec![] }; 32],
         }
     }
 
     fn clear(&mut self) {
         self.slots_by_size.iter_mut().for_each(|v| v.clear());
+        self.scalable_slots_by_size
+            .iter_mut()
+            .for_each(|v| v.clear());
+    }
+
+    fn slots(&mut self, size: SpillSlotSize) -> &mut Vec<SpillSlot> {
+        if size.is_scalable() {
+            &mut self.scalable_slots_by_size[size.log2_bytes() as usize]
+        } else {
+            &mut self.slots_by_size[size.log2_bytes() as usize]
+        }
     }
 
     fn acquire(
@@ -43,7 +59,7 @@ impl EmergencySpillSlotCache {
         size: SpillSlotSize,
         alloc_emergency_spillslot: &mut impl FnMut(SpillSlotSize) -> SpillSlot,
     ) -> SpillSlot {
-        if let Some(slot) = self.slots_by_size[size.log2_bytes() as usize].pop() {
+        if let Some(slot) = self.slots(size).pop() {
             slot
         } else {
             alloc_emergency_spillslot(size)
@@ -51,7 +67,7 @@ impl EmergencySpillSlotCache {
     }
 
     fn release(&mut self, slot: SpillSlot, size: SpillSlotSize) {
-        self.slots_by_size[size.log2_bytes() as usize].push(slot);
+        self.slots(size).push(slot);
     }
 }
 
@@ -210,6 +226,7 @@ impl ScratchAllocator {
                 value: None.into(),
                 from: Some(Allocation::reg(reg)).into(),
                 to: Some(Allocation::spillslot(spillslot)).into(),
+                input: None.into(),
             });
             self.evicted_reg = None;
             self.emergency_spillslot_cache.release(spillslot, size);
@@ -246,6 +263,7 @@ impl ScratchAllocator {
                 value: None.into(),
                 from: Some(Allocation::reg(evicted_reg)).into(),
                 to: Some(Allocation::spillslot(spillslot)).into(),
+                input: None.into(),
             });
             self.evicted_reg = None;
             self.emergency_spillslot_cache.release(spillslot, size);
@@ -343,6 +361,7 @@ impl ScratchAllocator {
             value: None.into(),
             from: Some(Allocation::spillslot(spillslot)).into(),
             to: Some(Allocation::reg(reg)).into(),
+            input: None.into(),
         });
 
         self.evicted_reg = Some((reg, spillslot, size));
@@ -476,17 +495,20 @@ impl ScratchAllocator {
                 value: Some(value).into(),
                 from: Some(scratch).into(),
                 to: Some(to).into(),
+                input: None.into(),
             });
             edits.push(Edit {
                 value: Some(value).into(),
                 from: Some(from).into(),
                 to: Some(scratch).into(),
+                input: None.into(),
             });
         } else {
             edits.push(Edit {
                 value: Some(value).into(),
                 from: Some(from).into(),
                 to: Some(to).into(),
+                input: None.into(),
             });
         }
 
@@ -542,8 +564,9 @@ pub struct ParallelMoves {
     moves: PrimaryMap<MoveIndex, Move>,
 
     /// List of values that will be re-materialized separately after all moves
-    /// have been performed.
-    remat: Vec<(Value, RegClass, Allocation)>,
+    /// have been performed, along with the register holding their input
+    /// value if they have one.
+    remat: Vec<(Value, RegClass, Allocation, Option<Allocation>)>,
 
     /// Same as `remat` but for rematerializations where an intermediate scratch
     /// register is needed.
@@ -604,6 +627,10 @@ impl ParallelMoves {
         let (_cost, class) = func
             .can_rematerialize(value)
             .expect("add_remat called with non-rematerializable value");
+        debug_assert!(
+            func.remat_input(value).is_none(),
+            "add_remat called with value depending on an input"
+        );
 
         let need_scratch = match dest.kind() {
             AllocationKind::PhysReg(reg) => !reginfo.class_members(class).contains(reg),
@@ -612,34 +639,57 @@ impl ParallelMoves {
         if need_scratch {
             self.remat_with_scratch.push((value, class, dest));
         } else {
-            self.remat.push((value, class, dest));
+            self.remat.push((value, class, dest, None));
         }
     }
 
+    /// Adds a move of `value` from `source` to `dest`.
+    ///
+    /// `input` is the register holding the input of `value` after all moves
+    /// have been performed, if `value` depends on one for rematerialization
+    /// and it is available in a register.
     pub fn add_move(
         &mut self,
         source: Allocation,
         dest: Allocation,
         value: Value,
+        input: Option<Allocation>,
         func: &impl Function,
         reginfo: &impl RegInfo,
     ) {
         // Self-moves will confuse our cycle detection algorithm.
         debug_assert_ne!(source, dest);
 
-        // Handle cases where rematerialization is always better than a move.
+        // Handle cases where rematerialization is cheaper than a move or a
+        // reload.
         if let Some((cost, class)) = func.can_rematerialize(value) {
-            if cost == RematCost::CheaperThanMove || source.is_memory(reginfo) {
+            let copy_cost = if source.is_memory(reginfo) {
+                SPILL_RELOAD_COST
+            } else {
+                MOVE_COST
+            };
+            if cost < copy_cost {
                 let need_scratch = match dest.kind() {
                     AllocationKind::PhysReg(reg) => !reginfo.class_members(class).contains(reg),
                     AllocationKind::SpillSlot(_) => !reginfo.class_includes_spillslots(class),
                 };
-                if need_scratch {
-                    self.remat_with_scratch.push((value, class, dest));
-                } else {
-                    self.remat.push((value, class, dest));
+                if func.remat_input(value).is_none() {
+                    if need_scratch {
+                        self.remat_with_scratch.push((value, class, dest));
+                    } else {
+                        self.remat.push((value, class, dest, None));
+                    }
+                    return;
+                }
+
+                // A value which depends on an input can only be rematerialized
+                // if the input is available in a register. Such values are
+                // never rematerialized through a scratch register since that
+                // register may have been evicted from the input.
+                if let Some(input) = input.filter(|&input| input != dest && !need_scratch) {
+                    self.remat.push((value, class, dest, Some(input)));
+                    return;
                 }
-                return;
             }
         }
 
@@ -693,13 +743,14 @@ impl ParallelMoves {
         // first since they can free up a scratch register for later
         // rematerializations.
         self.scratch.clear();
-        for &(value, class, dest) in &self.remat {
+        for &(value, class, dest, input) in &self.remat {
             trace!("Processing remat of {value} into {dest} with {class}");
 
             self.edits.push(Edit {
                 value: Some(value).into(),
                 from: None.into(),
                 to: Some(dest).into(),
+                input: input.into(),
             });
 
             // Make the destination register available as a scratch register.
@@ -748,17 +799,20 @@ impl ParallelMoves {
                     value: Some(value).into(),
                     from: Some(scratch2).into(),
                     to: Some(dest).into(),
+                    input: None.into(),
                 });
                 self.edits.push(Edit {
                     value: Some(value).into(),
                     from: Some(scratch).into(),
                     to: Some(scratch2).into(),
+                    input: None.into(),
                 });
             } else {
                 self.edits.push(Edit {
                     value: Some(value).into(),
                     from: Some(scratch).into(),
                     to: Some(dest).into(),
+                    input: None.into(),
                 });
             }
 
@@ -767,6 +821,7 @@ impl ParallelMoves {
                 value: Some(value).into(),
                 from: None.into(),
                 to: Some(scratch).into(),
+                input: None.into(),
             });
 
             // If get_scratch_reg gave us an emergency spillslot, release it so
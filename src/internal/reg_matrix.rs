@@ -342,6 +342,19 @@ impl RegMatrix {
         true
     }
 
+    /// Checks whether any unit of the given register is written by a fixed
+    /// def, clobber or pinned value anywhere in the function.
+    ///
+    /// Fixed uses are ignored since they only read the register.
+    pub fn has_fixed_def(&self, reg: PhysReg, reginfo: &impl RegInfo) -> bool {
+        reginfo.reg_units(reg).any(|unit| {
+            self.reservations[unit]
+                .btree
+                .iter()
+                .any(|(_to, entry)| entry.vreg.is_none() && entry.fixed_use == !0)
+        })
+    }
+
     /// Iterates over all the interference between `segments` and existing
     /// assignments to `reg`.
     ///
@@ -425,6 +438,50 @@ impl RegMatrix {
         }
     }
 
+    /// Reserves a physical register for all segments of a virtual register
+    /// whose value is pinned to that register.
+    ///
+    /// Unlike `assign`, the segments are recorded as fixed reservations so that
+    /// they can never be evicted. The caller must have checked that there is
+    /// no interference: the only reservations that may overlap are fixed uses
+    /// of the same value, which are superseded by this reservation.
+    pub fn reserve_pinned(
+        &mut self,
+        vreg: VirtReg,
+        reg: PhysReg,
+        virt_regs: &VirtRegs,
+        reginfo: &impl RegInfo,
+    ) {
+        trace!("Reserving {reg} for pinned {vreg}");
+
+        for unit in reginfo.reg_units(reg) {
+            let reservations = &mut self.reservations[unit];
+            for segment in virt_regs.segments(vreg) {
+                debug_assert!(!segment.live_range.is_empty());
+
+                let mut cursor = reservations
+                    .btree
+                    .cursor_mut_at(Bound::Excluded(segment.live_range().from));
+                while let Some((_to, &entry)) = cursor.entry() {
+                    if entry.from >= segment.live_range.to {
+                        break;
+                    }
+                    debug_assert_eq!(entry.vreg.expand(), None);
+                    debug_assert_eq!(entry.fixed_use as usize, segment.value.index());
+                    cursor.remove();
+                }
+                cursor.insert_before(
+                    segment.live_range.to,
+                    Entry {
+                        from: segment.live_range.from,
+                        vreg: None.into(),
+                        fixed_use: !0,
+                    },
+                );
+            }
+        }
+    }
+
     /// Evicts all segments of the given virtual register from a physical
     /// register.
     pub fn evict(
@@ -0,0 +1,215 @@
+//! Stack maps for reference values at safepoints.
+//!
+//! This is computed after move optimization from the final location of each
+//! value segment. A reference value is recorded in the stack map of a
+//! safepoint if it has a segment which covers the whole instruction, which is
+//! the same condition used by `Output::value_locations`.
+//!
+//! If requested, references held in registers across a safepoint are spilled
+//! to a dedicated spill slot before the instruction and reloaded after it. The
+//! stack map then only contains stack locations.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::allocator::Allocator;
+use super::live_range::{Slot, ValueSegment};
+use super::move_resolver::{Edit, MoveResolver};
+use super::spill_allocator::SpillAllocator;
+use super::uses::Uses;
+use super::virt_regs::VirtRegs;
+use crate::Stats;
+use crate::function::{Function, Inst, InstRange, OperandKind, Value};
+use crate::output::{Allocation, AllocationKind, SpillSlot};
+use crate::reginfo::{RegInfo, SpillSlotSize};
+
+pub struct Safepoints {
+    /// All safepoint instructions in the function, in order.
+    safepoints: Vec<Inst>,
+
+    /// Live reference values and their allocation at each safepoint, sorted by
+    /// instruction and then by value.
+    pub stack_maps: Vec<(Inst, Value, Allocation)>,
+
+    /// Spill slots used to hold references across safepoints, along with the
+    /// last safepoint at which they were used.
+    ///
+    /// These are only live across a single instruction and can therefore be
+    /// shared by all safepoints.
+    slots: Vec<(SpillSlotSize, SpillSlot, Inst)>,
+
+    /// Spills to insert at the end of the edits before each safepoint.
+    spills: Vec<(Inst, Edit)>,
+
+    /// Reloads to insert at the start of the edits after each safepoint.
+    reloads: Vec<(Inst, Edit)>,
+}
+
+impl Safepoints {
+    pub fn new() -> Self {
+        Self {
+            safepoints: vec![],
+            stack_maps: vec![],
+            slots: vec![],
+            spills: vec![],
+            reloads: vec![],
+        }
+    }
+
+    /// Computes the stack map for each safepoint and optionally forces
+    /// references into spill slots across safepoints.
+    pub fn compute(
+        &mut self,
+        allocator: &Allocator,
+        virt_regs: &VirtRegs,
+        spill_allocator: &mut SpillAllocator,
+        uses: &Uses,
+        move_resolver: &mut MoveResolver,
+        stats: &mut Stats,
+        func: &impl Function,
+        reginfo: &impl RegInfo,
+        spill_references: bool,
+    ) {
+        self.safepoints.clear();
+        self.stack_maps.clear();
+        self.slots.clear();
+        self.safepoints
+            .extend(func.insts().filter(|&inst| func.is_safepoint(inst)));
+        if self.safepoints.is_empty() {
+            return;
+        }
+        stat!(stats, safepoints, self.safepoints.len());
+
+        // Record every safepoint covered by a segment of a reference value.
+        for (vreg, reg) in allocator.assignments() {
+            for segment in virt_regs.segments(vreg) {
+                self.add_segment(segment, Allocation::reg(reg), uses, move_resolver, func);
+            }
+        }
+        for (slot, segment) in spill_allocator.spilled_segments() {
+            self.add_segment(
+                segment,
+                Allocation::spillslot(slot),
+                uses,
+                move_resolver,
+                func,
+            );
+        }
+        self.stack_maps.sort_unstable();
+        stat!(stats, stack_map_entries, self.stack_maps.len());
+
+        if !spill_references {
+            return;
+        }
+
+        // Move references held in registers to a spill slot for the duration
+        // of the safepoint. Memory registers are left in place since they are
+        // already on the stack.
+        self.spills.clear();
+        self.reloads.clear();
+        for (inst, value, alloc) in &mut self.stack_maps {
+            let AllocationKind::PhysReg(reg) = alloc.kind() else {
+                continue;
+            };
+            if reginfo.is_memory(reg) {
+                continue;
+            }
+            let size = reginfo.spillslot_size(func.value_bank(*value));
+            let slot = match self
+                .slots
+                .iter_mut()
+                .find(|(slot_size, _, last_use)| *slot_size == size && *last_use != *inst)
+            {
+                Some((_, slot, last_use)) => {
+                    *last_use = *inst;
+                    *slot
+                }
+                None => {
+                    let slot = spill_allocator.alloc_emergency_spillslot(size);
+                    self.slots.push((size, slot, *inst));
+                    slot
+                }
+            };
+            trace!("Spilling {value} from {reg} to {slot} across safepoint {inst}");
+            stat!(stats, safepoint_spills);
+            self.spills.push((
+                *inst,
+                Edit {
+                    value: Some(*value).into(),
+                    from: Some(*alloc).into(),
+                    to: Some(Allocation::spillslot(slot)).into(),
+                    input: None.into(),
+                },
+            ));
+            self.reloads.push((
+                inst.next(),
+                Edit {
+                    value: Some(*value).into(),
+                    from: Some(Allocation::spillslot(slot)).into(),
+                    to: Some(*alloc).into(),
+                    input: None.into(),
+                },
+            ));
+            *alloc = Allocation::spillslot(slot);
+        }
+        move_resolver.insert_edits(&self.spills, &self.reloads);
+    }
+
+    /// Adds a stack map entry for each safepoint that a segment is live across.
+    fn add_segment(
+        &mut self,
+        segment: &ValueSegment,
+        alloc: Allocation,
+        uses: &Uses,
+        move_resolver: &MoveResolver,
+        func: &impl Function,
+    ) {
+        if !func.is_reference(segment.value) || move_resolver.is_dead_def_segment(segment, uses) {
+            return;
+        }
+        let range = InstRange::new(
+            segment.live_range.from.round_to_next_inst().inst(),
+            segment.live_range.to.round_to_prev_inst().inst(),
+        );
+        if range.is_empty() {
+            return;
+        }
+        let start = self.safepoints.partition_point(|&inst| inst < range.from);
+        for &inst in &self.safepoints[start..] {
+            if inst >= range.to {
+                break;
+            }
+
+            // Reuse definitions start at the boundary before their
+            // instruction, but they are not live across it.
+            if segment.live_range.from == inst.slot(Slot::Boundary)
+                && func.inst_operands(inst).iter().any(|op| match op.kind() {
+                    OperandKind::Def(value) | OperandKind::EarlyDef(value) => {
+                        value == segment.value
+                    }
+                    OperandKind::DefGroup(group) | OperandKind::EarlyDefGroup(group) => {
+                        func.value_group_members(group).contains(&segment.value)
+                    }
+                    OperandKind::Use(_)
+                    | OperandKind::UseGroup(_)
+                    | OperandKind::NonAllocatable => false,
+                })
+            {
+                continue;
+            }
+
+            self.stack_maps.push((inst, segment.value, alloc));
+        }
+    }
+
+    /// Returns the stack map for the given safepoint.
+    pub fn stack_map(&self, inst: Inst) -> impl Iterator<Item = (Value, Allocation)> + '_ {
+        let idx = self
+            .stack_maps
+            .partition_point(|&(inst2, _, _)| inst2 < inst);
+        self.stack_maps[idx..]
+            .iter()
+            .take_while(move |&&(inst2, _, _)| inst2 == inst)
+            .map(|&(_, value, alloc)| (value, alloc))
+    }
+}
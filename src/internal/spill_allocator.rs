@@ -70,6 +70,7 @@ impl SpillAllocator {
             stack_layout: StackLayout {
                 slots: PrimaryMap::new(),
                 spillslot_area_size: 0,
+                scalable_spillslot_area_size: 0,
             },
             spilled_segments: vec![],
             sets_to_allocate: vec![],
@@ -153,12 +154,13 @@ impl SpillAllocator {
     /// none is available.
     pub fn alloc_emergency_spillslot(&mut self, size: SpillSlotSize) -> SpillSlot {
         // Ensure the new slot is properly aligned.
-        self.stack_layout.spillslot_area_size += size.bytes() - 1;
-        self.stack_layout.spillslot_area_size &= !(size.bytes() - 1);
+        let area_size = self.stack_layout.area_size_mut(size);
+        *area_size += size.bytes() - 1;
+        *area_size &= !(size.bytes() - 1);
 
         // Allocate a new slot.
-        let offset = self.stack_layout.spillslot_area_size;
-        self.stack_layout.spillslot_area_size += size.bytes();
+        let offset = *area_size;
+        *area_size += size.bytes();
         self.stack_layout.slots.push((offset, size))
     }
 
@@ -174,13 +176,16 @@ impl SpillAllocator {
     pub fn allocate(&mut self, stats: &mut Stats) -> Result<(), RegAllocError> {
         self.stack_layout.slots.clear();
         self.stack_layout.spillslot_area_size = 0;
+        self.stack_layout.scalable_spillslot_area_size = 0;
         self.active_sets.clear();
         self.available_slots.clear();
 
         trace!("Allocating spill slots:");
 
         // Gather the value sets that need to be allocated and sort them by
-        // spill slot size first, and then by start position.
+        // spill slot size first, and then by start position. Scalable sizes
+        // compare greater than fixed sizes so each area is allocated as a
+        // contiguous group.
         stat!(stats, spilled_sets, self.sets_to_allocate.len());
         stat!(stats, spill_segments, self.spilled_segments.len());
         self.sets_to_allocate.sort_unstable_by_key(|&set| {
@@ -224,21 +229,16 @@ impl SpillAllocator {
             let slot = match self.available_slots.pop() {
                 Some(slot) => slot,
                 None => {
-                    let slot = self
-                        .stack_layout
-                        .slots
-                        .push((self.stack_layout.spillslot_area_size, current_size));
+                    // Fixed and scalable spill slots are allocated in separate
+                    // areas of the stack frame.
+                    let area_size = *self.stack_layout.area_size_mut(current_size);
+                    let slot = self.stack_layout.slots.push((area_size, current_size));
 
                     // This is guaranteed to be properly aligned because we start
                     // allocating from larger sizes first, and all sizes are
                     // powers of 2.
-                    debug_assert_eq!(
-                        self.stack_layout.spillslot_area_size % current_size.bytes(),
-                        0
-                    );
-                    self.stack_layout.spillslot_area_size = self
-                        .stack_layout
-                        .spillslot_area_size
+                    debug_assert_eq!(area_size % current_size.bytes(), 0);
+                    *self.stack_layout.area_size_mut(current_size) = area_size
                         .checked_add(current_size.bytes())
                         .ok_or(RegAllocError::FunctionTooBig)?;
                     slot
@@ -254,6 +254,11 @@ impl SpillAllocator {
             spill_area_size,
             self.stack_layout.spillslot_area_size as usize
         );
+        stat!(
+            stats,
+            scalable_spill_area_size,
+            self.stack_layout.scalable_spillslot_area_size as usize
+        );
 
         Ok(())
     }
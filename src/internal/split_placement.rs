@@ -16,6 +16,12 @@
 //! Specifically, this works well if blocks are in reverse post-order and loops
 //! are properly nested: any loop exit blocks should be after any loop body
 //! blocks.
+//!
+//! Split points must also avoid instructions marked with
+//! `Function::no_edits_before` if a live range crosses the boundary before
+//! them, since the split would require a move at that point. The helper
+//! functions at the end of this module find the edges of such instruction
+//! sequences.
 
 use alloc::vec;
 use alloc::vec::Vec;
@@ -23,6 +29,7 @@ use alloc::vec::Vec;
 use crate::entity::SecondaryMap;
 use crate::entity::packed_option::PackedOption;
 use crate::function::{Block, Function, Inst};
+use crate::internal::live_range::{Slot, ValueSegment};
 
 /// Optimal placement of live range split points.
 pub struct SplitPlacement {
@@ -154,3 +161,53 @@ impl SplitPlacement {
         self.prev_lower_freq[block].expand()
     }
 }
+
+/// Returns the first instruction of the sequence of instructions without edits
+/// between them which contains `inst`.
+///
+/// This is `inst` itself unless it is marked with `Function::no_edits_before`.
+pub fn edit_sequence_start(func: &impl Function, mut inst: Inst) -> Inst {
+    // The first instruction of a block can't be marked so this never leaves
+    // the block.
+    while func.no_edits_before(inst) {
+        inst = inst.prev();
+    }
+    inst
+}
+
+/// Returns the first instruction after `inst` which allows edits before it.
+///
+/// This is `inst.next()` unless the following instruction is marked with
+/// `Function::no_edits_before`.
+pub fn edit_sequence_end(func: &impl Function, inst: Inst) -> Inst {
+    let mut inst = inst.next();
+    while inst.index() < func.num_insts() && func.no_edits_before(inst) {
+        inst = inst.next();
+    }
+    inst
+}
+
+/// Returns whether `inst` is part of a sequence of instructions without edits
+/// between them.
+///
+/// Such instructions must not be eliminated from the output since that would
+/// move the edits before them into the sequence.
+pub fn in_edit_sequence(func: &impl Function, inst: Inst) -> bool {
+    func.no_edits_before(inst)
+        || (inst.next().index() < func.num_insts() && func.no_edits_before(inst.next()))
+}
+
+/// Returns whether the given segments can be split before `inst`.
+///
+/// This is only forbidden if a segment is live across the boundary before an
+/// instruction marked with `Function::no_edits_before`, since this would
+/// require a move between the two halves.
+pub fn can_split_before(func: &impl Function, segments: &[ValueSegment], inst: Inst) -> bool {
+    if !func.no_edits_before(inst) {
+        return true;
+    }
+    let point = inst.slot(Slot::Boundary);
+    !segments
+        .iter()
+        .any(|seg| seg.live_range.from < point && seg.live_range.to > point)
+}
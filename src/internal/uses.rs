@@ -4,7 +4,7 @@ use core::fmt;
 use core::ops::{Index, IndexMut, Range};
 
 use super::live_range::{LiveRangePoint, Slot};
-use crate::function::Inst;
+use crate::function::{Function, Inst, MOVE_COST, SPILL_RELOAD_COST, Value};
 use crate::reginfo::{PhysReg, RegClass, RegInfo};
 
 /// A `Use` describes the way a value is used in a live range.
@@ -25,11 +25,16 @@ pub struct Use {
     pub kind: UseKind,
 }
 
-/// Cost of a spill/reload of a register from memory.
-pub const SPILL_RELOAD_COST: f32 = 1.0;
-
-/// Cost of a move between 2 registers.
-pub const MOVE_COST: f32 = 0.5;
+/// Returns the cost of rematerializing `value` if the value is rematerialized
+/// instead of being reloaded from a spill slot when it is spilled.
+///
+/// This excludes values which are more expensive to rematerialize than a
+/// reload and values which depend on an input, since those can't be
+/// rematerialized at every point.
+pub fn spill_remat_cost(value: Value, func: &impl Function) -> Option<f32> {
+    let (cost, _class) = func.can_rematerialize(value)?;
+    (cost < SPILL_RELOAD_COST && func.remat_input(value).is_none()).then_some(cost)
+}
 
 impl Use {
     /// Spill cost for this use.
@@ -37,7 +42,19 @@ impl Use {
     /// This is calculated as the cost to be paid if the virtual register
     /// containing this use is spilled to the stack instead of allocated to a
     /// register.
-    pub fn spill_cost(self, reginfo: &impl RegInfo) -> f32 {
+    ///
+    /// If `value` is cheaper to rematerialize than to reload then the cost is
+    /// capped to that of rematerializing it.
+    pub fn spill_cost(self, value: Value, func: &impl Function, reginfo: &impl RegInfo) -> f32 {
+        let cost = self.base_spill_cost(reginfo);
+        match spill_remat_cost(value, func) {
+            Some(remat_cost) => cost.min(remat_cost),
+            None => cost,
+        }
+    }
+
+    /// Spill cost for this use, ignoring rematerialization.
+    fn base_spill_cost(self, reginfo: &impl RegInfo) -> f32 {
         match self.kind {
             // Fixed uses/defs are simple: just pay the cost of the
             // spill/reload, except if reg represents a memory location.
@@ -81,6 +98,9 @@ impl Use {
             // register. It may matter a little if this introduces a copy, but
             // that is mostly covered by the live-in/live-out cost penalty.
             UseKind::BlockparamIn { blockparam_idx: _ } | UseKind::BlockparamOut {} => 0.0,
+
+            // Keep-alive uses accept any location since they are never read.
+            UseKind::KeepAlive {} => 0.0,
         }
     }
 
@@ -102,6 +122,7 @@ impl Use {
             UseKind::GroupClassDef { .. } => inst.next().slot(Slot::Boundary),
             UseKind::BlockparamIn { .. } => inst.slot(Slot::Boundary),
             UseKind::BlockparamOut {} => inst.next().slot(Slot::Boundary),
+            UseKind::KeepAlive {} => inst.slot(Slot::Normal),
         }
     }
 }
@@ -256,6 +277,16 @@ pub enum UseKind {
     /// source half-move is emitted at the point before the terminator
     /// instruction.
     BlockparamOut {},
+
+    /// Artificial use which keeps the value live until the end of its scope,
+    /// as reported by [`Function::value_scope_end`].
+    ///
+    /// This doesn't place any constraint on the allocation and doesn't
+    /// generate any moves: it only extends the live range so that the value
+    /// remains available to a debugger up to this instruction.
+    ///
+    /// [`Function::value_scope_end`]: crate::function::Function::value_scope_end
+    KeepAlive {},
 }
 impl UseKind {
     /// Whether this `UseKind` represents the definition of a `Value`.
@@ -270,7 +301,8 @@ impl UseKind {
             | UseKind::ConstraintConflict { .. }
             | UseKind::ClassUse { .. }
             | UseKind::GroupClassUse { .. }
-            | UseKind::BlockparamOut { .. } => false,
+            | UseKind::BlockparamOut { .. }
+            | UseKind::KeepAlive { .. } => false,
         }
     }
 }
@@ -319,6 +351,7 @@ impl fmt::Display for UseKind {
             UseKind::BlockparamOut {} => {
                 write!(f, "blockparam_out")
             }
+            UseKind::KeepAlive {} => write!(f, "keep_alive"),
         }
     }
 }
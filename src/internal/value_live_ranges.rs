@@ -25,6 +25,7 @@ use core::ops::{Index, IndexMut};
 use smallvec::SmallVec;
 
 use super::allocations::Allocations;
+use super::alternatives::AlternativeResolver;
 use super::hints::Hints;
 use super::live_range::{LiveRangeSegment, Slot, ValueSegment};
 use super::reg_matrix::RegMatrix;
@@ -156,6 +157,13 @@ pub struct ValueLiveRanges {
 
     /// Linked list of uses for each value.
     use_list_entries: PrimaryMap<UseListIndex, UseListEntry>,
+
+    /// Values with an explicit scope end, sorted by the instruction at which
+    /// the scope ends.
+    scope_ends: Vec<(Inst, Value)>,
+
+    /// Selection of alternative constraints for instruction operands.
+    alternatives: AlternativeResolver,
 }
 
 impl Index<ValueSet> for ValueLiveRanges {
@@ -182,6 +190,8 @@ impl ValueLiveRanges {
             worklist: vec![],
             reused_values: vec![],
             use_list_entries: PrimaryMap::new(),
+            scope_ends: vec![],
+            alternatives: AlternativeResolver::new(),
         }
     }
 
@@ -287,6 +297,18 @@ struct Context<'a, F, R> {
 impl<F: Function, R: RegInfo> Context<'_, F, R> {
     /// Iterate over all blocks and instructions to collect value uses.
     fn collect_uses(&mut self) {
+        // Collect the values which must be kept alive until the end of their
+        // scope, in instruction order so that the keep-alive uses can be
+        // added to the use lists while walking the instructions.
+        self.value_live_ranges.scope_ends.clear();
+        self.value_live_ranges.scope_ends.extend(
+            self.func
+                .values()
+                .filter_map(|value| Some((self.func.value_scope_end(value)?, value))),
+        );
+        self.value_live_ranges.scope_ends.sort_unstable();
+        let mut next_scope_end = 0;
+
         // Walk over all value uses and definitions.
         for block in self.func.blocks() {
             let block_insts = self.func.block_insts(block);
@@ -313,9 +335,19 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                 // Do an initial scan to find reused operands.
                 self.find_reused_values(inst);
 
+                // Select a constraint for operands with alternatives.
                 let operands = self.func.inst_operands(inst);
+                if self
+                    .value_live_ranges
+                    .alternatives
+                    .resolve(inst, self.func, self.reginfo)
+                    .is_err()
+                {
+                    unreachable!("No usable alternative constraint");
+                }
                 for (slot, &operand) in operands.iter().enumerate() {
-                    self.process_operand(inst, slot as u16, operand);
+                    let constraint = self.value_live_ranges.alternatives.constraint(slot);
+                    self.process_operand(inst, slot as u16, operand, constraint);
                 }
 
                 // Reserve fixed ranges for instruction clobbers.
@@ -328,6 +360,18 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                         ),
                     );
                 }
+
+                // Create keep-alive uses for values whose scope ends here.
+                while let Some(&(scope_end, value)) =
+                    self.value_live_ranges.scope_ends.get(next_scope_end)
+                {
+                    if scope_end != inst {
+                        break;
+                    }
+                    next_scope_end += 1;
+                    stat!(self.stats, keep_alive_uses);
+                    self.value_use(value, inst, UseKind::KeepAlive {});
+                }
             }
 
             // Create uses for outgoing block parameters.
@@ -351,8 +395,11 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                     OperandKind::EarlyDef(_) | OperandKind::EarlyDefGroup(_)
                 );
                 let use_op = operands[target];
+
+                // Defs tied to a fixed-register use don't need any special
+                // handling of the input value.
                 let OperandConstraint::Class(class) = use_op.constraint() else {
-                    unreachable!();
+                    continue;
                 };
                 match use_op.kind() {
                     OperandKind::Use(use_value) => {
@@ -491,9 +538,18 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
     /// - Set the relevant allocation in the allocation map in advance.
     /// - Mark the fixed portion of the live range as reserved in the
     ///   `RegMatrix`.
-    fn process_operand(&mut self, inst: Inst, slot: u16, operand: Operand) {
+    ///
+    /// `constraint` is the constraint of the operand after selecting an
+    /// alternative.
+    fn process_operand(
+        &mut self,
+        inst: Inst,
+        slot: u16,
+        operand: Operand,
+        constraint: OperandConstraint,
+    ) {
         trace!("Processing {inst}[{slot}]: {operand}");
-        match (operand.kind(), operand.constraint()) {
+        match (operand.kind(), constraint) {
             (OperandKind::Def(value), OperandConstraint::Class(class)) => {
                 stat!(self.stats, class_def);
                 self.value_def(
@@ -604,11 +660,25 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
             ) => {
                 // At this point we just copy the constraint of the target
                 // operand. Tying is handled by the use operands.
-                stat!(self.stats, reuse_def);
                 let target_operand = self.func.inst_operands(inst)[target];
-                let OperandConstraint::Class(class) = target_operand.constraint() else {
-                    unreachable!("Reuse target operand must have class constraint")
+                let class = match target_operand.constraint() {
+                    OperandConstraint::Class(class) => class,
+
+                    // A def tied to a fixed-register use overwrites that
+                    // register after the instruction, which is exactly a
+                    // fixed def. If the input value is still live then it is
+                    // copied into the fixed register like any other fixed use.
+                    OperandConstraint::Fixed(reg) => {
+                        let constraint = OperandConstraint::Fixed(reg);
+                        let operand = Operand::new(OperandKind::Def(value), constraint);
+                        self.process_operand(inst, slot, operand, constraint);
+                        return;
+                    }
+                    OperandConstraint::Reuse(_) | OperandConstraint::Alternatives(_) => {
+                        unreachable!("Reuse target operand must have class or fixed constraint")
+                    }
                 };
+                stat!(self.stats, reuse_def);
                 self.value_def(
                     value,
                     inst,
@@ -731,6 +801,7 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                 OperandKind::NonAllocatable,
                 OperandConstraint::Class(_) | OperandConstraint::Reuse(_),
             ) => unreachable!(),
+            (_, OperandConstraint::Alternatives(_)) => unreachable!(),
         }
     }
 
@@ -767,6 +838,7 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
             },
             next: None.into(),
         });
+        self.add_value_hint(value, inst);
     }
 
     /// Visits a use of a value.
@@ -781,6 +853,7 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
             },
             next: None.into(),
         });
+        self.add_value_hint(value, inst);
         let value_info = self.value_live_ranges.value_info[value]
             .as_mut()
             .expect("use before def");
@@ -792,6 +865,15 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
         value_info
     }
 
+    /// Records the client-provided register hint for a value, if it has one,
+    /// at a definition or use.
+    fn add_value_hint(&mut self, value: Value, inst: Inst) {
+        if let Some((reg, strength)) = self.func.value_hint(value) {
+            self.hints
+                .add_value_hint(value, inst, reg, strength, self.func);
+        }
+    }
+
     /// Calculates the live-in/live-out bitsets for each block of the value's
     /// live range.
     ///
@@ -28,7 +28,9 @@ use crate::function::{Function, OperandKind, Value, ValueGroup};
 use crate::internal::coalescing::Coalescing;
 use crate::internal::hints::Hints;
 use crate::internal::live_range::{LiveRangeSegment, Slot, ValueSegment};
-use crate::internal::split_placement::SplitPlacement;
+use crate::internal::split_placement::{
+    SplitPlacement, can_split_before, edit_sequence_end, edit_sequence_start,
+};
 use crate::internal::uses::{Use, UseIndex, UseKind, Uses};
 use crate::internal::value_live_ranges::ValueSet;
 use crate::internal::virt_regs::{VirtReg, VirtRegData, VirtRegGroup, VirtRegs};
@@ -54,6 +56,12 @@ pub fn normalize_spill_weight(spill_cost: f32, num_insts: u32, options: &Options
     weight.min(f32::MAX)
 }
 
+/// Inverse of `normalize_spill_weight`: recovers the total spill cost of the
+/// uses in a virtual register from its spill weight.
+pub fn denormalize_spill_weight(spill_weight: f32, num_insts: u32, options: &Options) -> f32 {
+    spill_weight * (num_insts + options.spill_weight_adjust) as f32
+}
+
 /// Utility type for building a virtual register from value live ranges.
 pub struct VirtRegBuilder {
     /// Mapping of [`ValueGroup`] to [`VirtRegGroup`].
@@ -132,6 +140,15 @@ impl VirtRegBuilder {
             constraints: VirtRegBuilderConstraints::new(top_level_class),
         };
 
+        // Values pinned to a register are never split: validation ensures that
+        // the register is in every class constraint on the value, so all its
+        // uses are satisfied by a single virtual register.
+        if func.value_pinned_reg(segments[0].value).is_some() {
+            trace!("Emitting single vreg for pinned value set {value_set}");
+            ctx.emit_vreg(segments);
+            return;
+        }
+
         ctx.compute_constraints(segments, split_placement.is_some());
         if !ctx.conflicting_uses.is_empty() {
             ctx.emit_vregs_for_conflicts();
@@ -385,7 +402,8 @@ impl VirtRegBuilderConstraints {
             | UseKind::TiedUse { .. }
             | UseKind::ConstraintConflict { .. }
             | UseKind::BlockparamIn { .. }
-            | UseKind::BlockparamOut { .. } => true,
+            | UseKind::BlockparamOut { .. }
+            | UseKind::KeepAlive { .. } => true,
         }
     }
 }
@@ -530,7 +548,7 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                                 "Conflicting constraints between {start_pos} and {end_pos}, \
                                  splitting vreg"
                             );
-                            let split_point =
+                            let mut split_point =
                                 self.split_placement.unwrap().find_optimal_split_point(
                                     start_pos,
                                     end_pos,
@@ -538,6 +556,19 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                                     self.func,
                                 );
 
+                            // Move the split point out of any sequence which
+                            // forbids edits.
+                            if !can_split_before(self.func, segments, split_point) {
+                                let seq_start = edit_sequence_start(self.func, split_point);
+                                let seq_end = edit_sequence_end(self.func, split_point);
+                                if seq_start > start_pos {
+                                    split_point = seq_start;
+                                } else if seq_end <= end_pos {
+                                    split_point = seq_end;
+                                }
+                                trace!("Moved split point to {split_point} to avoid edits");
+                            }
+
                             // Create a vreg for the portion before the split.
                             // This should never conflict and therefore can't
                             // recurse.
@@ -690,15 +721,28 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
     /// built.
     ///
     /// This also detects cases where a virtual register only spans a single
-    /// instruction, in which case it cannot be split further. This case is
+    /// instruction, or a single sequence of instructions which forbids edits
+    /// between them, in which case it cannot be split further. This case is
     /// represented by giving that virtual register an infinite spill weight.
     fn calc_spill_weight(&self, segments: &[ValueSegment]) -> f32 {
         let num_insts = ValueSegment::live_insts(segments);
         trace!("Computing spill weight with {num_insts} instructions");
 
+        // A virtual register which doesn't cross any boundary that allows edits
+        // can't be split.
+        let first_inst = segments[0].live_range.from.round_to_prev_inst().inst();
+        let end_inst = segments
+            .last()
+            .unwrap()
+            .live_range
+            .to
+            .round_to_next_inst()
+            .inst();
+        let unsplittable = edit_sequence_end(self.func, first_inst) >= end_inst;
+
         // Register classes that allow spillslots are always spillable.
         debug_assert_ne!(num_insts, 0);
-        let mut spill_weight = if num_insts <= 1
+        let mut spill_weight = if unsplittable
             && !self
                 .reginfo
                 .class_includes_spillslots(self.constraints.class)
@@ -714,7 +758,7 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                     self.uses[seg.use_list]
                         .iter()
                         .map(|u| {
-                            let spill_cost = u.spill_cost(self.reginfo);
+                            let spill_cost = u.spill_cost(seg.value, self.func, self.reginfo);
                             let block_freq = self.func.block_frequency(self.func.inst_block(u.pos));
                             trace!(
                                 "Use of {} at {} ({}) has spill cost {} ({spill_cost} * \
@@ -870,7 +914,8 @@ impl<F: Function, R: RegInfo> Context<'_, F, R> {
                                 | UseKind::ClassUse { .. }
                                 | UseKind::ClassDef { .. }
                                 | UseKind::BlockparamIn { .. }
-                                | UseKind::BlockparamOut { .. } => continue,
+                                | UseKind::BlockparamOut { .. }
+                                | UseKind::KeepAlive { .. } => continue,
                             };
                             let value_group =
                                 match self.func.inst_operands(u.pos)[slot as usize].kind() {
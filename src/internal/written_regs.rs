@@ -0,0 +1,119 @@
+//! Set of registers written by the output of the register allocator.
+//!
+//! This is computed after move optimization from the final instruction stream
+//! and is primarily used to determine which callee-saved registers need to be
+//! saved in the function prologue.
+
+use crate::entity::SecondaryMap;
+use crate::function::{Function, Inst, OperandConstraint, OperandKind};
+use crate::internal::allocations::Allocations;
+use crate::internal::move_resolver::MoveResolver;
+use crate::output::{Allocation, AllocationKind};
+use crate::reginfo::{PhysRegSet, RegBank, RegInfo, RegUnitSet};
+
+/// Registers and register units written by the function after register
+/// allocation.
+pub struct WrittenRegs {
+    /// All register units written by the function, including clobbers.
+    pub units: RegUnitSet,
+
+    /// Registers in each bank with at least one written register unit.
+    pub regs: SecondaryMap<RegBank, PhysRegSet>,
+}
+
+impl WrittenRegs {
+    pub fn new() -> Self {
+        Self {
+            units: RegUnitSet::new(),
+            regs: SecondaryMap::new(),
+        }
+    }
+
+    /// Collects the registers written by instruction defs, clobbers, moves and
+    /// rematerializations.
+    pub fn compute(
+        &mut self,
+        move_resolver: &MoveResolver,
+        allocations: &Allocations,
+        func: &impl Function,
+        reginfo: &impl RegInfo,
+    ) {
+        self.units.clear();
+        self.regs.clear_and_resize(reginfo.num_banks());
+
+        let add_alloc = |alloc: Allocation, units: &mut RegUnitSet| {
+            if let AllocationKind::PhysReg(reg) = alloc.kind() {
+                units.extend(reginfo.reg_units(reg));
+            }
+        };
+
+        for inst in func.insts() {
+            // Eliminated instructions don't write anything.
+            if move_resolver.is_dead_inst(inst) || move_resolver.is_eliminated_copy(inst) {
+                continue;
+            }
+
+            self.units.extend(func.inst_clobbers(inst));
+            let operands = func.inst_operands(inst);
+            let allocs = allocations.inst_allocations(inst);
+            for (&op, &alloc) in operands.iter().zip(allocs) {
+                match op.kind() {
+                    OperandKind::Def(_) | OperandKind::EarlyDef(_) => {
+                        add_alloc(alloc, &mut self.units);
+                    }
+                    OperandKind::DefGroup(_) | OperandKind::EarlyDefGroup(_) => {
+                        // Only the first register of the group is recorded in
+                        // the allocation, recover the full group from the
+                        // operand's class.
+                        let class = match op.constraint() {
+                            OperandConstraint::Class(class) => class,
+                            OperandConstraint::Reuse(target) => {
+                                match operands[target].constraint() {
+                                    OperandConstraint::Class(class) => class,
+                                    OperandConstraint::Fixed(_)
+                                    | OperandConstraint::Reuse(_)
+                                    | OperandConstraint::Alternatives(_) => {
+                                        unreachable!()
+                                    }
+                                }
+                            }
+                            OperandConstraint::Fixed(_) | OperandConstraint::Alternatives(_) => {
+                                unreachable!()
+                            }
+                        };
+                        let AllocationKind::PhysReg(reg) = alloc.kind() else {
+                            unreachable!();
+                        };
+                        let group = reginfo
+                            .group_for_reg(reg, 0, class)
+                            .expect("group allocation not in class");
+                        for &member in reginfo.reg_group_members(group) {
+                            self.units.extend(reginfo.reg_units(member));
+                        }
+                    }
+                    OperandKind::Use(_)
+                    | OperandKind::UseGroup(_)
+                    | OperandKind::NonAllocatable => {}
+                }
+            }
+        }
+
+        // Moves and rematerializations write to their destination. This
+        // includes scratch registers and emergency spills/reloads used during
+        // move resolution.
+        for &(_, edit) in move_resolver.edits_from(Inst::new(0)) {
+            if let Some(to) = edit.to.expand() {
+                add_alloc(to, &mut self.units);
+            }
+        }
+
+        // A register is considered written if any of its units are written.
+        for reg in reginfo.regs() {
+            if let Some(bank) = reginfo.bank_for_reg(reg) {
+                if reginfo.reg_units(reg).any(|unit| self.units.contains(unit)) {
+                    self.regs[bank].insert(reg);
+                }
+            }
+        }
+    }
+}
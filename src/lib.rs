@@ -84,24 +84,28 @@ extern crate std;
 
 extern crate alloc;
 
-use core::fmt;
+use alloc::vec::Vec;
+use core::{fmt, mem};
 
-use function::Function;
+use function::{Function, Inst, Value};
 use internal::allocations::Allocations;
 use internal::allocator::Allocator;
 use internal::coalescing::Coalescing;
+use internal::edge_split::{EdgeSplit, SplitFunction};
 use internal::hints::Hints;
 use internal::move_optimizer::MoveOptimizer;
 use internal::move_resolver::MoveResolver;
 use internal::reg_matrix::RegMatrix;
+use internal::safepoints::Safepoints;
 use internal::spill_allocator::SpillAllocator;
 use internal::split_placement::SplitPlacement;
 use internal::uses::Uses;
 use internal::value_live_ranges::ValueLiveRanges;
 use internal::virt_regs::VirtRegs;
 use internal::virt_regs::builder::VirtRegBuilder;
+use internal::written_regs::WrittenRegs;
 use output::Output;
-use reginfo::RegInfo;
+use reginfo::{PhysReg, RegClass, RegInfo, RegUnit};
 
 // Even when trace logging is disabled, the trace macro has a significant
 // performance cost so we disable it in release builds.
@@ -168,6 +172,9 @@ pub struct RegisterAllocator {
     spill_allocator: SpillAllocator,
     move_resolver: MoveResolver,
     move_optimizer: MoveOptimizer,
+    safepoints: Safepoints,
+    written_regs: WrittenRegs,
+    edge_split: EdgeSplit,
     stats: Stats,
 }
 
@@ -196,6 +203,9 @@ impl RegisterAllocator {
             spill_allocator: SpillAllocator::new(),
             move_resolver: MoveResolver::new(),
             move_optimizer: MoveOptimizer::new(),
+            safepoints: Safepoints::new(),
+            written_regs: WrittenRegs::new(),
+            edge_split: EdgeSplit::new(),
             stats: Stats::default(),
         }
     }
@@ -224,6 +234,87 @@ impl RegisterAllocator {
         stat!(self.stats, values, func.num_values());
         stat!(self.stats, value_groups, func.num_value_groups());
 
+        // If the function has critical edges or branches with block
+        // parameters, run the allocator on a view of the function in which
+        // those edges are split.
+        let mut edge_split = mem::take(&mut self.edge_split);
+        let result = if edge_split.compute(func) {
+            let split_func = SplitFunction {
+                func,
+                split: &edge_split,
+            };
+            stat!(
+                self.stats,
+                split_edges,
+                split_func.num_blocks() - func.num_blocks()
+            );
+            // Errors refer to instructions of the split view, which need to
+            // be mapped back to the original function.
+            self.run_with_fallback(&split_func, reginfo, options)
+                .map_err(|err| err.map_inst(|inst| edge_split.orig_inst_or_branch(inst)))
+        } else {
+            self.run_with_fallback(func, reginfo, options)
+        };
+        self.edge_split = edge_split;
+        result?;
+
+        // Only edge blocks which actually contain moves are exposed in the
+        // output.
+        self.edge_split
+            .collect_edge_blocks(func.num_blocks(), &self.move_resolver);
+        stat!(self.stats, edge_blocks, self.edge_split.edge_blocks().len());
+
+        let output = Output {
+            regalloc: self,
+            func,
+            reginfo,
+        };
+        trace!("Output:\n{output}");
+        if cfg!(feature = "stats") {
+            trace!("{}", self.stats);
+        }
+        Ok(output)
+    }
+
+    /// Runs all the register allocation passes on the given function, falling
+    /// back to the greedy algorithm if linear scan fails.
+    ///
+    /// Linear scan can only move unspillable virtual registers out of each
+    /// other's way in limited ways, which isn't always enough for instructions
+    /// with many constrained operands. Retrying with the greedy algorithm
+    /// ensures that linear scan never fails on a function which the greedy
+    /// algorithm can allocate.
+    fn run_with_fallback<F, R>(
+        &mut self,
+        func: &F,
+        reginfo: &R,
+        options: &Options,
+    ) -> Result<(), RegAllocError>
+    where
+        F: Function,
+        R: RegInfo,
+    {
+        let result = self.run(func, reginfo, options);
+        if options.algorithm == AllocationAlgorithm::LinearScan
+            && matches!(result, Err(RegAllocError::TooManyLiveRegs { .. }))
+        {
+            trace!("Linear scan failed, retrying with greedy allocation");
+            stat!(self.stats, linear_scan_fallbacks);
+            let options = Options {
+                algorithm: AllocationAlgorithm::Greedy,
+                ..options.clone()
+            };
+            return self.run(func, reginfo, &options);
+        }
+        result
+    }
+
+    /// Runs all the register allocation passes on the given function.
+    fn run<F, R>(&mut self, func: &F, reginfo: &R, options: &Options) -> Result<(), RegAllocError>
+    where
+        F: Function,
+        R: RegInfo,
+    {
         // Prepare data for computing optimal split placement.
         self.split_placement.prepare(func);
 
@@ -312,19 +403,65 @@ impl RegisterAllocator {
             options.move_optimization,
         );
 
-        let output = Output {
-            regalloc: self,
+        // Eliminate copies whose source and destination ended up in the same
+        // allocation. This must happen after move optimization since it may
+        // change operand allocations.
+        self.move_resolver
+            .find_eliminated_copies(&self.allocations, &mut self.stats, func);
+
+        // Compute stack maps for safepoints. This must happen after move
+        // optimization since it may insert additional spills and reloads.
+        self.safepoints.compute(
+            &self.allocator,
+            &self.virt_regs,
+            &mut self.spill_allocator,
+            &self.uses,
+            &mut self.move_resolver,
+            &mut self.stats,
             func,
             reginfo,
-        };
-        trace!("Output:\n{output}");
-        if cfg!(feature = "stats") {
-            trace!("{}", self.stats);
-        }
-        Ok(output)
+            options.spill_references_at_safepoints,
+        );
+
+        // Collect the set of registers written by the function.
+        self.written_regs
+            .compute(&self.move_resolver, &self.allocations, func, reginfo);
+
+        // Record the edits on each CFG edge, which must happen after all edits
+        // have been generated.
+        self.move_resolver
+            .collect_edge_edits(func, options.separate_edge_edits);
+
+        Ok(())
     }
 }
 
+/// Selects the algorithm used to assign virtual registers to physical
+/// registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum AllocationAlgorithm {
+    /// Greedy allocation with eviction and live range splitting.
+    ///
+    /// This is the default since it produces the best code.
+    Greedy,
+
+    /// Allocate registers in a single linear pass, spilling virtual registers
+    /// which conflict with an existing assignment.
+    ///
+    /// This is much faster than `Greedy` but produces worse code, which makes
+    /// it suitable for unoptimized builds.
+    ///
+    /// If this fails to allocate the operands of an instruction then the
+    /// function is allocated again with `Greedy`, so this never fails on a
+    /// function which `Greedy` can allocate. The retry starts over from
+    /// scratch, so such functions take longer to allocate than with `Greedy`
+    /// alone. A non-zero [`Options::recoloring_depth`] lets linear scan
+    /// resolve some of these conflicts itself.
+    LinearScan,
+}
+
 /// Controls how much optimization to perform after register allocation.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
@@ -361,6 +498,20 @@ pub enum SplitStrategy {
     /// Split live ranges by finding a region which is dense enough to evict
     /// interfering live ranges.
     Linear,
+
+    /// Split live ranges around a region of blocks in which a register is
+    /// free, placing split points on the least frequently executed CFG edges.
+    ///
+    /// Falls back to `Linear` if no profitable region is found.
+    Region,
+
+    /// Split live ranges around instructions which clobber most of a register
+    /// class, such as calls. Values can then use caller-saved registers between
+    /// calls and a callee-saved register or spill slot across them.
+    ///
+    /// Falls back to `Linear` if a live range isn't live across any such
+    /// instruction.
+    Call,
 }
 
 /// Configuration options for the register allocator.
@@ -368,6 +519,10 @@ pub enum SplitStrategy {
 #[cfg_attr(feature = "clap", derive(clap::Args))]
 #[non_exhaustive]
 pub struct Options {
+    /// Selects the register assignment algorithm.
+    #[cfg_attr(feature = "clap", clap(long, default_value = "greedy"))]
+    pub algorithm: AllocationAlgorithm,
+
     /// Controls how moves are optimized after register allocation.
     #[cfg_attr(feature = "clap", clap(long, default_value = "forward"))]
     pub move_optimization: MoveOptimizationLevel,
@@ -382,15 +537,49 @@ pub struct Options {
     /// for most cases.
     #[cfg_attr(feature = "clap", clap(long, default_value = "200"))]
     pub spill_weight_adjust: u32,
+
+    /// Maximum recursion depth for last-chance recoloring.
+    ///
+    /// When an unspillable live range can't be allocated by evicting or
+    /// splitting, the allocator attempts to move interfering unspillable live
+    /// ranges to other registers, recursively up to this depth, before giving
+    /// up with `RegAllocError::TooManyLiveRegs`. A depth of 0 disables
+    /// recoloring.
+    #[cfg_attr(feature = "clap", clap(long, default_value = "5"))]
+    pub recoloring_depth: u32,
+
+    /// Forces reference values which are live across a safepoint into spill
+    /// slots, so that stack maps only contain stack locations.
+    ///
+    /// This is done after allocation by spilling each reference held in a
+    /// register before every safepoint and reloading it after, even between
+    /// consecutive safepoints. The allocator doesn't account for this cost,
+    /// so a reference kept in a register across a loop containing a call pays
+    /// for a spill and a reload on every iteration instead of being spilled
+    /// once outside the loop.
+    #[cfg_attr(feature = "clap", clap(long))]
+    pub spill_references_at_safepoints: bool,
+
+    /// Leaves the moves on CFG edges out of [`Output::output_insts`].
+    ///
+    /// These moves are then only available from [`Output::edge_edits`], which
+    /// allows the client to decide where to place them. Edge blocks are never
+    /// needed in this mode.
+    #[cfg_attr(feature = "clap", clap(long))]
+    pub separate_edge_edits: bool,
 }
 
 #[cfg(feature = "arbitrary")]
 impl<'a> arbitrary::Arbitrary<'a> for Options {
     fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
         Ok(Self {
+            algorithm: u.arbitrary()?,
             move_optimization: u.arbitrary()?,
             split_strategy: u.arbitrary()?,
             spill_weight_adjust: u.int_in_range(0..=1000000)?,
+            recoloring_depth: u.int_in_range(0..=8)?,
+            spill_references_at_safepoints: u.arbitrary()?,
+            separate_edge_edits: u.arbitrary()?,
         })
     }
 }
@@ -399,9 +588,13 @@ impl Default for Options {
     #[inline]
     fn default() -> Self {
         Self {
+            algorithm: AllocationAlgorithm::Greedy,
             move_optimization: MoveOptimizationLevel::Forward,
             split_strategy: SplitStrategy::Linear,
             spill_weight_adjust: 200,
+            recoloring_depth: 5,
+            spill_references_at_safepoints: false,
+            separate_edge_edits: false,
         }
     }
 }
@@ -420,28 +613,142 @@ pub enum RegAllocError {
     /// Generally this can only occur due to excessive and/or invalid
     /// constraints on instruction operands, and should be considered a bug in
     /// the client.
-    TooManyLiveRegs,
+    ///
+    /// The reported operands, values and units form a minimal set which
+    /// together occupy every register of `class` that could have been used:
+    /// anything else occupying those registers is left out.
+    TooManyLiveRegs {
+        /// The instruction for which allocation failed.
+        ///
+        /// This is numbered as in the input function, even if edge blocks
+        /// were inserted for critical edges or branch block parameters.
+        inst: Inst,
+
+        /// The register class which ran out of registers.
+        class: RegClass,
+
+        /// Indices of the operands of `inst` which are occupying registers in
+        /// `class`, including the operand which failed to be allocated.
+        ///
+        /// This includes operands with fixed-register constraints.
+        operands: Vec<usize>,
+
+        /// Values which are not operands of `inst` but are live across it in
+        /// registers of `class`.
+        live_values: Vec<Value>,
+
+        /// Register units from `class` which are clobbered by `inst`.
+        clobbers: Vec<RegUnit>,
+
+        /// Register units from `class` which are reserved by fixed-register
+        /// operands of neighboring instructions.
+        ///
+        /// This can happen for example if a fixed-register definition on the
+        /// previous instruction is live into `inst`.
+        fixed_units: Vec<RegUnit>,
+    },
 
     /// The size of the function exceeded some internal limits in the allocator.
     ///
     /// E.g. number of virtual registers, total number of operands in the
     /// function, etc.
     FunctionTooBig,
+
+    /// A pinned register (see [`Function::value_pinned_reg`]) is needed by a
+    /// fixed-register operand, a clobber or another pinned value while the
+    /// pinned value is live.
+    ///
+    /// This should be considered a bug in the client.
+    ///
+    /// [`Function::value_pinned_reg`]: crate::function::Function::value_pinned_reg
+    PinnedRegConflict {
+        /// The pinned value.
+        value: Value,
+
+        /// The register which the value is pinned to.
+        reg: PhysReg,
+    },
+}
+
+impl RegAllocError {
+    /// Maps the instruction referenced by the error, if any, from the split
+    /// view of the function back to the original function.
+    fn map_inst(self, f: impl FnOnce(Inst) -> Inst) -> Self {
+        match self {
+            RegAllocError::TooManyLiveRegs {
+                inst,
+                class,
+                operands,
+                live_values,
+                clobbers,
+                fixed_units,
+            } => RegAllocError::TooManyLiveRegs {
+                inst: f(inst),
+                class,
+                operands,
+                live_values,
+                clobbers,
+                fixed_units,
+            },
+            RegAllocError::FunctionTooBig | RegAllocError::PinnedRegConflict { .. } => self,
+        }
+    }
 }
 
 impl fmt::Display for RegAllocError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            RegAllocError::TooManyLiveRegs => {
-                write!(f, "too many live registers in a single instruction")
+            RegAllocError::TooManyLiveRegs {
+                inst,
+                class,
+                operands,
+                live_values,
+                clobbers,
+                fixed_units,
+            } => {
+                write!(
+                    f,
+                    "too many live registers in {class} for {inst}: operands [{}]",
+                    debug_utils::display_iter(operands, ",")
+                )?;
+                if !live_values.is_empty() {
+                    write!(
+                        f,
+                        ", live values [{}]",
+                        debug_utils::display_iter(live_values, ",")
+                    )?;
+                }
+                if !clobbers.is_empty() {
+                    write!(
+                        f,
+                        ", clobbers [{}]",
+                        debug_utils::display_iter(clobbers, ",")
+                    )?;
+                }
+                if !fixed_units.is_empty() {
+                    write!(
+                        f,
+                        ", fixed units [{}]",
+                        debug_utils::display_iter(fixed_units, ",")
+                    )?;
+                }
+                Ok(())
             }
             RegAllocError::FunctionTooBig => {
                 write!(f, "function size exceeded implementation limits")
             }
+            RegAllocError::PinnedRegConflict { value, reg } => {
+                write!(
+                    f,
+                    "{value} is pinned to {reg} which is used elsewhere while it is live"
+                )
+            }
         }
     }
 }
 
+impl core::error::Error for RegAllocError {}
+
 /// Statistics collected by the register allocator.
 ///
 /// This is an opaque type since the set of statistics may vary between
@@ -459,6 +766,10 @@ pub struct Stats {
     values: usize,
     value_groups: usize,
 
+    // Stats from edge splitting.
+    split_edges: usize,
+    edge_blocks: usize,
+
     // Stats from value live ranges.
     fixed_def: usize,
     class_def: usize,
@@ -471,6 +782,7 @@ pub struct Stats {
     nonallocatable_operand: usize,
     blockparam_in: usize,
     blockparam_out: usize,
+    keep_alive_uses: usize,
     local_values: usize,
     global_values: usize,
     value_segments: usize,
@@ -481,10 +793,12 @@ pub struct Stats {
     coalesced_tied_group: usize,
     coalesced_blockparam: usize,
     coalesced_group: usize,
+    coalesced_copy: usize,
     coalesced_failed_tied: usize,
     coalesced_failed_tied_group: usize,
     coalesced_failed_blockparam: usize,
     coalesced_failed_group: usize,
+    coalesced_failed_copy: usize,
     coalesce_fast_path: usize,
     coalesce_slow_path: usize,
 
@@ -496,6 +810,9 @@ pub struct Stats {
     initial_vreg_segments: usize,
 
     // Stats from register allocation.
+    linear_scan_fallbacks: usize,
+    pinned_vregs: usize,
+    claimed_callee_saved: usize,
     dequeued_reg: usize,
     dequeued_group: usize,
     probe_for_free_reg: usize,
@@ -517,7 +834,14 @@ pub struct Stats {
     unevictable_initial_gap: usize,
     evict_for_null_split: usize,
     spill_cheaper_than_split: usize,
+    try_recoloring: usize,
+    recolored_vregs: usize,
+    recoloring_budget_exhausted: usize,
     split_vregs: usize,
+    region_split_vregs: usize,
+    no_region_split: usize,
+    call_split_vregs: usize,
+    no_call_split: usize,
     spilled_vregs: usize,
     spill_minimal_segments: usize,
     isolated_group_vregs: usize,
@@ -536,6 +860,7 @@ pub struct Stats {
     spill_segments: usize,
     spillslots: usize,
     spill_area_size: usize,
+    scalable_spill_area_size: usize,
 
     // Stats from move resolver.
     edits: usize,
@@ -545,15 +870,23 @@ pub struct Stats {
     reloads: usize,
     evict_spills: usize,
     evict_reloads: usize,
+    dead_insts: usize,
+    eliminated_copies: usize,
 
     // Stats from move optimizer.
     blocks_preprocessed_for_optimizer: usize,
     optimized_stack_use: usize,
     optimized_reload_to_move: usize,
+    optimized_remat_to_move: usize,
     optimized_redundant_remat: usize,
     optimized_redundant_move: usize,
     optimized_redundant_spill: usize,
     optimized_redundant_reload: usize,
+
+    // Stats from safepoints.
+    safepoints: usize,
+    stack_map_entries: usize,
+    safepoint_spills: usize,
 }
 
 impl fmt::Display for Stats {
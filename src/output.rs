@@ -33,17 +33,75 @@
 //! [`Allocation`]. This is often cheaper than spilling to the stack, especially
 //! for constant values.
 //!
+//! A rematerializable value may also depend on a single input value returned by
+//! [`Function::remat_input`], in which case the rematerialization also reports
+//! the register currently holding that input.
+//!
+//! # Copies
+//!
+//! Instructions marked with [`Function::is_copy`] are treated as plain copies
+//! from one [`Value`] to another. The allocator tries to assign both values to
+//! the same location, in which case the copy is omitted from
+//! [`Output::output_insts`].
+//!
+//! # Owned output
+//!
+//! [`Output`] borrows the [`RegisterAllocator`] as well as the input function
+//! and register information. [`Output::to_owned`] can be used to copy the
+//! results into an [`OwnedOutput`] which is detached from all of these, which
+//! allows the `RegisterAllocator` to be re-used for another function while
+//! keeping the results.
+//!
+//! # Edge blocks
+//!
+//! If [`Function::allows_critical_edges`] is set then moves may need to be
+//! placed on a critical edge of the CFG. The same applies to the moves for
+//! block parameters passed by a branch terminator with
+//! [`Function::branch_blockparams`]. Each such edge is reported by
+//! [`Output::edge_blocks`] as an [`EdgeBlock`]: the client must insert a new
+//! block on that edge which contains the output instructions of the edge
+//! block followed by a jump to the successor. Edge blocks are numbered after
+//! the blocks of the input function and can be passed to
+//! [`Output::output_insts`] like any other block.
+//!
+//! Edges which don't appear in [`Output::edge_blocks`] don't need any moves
+//! and can be left as they are.
+//!
+//! # Edge edits
+//!
+//! The moves which connect the values at the end of a block to the start of
+//! one of its successors are normally placed before the jump at the end of the
+//! predecessor, at the start of the successor or in an edge block.
+//! [`Output::edge_edits`] returns these moves separately for each CFG edge,
+//! which is useful for clients that perform their own block layout. Moves
+//! for the operands of the first instruction of the successor are not part of
+//! the edge unless they read a value directly from the predecessor.
+//!
+//! If [`Options::separate_edge_edits`] is set then these moves are left out of
+//! [`Output::output_insts`] entirely and the client is responsible for
+//! placing them on the edge. No edge blocks are reported in that case.
+//!
+//! # Debug info
+//!
+//! [`Output::value_location_ranges`] describes where each [`Value`] is
+//! located at every point of the output instruction stream, which can be used
+//! to generate location lists for a debugger.
+//!
 //! [`Operand`]: super::function::Operand
+//! [`Options::separate_edge_edits`]: crate::Options::separate_edge_edits
 
-use core::fmt;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::{fmt, slice};
 
 use crate::RegisterAllocator;
-use crate::entity::PrimaryMap;
 use crate::entity::iter::Keys;
 use crate::entity::packed_option::ReservedValue;
+use crate::entity::{PrimaryMap, SecondaryMap};
 use crate::function::{Block, Function, Inst, InstRange, Value};
+use crate::internal::location_ranges;
 use crate::internal::move_resolver::Edit;
-use crate::reginfo::{PhysReg, RegInfo, SpillSlotSize};
+use crate::reginfo::{PhysReg, PhysRegSet, RegBank, RegInfo, RegUnitSet, SpillSlotSize};
 
 /// Maximum size of the spill area.
 pub const MAX_SPILL_AREA_SIZE: u32 = 1 << 29;
@@ -203,18 +261,137 @@ where
     ///
     /// This consists of original program instructions as well as moves and
     /// rematerializations inserted by the register allocator.
+    ///
+    /// `block` may also be one of the blocks returned by
+    /// [`Output::edge_blocks`], in which case only moves and
+    /// rematerializations are returned.
+    ///
+    /// Copy instructions (see [`Function::is_copy`]) whose source and
+    /// destination were assigned the same [`Allocation`] are omitted.
     #[inline]
     #[must_use]
     pub fn output_insts(&self, block: Block) -> OutputIter<'a> {
-        let insts = self.func.block_insts(block);
+        OutputIter {
+            keep_eliminated_copies: false,
+            ..self.output_insts_with_copies(block)
+        }
+    }
+
+    /// Same as [`Output::output_insts`] but also returns eliminated copies.
+    ///
+    /// An eliminated copy doesn't occupy a position in the output, but it is
+    /// still the point where the copy's destination value is defined.
+    #[inline]
+    pub(crate) fn output_insts_with_copies(&self, block: Block) -> OutputIter<'a> {
+        let (insts, is_edge_block) = self.regalloc.edge_split.block_insts(self.func, block);
         let edits = self.regalloc.move_resolver.edits_from(insts.from);
         OutputIter {
             insts,
             edits,
+            edits_only: is_edge_block,
+            keep_eliminated_copies: true,
+            regalloc: self.regalloc,
+        }
+    }
+
+    /// Returns whether the given copy instruction was omitted from
+    /// [`Output::output_insts`].
+    #[inline]
+    pub(crate) fn is_eliminated_copy(&self, inst: Inst) -> bool {
+        let inst = self.regalloc.edge_split.split_inst(self.func, inst);
+        self.regalloc.move_resolver.is_eliminated_copy(inst)
+    }
+
+    /// Returns an iterator over the moves and rematerializations on the CFG
+    /// edge from `pred` to its successor at index `succ_idx` in
+    /// [`Function::block_succs`].
+    ///
+    /// These form a sequence which must be executed after leaving `pred` and
+    /// before entering the successor. Unless [`Options::separate_edge_edits`]
+    /// is set, they are also included in [`Output::output_insts`] for either
+    /// `pred`, the successor or the edge block between them.
+    ///
+    /// Edges are identified by successor index rather than by successor block
+    /// because a branch may have the same block as several of its successors.
+    ///
+    /// [`Options::separate_edge_edits`]: crate::Options::separate_edge_edits
+    #[inline]
+    #[must_use]
+    pub fn edge_edits(&self, pred: Block, succ_idx: usize) -> OutputIter<'a> {
+        let move_resolver = &self.regalloc.move_resolver;
+        let (pred, edge_block, _) = self
+            .regalloc
+            .edge_split
+            .split_edge(self.func, pred, succ_idx);
+
+        // The edits of a split edge come from both sides of the edge block's
+        // jump, which are adjacent.
+        let range = match edge_block {
+            Some(edge_block) => {
+                match (
+                    move_resolver.edge_edit_range(pred, succ_idx),
+                    move_resolver.edge_edit_range(edge_block, 0),
+                ) {
+                    (Some((start, _)), Some((_, end))) => Some((start, end)),
+                    (range, None) | (None, range) => range,
+                }
+            }
+            None => move_resolver.edge_edit_range(pred, succ_idx),
+        };
+        let (start, end) = range.unwrap_or((0, 0));
+        let edits = &move_resolver.edge_edits()[start as usize..end as usize];
+        let insts = match edits.first() {
+            Some(&(inst, _)) => InstRange::new(inst, inst.next()),
+            None => InstRange::new(Inst::new(0), Inst::new(0)),
+        };
+        OutputIter {
+            insts,
+            edits,
+            edits_only: true,
+            keep_eliminated_copies: false,
             regalloc: self.regalloc,
         }
     }
 
+    /// Returns whether [`Options::separate_edge_edits`] was set.
+    ///
+    /// [`Options::separate_edge_edits`]: crate::Options::separate_edge_edits
+    #[inline]
+    pub(crate) fn edge_edits_separated(&self) -> bool {
+        self.regalloc.move_resolver.edge_edits_separated()
+    }
+
+    /// Returns the blocks which must be inserted on CFG edges to hold moves
+    /// generated by the register allocator.
+    ///
+    /// This is always empty unless [`Function::allows_critical_edges`] is set
+    /// or branch terminators have block parameters. It is also empty if
+    /// [`Options::separate_edge_edits`] is set. See the [module-level
+    /// documentation] for details.
+    ///
+    /// [module-level documentation]: self
+    /// [`Options::separate_edge_edits`]: crate::Options::separate_edge_edits
+    #[inline]
+    #[must_use]
+    pub fn edge_blocks(&self) -> &'a [EdgeBlock] {
+        self.regalloc.edge_split.edge_blocks()
+    }
+
+    /// Returns the edge block on the edge from `pred` to its successor at
+    /// index `succ_idx` in [`Function::block_succs`], if one needs to be
+    /// inserted there.
+    #[inline]
+    #[must_use]
+    pub fn edge_block_between(&self, pred: Block, succ_idx: usize) -> Option<Block> {
+        let edge_blocks = self.edge_blocks();
+        let idx = edge_blocks.partition_point(|edge| edge.pred < pred);
+        edge_blocks[idx..]
+            .iter()
+            .take_while(|edge| edge.pred == pred)
+            .find(|edge| edge.succ_idx == succ_idx)
+            .map(|edge| edge.block)
+    }
+
     /// Returns the layout of the stack frame containing all spill slots.
     #[inline]
     #[must_use]
@@ -222,9 +399,55 @@ where
         &self.regalloc.spill_allocator.stack_layout
     }
 
+    /// Returns the set of register units written by the function.
+    ///
+    /// This includes operand definitions, clobbers, and the destinations of
+    /// moves and rematerializations inserted by the register allocator
+    /// (including scratch registers used during move resolution). Units are
+    /// reported even if they are not part of any register bank.
+    #[inline]
+    #[must_use]
+    pub fn written_units(&self) -> &RegUnitSet {
+        &self.regalloc.written_regs.units
+    }
+
+    /// Returns the set of registers in `bank` which are written by the
+    /// function.
+    ///
+    /// A register is considered written if any of its units are in
+    /// [`Output::written_units`]. This is typically intersected with the set
+    /// of callee-saved registers to determine which registers need to be saved
+    /// in the function prologue.
+    #[inline]
+    #[must_use]
+    pub fn written_regs(&self, bank: RegBank) -> &PhysRegSet {
+        &self.regalloc.written_regs.regs[bank]
+    }
+
+    /// Returns the reference values which are live across the given safepoint
+    /// instruction, along with the [`Allocation`] holding each of them.
+    ///
+    /// A moving garbage collector may update the references in these
+    /// locations while the safepoint executes. If
+    /// [`Options::spill_references_at_safepoints`] is enabled then all of these
+    /// allocations are on the stack.
+    ///
+    /// This is empty for instructions which are not safepoints.
+    ///
+    /// [`Options::spill_references_at_safepoints`]: crate::Options::spill_references_at_safepoints
+    #[inline]
+    pub fn stack_map(&self, inst: Inst) -> impl Iterator<Item = (Value, Allocation)> + 'a {
+        let inst = self.regalloc.edge_split.split_inst(self.func, inst);
+        self.regalloc.safepoints.stack_map(inst)
+    }
+
     /// Returns the [`Allocation`]s assigned to each [`Value`] at different
     /// points in the function.
     ///
+    /// This is a coarse approximation which is cheap to compute. See
+    /// [`Output::value_location_ranges`] for exact locations at every point in
+    /// the output instruction stream.
+    ///
     /// Specifically, this indicates where values are located when they are
     /// *live through* an instruction. This means that the value was defined in
     /// a previous instruction and this is not the last use of this value.
@@ -243,20 +466,28 @@ where
     #[inline]
     pub fn value_locations(&self) -> impl Iterator<Item = (Value, InstRange, Allocation)> + 'a {
         // Gather segments from assigned registers and spill slots and return
-        // the allocation assigned to each.
-        self.regalloc
+        // the allocation assigned to each. Segments for definitions that have
+        // been eliminated are skipped since they never hold the value.
+        let regalloc = self.regalloc;
+        regalloc
             .allocator
             .assignments()
-            .flat_map(|(vreg, reg)| {
-                self.regalloc
+            .flat_map(move |(vreg, reg)| {
+                regalloc
                     .virt_regs
                     .segments(vreg)
                     .iter()
                     .filter_map(move |segment| {
-                        let inst_range = InstRange::new(
+                        if regalloc
+                            .move_resolver
+                            .is_dead_def_segment(segment, &regalloc.uses)
+                        {
+                            return None;
+                        }
+                        let inst_range = regalloc.edge_split.orig_inst_range(InstRange::new(
                             segment.live_range.from.round_to_next_inst().inst(),
                             segment.live_range.to.round_to_prev_inst().inst(),
-                        );
+                        ));
                         if !inst_range.is_empty() {
                             Some((segment.value, inst_range, Allocation::reg(reg)))
                         } else {
@@ -265,27 +496,158 @@ where
                     })
             })
             .chain(
-                self.regalloc
+                regalloc
                     .spill_allocator
                     .spilled_segments()
-                    .map(|(spillslot, segment)| {
-                        let inst_range = InstRange::new(
+                    .filter(move |(_, segment)| {
+                        !regalloc
+                            .move_resolver
+                            .is_dead_def_segment(segment, &regalloc.uses)
+                    })
+                    .filter_map(|(spillslot, segment)| {
+                        let inst_range = regalloc.edge_split.orig_inst_range(InstRange::new(
                             segment.live_range.from.round_to_next_inst().inst(),
                             segment.live_range.to.round_to_prev_inst().inst(),
-                        );
-                        (segment.value, inst_range, Allocation::spillslot(spillslot))
+                        ));
+                        // Segments that only cover edge blocks don't have any
+                        // instructions in the input function. Drop empty
+                        // ranges like for registers.
+                        if !inst_range.is_empty() {
+                            Some((segment.value, inst_range, Allocation::spillslot(spillslot)))
+                        } else {
+                            None
+                        }
                     }),
             )
     }
+
+    /// Returns the precise locations of each [`Value`] in terms of positions
+    /// in the output instruction stream, as returned by
+    /// [`Output::output_insts`].
+    ///
+    /// Each entry `(value, start, end, alloc)` indicates that `alloc` holds
+    /// `value` just before executing each output instruction from `start` up
+    /// to but excluding `end`. Both positions are always in the same block.
+    ///
+    /// Unlike [`Output::value_locations`], this accounts for every move and
+    /// rematerialization inserted by the register allocator and is suitable
+    /// for generating debug info location lists. A value may be in several
+    /// allocations at once, and copies of a value are reported even after
+    /// its last use as long as they haven't been overwritten. References are
+    /// only reported in their stack map locations after a safepoint.
+    ///
+    /// The values that are reported can be extended by keeping them live with
+    /// [`Function::value_scope_end`].
+    ///
+    /// This is computed on demand by walking the output of every block, which
+    /// is relatively expensive.
+    #[must_use]
+    pub fn value_location_ranges(&self) -> Vec<(Value, OutputPos, OutputPos, Allocation)> {
+        location_ranges::compute(self)
+    }
+
+    /// Copies the results of register allocation into an [`OwnedOutput`]
+    /// which doesn't borrow the `RegisterAllocator`, the `Function` or the
+    /// `RegInfo`.
+    ///
+    /// This doesn't include [`Output::value_location_ranges`] since they are
+    /// relatively expensive to compute, use
+    /// [`Output::to_owned_with_location_ranges`] if they are needed.
+    #[must_use]
+    pub fn to_owned(&self) -> OwnedOutput {
+        self.to_owned_impl(false)
+    }
+
+    /// Same as [`Output::to_owned`], but also copies
+    /// [`Output::value_location_ranges`].
+    #[must_use]
+    pub fn to_owned_with_location_ranges(&self) -> OwnedOutput {
+        self.to_owned_impl(true)
+    }
+
+    fn to_owned_impl(&self, location_ranges: bool) -> OwnedOutput {
+        let num_blocks = self.func.num_blocks() + self.edge_blocks().len();
+        let mut block_entries = Vec::with_capacity(num_blocks + 1);
+        let mut entries = vec![];
+        let mut operand_allocs = vec![];
+        let mut to_entry = |inst| match inst {
+            OutputInst::Inst {
+                inst,
+                operand_allocs: allocs,
+            } => {
+                let start = operand_allocs.len() as u32;
+                operand_allocs.extend_from_slice(allocs);
+                OwnedOutputEntry::Inst {
+                    inst,
+                    operand_allocs: (start, operand_allocs.len() as u32),
+                }
+            }
+            OutputInst::Rematerialize { value, to, input } => {
+                OwnedOutputEntry::Rematerialize { value, to, input }
+            }
+            OutputInst::Move { from, to, value } => OwnedOutputEntry::Move { from, to, value },
+        };
+        block_entries.push(0);
+        for block in (0..num_blocks).map(Block::new) {
+            entries.extend(self.output_insts(block).map(&mut to_entry));
+            block_entries.push(entries.len() as u32);
+        }
+
+        // Edge edits are stored after the blocks.
+        let mut edge_edits = vec![];
+        for pred in self.func.blocks() {
+            for succ_idx in 0..self.func.block_succs(pred).len() {
+                let start = entries.len() as u32;
+                entries.extend(self.edge_edits(pred, succ_idx).map(&mut to_entry));
+                if entries.len() as u32 != start {
+                    edge_edits.push((pred, succ_idx as u32, start, entries.len() as u32));
+                }
+            }
+        }
+
+        OwnedOutput {
+            block_entries,
+            edge_edits,
+            entries,
+            operand_allocs,
+            stack_layout: self.stack_layout().clone(),
+            value_locations: self.value_locations().collect(),
+            value_location_ranges: location_ranges.then(|| self.value_location_ranges()),
+            written_units: *self.written_units(),
+            written_regs: self.regalloc.written_regs.regs.clone(),
+            stack_maps: self
+                .regalloc
+                .safepoints
+                .stack_maps
+                .iter()
+                .map(|&(inst, value, alloc)| {
+                    let inst = self.regalloc.edge_split.orig_inst(inst).unwrap();
+                    (inst, value, alloc)
+                })
+                .collect(),
+            edge_blocks: self.edge_blocks().to_vec(),
+        }
+    }
 }
 
 /// Positions of all the spill slots in the stack frame.
+///
+/// Spill slots are split between 2 areas of the stack frame: a fixed spill area
+/// whose size is known at compile time, and a scalable spill area for spill
+/// slots with a scalable [`SpillSlotSize`]. Offsets and sizes in the scalable
+/// spill area are expressed in multiples of the vector length (VL), which is
+/// only known at runtime.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StackLayout {
     /// Size and offset of each spill slot.
     pub(crate) slots: PrimaryMap<SpillSlot, (u32, SpillSlotSize)>,
 
-    /// Total size of the spill area.
+    /// Total size of the fixed spill area, in bytes.
     pub(crate) spillslot_area_size: u32,
+
+    /// Total size of the scalable spill area, in multiples of VL.
+    pub(crate) scalable_spillslot_area_size: u32,
 }
 
 impl StackLayout {
@@ -304,6 +666,9 @@ impl StackLayout {
     }
 
     /// Returns the offset of a spill slot in the spill area.
+    ///
+    /// For spill slots with a scalable size, this is an offset in multiples of
+    /// VL from the start of the scalable spill area.
     #[inline]
     #[must_use]
     pub fn spillslot_offset(&self, slot: SpillSlot) -> u32 {
@@ -318,21 +683,65 @@ impl StackLayout {
     }
 
     /// Returns the amount of space on the stack needed for all allocated
-    /// spill slots.
+    /// spill slots with a fixed size.
     ///
-    /// Each spill slot used by the allocator encodes an offset from the start
-    /// of this spill area.
+    /// Each fixed-size spill slot used by the allocator encodes an offset from
+    /// the start of this spill area.
     #[inline]
     #[must_use]
     pub fn spillslot_area_size(&self) -> u32 {
         self.spillslot_area_size
     }
+
+    /// Returns the amount of space on the stack needed for all allocated
+    /// spill slots with a scalable size, in multiples of VL.
+    ///
+    /// Each scalable spill slot used by the allocator encodes an offset from
+    /// the start of this spill area.
+    #[inline]
+    #[must_use]
+    pub fn scalable_spillslot_area_size(&self) -> u32 {
+        self.scalable_spillslot_area_size
+    }
+
+    /// Returns the size of the spill area holding spill slots of the given
+    /// size.
+    pub(crate) fn area_size_mut(&mut self, size: SpillSlotSize) -> &mut u32 {
+        if size.is_scalable() {
+            &mut self.scalable_spillslot_area_size
+        } else {
+            &mut self.spillslot_area_size
+        }
+    }
+}
+
+/// A block which must be inserted on a CFG edge to hold moves.
+///
+/// See [`Output::edge_blocks`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EdgeBlock {
+    /// Block number used for this edge block in [`Output::output_insts`] and
+    /// [`OutputPos`]. This comes after all the blocks of the input function.
+    pub block: Block,
+
+    /// Predecessor of the edge, which ends with a branch terminator.
+    pub pred: Block,
+
+    /// Successor of the edge.
+    pub succ: Block,
+
+    /// Index of `succ` in the successors of `pred`.
+    pub succ_idx: usize,
 }
 
-/// Iterator over the [`OutputInst`] of a block after register allocation.
+/// Iterator over the [`OutputInst`] of a block or CFG edge after register
+/// allocation.
 pub struct OutputIter<'a> {
     insts: InstRange,
     edits: &'a [(Inst, Edit)],
+    edits_only: bool,
+    keep_eliminated_copies: bool,
     regalloc: &'a RegisterAllocator,
 }
 
@@ -340,9 +749,7 @@ impl<'a> Iterator for OutputIter<'a> {
     type Item = OutputInst<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.insts.is_empty() {
-            None
-        } else {
+        while !self.insts.is_empty() {
             // Any edits happen before the corresponding instruction.
             while let Some((&(pos, edit), rest)) = self.edits.split_first() {
                 if pos > self.insts.from {
@@ -365,17 +772,52 @@ impl<'a> Iterator for OutputIter<'a> {
                     None => OutputInst::Rematerialize {
                         to,
                         value: edit.value.expect("remat without value"),
+                        input: edit.input.expand(),
                     },
                 });
             }
 
             let inst = self.insts.from;
             self.insts.from = self.insts.from.next();
-            Some(OutputInst::Inst {
-                inst,
+
+            // Skip instructions that have been eliminated because their
+            // outputs are unused, as well as the jump at the end of edge
+            // blocks which isn't part of the input function. Only the edits
+            // are returned for CFG edges.
+            let move_resolver = &self.regalloc.move_resolver;
+            if self.edits_only
+                || move_resolver.is_dead_inst(inst)
+                || (!self.keep_eliminated_copies && move_resolver.is_eliminated_copy(inst))
+            {
+                continue;
+            }
+
+            return Some(OutputInst::Inst {
+                inst: self.regalloc.edge_split.orig_inst(inst).unwrap(),
                 operand_allocs: self.regalloc.allocations.inst_allocations(inst),
-            })
+            });
         }
+        None
+    }
+}
+
+/// A position in the output instruction stream of a block.
+///
+/// `index` counts the output instructions of `block` as returned by
+/// [`Output::output_insts`], starting from 0.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OutputPos {
+    /// Block containing the output instruction.
+    pub block: Block,
+
+    /// Index of the output instruction in the block.
+    pub index: u32,
+}
+
+impl fmt::Display for OutputPos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.block, self.index)
     }
 }
 
@@ -385,8 +827,9 @@ pub enum OutputInst<'a> {
     /// An original instruction, with its operands mapped to `Allocation`s.
     ///
     /// Note that due to rematerialization, not all instructions of the original
-    /// function may be present. This can happen if the original instruction had
-    /// no side effects and its outputs are not needed.
+    /// function may be present. This can happen if the original instruction was
+    /// marked as pure by [`Function::can_eliminate_dead_inst`] and its outputs
+    /// are not needed.
     Inst {
         /// The reference to the original instruction.
         inst: Inst,
@@ -401,14 +844,20 @@ pub enum OutputInst<'a> {
     /// A value which should be re-materialized into the given `Allocation`.
     ///
     /// There are strict restrictions on rematerializable values: they cannot
-    /// depend on any other allocatable register and they are only allowed to
-    /// use a single destination register as scratch space.
+    /// depend on any other allocatable register, except for the input value
+    /// returned by [`Function::remat_input`], and they are only allowed to use
+    /// a single destination register as scratch space.
     Rematerialize {
         /// Value being rematerialized.
         value: Value,
 
         /// Destination into which the rematerialized value should be wrriten.
         to: Allocation,
+
+        /// Register holding the input value, if the value has one.
+        ///
+        /// The register is not modified unless it is the same as `to`.
+        input: Option<Allocation>,
     },
 
     /// A move instruction inserted by the register allocator.
@@ -430,3 +879,216 @@ pub enum OutputInst<'a> {
         value: Option<Value>,
     },
 }
+
+/// Results of register allocation, copied out of an [`Output`] by
+/// [`Output::to_owned`].
+///
+/// This holds the same information as `Output` but doesn't borrow the
+/// [`RegisterAllocator`], the [`Function`] or the [`RegInfo`].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedOutput {
+    /// Range of `entries` for each block, indexed by block.
+    block_entries: Vec<u32>,
+
+    /// Range of `entries` for each CFG edge with edits, as
+    /// `(pred, succ_idx, start, end)`, sorted by edge.
+    edge_edits: Vec<(Block, u32, u32, u32)>,
+
+    /// Output instructions for all blocks.
+    entries: Vec<OwnedOutputEntry>,
+
+    /// Operand allocations for all instructions in `entries`.
+    operand_allocs: Vec<Allocation>,
+
+    /// Layout of the spill area.
+    stack_layout: StackLayout,
+
+    /// Location of values, as returned by `Output::value_locations`.
+    value_locations: Vec<(Value, InstRange, Allocation)>,
+
+    /// Location ranges of values, as returned by
+    /// `Output::value_location_ranges`, if they were requested.
+    value_location_ranges: Option<Vec<(Value, OutputPos, OutputPos, Allocation)>>,
+
+    /// Register units written by the function.
+    written_units: RegUnitSet,
+
+    /// Registers written by the function in each bank.
+    written_regs: SecondaryMap<RegBank, PhysRegSet>,
+
+    /// Stack map entries for all safepoints, sorted by instruction.
+    stack_maps: Vec<(Inst, Value, Allocation)>,
+
+    /// Blocks to insert on CFG edges.
+    edge_blocks: Vec<EdgeBlock>,
+}
+
+impl OwnedOutput {
+    /// Returns the number of blocks in the function, including edge blocks.
+    #[inline]
+    #[must_use]
+    pub fn num_blocks(&self) -> usize {
+        self.block_entries.len() - 1
+    }
+
+    /// Returns an iterator over the output instructions in the given block.
+    ///
+    /// This consists of original program instructions as well as moves and
+    /// rematerializations inserted by the register allocator.
+    #[inline]
+    #[must_use]
+    pub fn output_insts(&self, block: Block) -> OwnedOutputIter<'_> {
+        let start = self.block_entries[block.index()] as usize;
+        let end = self.block_entries[block.index() + 1] as usize;
+        OwnedOutputIter {
+            entries: self.entries[start..end].iter(),
+            operand_allocs: &self.operand_allocs,
+        }
+    }
+
+    /// Returns an iterator over the moves and rematerializations on the CFG
+    /// edge from `pred` to its successor at index `succ_idx`.
+    ///
+    /// See [`Output::edge_edits`] for details.
+    #[inline]
+    #[must_use]
+    pub fn edge_edits(&self, pred: Block, succ_idx: usize) -> OwnedOutputIter<'_> {
+        let (start, end) = match self
+            .edge_edits
+            .binary_search_by_key(&(pred, succ_idx as u32), |&(pred, succ_idx, _, _)| {
+                (pred, succ_idx)
+            }) {
+            Ok(idx) => (self.edge_edits[idx].2, self.edge_edits[idx].3),
+            Err(_) => (0, 0),
+        };
+        OwnedOutputIter {
+            entries: self.entries[start as usize..end as usize].iter(),
+            operand_allocs: &self.operand_allocs,
+        }
+    }
+
+    /// Returns the blocks which must be inserted on CFG edges.
+    ///
+    /// See [`Output::edge_blocks`] for details.
+    #[inline]
+    #[must_use]
+    pub fn edge_blocks(&self) -> &[EdgeBlock] {
+        &self.edge_blocks
+    }
+
+    /// Returns the layout of the stack frame containing all spill slots.
+    #[inline]
+    #[must_use]
+    pub fn stack_layout(&self) -> &StackLayout {
+        &self.stack_layout
+    }
+
+    /// Returns the set of register units written by the function.
+    ///
+    /// See [`Output::written_units`] for details.
+    #[inline]
+    #[must_use]
+    pub fn written_units(&self) -> &RegUnitSet {
+        &self.written_units
+    }
+
+    /// Returns the set of registers in `bank` which are written by the
+    /// function.
+    ///
+    /// See [`Output::written_regs`] for details.
+    #[inline]
+    #[must_use]
+    pub fn written_regs(&self, bank: RegBank) -> &PhysRegSet {
+        &self.written_regs[bank]
+    }
+
+    /// Returns the reference values which are live across the given safepoint
+    /// instruction, along with the [`Allocation`] holding each of them.
+    ///
+    /// See [`Output::stack_map`] for details.
+    #[inline]
+    pub fn stack_map(&self, inst: Inst) -> impl Iterator<Item = (Value, Allocation)> + '_ {
+        let idx = self
+            .stack_maps
+            .partition_point(|&(inst2, _, _)| inst2 < inst);
+        self.stack_maps[idx..]
+            .iter()
+            .take_while(move |&&(inst2, _, _)| inst2 == inst)
+            .map(|&(_, value, alloc)| (value, alloc))
+    }
+
+    /// Returns the [`Allocation`]s assigned to each [`Value`] at different
+    /// points in the function.
+    ///
+    /// See [`Output::value_locations`] for details.
+    #[inline]
+    pub fn value_locations(&self) -> impl Iterator<Item = (Value, InstRange, Allocation)> + '_ {
+        self.value_locations.iter().copied()
+    }
+
+    /// Returns the precise locations of each [`Value`] in terms of positions
+    /// in the output instruction stream.
+    ///
+    /// This is `None` unless the `OwnedOutput` was created with
+    /// [`Output::to_owned_with_location_ranges`]. See
+    /// [`Output::value_location_ranges`] for details.
+    #[inline]
+    #[must_use]
+    pub fn value_location_ranges(&self) -> Option<&[(Value, OutputPos, OutputPos, Allocation)]> {
+        self.value_location_ranges.as_deref()
+    }
+}
+
+/// Compact form of [`OutputInst`] stored in an [`OwnedOutput`].
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum OwnedOutputEntry {
+    Inst {
+        inst: Inst,
+        operand_allocs: (u32, u32),
+    },
+    Rematerialize {
+        value: Value,
+        to: Allocation,
+        input: Option<Allocation>,
+    },
+    Move {
+        from: Allocation,
+        to: Allocation,
+        value: Option<Value>,
+    },
+}
+
+/// Iterator over the [`OutputInst`] of a block in an [`OwnedOutput`].
+pub struct OwnedOutputIter<'a> {
+    entries: slice::Iter<'a, OwnedOutputEntry>,
+    operand_allocs: &'a [Allocation],
+}
+
+impl<'a> Iterator for OwnedOutputIter<'a> {
+    type Item = OutputInst<'a>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let inst = match *self.entries.next()? {
+            OwnedOutputEntry::Inst {
+                inst,
+                operand_allocs: (start, end),
+            } => OutputInst::Inst {
+                inst,
+                operand_allocs: &self.operand_allocs[start as usize..end as usize],
+            },
+            OwnedOutputEntry::Rematerialize { value, to, input } => {
+                OutputInst::Rematerialize { value, to, input }
+            }
+            OwnedOutputEntry::Move { from, to, value } => OutputInst::Move { from, to, value },
+        };
+        Some(inst)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
@@ -57,6 +57,22 @@
 //! allocation order so that they are only used as a last resort. This minimizes
 //! the set of registers that need to be spilled on function entry.
 //!
+//! # Callee-saved registers
+//!
+//! Using a callee-saved register requires the client to save it in the
+//! function prologue and restore it in the epilogue. This cost is only paid
+//! once: later uses of the same register in the function are free.
+//!
+//! [`RegInfo::callee_saved_cost`] can be used to describe this cost to the
+//! register allocator. Callee-saved registers that have not been claimed yet
+//! by another value are tried after all other registers in the allocation
+//! order. A value will only claim a new callee-saved register if the cost of
+//! doing so is lower than the cost of spilling that value, otherwise the value
+//! is evicted, split or spilled instead.
+//!
+//! Registers that are already written by a fixed-register def, clobber or
+//! pinned value in the function are considered to be claimed from the start.
+//!
 //! Any registers that are members of a class but not in the allocation order
 //! are only selected by the register allocator if it helps to satisfy a `Fixed`
 //! register constraint and are otherwise never used.
@@ -118,6 +134,14 @@
 //! single entry for the operand which holds the first register of the register
 //! group that was allocated for the operand.
 //!
+//! # Excluding registers
+//!
+//! Some registers may only be available in some functions, for example a frame
+//! pointer which only needs to be reserved when a function has a dynamically
+//! sized stack frame. Rather than maintaining multiple [`RegInfo`]
+//! implementations, [`ExcludeRegs`] can be used to wrap an existing
+//! [`RegInfo`] and make a set of registers non-allocatable.
+//!
 //! [`Value`]: super::function::Value
 //! [`ValueGroup`]: super::function::ValueGroup
 //! [`OperandKind::UseGroup`]: super::function::OperandKind::UseGroup
@@ -128,10 +152,11 @@
 //! [`Operand`]: super::function::Operand
 //! [`Allocation`]: super::output::Allocation
 
+use alloc::vec::Vec;
 use core::fmt;
 
-use crate::entity::SmallEntitySet;
 use crate::entity::iter::Keys;
+use crate::entity::{PackedOption, PrimaryMap, SecondaryMap, SmallEntitySet};
 
 /// Maximum number of register units.
 pub const MAX_REG_UNITS: usize = 512;
@@ -208,18 +233,34 @@ pub type RegClassSet = SmallEntitySet<RegClass, u64, { MAX_REG_CLASSES / 64 }>;
 
 /// The size of a spillslot, which must be a power of two.
 ///
-/// This is represented compactly as a `u8` holding the log2 of the size.
+/// A spillslot size is either a fixed number of bytes or *scalable*: a
+/// power-of-two multiple of a vector length (VL) which is only known at
+/// runtime. Scalable sizes are used for registers such as SVE `z` registers or
+/// RVV vector registers. Scalable spill slots are placed in a separate area of
+/// the stack frame, see [`StackLayout`].
+///
+/// This is represented compactly as a `u8` holding the log2 of the size, with
+/// the top bit set for scalable sizes.
+///
+/// [`StackLayout`]: super::output::StackLayout
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SpillSlotSize(u8);
 
 impl fmt::Display for SpillSlotSize {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.bytes())
+        if self.is_scalable() {
+            write!(f, "{}vl", self.bytes())
+        } else {
+            write!(f, "{}", self.bytes())
+        }
     }
 }
 
 impl SpillSlotSize {
+    /// Bit indicating a scalable size.
+    const SCALABLE_BIT: u8 = 0x80;
+
     /// Returns a `SpillSlotSize` of `bytes` bytes.
     ///
     /// `bytes` must be a power of two.
@@ -240,7 +281,39 @@ impl SpillSlotSize {
         Self(log2_bytes as u8)
     }
 
+    /// Returns a scalable `SpillSlotSize` of `vl_multiple` times the vector
+    /// length.
+    ///
+    /// `vl_multiple` must be a power of two.
+    #[inline]
+    #[must_use]
+    pub const fn scalable(vl_multiple: u32) -> Self {
+        debug_assert!(vl_multiple.is_power_of_two());
+        Self::scalable_from_log2(vl_multiple.trailing_zeros())
+    }
+
+    /// Returns a scalable `SpillSlotSize` of `1 << log2_vl_multiple` times the
+    /// vector length.
+    ///
+    /// `log2_vl_multiple` must be less than 32.
+    #[inline]
+    #[must_use]
+    pub const fn scalable_from_log2(log2_vl_multiple: u32) -> Self {
+        debug_assert!(log2_vl_multiple < 32);
+        Self(log2_vl_multiple as u8 | Self::SCALABLE_BIT)
+    }
+
+    /// Returns whether the size of the spill slot is a multiple of the vector
+    /// length instead of a fixed number of bytes.
+    #[inline]
+    #[must_use]
+    pub const fn is_scalable(self) -> bool {
+        self.0 & Self::SCALABLE_BIT != 0
+    }
+
     /// Returns the size of the spill slot in bytes.
+    ///
+    /// For scalable sizes, this is the size in multiples of the vector length.
     #[inline]
     #[must_use]
     pub const fn bytes(self) -> u32 {
@@ -248,10 +321,13 @@ impl SpillSlotSize {
     }
 
     /// Returns the log2 of the size of the spill slot in bytes.
+    ///
+    /// For scalable sizes, this is the log2 of the size in multiples of the
+    /// vector length.
     #[inline]
     #[must_use]
     pub const fn log2_bytes(self) -> u32 {
-        self.0 as u32
+        (self.0 & !Self::SCALABLE_BIT) as u32
     }
 }
 
@@ -306,7 +382,8 @@ pub trait RegInfo {
 
     /// Spillslot size needed for a value in this register bank.
     ///
-    /// The spillslot is guaranteed to be aligned to this size.
+    /// The spillslot is guaranteed to be aligned to this size. Scalable sizes
+    /// are aligned within the scalable spill area.
     fn spillslot_size(&self, bank: RegBank) -> SpillSlotSize;
 
     // ----------------
@@ -454,13 +531,36 @@ pub trait RegInfo {
     /// - Memory to memory moves are resolved by using a temporary scratch
     ///   register since such more are not supported by hardware.
     /// - Rematerialization can be more aggressive when it can avoid a load from
-    ///   memory, depending on [`RematCost`].
+    ///   memory, depending on the cost returned by
+    ///   [`Function::can_rematerialize`].
     ///
-    /// [`RematCost`]: super::function::RematCost
+    /// [`Function::can_rematerialize`]: super::function::Function::can_rematerialize
     /// [`Allocation`]: super::output::Allocation
     /// [`Allocation::is_memory`]: super::output::Allocation::is_memory
     fn is_memory(&self, reg: PhysReg) -> bool;
 
+    /// Returns the cost of using a callee-saved register for the first time
+    /// in a function.
+    ///
+    /// This represents the save in the prologue and restore in the epilogue
+    /// that the client will need to emit if the register is allocated at all.
+    /// Once paid, further uses of the register in the same function are free.
+    /// The cost uses the same units as spill costs weighted by block
+    /// frequency: a cost of 2.0 is equivalent to a spill and reload in a block
+    /// with a frequency of 1.0.
+    ///
+    /// A cost of 0.0 indicates that the register is not callee-saved, or that
+    /// the register allocator should not take the save/restore into account.
+    /// The cost must be finite and non-negative.
+    ///
+    /// See the [module-level documentation] for more details.
+    ///
+    /// [module-level documentation]: self#callee-saved-registers
+    #[inline]
+    fn callee_saved_cost(&self, _reg: PhysReg) -> f32 {
+        0.0
+    }
+
     // ---------------
     // Register groups
     // ---------------
@@ -484,3 +584,224 @@ pub trait RegInfo {
     /// than 1.
     fn group_for_reg(&self, reg: PhysReg, group_index: usize, class: RegClass) -> Option<RegGroup>;
 }
+
+/// Register class data after exclusions have been applied.
+struct ExcludedClassData {
+    members: PhysRegSet,
+    group_members: RegGroupSet,
+    allocation_order: Vec<PhysReg>,
+    group_allocation_order: Vec<RegGroup>,
+}
+
+/// Wrapper around a [`RegInfo`] which makes a set of registers
+/// non-allocatable.
+///
+/// Excluded registers are removed from all register classes and allocation
+/// orders and are no longer part of any register bank. They can still be used
+/// by [`OperandKind::NonAllocatable`] operands and as clobbers.
+///
+/// Register groups which contain an excluded register are removed entirely.
+/// The remaining groups are renumbered, so the [`RegGroup`] indices of the
+/// wrapper differ from those of the underlying [`RegInfo`]. [`PhysReg`],
+/// [`RegClass`] and [`RegBank`] indices are unchanged.
+///
+/// The result passes [`validate_reginfo`] as long as the underlying
+/// [`RegInfo`] does and every register class which doesn't allow spillslots
+/// still has a non-empty allocation order after exclusion.
+///
+/// [`OperandKind::NonAllocatable`]: super::function::OperandKind::NonAllocatable
+/// [`validate_reginfo`]: super::debug_utils::validate_reginfo
+pub struct ExcludeRegs<'a, R> {
+    reginfo: &'a R,
+    excluded: PhysRegSet,
+    classes: PrimaryMap<RegClass, ExcludedClassData>,
+
+    /// Original index of each remaining register group.
+    groups: PrimaryMap<RegGroup, RegGroup>,
+
+    /// Mapping from original register groups to their new index, or `None` if
+    /// the group contains an excluded register.
+    group_map: SecondaryMap<RegGroup, PackedOption<RegGroup>>,
+}
+
+impl<'a, R: RegInfo> ExcludeRegs<'a, R> {
+    /// Wraps `reginfo`, making the registers in `excluded` non-allocatable.
+    #[must_use]
+    pub fn new(reginfo: &'a R, excluded: PhysRegSet) -> Self {
+        let mut groups = PrimaryMap::with_capacity(reginfo.num_reg_groups());
+        let mut group_map: SecondaryMap<RegGroup, PackedOption<RegGroup>> =
+            SecondaryMap::with_max_index(reginfo.num_reg_groups());
+        for group in reginfo.reg_groups() {
+            if reginfo
+                .reg_group_members(group)
+                .iter()
+                .all(|&reg| !excluded.contains(reg))
+            {
+                group_map[group] = Some(groups.push(group)).into();
+            }
+        }
+
+        let mut classes = PrimaryMap::with_capacity(reginfo.num_classes());
+        for class in reginfo.classes() {
+            classes.push(ExcludedClassData {
+                members: reginfo.class_members(class) & !excluded,
+                group_members: reginfo
+                    .class_group_members(class)
+                    .into_iter()
+                    .filter_map(|group| group_map[group].expand())
+                    .collect(),
+                allocation_order: reginfo
+                    .allocation_order(class)
+                    .iter()
+                    .copied()
+                    .filter(|&reg| !excluded.contains(reg))
+                    .collect(),
+                group_allocation_order: reginfo
+                    .group_allocation_order(class)
+                    .iter()
+                    .filter_map(|&group| group_map[group].expand())
+                    .collect(),
+            });
+        }
+
+        Self {
+            reginfo,
+            excluded,
+            classes,
+            groups,
+            group_map,
+        }
+    }
+
+    /// Returns the underlying [`RegInfo`].
+    #[inline]
+    #[must_use]
+    pub fn inner(&self) -> &'a R {
+        self.reginfo
+    }
+
+    /// Returns the set of excluded registers.
+    #[inline]
+    #[must_use]
+    pub fn excluded(&self) -> PhysRegSet {
+        self.excluded
+    }
+}
+
+impl<R: RegInfo> RegInfo for ExcludeRegs<'_, R> {
+    #[inline]
+    fn num_banks(&self) -> usize {
+        self.reginfo.num_banks()
+    }
+
+    #[inline]
+    fn top_level_class(&self, bank: RegBank) -> RegClass {
+        self.reginfo.top_level_class(bank)
+    }
+
+    #[inline]
+    fn stack_to_stack_class(&self, bank: RegBank) -> RegClass {
+        self.reginfo.stack_to_stack_class(bank)
+    }
+
+    #[inline]
+    fn bank_for_class(&self, class: RegClass) -> RegBank {
+        self.reginfo.bank_for_class(class)
+    }
+
+    #[inline]
+    fn bank_for_reg(&self, reg: PhysReg) -> Option<RegBank> {
+        if self.excluded.contains(reg) {
+            None
+        } else {
+            self.reginfo.bank_for_reg(reg)
+        }
+    }
+
+    #[inline]
+    fn spillslot_size(&self, bank: RegBank) -> SpillSlotSize {
+        self.reginfo.spillslot_size(bank)
+    }
+
+    #[inline]
+    fn num_classes(&self) -> usize {
+        self.reginfo.num_classes()
+    }
+
+    #[inline]
+    fn class_members(&self, class: RegClass) -> PhysRegSet {
+        self.classes[class].members
+    }
+
+    #[inline]
+    fn class_group_members(&self, class: RegClass) -> RegGroupSet {
+        self.classes[class].group_members
+    }
+
+    #[inline]
+    fn class_includes_spillslots(&self, class: RegClass) -> bool {
+        self.reginfo.class_includes_spillslots(class)
+    }
+
+    #[inline]
+    fn class_spill_cost(&self, class: RegClass) -> f32 {
+        self.reginfo.class_spill_cost(class)
+    }
+
+    #[inline]
+    fn allocation_order(&self, class: RegClass) -> &[PhysReg] {
+        &self.classes[class].allocation_order
+    }
+
+    #[inline]
+    fn group_allocation_order(&self, class: RegClass) -> &[RegGroup] {
+        &self.classes[class].group_allocation_order
+    }
+
+    #[inline]
+    fn sub_classes(&self, class: RegClass) -> RegClassSet {
+        self.reginfo.sub_classes(class)
+    }
+
+    #[inline]
+    fn class_group_size(&self, class: RegClass) -> usize {
+        self.reginfo.class_group_size(class)
+    }
+
+    #[inline]
+    fn num_regs(&self) -> usize {
+        self.reginfo.num_regs()
+    }
+
+    #[inline]
+    fn reg_units(&self, reg: PhysReg) -> impl Iterator<Item = RegUnit> {
+        self.reginfo.reg_units(reg)
+    }
+
+    #[inline]
+    fn is_memory(&self, reg: PhysReg) -> bool {
+        self.reginfo.is_memory(reg)
+    }
+
+    #[inline]
+    fn callee_saved_cost(&self, reg: PhysReg) -> f32 {
+        self.reginfo.callee_saved_cost(reg)
+    }
+
+    #[inline]
+    fn num_reg_groups(&self) -> usize {
+        self.groups.len()
+    }
+
+    #[inline]
+    fn reg_group_members(&self, group: RegGroup) -> &[PhysReg] {
+        self.reginfo.reg_group_members(self.groups[group])
+    }
+
+    #[inline]
+    fn group_for_reg(&self, reg: PhysReg, group_index: usize, class: RegClass) -> Option<RegGroup> {
+        self.reginfo
+            .group_for_reg(reg, group_index, class)
+            .and_then(|group| self.group_map[group].expand())
+    }
+}
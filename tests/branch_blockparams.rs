@@ -0,0 +1,82 @@
+//! Checks branch terminators which pass block parameters to their successors.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::function::{Block, Inst, Value};
+use regalloc3::output::OutputInst;
+use regalloc3::{Options, RegAllocError};
+
+mod common;
+
+#[test]
+fn blockparam_moves_on_edge() {
+    // %0 is passed to block2 directly from the branch and needs a move into
+    // r0, which must be placed on the edge rather than in either block.
+    let func = "
+%0 = bank0
+%1 = bank0
+%2 = bank0
+
+allow_critical_edges
+
+block0() freq(1):
+    inst0: inst Def(%0):r1 Def(%1):r2
+    inst1: branch(block2(%0), block1)
+block1() freq(1):
+    inst2: jump block2(%1)
+block2(%2) freq(1):
+    inst3: ret Use(%2):r0
+";
+    let (reginfo, func) = common::parse(common::REGINFO, func);
+    common::allocate(&reginfo, &func, &Options::default(), |output| {
+        let block0 = Block::new(0);
+        let edges: Vec<_> = output
+            .edge_blocks()
+            .iter()
+            .map(|edge| (edge.pred, edge.succ, edge.succ_idx))
+            .collect();
+        assert_eq!(edges, [(block0, Block::new(2), 0)]);
+        let edge_block = output.edge_block_between(block0, 0).unwrap();
+        assert!(output.output_insts(edge_block).any(|inst| matches!(
+            inst,
+            OutputInst::Move { value: Some(value), .. } if value == Value::new(0)
+        )));
+    })
+    .unwrap();
+}
+
+#[test]
+fn too_many_live_regs_original_inst() {
+    // The edge block inserted for the branch argument comes before block1 and
+    // block2 in the split view, but the error must refer to the original
+    // inst4.
+    let func = "
+%0 = bank0
+%1 = bank0
+%2 = bank0
+%3 = bank0
+%4 = bank0
+%5 = bank0
+
+allow_critical_edges
+
+block0() freq(1):
+    inst0: inst Def(%0):class1
+    inst1: branch(block2(%0), block1)
+block1() freq(1):
+    inst2: jump block2(%0)
+block2(%1) freq(1):
+    inst3: inst
+    inst4: inst Def(%2):class1 Def(%3):class1 Def(%4):class1 Def(%5):class1
+    inst5: ret
+";
+    let (reginfo, func) = common::parse(common::REGINFO, func);
+    match common::allocate(&reginfo, &func, &Options::default(), |_| ()) {
+        Err(RegAllocError::TooManyLiveRegs { inst, operands, .. }) => {
+            assert_eq!(inst, Inst::new(4));
+            assert_eq!(operands, [0, 1, 2, 3]);
+        }
+        Ok(()) => panic!("allocation succeeded"),
+        Err(err) => panic!("unexpected error: {err}"),
+    }
+}
@@ -0,0 +1,57 @@
+//! Checks that call-aware splitting keeps values out of registers across calls
+//! and reloads them only after the last call.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::debug_utils::CostModel;
+use regalloc3::function::Block;
+use regalloc3::output::OutputInst;
+use regalloc3::{Options, SplitStrategy};
+
+mod common;
+
+#[test]
+fn reload_after_call() {
+    // inst1 and inst7 clobber r0 and r1, and all registers are needed in
+    // inst2, so %0 has to be spilled. Linear splitting reloads it before the
+    // second call, at the end of the hot block1, call splitting only after it.
+    let func = "
+%0 = bank0
+%1 = bank0
+%2 = bank0
+%3 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):class1
+    inst1: inst Clobber:unit0 Clobber:unit1
+    inst2: inst Def(%1):class1 Def(%2):class1 Def(%3):class1
+    inst3: inst Use(%1):class1 Use(%2):class1 Use(%3):class1
+    inst4: branch(block1, block2)
+block1() freq(100):
+    inst5: jump block3()
+block2() freq(1):
+    inst6: jump block3()
+block3() freq(10):
+    inst7: inst Clobber:unit0 Clobber:unit1
+    inst8: inst Use(%0):class1
+    inst9: ret
+";
+    let (reginfo, func) = common::parse(common::REGINFO, func);
+    let allocate = |split_strategy| {
+        let mut options = Options::default();
+        options.split_strategy = split_strategy;
+        common::allocate(&reginfo, &func, &options, |output| {
+            let hot_moves = output
+                .output_insts(Block::new(1))
+                .filter(|inst| matches!(inst, OutputInst::Move { .. }))
+                .count();
+            (hot_moves, CostModel::default().evaluate(output))
+        })
+        .unwrap()
+    };
+    let (linear_moves, linear_cost) = allocate(SplitStrategy::Linear);
+    let (call_moves, call_cost) = allocate(SplitStrategy::Call);
+    assert_eq!(linear_moves, 1);
+    assert_eq!(call_moves, 0);
+    assert!(call_cost < linear_cost);
+}
@@ -0,0 +1,94 @@
+//! Checks that callee-saved register costs steer the allocation order.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::Options;
+use regalloc3::function::Inst;
+use regalloc3::output::{Allocation, AllocationKind};
+use regalloc3::reginfo::PhysReg;
+
+mod common;
+
+/// Register description in which `r1` comes first in the allocation order and
+/// is callee-saved with the given cost, if any.
+fn reginfo(callee_saved_cost: Option<f32>) -> String {
+    let callee_saved = match callee_saved_cost {
+        Some(cost) => format!(" callee_saved({cost})"),
+        None => String::new(),
+    };
+    format!(
+        "
+r0 = reg unit0
+r1 = reg unit1{callee_saved}
+r2 = stack unit2
+
+bank0 {{
+    top_level_class = class0
+    stack_to_stack_class = class1
+    spillslot_size = 8
+
+    class0 {{
+        allows_spillslots
+        spill_cost = 0.5
+        members = r0 r1 r2
+        allocation_order = r1 r0
+    }}
+
+    class1: class0 {{
+        spill_cost = 1
+        members = r0 r1
+        allocation_order = r1 r0
+    }}
+}}
+"
+    )
+}
+
+/// Returns the allocation of the first operand of `inst`.
+fn operand_alloc(reginfo: &str, func: &str, inst: usize) -> Allocation {
+    let (reginfo, func) = common::parse(reginfo, func);
+    common::allocate(&reginfo, &func, &Options::default(), |output| {
+        common::operand_allocs(output, Inst::new(inst))[0]
+    })
+    .unwrap()
+}
+
+#[test]
+fn free_reg_before_callee_saved() {
+    let func = "
+%0 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):class1
+    inst1: inst Use(%0):class1
+    inst2: ret
+";
+    let r0 = Allocation::reg(PhysReg::new(0));
+    let r1 = Allocation::reg(PhysReg::new(1));
+    assert_eq!(operand_alloc(&reginfo(None), func, 0), r1);
+    assert_eq!(operand_alloc(&reginfo(Some(1.0)), func, 0), r0);
+}
+
+#[test]
+fn spill_instead_of_claiming() {
+    // r0 is taken by %0, so %1 has to either claim r1 or be spilled. Claiming
+    // is only worth it if it is cheaper than spilling.
+    let func = "
+%0 = bank0
+%1 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):r0
+    inst1: inst Def(%1):class0
+    inst2: inst Use(%1):class0
+    inst3: inst Use(%0):r0
+    inst4: ret
+";
+    let r1 = Allocation::reg(PhysReg::new(1));
+    assert_eq!(operand_alloc(&reginfo(None), func, 1), r1);
+    assert_eq!(operand_alloc(&reginfo(Some(0.01)), func, 1), r1);
+    assert!(matches!(
+        operand_alloc(&reginfo(Some(100.0)), func, 1).kind(),
+        AllocationKind::SpillSlot(_)
+    ));
+}
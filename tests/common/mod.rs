@@ -0,0 +1,81 @@
+//! Helpers shared by the integration tests.
+
+#![allow(dead_code)]
+
+use regalloc3::debug_utils::{self, GenericFunction, GenericRegInfo};
+use regalloc3::function::{Function, Inst};
+use regalloc3::output::{Allocation, Output, OutputInst};
+use regalloc3::{Options, RegAllocError, RegisterAllocator};
+
+/// Register description with a single bank of 3 registers and a stack
+/// register.
+///
+/// `class0` allows spill slots, `class1` only contains the registers.
+pub const REGINFO: &str = "
+r0 = reg unit0
+r1 = reg unit1
+r2 = reg unit2
+r3 = stack unit3
+
+bank0 {
+    top_level_class = class0
+    stack_to_stack_class = class1
+    spillslot_size = 8
+
+    class0 {
+        allows_spillslots
+        spill_cost = 0.5
+        members = r0 r1 r2 r3
+        allocation_order = r0 r1 r2
+    }
+
+    class1: class0 {
+        spill_cost = 1
+        members = r0 r1 r2
+        allocation_order = r0 r1 r2
+    }
+}
+";
+
+/// Parses and validates a register description and a function.
+pub fn parse(reginfo: &str, func: &str) -> (GenericRegInfo, GenericFunction) {
+    let reginfo = GenericRegInfo::parse(reginfo).unwrap();
+    debug_utils::validate_reginfo(&reginfo).unwrap();
+    let func = GenericFunction::parse(func).unwrap();
+    debug_utils::validate_function(&func, &reginfo).unwrap();
+    (reginfo, func)
+}
+
+/// Allocates registers for `func` and checks the result before passing it to
+/// `f`.
+pub fn allocate<T>(
+    reginfo: &GenericRegInfo,
+    func: &GenericFunction,
+    options: &Options,
+    f: impl FnOnce(&Output<'_, GenericFunction, GenericRegInfo>) -> T,
+) -> Result<T, RegAllocError> {
+    let mut regalloc = RegisterAllocator::new();
+    let output = regalloc.allocate_registers(func, reginfo, options)?;
+    if let Err(err) = debug_utils::check_output(&output) {
+        panic!("checker failed with {options:?}: {err}");
+    }
+    Ok(f(&output))
+}
+
+/// Returns the allocations assigned to the operands of `inst`.
+pub fn operand_allocs(
+    output: &Output<'_, GenericFunction, GenericRegInfo>,
+    inst: Inst,
+) -> Vec<Allocation> {
+    let block = output.function().inst_block(inst);
+    output
+        .output_insts(block)
+        .find_map(|output_inst| match output_inst {
+            OutputInst::Inst {
+                inst: i,
+                operand_allocs,
+            } if i == inst => Some(operand_allocs.to_vec()),
+            _ => None,
+        })
+        .expect("instruction missing from the output")
+}
@@ -0,0 +1,38 @@
+//! Checks that copy instructions are coalesced and removed from the output.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::Options;
+use regalloc3::function::{Block, Inst};
+use regalloc3::output::OutputInst;
+
+mod common;
+
+/// Returns whether `inst1` is present in the output.
+fn keeps_inst1(func: &str) -> bool {
+    let (reginfo, func) = common::parse(common::REGINFO, func);
+    common::allocate(&reginfo, &func, &Options::default(), |output| {
+        output
+            .output_insts(Block::new(0))
+            .any(|inst| matches!(inst, OutputInst::Inst { inst, .. } if inst == Inst::new(1)))
+    })
+    .unwrap()
+}
+
+#[test]
+fn copy_is_eliminated() {
+    let func = "
+%0 = bank0
+%1 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):class1
+    inst1: inst Def(%1):class1 Use(%0):class1
+    inst2: inst Use(%1):class1
+    inst3: ret
+";
+    assert!(keeps_inst1(func));
+    assert!(!keeps_inst1(
+        &func.replace("inst1: inst Def", "inst1: inst copy Def")
+    ));
+}
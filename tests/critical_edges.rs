@@ -0,0 +1,153 @@
+//! Checks that moves on critical edges are attributed to the right edge.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::function::{Block, Inst, Value};
+use regalloc3::output::OutputInst;
+use regalloc3::{Options, RegAllocError};
+
+mod common;
+
+/// Returns the distinct values moved by a sequence of output instructions.
+fn moved_values<'a>(insts: impl Iterator<Item = OutputInst<'a>>) -> Vec<Value> {
+    let mut values: Vec<_> = insts
+        .filter_map(|inst| match inst {
+            OutputInst::Move { value, .. } => value,
+            _ => None,
+        })
+        .collect();
+    values.sort_unstable();
+    values.dedup();
+    values
+}
+
+#[test]
+fn critical_edge() {
+    // The edge from block0 to block2 is critical. %0 has to be moved into r2
+    // on that edge but not on the edge through block1, which already does so.
+    let func = "
+%0 = bank0
+%1 = bank0
+
+allow_critical_edges
+
+block0() freq(1):
+    inst0: inst Def(%0):r0
+    inst1: branch(block1, block2)
+block1() freq(1):
+    inst2: inst Use(%0):r0 Def(%1):r2
+    inst3: inst Use(%1):r2
+    inst4: jump block2()
+block2() freq(1):
+    inst5: ret Use(%0):r2
+";
+    let (reginfo, func) = common::parse(common::REGINFO, func);
+    common::allocate(&reginfo, &func, &Options::default(), |output| {
+        let block0 = Block::new(0);
+        assert_eq!(output.edge_block_between(block0, 0), None);
+        let edge_block = output.edge_block_between(block0, 1).unwrap();
+        assert_eq!(output.edge_blocks().len(), 1);
+        let edge = output.edge_blocks()[0];
+        assert_eq!(edge.block, edge_block);
+        assert_eq!(
+            (edge.pred, edge.succ, edge.succ_idx),
+            (block0, Block::new(2), 1)
+        );
+        assert_eq!(
+            moved_values(output.output_insts(edge_block)),
+            [Value::new(0)]
+        );
+    })
+    .unwrap();
+}
+
+/// Branch which reaches the same block through both of its successors, with
+/// different arguments.
+const DUPLICATE_SUCC_FUNC: &str = "
+%0 = bank0
+%1 = bank0
+%2 = bank0
+
+allow_critical_edges
+
+block0() freq(1):
+    inst0: inst Def(%0):r1 Def(%1):r2
+    inst1: branch(block1(%0), block1(%1))
+block1(%2) freq(1):
+    inst2: ret Use(%2):r0
+";
+
+#[test]
+fn duplicate_succ_edge_blocks() {
+    let (reginfo, func) = common::parse(common::REGINFO, DUPLICATE_SUCC_FUNC);
+    common::allocate(&reginfo, &func, &Options::default(), |output| {
+        let block0 = Block::new(0);
+        let block1 = Block::new(1);
+        let edges: Vec<_> = output
+            .edge_blocks()
+            .iter()
+            .map(|edge| (edge.pred, edge.succ, edge.succ_idx))
+            .collect();
+        assert_eq!(edges, [(block0, block1, 0), (block0, block1, 1)]);
+        for (succ_idx, value) in [(0, Value::new(0)), (1, Value::new(1))] {
+            let edge_block = output.edge_block_between(block0, succ_idx).unwrap();
+            assert_eq!(moved_values(output.output_insts(edge_block)), [value]);
+        }
+    })
+    .unwrap();
+}
+
+#[test]
+fn duplicate_succ_edge_edits() {
+    let (reginfo, func) = common::parse(common::REGINFO, DUPLICATE_SUCC_FUNC);
+    let mut options = Options::default();
+    options.separate_edge_edits = true;
+    common::allocate(&reginfo, &func, &options, |output| {
+        let block0 = Block::new(0);
+        assert!(output.edge_blocks().is_empty());
+        for (succ_idx, value) in [(0, Value::new(0)), (1, Value::new(1))] {
+            assert_eq!(moved_values(output.edge_edits(block0, succ_idx)), [value]);
+        }
+
+        let owned = output.to_owned();
+        for succ_idx in 0..2 {
+            assert_eq!(
+                owned.edge_edits(block0, succ_idx).count(),
+                output.edge_edits(block0, succ_idx).count()
+            );
+        }
+    })
+    .unwrap();
+}
+
+#[test]
+fn too_many_live_regs_original_inst() {
+    // The edge block inserted between block0 and block2 comes before block2
+    // in the split view, but the error must refer to the original inst3.
+    let func = "
+%0 = bank0
+%1 = bank0
+%2 = bank0
+%3 = bank0
+
+allow_critical_edges
+
+block0() freq(1):
+    inst0: branch(block1, block2)
+block1() freq(1):
+    inst1: jump block2()
+block2() freq(1):
+    inst2: inst
+    inst3: inst Def(%0):class1 Def(%1):class1 Def(%2):class1 Def(%3):class1
+    inst4: ret
+";
+    let (reginfo, func) = common::parse(common::REGINFO, func);
+    match common::allocate(&reginfo, &func, &Options::default(), |_| ()) {
+        Err(RegAllocError::TooManyLiveRegs { inst, operands, .. }) => {
+            assert_eq!(inst, Inst::new(3));
+            assert_eq!(operands, [0, 1, 2, 3]);
+        }
+        Ok(()) => panic!("allocation succeeded"),
+        Err(err) => panic!("unexpected error: {err}"),
+    }
+}
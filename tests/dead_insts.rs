@@ -0,0 +1,86 @@
+//! Checks that pure instructions are only eliminated when none of their
+//! outputs are needed.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::Options;
+use regalloc3::function::{Block, Inst};
+use regalloc3::output::OutputInst;
+
+mod common;
+
+/// Allocates `func` and returns whether `inst0` is still present in the
+/// output, along with the number of rematerializations.
+fn keeps_inst0(func: &str) -> (bool, usize) {
+    let (reginfo, func) = common::parse(common::REGINFO, func);
+    common::allocate(&reginfo, &func, &Options::default(), |output| {
+        let insts: Vec<_> = output.output_insts(Block::new(0)).collect();
+        let kept = insts
+            .iter()
+            .any(|inst| matches!(inst, OutputInst::Inst { inst, .. } if *inst == Inst::new(0)));
+        let remats = insts
+            .iter()
+            .filter(|inst| matches!(inst, OutputInst::Rematerialize { .. }))
+            .count();
+        (kept, remats)
+    })
+    .unwrap()
+}
+
+#[test]
+fn rematerialized_def_is_dropped() {
+    // %0 can't stay in a register across inst1, so it is rematerialized
+    // before inst3 and inst0 is no longer needed.
+    let func = "
+%0 = bank0 remat(0.25, class1)
+%1 = bank0
+%2 = bank0
+%3 = bank0
+
+block0() freq(1):
+    inst0: inst pure Def(%0):class1
+    inst1: inst Def(%1):class1 Def(%2):class1 Def(%3):class1
+    inst2: inst Use(%1):class1 Use(%2):class1 Use(%3):class1
+    inst3: inst Use(%0):class1
+    inst4: ret
+";
+    assert_eq!(keeps_inst0(func), (false, 1));
+}
+
+#[test]
+fn def_with_needed_output_is_kept() {
+    // Same as above, except that inst0 also defines %4 which can't be
+    // rematerialized.
+    let func = "
+%0 = bank0 remat(0.25, class1)
+%1 = bank0
+%2 = bank0
+%3 = bank0
+%4 = bank0
+
+block0() freq(1):
+    inst0: inst pure Def(%0):class1 Def(%4):class0
+    inst1: inst Def(%1):class1 Def(%2):class1 Def(%3):class1
+    inst2: inst Use(%1):class1 Use(%2):class1 Use(%3):class1
+    inst3: inst Use(%0):class1 Use(%4):class1
+    inst4: ret
+";
+    assert_eq!(keeps_inst0(func), (true, 1));
+}
+
+#[test]
+fn dead_inst_in_sequence_is_kept() {
+    // inst0 is dead but eliminating it would place any edits before it inside
+    // the sequence formed with inst1.
+    let func = "
+%0 = bank0
+%1 = bank0
+
+block0() freq(1):
+    inst0: inst pure Def(%0):class1
+    inst1: inst no_edits_before Def(%1):class1
+    inst2: inst Use(%1):class1
+    inst3: ret
+";
+    assert_eq!(keeps_inst0(func), (true, 0));
+}
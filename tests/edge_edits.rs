@@ -0,0 +1,82 @@
+//! Checks which moves are reported as being on a CFG edge.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::Options;
+use regalloc3::function::Block;
+use regalloc3::output::OutputInst;
+
+mod common;
+
+#[test]
+fn first_inst_operands_stay_in_block() {
+    // %0 stays in r0 across the edge into block1, so the move into r2 for the
+    // fixed use in inst2 only belongs to that instruction.
+    let func = "
+%0 = bank0
+%1 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):r0
+    inst1: branch(block1, block2)
+block1() freq(1):
+    inst2: inst Use(%0):r2 Def(%1):class1
+    inst3: inst Use(%0):r0 Use(%1):class1
+    inst4: ret
+block2() freq(1):
+    inst5: ret
+";
+    let (reginfo, func) = common::parse(common::REGINFO, func);
+    for separate_edge_edits in [false, true] {
+        let mut options = Options::default();
+        options.separate_edge_edits = separate_edge_edits;
+        common::allocate(&reginfo, &func, &options, |output| {
+            let block0 = Block::new(0);
+            let block1 = Block::new(1);
+            assert_eq!(output.edge_edits(block0, 0).count(), 0);
+            assert!(
+                output
+                    .output_insts(block1)
+                    .any(|inst| matches!(inst, OutputInst::Move { .. }))
+            );
+        })
+        .unwrap();
+    }
+}
+
+#[test]
+fn separated_edits_leave_blocks() {
+    // Both edges into block2 need moves for the block parameter, one of them
+    // on the critical edge from the branch.
+    let func = "
+%0 = bank0
+%1 = bank0
+%2 = bank0
+
+allow_critical_edges
+
+block0() freq(1):
+    inst0: inst Def(%0):r1 Def(%1):r2
+    inst1: branch(block2(%0), block1)
+block1() freq(1):
+    inst2: jump block2(%1)
+block2(%2) freq(1):
+    inst3: ret Use(%2):r0
+";
+    let (reginfo, func) = common::parse(common::REGINFO, func);
+    let mut options = Options::default();
+    options.separate_edge_edits = true;
+    common::allocate(&reginfo, &func, &options, |output| {
+        let is_edit = |inst: &OutputInst<'_>| !matches!(inst, OutputInst::Inst { .. });
+        assert!(output.edge_blocks().is_empty());
+        for block in (0..3).map(Block::new) {
+            assert!(!output.output_insts(block).any(|inst| is_edit(&inst)));
+        }
+        for (pred, succ_idx) in [(Block::new(0), 0), (Block::new(1), 0)] {
+            let edits: Vec<_> = output.edge_edits(pred, succ_idx).collect();
+            assert!(!edits.is_empty());
+            assert!(edits.iter().all(is_edit));
+        }
+    })
+    .unwrap();
+}
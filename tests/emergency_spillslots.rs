@@ -0,0 +1,107 @@
+//! Checks that emergency spill slots are not shared between fixed-size and
+//! scalable register banks.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::Options;
+use regalloc3::reginfo::SpillSlotSize;
+
+mod common;
+
+/// Two banks whose spill slots have the same log2 size, one of which is
+/// scalable. Stack-to-stack moves need a scratch register from a class
+/// without spill slots.
+const REGINFO: &str = "
+r0 = reg unit0
+r1 = reg unit1
+r2 = reg unit2
+r3 = reg unit3
+r4 = stack unit4
+r5 = stack unit5
+r6 = stack unit6
+r7 = stack unit7
+
+bank0 {
+    top_level_class = class0
+    stack_to_stack_class = class1
+    spillslot_size = 16
+
+    class0 {
+        allows_spillslots
+        spill_cost = 0.5
+        members = r0 r1 r4 r5
+        allocation_order = r0 r1
+    }
+
+    class1: class0 {
+        spill_cost = 1
+        members = r0 r1
+        allocation_order = r0 r1
+    }
+}
+
+bank1 {
+    top_level_class = class2
+    stack_to_stack_class = class3
+    spillslot_size = 16vl
+
+    class2 {
+        allows_spillslots
+        spill_cost = 0.5
+        members = r2 r3 r6 r7
+        allocation_order = r2 r3
+    }
+
+    class3: class2 {
+        spill_cost = 1
+        members = r2 r3
+        allocation_order = r2 r3
+    }
+}
+";
+
+/// All registers are occupied by pinned values while the block parameters are
+/// moved between stack registers, which forces a register in each bank to be
+/// evicted to an emergency spill slot on both incoming edges of block3.
+const FUNCTION: &str = "
+%0 = bank0 pinned(r0)
+%1 = bank0 pinned(r1)
+%2 = bank1 pinned(r2)
+%3 = bank1 pinned(r3)
+%4 = bank0
+%5 = bank1
+%6 = bank0
+%7 = bank1
+%8 = bank0
+%9 = bank1
+
+block0() freq(1):
+    inst0: inst Def(%0):r0 Def(%1):r1 Def(%2):r2 Def(%3):r3 Def(%4):r4 Def(%5):r6
+    inst1: branch(block1, block2)
+block1() freq(1):
+    inst2: jump block3(%4, %5)
+block2() freq(1):
+    inst3: inst Def(%6):r4 Def(%7):r6
+    inst4: jump block3(%6, %7)
+block3(%8, %9) freq(1):
+    inst5: inst Use(%8):r5 Use(%9):r7 Use(%0):r0 Use(%1):r1 Use(%2):r2 Use(%3):r3
+    inst6: ret
+";
+
+#[test]
+fn fixed_and_scalable_banks() {
+    let (reginfo, func) = common::parse(REGINFO, FUNCTION);
+
+    // One emergency spill slot is needed for each bank, and they are each
+    // reused on the second edge.
+    let mut sizes = common::allocate(&reginfo, &func, &Options::default(), |output| {
+        let stack_layout = output.stack_layout();
+        stack_layout
+            .spillslots()
+            .map(|slot| stack_layout.spillslot_size(slot))
+            .collect::<Vec<_>>()
+    })
+    .unwrap();
+    sizes.sort_unstable_by_key(|size| (size.is_scalable(), size.bytes()));
+    assert_eq!(sizes, [SpillSlotSize::new(16), SpillSlotSize::scalable(16)]);
+}
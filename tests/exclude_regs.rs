@@ -0,0 +1,69 @@
+//! Checks that registers excluded with `ExcludeRegs` are never allocated.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::debug_utils::{self, GenericFunction};
+use regalloc3::function::{Block, Function};
+use regalloc3::output::{AllocationKind, OutputInst};
+use regalloc3::reginfo::{ExcludeRegs, PhysReg, PhysRegSet, RegBank, RegInfo};
+use regalloc3::{Options, RegisterAllocator};
+
+mod common;
+
+/// Three values which are live at the same time, so that all registers would
+/// be used without the exclusion.
+const FUNC: &str = "
+%0 = bank0
+%1 = bank0
+%2 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):class1
+    inst1: inst Def(%1):class1
+    inst2: inst Def(%2):class1
+    inst3: inst Use(%0):class1 Use(%1):class1
+    inst4: inst Use(%2):class1
+    inst5: ret
+";
+
+/// Allocates `FUNC` and returns all the registers holding a value.
+fn used_regs(reginfo: &impl RegInfo, func: &GenericFunction) -> PhysRegSet {
+    let mut regalloc = RegisterAllocator::new();
+    let output = regalloc
+        .allocate_registers(func, reginfo, &Options::default())
+        .unwrap();
+    debug_utils::check_output(&output).unwrap();
+
+    let mut allocs = vec![];
+    let num_blocks = func.num_blocks() + output.edge_blocks().len();
+    for block in (0..num_blocks).map(Block::new) {
+        for inst in output.output_insts(block) {
+            match inst {
+                OutputInst::Inst { operand_allocs, .. } => allocs.extend_from_slice(operand_allocs),
+                OutputInst::Move { from, to, .. } => allocs.extend([from, to]),
+                OutputInst::Rematerialize { to, .. } => allocs.push(to),
+            }
+        }
+    }
+    let mut regs: PhysRegSet = allocs
+        .iter()
+        .filter_map(|alloc| match alloc.kind() {
+            AllocationKind::PhysReg(reg) => Some(reg),
+            AllocationKind::SpillSlot(_) => None,
+        })
+        .collect();
+    regs |= *output.written_regs(RegBank::new(0));
+    regs
+}
+
+#[test]
+fn excluded_reg_not_allocated() {
+    let (reginfo, func) = common::parse(common::REGINFO, FUNC);
+    let r1 = PhysReg::new(1);
+    assert!(used_regs(&reginfo, &func).contains(r1));
+
+    let excluded: PhysRegSet = [r1].into_iter().collect();
+    let reginfo = ExcludeRegs::new(&reginfo, excluded);
+    debug_utils::validate_reginfo(&reginfo).unwrap();
+    assert!(!used_regs(&reginfo, &func).contains(r1));
+}
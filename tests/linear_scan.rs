@@ -0,0 +1,100 @@
+//! Checks that linear scan allocates functions with many constrained operands.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::{AllocationAlgorithm, Options};
+
+mod common;
+
+/// Same as `common::REGINFO` with a fourth register and classes containing
+/// subsets of the registers.
+const REGINFO: &str = "
+r0 = reg unit0
+r1 = reg unit1
+r2 = reg unit2
+r3 = reg unit3
+r4 = stack unit4
+
+bank0 {
+    top_level_class = class0
+    stack_to_stack_class = class1
+    spillslot_size = 8
+
+    class0 {
+        allows_spillslots
+        spill_cost = 0.5
+        members = r0 r1 r2 r3 r4
+        allocation_order = r0 r1 r2 r3
+    }
+
+    class1: class0 {
+        spill_cost = 1
+        members = r0 r1 r2 r3
+        allocation_order = r0 r1 r2 r3
+    }
+
+    class2: class1 {
+        spill_cost = 1
+        members = r0 r1
+        allocation_order = r0 r1
+    }
+
+    class3: class1 {
+        spill_cost = 1
+        members = r0
+        allocation_order = r0
+    }
+
+    class4: class1 {
+        spill_cost = 1
+        members = r1 r2
+        allocation_order = r1 r2
+    }
+}
+";
+
+/// Each use at inst2 is allocated in operand order. Evicting %0 from r0 for
+/// %1 leaves no free register for it, so more than one unspillable virtual
+/// register needs to be moved.
+const FUNC: &str = "
+%0 = bank0
+%1 = bank0
+%2 = bank0
+%3 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):class1 Def(%1):class1 Def(%2):class1 Def(%3):class1
+    inst1: inst
+    inst2: inst Use(%0):class1 Use(%1):class3 Use(%2):class4 Use(%3):class2
+    inst3: ret
+";
+
+/// Allocates `FUNC` and returns the checked output as text.
+fn allocate(algorithm: AllocationAlgorithm, recoloring_depth: u32) -> String {
+    let (reginfo, func) = common::parse(REGINFO, FUNC);
+    let mut options = Options::default();
+    options.algorithm = algorithm;
+    options.recoloring_depth = recoloring_depth;
+    common::allocate(&reginfo, &func, &options, |output| output.to_string()).unwrap()
+}
+
+#[test]
+fn recoloring() {
+    // Linear scan succeeds on its own and its result differs from the greedy
+    // allocator's.
+    let depth = Options::default().recoloring_depth;
+    assert_ne!(
+        allocate(AllocationAlgorithm::LinearScan, depth),
+        allocate(AllocationAlgorithm::Greedy, depth)
+    );
+}
+
+#[test]
+fn greedy_fallback() {
+    // Without recoloring, linear scan gives up and the function is allocated
+    // with the greedy algorithm instead.
+    assert_eq!(
+        allocate(AllocationAlgorithm::LinearScan, 0),
+        allocate(AllocationAlgorithm::Greedy, 0)
+    );
+}
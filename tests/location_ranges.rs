@@ -0,0 +1,62 @@
+//! Checks the precise value locations reported for debug info.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::Options;
+use regalloc3::function::{Block, Inst, Value};
+use regalloc3::output::{AllocationKind, OutputInst, OutputPos};
+
+mod common;
+
+const FUNC: &str = "
+%0 = bank0
+%1 = bank0
+%2 = bank0
+%3 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):class1
+    inst1: inst Use(%0):class1
+    inst2: inst Def(%1):class1 Def(%2):class1 Def(%3):class1
+    inst3: inst Use(%1):class1 Use(%2):class1 Use(%3):class1
+    inst4: ret
+";
+
+/// Returns the allocations of %0 at the `ret` instruction.
+fn locations_at_ret(func: &str) -> Vec<AllocationKind> {
+    let (reginfo, func) = common::parse(common::REGINFO, func);
+    common::allocate(&reginfo, &func, &Options::default(), |output| {
+        let block = Block::new(0);
+        let index = output
+            .output_insts(block)
+            .position(|inst| matches!(inst, OutputInst::Inst { inst, .. } if inst == Inst::new(4)))
+            .unwrap();
+        let pos = OutputPos {
+            block,
+            index: index as u32,
+        };
+        output
+            .value_location_ranges()
+            .into_iter()
+            .filter(|&(value, start, end, _)| value == Value::new(0) && start <= pos && pos < end)
+            .map(|(_, _, _, alloc)| alloc.kind())
+            .collect()
+    })
+    .unwrap()
+}
+
+#[test]
+fn value_dies_after_last_use() {
+    // The register holding %0 is reused by inst2.
+    assert_eq!(locations_at_ret(FUNC), []);
+}
+
+#[test]
+fn scope_end_extends_location() {
+    // %0 is kept live until the end of its scope and has to be spilled to
+    // survive inst2.
+    let func = FUNC.replace("%0 = bank0\n", "%0 = bank0 scope_end(inst4)\n");
+    let locations = locations_at_ret(&func);
+    assert_eq!(locations.len(), 1);
+    assert!(matches!(locations[0], AllocationKind::SpillSlot(_)));
+}
@@ -0,0 +1,216 @@
+//! Checks that the checker rejects edits inside an instruction sequence marked
+//! with `Function::no_edits_before`.
+
+#![cfg(feature = "parse")]
+
+use core::cell::Cell;
+
+use regalloc3::debug_utils::{self, GenericFunction};
+use regalloc3::function::{
+    AlternativeSet, Block, Function, Inst, InstRange, Operand, OperandConstraint, TerminatorKind,
+    Value, ValueGroup,
+};
+use regalloc3::output::OutputInst;
+use regalloc3::reginfo::{PhysReg, RegBank, RegClass, RegUnit};
+use regalloc3::{Options, RegisterAllocator};
+
+mod common;
+
+/// Wrapper which forbids edits before `forbid` once the function has been
+/// allocated.
+struct ForbidEdits<'a> {
+    func: &'a GenericFunction,
+    forbid: Cell<Option<Inst>>,
+}
+
+impl Function for ForbidEdits<'_> {
+    fn num_insts(&self) -> usize {
+        self.func.num_insts()
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.func.num_blocks()
+    }
+
+    fn block_insts(&self, block: Block) -> InstRange {
+        self.func.block_insts(block)
+    }
+
+    fn inst_block(&self, inst: Inst) -> Block {
+        self.func.inst_block(inst)
+    }
+
+    fn block_succs(&self, block: Block) -> &[Block] {
+        self.func.block_succs(block)
+    }
+
+    fn block_preds(&self, block: Block) -> &[Block] {
+        self.func.block_preds(block)
+    }
+
+    fn block_immediate_dominator(&self, block: Block) -> Option<Block> {
+        self.func.block_immediate_dominator(block)
+    }
+
+    fn block_params(&self, block: Block) -> &[Value] {
+        self.func.block_params(block)
+    }
+
+    fn terminator_kind(&self, inst: Inst) -> Option<TerminatorKind> {
+        self.func.terminator_kind(inst)
+    }
+
+    fn jump_blockparams(&self, block: Block) -> &[Value] {
+        self.func.jump_blockparams(block)
+    }
+
+    fn branch_blockparams(&self, block: Block, succ_idx: usize) -> &[Value] {
+        self.func.branch_blockparams(block, succ_idx)
+    }
+
+    fn block_frequency(&self, block: Block) -> f32 {
+        self.func.block_frequency(block)
+    }
+
+    fn block_is_critical_edge(&self, block: Block) -> bool {
+        self.func.block_is_critical_edge(block)
+    }
+
+    fn allows_critical_edges(&self) -> bool {
+        self.func.allows_critical_edges()
+    }
+
+    fn inst_operands(&self, inst: Inst) -> &[Operand] {
+        self.func.inst_operands(inst)
+    }
+
+    fn inst_clobbers(&self, inst: Inst) -> impl Iterator<Item = RegUnit> {
+        self.func.inst_clobbers(inst)
+    }
+
+    fn num_values(&self) -> usize {
+        self.func.num_values()
+    }
+
+    fn value_bank(&self, value: Value) -> RegBank {
+        self.func.value_bank(value)
+    }
+
+    fn num_value_groups(&self) -> usize {
+        self.func.num_value_groups()
+    }
+
+    fn value_group_members(&self, group: ValueGroup) -> &[Value] {
+        self.func.value_group_members(group)
+    }
+
+    fn num_alternative_sets(&self) -> usize {
+        self.func.num_alternative_sets()
+    }
+
+    fn alternative_set(&self, set: AlternativeSet) -> &[(OperandConstraint, f32)] {
+        self.func.alternative_set(set)
+    }
+
+    fn value_hint(&self, value: Value) -> Option<(PhysReg, f32)> {
+        self.func.value_hint(value)
+    }
+
+    fn value_pinned_reg(&self, value: Value) -> Option<PhysReg> {
+        self.func.value_pinned_reg(value)
+    }
+
+    fn can_rematerialize(&self, value: Value) -> Option<(f32, RegClass)> {
+        self.func.can_rematerialize(value)
+    }
+
+    fn remat_input(&self, value: Value) -> Option<Value> {
+        self.func.remat_input(value)
+    }
+
+    fn can_eliminate_dead_inst(&self, inst: Inst) -> bool {
+        self.func.can_eliminate_dead_inst(inst)
+    }
+
+    fn is_copy(&self, inst: Inst) -> bool {
+        self.func.is_copy(inst)
+    }
+
+    fn value_scope_end(&self, value: Value) -> Option<Inst> {
+        self.func.value_scope_end(value)
+    }
+
+    fn no_edits_before(&self, inst: Inst) -> bool {
+        self.forbid.get() == Some(inst) || self.func.no_edits_before(inst)
+    }
+
+    fn is_reference(&self, value: Value) -> bool {
+        self.func.is_reference(value)
+    }
+
+    fn is_safepoint(&self, inst: Inst) -> bool {
+        self.func.is_safepoint(inst)
+    }
+}
+
+#[test]
+fn checker_rejects_edit_in_sequence() {
+    // The move of %0 into r1 must be placed right before inst1.
+    let func = "
+%0 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):r0
+    inst1: inst Use(%0):r1
+    inst2: ret
+";
+    let (reginfo, func) = common::parse(common::REGINFO, func);
+    let func = ForbidEdits {
+        func: &func,
+        forbid: Cell::new(None),
+    };
+    let mut regalloc = RegisterAllocator::new();
+    let output = regalloc
+        .allocate_registers(&func, &reginfo, &Options::default())
+        .unwrap();
+    debug_utils::check_output(&output).unwrap();
+
+    // Pretend that the move was placed inside a sequence.
+    func.forbid.set(Some(Inst::new(1)));
+    assert!(debug_utils::check_output(&output).is_err());
+}
+
+#[test]
+fn checker_rejects_edit_after_eliminated_inst() {
+    // inst1 is dead and eliminated, so the move of %0 into r1 directly
+    // precedes inst2 in the output.
+    let func = "
+%0 = bank0
+%1 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):r0
+    inst1: inst pure Def(%1):class1
+    inst2: inst Use(%0):r1
+    inst3: ret
+";
+    let (reginfo, func) = common::parse(common::REGINFO, func);
+    let func = ForbidEdits {
+        func: &func,
+        forbid: Cell::new(None),
+    };
+    let mut regalloc = RegisterAllocator::new();
+    let output = regalloc
+        .allocate_registers(&func, &reginfo, &Options::default())
+        .unwrap();
+    debug_utils::check_output(&output).unwrap();
+    assert!(
+        !output
+            .output_insts(Block::new(0))
+            .any(|inst| matches!(inst, OutputInst::Inst { inst, .. } if inst == Inst::new(1)))
+    );
+
+    // Pretend that inst1 and inst2 form a sequence.
+    func.forbid.set(Some(Inst::new(2)));
+    assert!(debug_utils::check_output(&output).is_err());
+}
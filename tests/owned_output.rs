@@ -0,0 +1,50 @@
+//! Checks that `OwnedOutput` matches the `Output` it was copied from.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::Options;
+
+mod common;
+
+#[test]
+fn location_ranges_are_opt_in() {
+    // %3 is live across inst1 and needs to be spilled.
+    let func = "
+%0 = bank0
+%1 = bank0
+%2 = bank0
+%3 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):class0 Def(%1):class0 Def(%2):class0 Def(%3):class0
+    inst1: inst Use(%0):class1 Use(%1):class1 Use(%2):class1
+    inst2: inst Use(%3):class1
+    inst3: ret
+";
+    let (reginfo, func) = common::parse(common::REGINFO, func);
+    common::allocate(&reginfo, &func, &Options::default(), |output| {
+        let owned = output.to_owned();
+        assert!(owned.value_location_ranges().is_none());
+        assert_eq!(
+            owned.value_locations().collect::<Vec<_>>(),
+            output.value_locations().collect::<Vec<_>>()
+        );
+        assert!(
+            owned
+                .value_locations()
+                .all(|(_, range, _)| !range.is_empty())
+        );
+        assert!(
+            owned
+                .value_locations()
+                .any(|(_, _, alloc)| alloc.is_memory(&reginfo))
+        );
+
+        let owned = output.to_owned_with_location_ranges();
+        assert_eq!(
+            owned.value_location_ranges(),
+            Some(&output.value_location_ranges()[..])
+        );
+    })
+    .unwrap();
+}
@@ -0,0 +1,48 @@
+//! Checks that pinned values stay in their register and that conflicting
+//! uses of that register are reported.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::function::{Inst, Value};
+use regalloc3::output::Allocation;
+use regalloc3::reginfo::PhysReg;
+use regalloc3::{Options, RegAllocError};
+
+mod common;
+
+const FUNC: &str = "
+%0 = bank0 pinned(r2)
+%1 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):class1
+    inst1: inst Def(%1):class1 Use(%0):class1
+    inst2: inst Use(%1):class1 Use(%0):class1
+    inst3: ret
+";
+
+#[test]
+fn pinned_value_uses_its_register() {
+    let (reginfo, func) = common::parse(common::REGINFO, FUNC);
+    common::allocate(&reginfo, &func, &Options::default(), |output| {
+        let r2 = Allocation::reg(PhysReg::new(2));
+        for inst in 0..3 {
+            let allocs = common::operand_allocs(output, Inst::new(inst));
+            assert_eq!(*allocs.last().unwrap(), r2);
+        }
+    })
+    .unwrap();
+}
+
+#[test]
+fn pinned_reg_conflict() {
+    // inst1 defines %1 in r2 while %0 is still live.
+    let func = FUNC.replace("inst1: inst Def(%1):class1", "inst1: inst Def(%1):r2");
+    let (reginfo, func) = common::parse(common::REGINFO, &func);
+    let err = common::allocate(&reginfo, &func, &Options::default(), |_| ()).unwrap_err();
+    assert!(matches!(
+        err,
+        RegAllocError::PinnedRegConflict { value, reg }
+            if value == Value::new(0) && reg == PhysReg::new(2)
+    ));
+}
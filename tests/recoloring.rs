@@ -0,0 +1,80 @@
+//! Checks that last-chance recoloring moves unspillable virtual registers out
+//! of the way.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::{Options, RegAllocError};
+
+mod common;
+
+/// Same as `common::REGINFO` with a fourth register and classes containing
+/// subsets of the registers.
+const REGINFO: &str = "
+r0 = reg unit0
+r1 = reg unit1
+r2 = reg unit2
+r3 = reg unit3
+r4 = stack unit4
+
+bank0 {
+    top_level_class = class0
+    stack_to_stack_class = class1
+    spillslot_size = 8
+
+    class0 {
+        allows_spillslots
+        spill_cost = 0.5
+        members = r0 r1 r2 r3 r4
+        allocation_order = r0 r1 r2 r3
+    }
+
+    class1: class0 {
+        spill_cost = 1
+        members = r0 r1 r2 r3
+        allocation_order = r0 r1 r2 r3
+    }
+
+    class2: class1 {
+        spill_cost = 1
+        members = r0 r1
+        allocation_order = r0 r1
+    }
+
+    class3: class1 {
+        spill_cost = 1
+        members = r0
+        allocation_order = r0
+    }
+}
+";
+
+/// The uses at inst2 are all unspillable. The last of them to be allocated
+/// needs r0, which means moving the value already in r0 to r1 and in turn the
+/// value in r1 to another register.
+const FUNC: &str = "
+%0 = bank0
+%1 = bank0
+%2 = bank0
+%3 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):class1 Def(%1):class1 Def(%2):class1 Def(%3):class1
+    inst1: inst
+    inst2: inst Use(%0):class1 Use(%1):class2 Use(%2):class1 Use(%3):class3
+    inst3: ret
+";
+
+#[test]
+fn recoloring() {
+    let (reginfo, func) = common::parse(REGINFO, FUNC);
+    common::allocate(&reginfo, &func, &Options::default(), |_| ()).unwrap();
+}
+
+#[test]
+fn no_recoloring() {
+    let (reginfo, func) = common::parse(REGINFO, FUNC);
+    let mut options = Options::default();
+    options.recoloring_depth = 0;
+    let err = common::allocate(&reginfo, &func, &options, |_| ()).unwrap_err();
+    assert!(matches!(err, RegAllocError::TooManyLiveRegs { .. }));
+}
@@ -0,0 +1,58 @@
+//! Checks that region splitting places split points on infrequently executed
+//! CFG edges.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::debug_utils::CostModel;
+use regalloc3::function::Block;
+use regalloc3::output::OutputInst;
+use regalloc3::{Options, SplitStrategy};
+
+mod common;
+
+#[test]
+fn spill_leaves_hot_block() {
+    // %0 must be spilled around the register pressure in block3. Linear
+    // splitting spills it at the end of the hot block1, region splitting only
+    // on entry to block3.
+    let func = "
+%0 = bank0
+%1 = bank0
+%2 = bank0
+%3 = bank0
+
+allow_critical_edges
+
+block0() freq(1):
+    inst0: inst Def(%0):class1
+    inst1: branch(block1, block2)
+block1() freq(100):
+    inst2: inst Use(%0):class1
+    inst3: jump block2()
+block2() freq(10):
+    inst4: branch(block2, block3)
+block3() freq(10):
+    inst5: inst Def(%1):class1 Def(%2):class1 Def(%3):class1
+    inst6: inst Use(%1):class1 Use(%2):class1 Use(%3):class1
+    inst7: inst Use(%0):class1
+    inst8: ret
+";
+    let (reginfo, func) = common::parse(common::REGINFO, func);
+    let allocate = |split_strategy| {
+        let mut options = Options::default();
+        options.split_strategy = split_strategy;
+        common::allocate(&reginfo, &func, &options, |output| {
+            let hot_moves = output
+                .output_insts(Block::new(1))
+                .filter(|inst| matches!(inst, OutputInst::Move { .. }))
+                .count();
+            (hot_moves, CostModel::default().evaluate(output))
+        })
+        .unwrap()
+    };
+    let (linear_moves, linear_cost) = allocate(SplitStrategy::Linear);
+    let (region_moves, region_cost) = allocate(SplitStrategy::Region);
+    assert_eq!(linear_moves, 1);
+    assert_eq!(region_moves, 0);
+    assert!(region_cost < linear_cost);
+}
@@ -0,0 +1,134 @@
+//! Checks how rematerialization costs compare against the costs of moves and
+//! reloads.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::Options;
+use regalloc3::debug_utils::CostModel;
+use regalloc3::function::Block;
+use regalloc3::output::{AllocationKind, OutputInst};
+
+mod common;
+
+/// Kind of edit in the output.
+#[derive(Debug, PartialEq)]
+enum Edit {
+    Remat,
+    Move,
+    Spill,
+    Reload,
+}
+
+/// Allocates `func` with `%0` rematerializable at the given cost and returns
+/// the edits in the output.
+fn edits(func: &str, remat_cost: f32) -> Vec<Edit> {
+    let func = func.replace("COST", &remat_cost.to_string());
+    let (reginfo, func) = common::parse(common::REGINFO, &func);
+    common::allocate(&reginfo, &func, &Options::default(), |output| {
+        output
+            .output_insts(Block::new(0))
+            .filter_map(|inst| match inst {
+                OutputInst::Inst { .. } => None,
+                OutputInst::Rematerialize { .. } => Some(Edit::Remat),
+                OutputInst::Move { from, to, .. } => Some(match (from.kind(), to.kind()) {
+                    (_, AllocationKind::SpillSlot(_)) => Edit::Spill,
+                    (AllocationKind::SpillSlot(_), _) => Edit::Reload,
+                    _ => Edit::Move,
+                }),
+            })
+            .collect()
+    })
+    .unwrap()
+}
+
+#[test]
+fn remat_instead_of_move() {
+    // %0 is still in r0 when it is needed in r1, so it is only rematerialized
+    // if that is cheaper than a move.
+    let func = "
+%0 = bank0 remat(COST, class1)
+
+block0() freq(1):
+    inst0: inst Def(%0):r0
+    inst1: inst Use(%0):r1
+    inst2: inst Use(%0):r0
+    inst3: ret
+";
+    assert_eq!(edits(func, 0.25), [Edit::Remat]);
+    assert_eq!(edits(func, 0.5), [Edit::Move]);
+    assert_eq!(edits(func, 0.75), [Edit::Move]);
+}
+
+/// `%0` can't stay in a register across inst1. It is then needed in both r0
+/// and r1.
+const SPILLED_FUNC: &str = "
+%0 = bank0 remat(COST, class1)
+%1 = bank0
+%2 = bank0
+%3 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):class1
+    inst1: inst Def(%1):class1 Def(%2):class1 Def(%3):class1
+    inst2: inst Use(%1):class1 Use(%2):class1 Use(%3):class1
+    inst3: inst Use(%0):r0
+    inst4: inst Use(%0):r1
+    inst5: ret
+";
+
+#[test]
+fn remat_instead_of_reload() {
+    // Values which are at least as expensive to rematerialize as a reload
+    // don't have their spill weight capped by the remat cost and are spilled
+    // to the stack instead.
+    assert_eq!(edits(SPILLED_FUNC, 0.75)[0], Edit::Remat);
+    assert_eq!(
+        edits(SPILLED_FUNC, 1.0),
+        [Edit::Spill, Edit::Reload, Edit::Move]
+    );
+    assert_eq!(
+        edits(SPILLED_FUNC, 1.5),
+        [Edit::Spill, Edit::Reload, Edit::Move]
+    );
+}
+
+#[test]
+fn remat_replaced_with_move() {
+    // The second rematerialization is replaced with a move from the first one
+    // if it is more expensive than a move.
+    assert_eq!(edits(SPILLED_FUNC, 0.25), [Edit::Remat, Edit::Remat]);
+    assert_eq!(edits(SPILLED_FUNC, 0.5), [Edit::Remat, Edit::Remat]);
+    assert_eq!(edits(SPILLED_FUNC, 0.75), [Edit::Remat, Edit::Move]);
+}
+
+#[test]
+fn cost_model_interpolation() {
+    // inst0 is kept and charged the cost of rematerializing %0, which is
+    // interpolated between the move and load costs of the cost model.
+    let func = "
+%0 = bank0 remat(COST, class1)
+
+block0() freq(1):
+    inst0: inst pure Def(%0):class1
+    inst1: inst Use(%0):class1
+    inst2: ret
+";
+    let cost_model = CostModel {
+        move_cost: 1.0,
+        load_cost: 3.0,
+        ..CostModel::default()
+    };
+    let score = |remat_cost: f32| {
+        let func = func.replace("COST", &remat_cost.to_string());
+        let (reginfo, func) = common::parse(common::REGINFO, &func);
+        common::allocate(&reginfo, &func, &Options::default(), |output| {
+            cost_model.evaluate(output)
+        })
+        .unwrap()
+    };
+    let base = score(0.0);
+    assert_eq!(score(0.25) - base, 0.5);
+    assert_eq!(score(0.5) - base, 1.0);
+    assert_eq!(score(0.75) - base, 2.0);
+    assert_eq!(score(1.0) - base, 3.0);
+}
@@ -0,0 +1,45 @@
+//! Checks that values are only rematerialized from their input when the input
+//! is actually held in a register at the point of the move.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::{AllocationAlgorithm, Options};
+
+mod common;
+
+/// Allocates `func` with each algorithm and runs the checker on the result.
+fn check(func: &str) {
+    let (reginfo, func) = common::parse(common::REGINFO, func);
+    for algorithm in [AllocationAlgorithm::Greedy, AllocationAlgorithm::LinearScan] {
+        let mut options = Options::default();
+        options.algorithm = algorithm;
+        common::allocate(&reginfo, &func, &options, |_| ()).unwrap();
+    }
+}
+
+#[test]
+fn input_defined_at_loop_header() {
+    // The move of %2 on the back edge is placed at the end of block2, where
+    // the segment holding %1 hasn't started yet: its reused definition covers
+    // the start of block1 even though %1 is only defined by inst2.
+    let func = "
+%0 = bank0
+%1 = bank0
+%2 = bank0 remat(0.25, class1) remat_input(%1)
+%3 = bank0
+%4 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):class1 Def(%3):class1
+    inst1: jump block1(%3)
+block1(%4) freq(8):
+    inst2: inst Def(%1):reuse(1) Use(%0):class1 Use(%4):r0
+    inst3: inst Def(%2):r1 Use(%1):class1
+    inst4: branch(block2, block3)
+block2() freq(8):
+    inst5: jump block1(%2)
+block3() freq(1):
+    inst6: ret
+";
+    check(func);
+}
@@ -0,0 +1,35 @@
+//! Checks that a def can reuse the register of a fixed-register use.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::Options;
+use regalloc3::function::Inst;
+use regalloc3::output::Allocation;
+use regalloc3::reginfo::PhysReg;
+
+mod common;
+
+#[test]
+fn def_reuses_fixed_use() {
+    // %0 is still live after inst1, so it must be copied into r1 for inst1
+    // instead of being overwritten.
+    let func = "
+%0 = bank0
+%1 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):class1
+    inst1: inst Use(%0):r1 Def(%1):reuse(0)
+    inst2: inst Use(%0):class1 Use(%1):class1
+    inst3: ret
+";
+    let (reginfo, func) = common::parse(common::REGINFO, func);
+    common::allocate(&reginfo, &func, &Options::default(), |output| {
+        let r1 = Allocation::reg(PhysReg::new(1));
+        assert_eq!(common::operand_allocs(output, Inst::new(1)), [r1, r1]);
+        let allocs = common::operand_allocs(output, Inst::new(2));
+        assert_ne!(allocs[0], r1);
+        assert_eq!(allocs[1], r1);
+    })
+    .unwrap();
+}
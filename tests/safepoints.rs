@@ -0,0 +1,89 @@
+//! Checks the stack maps reported at safepoints.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::Options;
+use regalloc3::function::{Block, Inst, Value};
+use regalloc3::output::{AllocationKind, OutputInst};
+
+mod common;
+
+#[test]
+fn stack_map_contains_live_references() {
+    // Only the reference %0 is reported at inst1. It stays in a register
+    // unless references are forced into spill slots at safepoints.
+    let func = "
+%0 = bank0 ref
+%1 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):class1 Def(%1):class1
+    inst1: inst safepoint Clobber:unit0
+    inst2: inst Use(%0):class1 Use(%1):class1
+    inst3: ret
+";
+    let (reginfo, func) = common::parse(common::REGINFO, func);
+    for spill_references_at_safepoints in [false, true] {
+        let mut options = Options::default();
+        options.spill_references_at_safepoints = spill_references_at_safepoints;
+        common::allocate(&reginfo, &func, &options, |output| {
+            let stack_map: Vec<_> = output.stack_map(Inst::new(1)).collect();
+            assert_eq!(stack_map.len(), 1);
+            let (value, alloc) = stack_map[0];
+            assert_eq!(value, Value::new(0));
+            assert_eq!(
+                matches!(alloc.kind(), AllocationKind::SpillSlot(_)),
+                spill_references_at_safepoints
+            );
+            assert_eq!(output.stack_map(Inst::new(2)).count(), 0);
+        })
+        .unwrap();
+    }
+}
+
+#[test]
+fn spill_across_consecutive_safepoints() {
+    // %0 is spilled before and reloaded after each safepoint separately, even
+    // though nothing uses it in between. Instructions are shown as `None`.
+    let func = "
+%0 = bank0 ref
+
+block0() freq(1):
+    inst0: inst Def(%0):class1
+    inst1: inst safepoint
+    inst2: inst safepoint
+    inst3: inst Use(%0):class1
+    inst4: ret
+";
+    let (reginfo, func) = common::parse(common::REGINFO, func);
+    let mut options = Options::default();
+    options.spill_references_at_safepoints = true;
+    common::allocate(&reginfo, &func, &options, |output| {
+        let reg = common::operand_allocs(output, Inst::new(0))[0];
+        let slot = output.stack_map(Inst::new(1)).next().unwrap().1;
+        assert!(matches!(slot.kind(), AllocationKind::SpillSlot(_)));
+        for inst in [Inst::new(1), Inst::new(2)] {
+            let stack_map: Vec<_> = output.stack_map(inst).collect();
+            assert_eq!(stack_map, [(Value::new(0), slot)]);
+        }
+
+        let spill = Some((reg, slot));
+        let reload = Some((slot, reg));
+        let insts: Vec<_> = output
+            .output_insts(Block::new(0))
+            .map(|inst| match inst {
+                OutputInst::Inst { .. } => None,
+                OutputInst::Move { from, to, value } => {
+                    assert_eq!(value, Some(Value::new(0)));
+                    Some((from, to))
+                }
+                OutputInst::Rematerialize { .. } => panic!("unexpected remat"),
+            })
+            .collect();
+        assert_eq!(
+            insts,
+            [None, spill, None, reload, spill, None, reload, None, None]
+        );
+    })
+    .unwrap();
+}
@@ -0,0 +1,239 @@
+//! Checks the contents of `RegAllocError::TooManyLiveRegs`.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::function::{Inst, Value};
+use regalloc3::reginfo::{RegClass, RegUnit};
+use regalloc3::{AllocationAlgorithm, Options, RegAllocError};
+
+mod common;
+
+/// Contents of `RegAllocError::TooManyLiveRegs`.
+#[derive(Debug, PartialEq)]
+struct TooManyLiveRegs {
+    inst: Inst,
+    class: RegClass,
+    operands: Vec<usize>,
+    live_values: Vec<Value>,
+    clobbers: Vec<RegUnit>,
+    fixed_units: Vec<RegUnit>,
+}
+
+/// Allocates `func` with each algorithm and returns the errors.
+fn allocate(reginfo: &str, func: &str) -> Vec<TooManyLiveRegs> {
+    let (reginfo, func) = common::parse(reginfo, func);
+    [AllocationAlgorithm::Greedy, AllocationAlgorithm::LinearScan]
+        .into_iter()
+        .map(|algorithm| {
+            let mut options = Options::default();
+            options.algorithm = algorithm;
+            match common::allocate(&reginfo, &func, &options, |_| ()) {
+                Ok(_) => panic!("allocation succeeded with {algorithm:?}"),
+                Err(RegAllocError::TooManyLiveRegs {
+                    inst,
+                    class,
+                    operands,
+                    live_values,
+                    clobbers,
+                    fixed_units,
+                }) => TooManyLiveRegs {
+                    inst,
+                    class,
+                    operands,
+                    live_values,
+                    clobbers,
+                    fixed_units,
+                },
+                Err(err) => panic!("unexpected error with {algorithm:?}: {err}"),
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn operands_only() {
+    // The clobber on inst2 must not be reported for inst1.
+    let func = "
+%0 = bank0
+%1 = bank0
+%2 = bank0
+%3 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):class1 Def(%1):class1 Def(%2):class1
+    inst1: inst Use(%0):class1 Use(%1):class1 Use(%2):class1 EarlyDef(%3):class1
+    inst2: inst Use(%3):class1 Clobber:unit0
+    inst3: ret
+";
+    for err in allocate(common::REGINFO, func) {
+        assert_eq!(
+            err,
+            TooManyLiveRegs {
+                inst: Inst::new(1),
+                class: RegClass::new(1),
+                operands: vec![0, 1, 2, 3],
+                live_values: vec![],
+                clobbers: vec![],
+                fixed_units: vec![],
+            }
+        );
+    }
+}
+
+#[test]
+fn live_through_value() {
+    // %2 is live across inst1 and cannot be spilled since no edits may be
+    // inserted in the sequence.
+    let func = "
+%0 = bank0
+%1 = bank0
+%2 = bank0
+%3 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):class1 Def(%1):class1 Def(%2):class1
+    inst1: inst no_edits_before Use(%0):class1 Use(%1):class1 EarlyDef(%3):class1
+    inst2: inst no_edits_before Use(%2):class1 Use(%3):class1
+    inst3: ret
+";
+    for err in allocate(common::REGINFO, func) {
+        assert_eq!(
+            err,
+            TooManyLiveRegs {
+                inst: Inst::new(1),
+                class: RegClass::new(1),
+                operands: vec![0, 1, 2],
+                live_values: vec![Value::new(2)],
+                clobbers: vec![],
+                fixed_units: vec![],
+            }
+        );
+    }
+}
+
+/// Same as `common::REGINFO` with overlapping pairs of registers.
+const GROUP_REGINFO: &str = "
+r0 = reg unit0
+r1 = reg unit1
+r2 = reg unit2
+r3 = stack unit3
+
+rg0 = r0 r1
+rg1 = r1 r2
+
+bank0 {
+    top_level_class = class0
+    stack_to_stack_class = class1
+    spillslot_size = 8
+
+    class0 {
+        allows_spillslots
+        spill_cost = 0.5
+        members = r0 r1 r2 r3
+        allocation_order = r0 r1 r2
+    }
+
+    class1: class0 {
+        spill_cost = 1
+        members = r0 r1 r2
+        allocation_order = r0 r1 r2
+    }
+
+    class2: class1 {
+        group_size = 2
+        spill_cost = 1
+        members = rg0 rg1
+        allocation_order = rg0 rg1
+    }
+}
+";
+
+#[test]
+fn minimal_set() {
+    // The fixed use of r1 blocks both register pairs, so the clobber of unit0
+    // isn't needed to explain why there is no pair left for %1 and %2.
+    let func = "
+%0 = bank0
+%1 = bank0
+%2 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):class1
+    inst1: inst Use(%0):r1 EarlyDef(%1, %2):class2 Clobber:unit0
+    inst2: inst Use(%1, %2):class2
+    inst3: ret
+";
+    for err in allocate(GROUP_REGINFO, func) {
+        assert_eq!(
+            err,
+            TooManyLiveRegs {
+                inst: Inst::new(1),
+                class: RegClass::new(2),
+                operands: vec![0, 1],
+                live_values: vec![],
+                clobbers: vec![],
+                fixed_units: vec![],
+            }
+        );
+    }
+}
+
+/// Same as `common::REGINFO` with a fourth register.
+const WIDE_REGINFO: &str = "
+r0 = reg unit0
+r1 = reg unit1
+r2 = reg unit2
+r3 = reg unit3
+r4 = stack unit4
+
+bank0 {
+    top_level_class = class0
+    stack_to_stack_class = class1
+    spillslot_size = 8
+
+    class0 {
+        allows_spillslots
+        spill_cost = 0.5
+        members = r0 r1 r2 r3 r4
+        allocation_order = r0 r1 r2 r3
+    }
+
+    class1: class0 {
+        spill_cost = 1
+        members = r0 r1 r2 r3
+        allocation_order = r0 r1 r2 r3
+    }
+}
+";
+
+#[test]
+fn all_occupants() {
+    // Each register is blocked by a different kind of occupant: r0 by the
+    // pinned %0, r1 by the clobber, and the remaining two by the def of %2
+    // and by %1 which is live across inst1.
+    let func = "
+%0 = bank0 pinned(r0)
+%1 = bank0
+%2 = bank0
+%3 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):class1 Def(%1):class1
+    inst1: inst no_edits_before Def(%2):class1 EarlyDef(%3):class1 Clobber:unit1
+    inst2: inst no_edits_before Use(%0):class1 Use(%1):class1 Use(%2):class1 Use(%3):class1
+    inst3: ret
+";
+    for err in allocate(WIDE_REGINFO, func) {
+        assert_eq!(
+            err,
+            TooManyLiveRegs {
+                inst: Inst::new(1),
+                class: RegClass::new(1),
+                operands: vec![0, 1],
+                live_values: vec![Value::new(1)],
+                clobbers: vec![RegUnit::new(1)],
+                fixed_units: vec![RegUnit::new(0)],
+            }
+        );
+    }
+}
@@ -0,0 +1,114 @@
+//! Checks the errors reported by input validation.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::debug_utils::{self, GenericFunction, GenericRegInfo, ValidationError};
+use regalloc3::function::{Inst, Value};
+use regalloc3::reginfo::{PhysReg, RegBank, RegClass, RegUnit};
+
+mod common;
+
+/// Function with several independent errors.
+const INVALID_FUNC: &str = "
+%0 = bank0
+%1 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):class1 Clobber:unit1 Clobber:unit1
+    inst1: inst Use(%1):class1
+    inst2: ret Def(%0):class1
+";
+
+#[test]
+fn function_first_error() {
+    let reginfo = GenericRegInfo::parse(common::REGINFO).unwrap();
+    let func = GenericFunction::parse(INVALID_FUNC).unwrap();
+    assert_eq!(
+        debug_utils::validate_function(&func, &reginfo),
+        Err(ValidationError::DuplicateClobber {
+            inst: Inst::new(0),
+            unit: RegUnit::new(1),
+        })
+    );
+}
+
+#[test]
+fn function_all_errors() {
+    let reginfo = GenericRegInfo::parse(common::REGINFO).unwrap();
+    let func = GenericFunction::parse(INVALID_FUNC).unwrap();
+    assert_eq!(
+        debug_utils::validate_function_all(&func, &reginfo),
+        Err(vec![
+            ValidationError::DuplicateClobber {
+                inst: Inst::new(0),
+                unit: RegUnit::new(1),
+            },
+            ValidationError::RetWithDef {
+                inst: Inst::new(2),
+                operand: 0,
+            },
+            ValidationError::ValueDefinedMultipleTimes(Value::new(0)),
+            ValidationError::UndefinedValue(Value::new(1)),
+        ])
+    );
+}
+
+/// Same as `common::REGINFO`, but the stack-to-stack class allows spill slots
+/// and its allocation order contains the stack register.
+fn invalid_reginfo() -> GenericRegInfo {
+    let reginfo = common::REGINFO
+        .replace(
+            "class1: class0 {",
+            "class1: class0 {\n        allows_spillslots",
+        )
+        .replace(
+            "members = r0 r1 r2\n        allocation_order = r0 r1 r2",
+            "members = r0 r1 r2\n        allocation_order = r0 r1 r3",
+        );
+    GenericRegInfo::parse(&reginfo).unwrap()
+}
+
+#[test]
+fn reginfo_first_error() {
+    assert_eq!(
+        debug_utils::validate_reginfo(&invalid_reginfo()),
+        Err(ValidationError::StackToStackClassWithSpillslots {
+            bank: RegBank::new(0),
+            class: RegClass::new(1),
+        })
+    );
+}
+
+#[test]
+fn reginfo_all_errors() {
+    assert_eq!(
+        debug_utils::validate_reginfo_all(&invalid_reginfo()),
+        Err(vec![
+            ValidationError::StackToStackClassWithSpillslots {
+                bank: RegBank::new(0),
+                class: RegClass::new(1),
+            },
+            ValidationError::AllocationOrderRegNotInClass {
+                class: RegClass::new(1),
+                reg: PhysReg::new(3),
+            },
+        ])
+    );
+}
+
+#[test]
+fn valid_inputs() {
+    let reginfo = GenericRegInfo::parse(common::REGINFO).unwrap();
+    let func = GenericFunction::parse(
+        "
+%0 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):class1
+    inst1: ret Use(%0):class1
+",
+    )
+    .unwrap();
+    assert_eq!(debug_utils::validate_reginfo_all(&reginfo), Ok(()));
+    assert_eq!(debug_utils::validate_function_all(&func, &reginfo), Ok(()));
+}
@@ -0,0 +1,37 @@
+//! Checks that value hints steer the allocation towards the hinted register.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::Options;
+use regalloc3::function::Inst;
+use regalloc3::output::Allocation;
+use regalloc3::reginfo::PhysReg;
+
+mod common;
+
+/// Returns the register assigned to the definition of `%0` in `inst0`.
+fn def_alloc(func: &str) -> Allocation {
+    let (reginfo, func) = common::parse(common::REGINFO, func);
+    common::allocate(&reginfo, &func, &Options::default(), |output| {
+        common::operand_allocs(output, Inst::new(0))[0]
+    })
+    .unwrap()
+}
+
+#[test]
+fn hint_selects_register() {
+    let func = "
+%0 = bank0
+%1 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):class1
+    inst1: inst Def(%1):class1 Use(%0):class1
+    inst2: inst Use(%1):class1
+    inst3: ret
+";
+    assert_eq!(def_alloc(func), Allocation::reg(PhysReg::new(0)));
+
+    let hinted = func.replace("%0 = bank0", "%0 = bank0 hint(r2, 1)");
+    assert_eq!(def_alloc(&hinted), Allocation::reg(PhysReg::new(2)));
+}
@@ -0,0 +1,52 @@
+//! Checks the set of registers reported as written by the function.
+
+#![cfg(feature = "parse")]
+
+use regalloc3::Options;
+use regalloc3::reginfo::{PhysReg, RegBank, RegUnit};
+
+mod common;
+
+#[test]
+fn defs_moves_and_clobbers() {
+    // r0 is written by the def in inst0, r1 by the move for the fixed use in
+    // inst1 and r2 by the clobber in inst2. The stack register is never
+    // written.
+    let func = "
+%0 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):r0
+    inst1: inst Use(%0):r1
+    inst2: inst Clobber:unit2
+    inst3: ret
+";
+    let (reginfo, func) = common::parse(common::REGINFO, func);
+    common::allocate(&reginfo, &func, &Options::default(), |output| {
+        let regs = output.written_regs(RegBank::new(0));
+        let written: Vec<bool> = (0..4).map(|i| regs.contains(PhysReg::new(i))).collect();
+        assert_eq!(written, [true, true, true, false]);
+        let units = output.written_units();
+        assert!(units.contains(RegUnit::new(2)));
+        assert!(!units.contains(RegUnit::new(3)));
+    })
+    .unwrap();
+}
+
+#[test]
+fn unused_registers_are_not_written() {
+    let func = "
+%0 = bank0
+
+block0() freq(1):
+    inst0: inst Def(%0):class1
+    inst1: inst Use(%0):class1
+    inst2: ret
+";
+    let (reginfo, func) = common::parse(common::REGINFO, func);
+    common::allocate(&reginfo, &func, &Options::default(), |output| {
+        assert_eq!(output.written_regs(RegBank::new(0)).count(), 1);
+        assert_eq!(output.written_units().count(), 1);
+    })
+    .unwrap();
+}